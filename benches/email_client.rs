@@ -0,0 +1,82 @@
+//! Backs up two claims in `email_client.rs`: cloning an `EmailClient` costs next to nothing next to
+//! building one, and spreading concurrent sends over a bigger `EmailClientPool` barely changes how
+//! long they take when the mail API is slow. Run with `cargo bench --bench email_client`.
+
+use std::{hint::black_box, sync::Arc, time::Duration};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use secrecy::Secret;
+use tokio::runtime::Runtime;
+use wiremock::{Mock, MockServer, ResponseTemplate, matchers::any};
+use zero_to_prod::{
+    configuration::EmailClientSettings, domain::SubscriberEmail, email_client::EmailClientPool,
+};
+
+// Sends in flight at once, about what a burst of sign ups puts on the API
+const CONCURRENT_SENDS: usize = 64;
+// How long the mock mail API takes to answer
+const API_LATENCY: Duration = Duration::from_millis(10);
+
+fn settings(base_url: String) -> EmailClientSettings {
+    EmailClientSettings {
+        base_url,
+        sender_email: "newsletter@mail.com".into(),
+        authorisation_token: Secret::new("token".into()),
+        timeout_milliseconds: 10_000,
+        proxy_url: None,
+        proxy_username: None,
+        proxy_password: None,
+        use_env_proxy: false,
+    }
+}
+
+fn cloning(c: &mut Criterion) {
+    let settings = settings("http://127.0.0.1:1".into());
+    let client = settings.client();
+    let mut group = c.benchmark_group("email_client");
+    group.bench_function("clone", |b| b.iter(|| black_box(client.clone())));
+    group.bench_function("build", |b| b.iter(|| black_box(settings.client())));
+    group.finish();
+}
+
+fn concurrent_sends(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mock_server = runtime.block_on(async {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(API_LATENCY))
+            .mount(&mock_server)
+            .await;
+        mock_server
+    });
+    let settings = settings(mock_server.uri());
+    let recipient = Arc::new(SubscriberEmail::parse("reader@mail.com".into()).unwrap());
+
+    let mut group = c.benchmark_group("concurrent_sends");
+    group.sample_size(20);
+    for pool_size in [1, 4, 8] {
+        let pool = Arc::new(EmailClientPool::new(pool_size, &settings));
+        group.bench_with_input(BenchmarkId::from_parameter(pool_size), &pool, |b, pool| {
+            b.to_async(&runtime).iter(|| async {
+                let sends: Vec<_> = (0..CONCURRENT_SENDS)
+                    .map(|_| {
+                        let pool = pool.clone();
+                        let recipient = recipient.clone();
+                        tokio::spawn(async move {
+                            pool.send(None, &recipient, "Subject", "<p>Hi</p>", "Hi")
+                                .await
+                                .unwrap();
+                        })
+                    })
+                    .collect();
+                for send in sends {
+                    send.await.unwrap();
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cloning, concurrent_sends);
+criterion_main!(benches);
@@ -0,0 +1 @@
+ALTER TABLE idempotency ADD COLUMN response_location TEXT NULL;
@@ -0,0 +1,7 @@
+CREATE TABLE issue_feedback (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    rating TEXT NOT NULL,
+    submitted_at timestamptz NOT NULL,
+    PRIMARY KEY(newsletter_issue_id, subscriber_id)
+);
@@ -0,0 +1,6 @@
+-- Issues published before tracking existed have already been fully delivered
+ALTER TABLE newsletter_issues ADD COLUMN published_by uuid NULL REFERENCES users (user_id);
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT NOT NULL DEFAULT 'sent';
+ALTER TABLE newsletter_issues ADD COLUMN n_delivered INT NOT NULL DEFAULT 0;
+ALTER TABLE newsletter_issues ADD COLUMN n_failed INT NOT NULL DEFAULT 0;
+ALTER TABLE newsletter_issues ADD COLUMN completed_at timestamptz NULL;
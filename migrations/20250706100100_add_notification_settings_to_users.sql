@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN email TEXT NULL;
+ALTER TABLE users ADD COLUMN notify_on_completion BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,9 @@
+CREATE TABLE notification_queue (
+    notification_id uuid NOT NULL,
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    enqueued_at timestamptz NOT NULL,
+    PRIMARY KEY(notification_id)
+);
@@ -0,0 +1 @@
+ALTER TABLE issue_delivery_queue ADD COLUMN retry_count INT NOT NULL DEFAULT 0;
@@ -0,0 +1,18 @@
+CREATE TABLE webhooks (
+    webhook_id uuid NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(webhook_id)
+);
+
+CREATE TABLE webhook_deliveries (
+    delivery_id uuid NOT NULL,
+    webhook_id uuid NOT NULL REFERENCES webhooks (webhook_id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at timestamptz NOT NULL,
+    PRIMARY KEY(delivery_id)
+);
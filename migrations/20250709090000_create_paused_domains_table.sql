@@ -0,0 +1,6 @@
+CREATE TABLE paused_domains(
+    domain TEXT NOT NULL,
+    paused_at timestamptz NOT NULL,
+    PRIMARY KEY(domain)
+);
+ALTER TABLE issue_delivery_queue ADD COLUMN execute_after timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,2 @@
+-- Soft-deleted issues are kept for delivery records but hidden from the public archive
+ALTER TABLE newsletter_issues ADD COLUMN deleted_at timestamptz NULL;
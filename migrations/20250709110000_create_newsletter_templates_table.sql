@@ -0,0 +1,9 @@
+CREATE TABLE newsletter_templates(
+    id uuid NOT NULL,
+    name TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    created_by uuid NOT NULL REFERENCES users (user_id),
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(id)
+);
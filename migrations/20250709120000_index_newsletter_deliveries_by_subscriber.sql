@@ -0,0 +1,3 @@
+-- Frequency capping looks up a subscriber's recent deliveries
+CREATE INDEX newsletter_deliveries_subscriber_email_idx
+    ON newsletter_deliveries (subscriber_email, attempted_at);
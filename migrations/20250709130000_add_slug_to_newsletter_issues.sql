@@ -0,0 +1,5 @@
+-- Issues published before slugs existed keep their id as their slug
+ALTER TABLE newsletter_issues ADD COLUMN slug TEXT NULL;
+UPDATE newsletter_issues SET slug = newsletter_issue_id::text WHERE slug IS NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE newsletter_issues ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
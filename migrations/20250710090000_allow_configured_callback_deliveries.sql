@@ -0,0 +1,2 @@
+-- Deliveries without a webhook go to the confirmation callback from the configuration
+ALTER TABLE webhook_deliveries ALTER COLUMN webhook_id DROP NOT NULL;
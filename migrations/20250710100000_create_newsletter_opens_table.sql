@@ -0,0 +1,17 @@
+-- Each delivery gets a stable id so its tracking pixel can be traced back to it
+ALTER TABLE newsletter_deliveries ADD COLUMN delivery_id uuid NULL;
+UPDATE newsletter_deliveries SET delivery_id = gen_random_uuid() WHERE delivery_id IS NULL;
+ALTER TABLE newsletter_deliveries ALTER COLUMN delivery_id SET NOT NULL;
+ALTER TABLE newsletter_deliveries ADD CONSTRAINT newsletter_deliveries_delivery_id_key UNIQUE (delivery_id);
+
+CREATE TABLE newsletter_opens(
+    id uuid NOT NULL,
+    delivery_id uuid NOT NULL REFERENCES newsletter_deliveries (delivery_id),
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    opened_at timestamptz NOT NULL,
+    ip_address TEXT NULL,
+    user_agent TEXT NULL,
+    PRIMARY KEY(id)
+);
+CREATE INDEX newsletter_opens_newsletter_issue_id_idx ON newsletter_opens (newsletter_issue_id);
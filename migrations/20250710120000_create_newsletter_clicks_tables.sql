@@ -0,0 +1,16 @@
+-- Rewritten links carry sha256(original_url) rather than the URL itself
+CREATE TABLE newsletter_links(
+    link_hash TEXT NOT NULL,
+    original_url TEXT NOT NULL,
+    PRIMARY KEY(link_hash)
+);
+
+CREATE TABLE newsletter_clicks(
+    id uuid NOT NULL,
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    original_url TEXT NOT NULL,
+    clicked_at timestamptz NOT NULL,
+    PRIMARY KEY(id)
+);
+CREATE INDEX newsletter_clicks_newsletter_issue_id_idx ON newsletter_clicks (newsletter_issue_id);
@@ -0,0 +1,2 @@
+-- Whether the issue's links get UTM parameters, decided when it is published
+ALTER TABLE newsletter_issues ADD COLUMN utm_injection BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,20 @@
+-- Addresses we must never email, whatever their subscription says
+CREATE TABLE suppressed_emails(
+    email TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    suppressed_at timestamptz NOT NULL,
+    suppressed_by uuid NULL REFERENCES users (user_id),
+    PRIMARY KEY(email)
+);
+
+-- Who did what to which record, kept after the record itself is gone
+CREATE TABLE audit_log(
+    id uuid NOT NULL,
+    user_id uuid NULL REFERENCES users (user_id),
+    action TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    details TEXT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(id)
+);
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
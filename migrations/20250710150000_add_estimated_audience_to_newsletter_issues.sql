@@ -0,0 +1,3 @@
+-- Set at publish time; issues published before it existed fall back to everyone they were sent to
+ALTER TABLE newsletter_issues ADD COLUMN estimated_audience INT NOT NULL DEFAULT 0;
+UPDATE newsletter_issues SET estimated_audience = n_delivered + n_failed;
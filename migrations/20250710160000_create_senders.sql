@@ -0,0 +1,12 @@
+-- Addresses issues may be sent from instead of the configured sender. `verified_at` is set by an
+-- admin for now, a confirmation email can set it later.
+CREATE TABLE senders(
+    sender_id uuid NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    display_name TEXT NOT NULL,
+    verified_at timestamptz NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(sender_id)
+);
+-- Left empty to send from the configured sender
+ALTER TABLE newsletter_issues ADD COLUMN sender_id uuid NULL REFERENCES senders (sender_id);
@@ -0,0 +1,2 @@
+-- Whether `<style>` rules are moved into style attributes before sending, picked when publishing
+ALTER TABLE newsletter_issues ADD COLUMN inline_css BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,20 @@
+-- A user can hold several API tokens. Only a hash of each token's secret is stored, along with
+-- its last four characters so the admin can tell them apart.
+CREATE TABLE api_tokens(
+    token_id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    token_hash TEXT NOT NULL,
+    last_four TEXT NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NULL,
+    last_used_at timestamptz NULL,
+    PRIMARY KEY(token_id)
+);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
+-- Tokens handed out so far start with the user's id, so reusing it as the token id keeps them
+-- working
+INSERT INTO api_tokens (token_id, user_id, token_hash, created_at)
+SELECT user_id, user_id, api_token_hash, now()
+FROM users
+WHERE api_token_hash IS NOT NULL;
+ALTER TABLE users DROP COLUMN api_token_hash;
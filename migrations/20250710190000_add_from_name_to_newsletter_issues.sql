@@ -0,0 +1,2 @@
+-- Overrides the sender's display name for a single issue, e.g. with the author's name
+ALTER TABLE newsletter_issues ADD COLUMN from_name TEXT NULL;
@@ -0,0 +1,4 @@
+-- Lets the admin search match any part of an email or name without scanning every subscription
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX subscriptions_email_trgm_idx ON subscriptions USING GIN (email gin_trgm_ops);
+CREATE INDEX subscriptions_name_trgm_idx ON subscriptions USING GIN (name gin_trgm_ops);
@@ -0,0 +1,4 @@
+-- Archived issues are tucked away from the admin list and the public archive but can be restored.
+-- Unarchiving clears `archived_at`, so `archive_changed_at` is what tells caches the archive changed.
+ALTER TABLE newsletter_issues ADD COLUMN archived_at timestamptz NULL;
+ALTER TABLE newsletter_issues ADD COLUMN archive_changed_at timestamptz NULL;
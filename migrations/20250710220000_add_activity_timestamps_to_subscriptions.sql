@@ -0,0 +1,4 @@
+-- Set when an issue finishes delivering and when a tracking pixel is loaded, for spotting
+-- subscribers who have stopped reading
+ALTER TABLE subscriptions ADD COLUMN last_emailed_at timestamptz NULL;
+ALTER TABLE subscriptions ADD COLUMN last_opened_at timestamptz NULL;
@@ -0,0 +1,2 @@
+-- Only ever shown to admins, never to the subscriber
+ALTER TABLE subscriptions ADD COLUMN admin_note TEXT NULL;
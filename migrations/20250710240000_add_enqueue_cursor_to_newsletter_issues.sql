@@ -0,0 +1,3 @@
+-- Deliveries are queued a chunk at a time while an issue is 'enqueuing'. The cursor is the last
+-- subscription id queued so far, so an interrupted issue carries on where it stopped.
+ALTER TABLE newsletter_issues ADD COLUMN enqueue_cursor uuid NULL;
@@ -0,0 +1,10 @@
+-- The address a subscriber is moving to, only swapped in once it has been confirmed from its inbox
+ALTER TABLE subscriptions ADD COLUMN pending_email TEXT NULL;
+-- Single use, a token is deleted as soon as it is clicked
+CREATE TABLE email_change_tokens(
+    email_change_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    requested_by uuid NOT NULL REFERENCES users (user_id),
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (email_change_token)
+);
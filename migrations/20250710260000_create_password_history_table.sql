@@ -0,0 +1,9 @@
+-- Hashes of the passwords a user has set, so recent ones can't be picked again
+CREATE TABLE password_history(
+    id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    password_hash TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (id)
+);
+CREATE INDEX password_history_user_id_idx ON password_history (user_id, created_at);
@@ -0,0 +1,18 @@
+-- When a subscriber left, for re-engagement campaigns. Kept up to date by a trigger so every way
+-- of unsubscribing someone records it, and cleared again if they come back.
+ALTER TABLE subscriptions ADD COLUMN unsubscribed_at timestamptz NULL;
+
+CREATE FUNCTION set_unsubscribed_at() RETURNS trigger AS $$
+BEGIN
+    IF NEW.status = 'unsubscribed' AND OLD.status IS DISTINCT FROM 'unsubscribed' THEN
+        NEW.unsubscribed_at := now();
+    ELSIF NEW.status <> 'unsubscribed' THEN
+        NEW.unsubscribed_at := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER subscriptions_unsubscribed_at
+    BEFORE UPDATE OF status ON subscriptions
+    FOR EACH ROW EXECUTE FUNCTION set_unsubscribed_at();
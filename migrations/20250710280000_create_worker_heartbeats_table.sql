@@ -0,0 +1,8 @@
+-- One row per delivery worker, refreshed on every pass of its loop
+CREATE TABLE worker_heartbeats(
+    worker_id TEXT NOT NULL,
+    last_beat timestamptz NOT NULL,
+    last_task_at timestamptz,
+    queue_depth BIGINT NOT NULL,
+    PRIMARY KEY (worker_id)
+);
@@ -0,0 +1,8 @@
+-- A single row of counts for the public stats endpoint, so it never counts the tables itself
+CREATE TABLE public_stats(
+    id BOOLEAN NOT NULL DEFAULT TRUE CHECK (id),
+    confirmed_subscribers BIGINT NOT NULL,
+    issues_published BIGINT NOT NULL,
+    refreshed_at timestamptz NOT NULL,
+    PRIMARY KEY (id)
+);
@@ -0,0 +1,7 @@
+-- A confirmation link works once, so a leaked email can't resubscribe someone who has since left
+ALTER TABLE subscription_tokens ADD COLUMN used BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Links already clicked. Those of unsubscribed subscribers can't be told apart from the fresh
+-- link sent when someone subscribes again, so they stay usable.
+UPDATE subscription_tokens SET used = TRUE
+WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE status = 'confirmed');
@@ -0,0 +1,7 @@
+-- Who a newly published issue goes to. Enqueueing and the dry run both read from here so the two
+-- can't disagree about the audience.
+CREATE VIEW newsletter_recipients AS
+SELECT s.id, s.email
+FROM subscriptions s
+WHERE s.status = 'confirmed'
+  AND NOT EXISTS (SELECT 1 FROM suppressed_emails e WHERE e.email = lower(s.email));
@@ -0,0 +1,6 @@
+-- Where a subscriber signed up from, for growth analytics. Recorded once at signup and left alone
+-- when they subscribe again. The country is only filled in when a geo lookup is plugged in.
+ALTER TABLE subscriptions
+    ADD COLUMN signup_referer TEXT NULL,
+    ADD COLUMN signup_utm_source TEXT NULL,
+    ADD COLUMN signup_country TEXT NULL;
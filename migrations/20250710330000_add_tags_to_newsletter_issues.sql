@@ -0,0 +1,4 @@
+-- Labels for grouping issues in the archive, normalised by `IssueTag`
+ALTER TABLE newsletter_issues ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+-- For `tags @> ...` when filtering the archive by tag
+CREATE INDEX newsletter_issues_tags_idx ON newsletter_issues USING GIN (tags);
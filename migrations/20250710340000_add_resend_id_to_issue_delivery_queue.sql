@@ -0,0 +1,3 @@
+-- Set when an admin resends an issue to a single subscriber, so the provider gets a new
+-- idempotency key instead of treating the copy as a retry of the first one
+ALTER TABLE issue_delivery_queue ADD COLUMN resend_id uuid NULL;
@@ -0,0 +1,3 @@
+-- Keys are also claimed on behalf of subscribers resending their confirmation email, whose ids
+-- aren't users
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_user_id_fkey;
@@ -0,0 +1,24 @@
+-- With pii_encryption configured a subscriber's email and name are stored encrypted instead of in
+-- plain text, and the email hash stands in for the address in lookups and the uniqueness check
+ALTER TABLE subscriptions
+    ALTER COLUMN email DROP NOT NULL,
+    ALTER COLUMN name DROP NOT NULL,
+    ADD COLUMN email_encrypted TEXT NULL,
+    ADD COLUMN name_encrypted TEXT NULL,
+    ADD COLUMN email_hash TEXT NULL UNIQUE,
+    ADD CONSTRAINT subscriptions_email_stored
+        CHECK (email IS NOT NULL OR (email_encrypted IS NOT NULL AND email_hash IS NOT NULL)),
+    ADD CONSTRAINT subscriptions_name_stored CHECK (name IS NOT NULL OR name_encrypted IS NOT NULL);
+
+-- Suppressions keep their plain address, the hash lets them match encrypted subscribers
+ALTER TABLE suppressed_emails ADD COLUMN email_hash TEXT NULL;
+CREATE INDEX suppressed_emails_email_hash_idx ON suppressed_emails (email_hash);
+
+CREATE OR REPLACE VIEW newsletter_recipients AS
+SELECT s.id, s.email, s.email_encrypted
+FROM subscriptions s
+WHERE s.status = 'confirmed'
+  AND NOT EXISTS (
+      SELECT 1 FROM suppressed_emails e
+      WHERE e.email = lower(s.email) OR e.email_hash = s.email_hash
+  );
@@ -0,0 +1,15 @@
+-- Issues being written through the compose API, removed once they're published
+CREATE TABLE newsletter_drafts(
+    draft_id uuid NOT NULL,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    template_id uuid NULL REFERENCES newsletter_templates (id) ON DELETE SET NULL,
+    sender_id uuid NULL REFERENCES senders (sender_id) ON DELETE SET NULL,
+    from_name TEXT NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    inline_css BOOLEAN NOT NULL DEFAULT false,
+    updated_by uuid NOT NULL REFERENCES users (user_id),
+    updated_at timestamptz NOT NULL,
+    PRIMARY KEY(draft_id)
+);
@@ -0,0 +1,6 @@
+-- An issue's deliveries with a given outcome: the delivered ones once it's sent, the failed ones to resend.
+-- sqlx runs each migration in a transaction, which CREATE INDEX CONCURRENTLY can't be part of.
+CREATE INDEX newsletter_deliveries_issue_status_idx
+    ON newsletter_deliveries (newsletter_issue_id, status);
+-- Confirmed subscribers who signed up before a cutoff, e.g. the inactive subscribers report
+CREATE INDEX subscriptions_status_subscribed_at_idx ON subscriptions (status, subscribed_at);
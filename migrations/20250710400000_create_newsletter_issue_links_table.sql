@@ -0,0 +1,15 @@
+-- Which issue each rewritten link was sent in, so a click is only counted for the issue it's from.
+-- The same URL in several issues is stored once in newsletter_links and paired with each of them.
+CREATE TABLE newsletter_issue_links(
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    link_hash TEXT NOT NULL REFERENCES newsletter_links (link_hash),
+    PRIMARY KEY(newsletter_issue_id, link_hash)
+);
+
+-- Links that were clicked before are known to belong to the issue they were clicked in. The rest
+-- of the links already sent still redirect, their clicks just aren't counted.
+INSERT INTO newsletter_issue_links (newsletter_issue_id, link_hash)
+SELECT DISTINCT c.newsletter_issue_id, l.link_hash
+FROM newsletter_clicks c
+JOIN newsletter_links l ON l.link_hash = encode(sha256(convert_to(c.original_url, 'UTF8')), 'hex')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,6 @@
+-- The backfill that made links single use left unsubscribed subscribers' old links usable, and
+-- confirming one would resubscribe them. Only someone still waiting to confirm needs theirs. Anyone
+-- who subscribes again is sent a fresh link.
+UPDATE subscription_tokens SET used = TRUE
+WHERE NOT used
+  AND subscriber_id IN (SELECT id FROM subscriptions WHERE status <> 'pending_confirmation');
@@ -0,0 +1,39 @@
+-- Queued and recorded deliveries point at the subscriber instead of keeping a plain text copy of
+-- their address, the worker decrypts it when it sends. The recipient's domain stays on the queue so
+-- the worker can still skip over domains it has sent to too recently.
+ALTER TABLE issue_delivery_queue
+    DROP CONSTRAINT issue_delivery_queue_pkey,
+    ALTER COLUMN subscriber_email DROP NOT NULL,
+    ADD COLUMN subscriber_id uuid NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    ADD COLUMN recipient_domain TEXT NULL,
+    ADD CONSTRAINT issue_delivery_queue_issue_subscriber_key UNIQUE (newsletter_issue_id, subscriber_id);
+UPDATE issue_delivery_queue SET recipient_domain = lower(substring(subscriber_email FROM '[^@]*$'));
+ALTER TABLE issue_delivery_queue ALTER COLUMN recipient_domain SET NOT NULL;
+
+ALTER TABLE newsletter_deliveries
+    DROP CONSTRAINT newsletter_deliveries_pkey,
+    ALTER COLUMN subscriber_email DROP NOT NULL,
+    -- Kept once the subscriber is gone, so the issue's counts still add up
+    ADD COLUMN subscriber_id uuid NULL REFERENCES subscriptions (id) ON DELETE SET NULL,
+    ADD CONSTRAINT newsletter_deliveries_issue_subscriber_key UNIQUE (newsletter_issue_id, subscriber_id);
+DROP INDEX newsletter_deliveries_subscriber_email_idx;
+CREATE INDEX newsletter_deliveries_subscriber_id_idx
+    ON newsletter_deliveries (subscriber_id, attempted_at);
+
+-- Addresses still in plain text are matched here. One that only matches an encrypted subscriber
+-- is left for `zero2prod encrypt-pii`, queued deliveries wait for it before they are sent.
+UPDATE issue_delivery_queue q
+SET subscriber_id = s.id, subscriber_email = NULL
+FROM subscriptions s
+WHERE s.email = q.subscriber_email;
+UPDATE newsletter_deliveries d
+SET subscriber_id = s.id, subscriber_email = NULL
+FROM subscriptions s
+WHERE s.email = d.subscriber_email;
+
+-- Without encryption there is nothing left to match, the rest went to addresses that have since
+-- been changed or merged away
+DELETE FROM issue_delivery_queue
+WHERE subscriber_id IS NULL AND NOT EXISTS (SELECT 1 FROM subscriptions WHERE email_hash IS NOT NULL);
+UPDATE newsletter_deliveries SET subscriber_email = NULL
+WHERE subscriber_id IS NULL AND NOT EXISTS (SELECT 1 FROM subscriptions WHERE email_hash IS NOT NULL);
@@ -0,0 +1,3 @@
+-- An address waiting to be confirmed is stored like the current one, encrypted once pii_encryption
+-- is configured
+ALTER TABLE subscriptions ADD COLUMN pending_email_encrypted TEXT NULL;
@@ -0,0 +1,9 @@
+-- Audit entries name subscribers by id, the address they had at the time isn't kept
+UPDATE audit_log SET details = NULL
+WHERE action IN ('subscriber.email_changed', 'subscriber.email_change_requested');
+UPDATE audit_log SET details = 'Merged ' || substring(details FROM '\(([^()]*)\)$')
+WHERE action = 'subscriber.merged';
+UPDATE audit_log a SET details = s.id::text
+FROM subscriptions s
+WHERE a.action = 'issue.resent' AND s.email = a.details;
+UPDATE audit_log SET details = NULL WHERE action = 'issue.resent' AND details LIKE '%@%';
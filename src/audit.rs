@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::PiiCipher;
+
+pub struct AuditEntry {
+    // None once the user is gone
+    pub username: Option<String>,
+    pub action: String,
+    pub subject: String,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // Set while the subject is a subscriber or issue that still exists, and for a subscriber only
+    // while their email can still be decrypted
+    pub subscriber_email: Option<String>,
+    pub issue_title: Option<String>,
+}
+
+// Run it in the same transaction as the change it describes, so neither can exist without the other
+#[tracing::instrument(skip(executor))]
+pub async fn record_audit_event<'c, E>(
+    executor: E,
+    // `None` for what the app did on its own, with nobody to attribute it to
+    user_id: impl Into<Option<Uuid>> + std::fmt::Debug,
+    action: &str,
+    subject: &str,
+    details: Option<&str>,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (id, user_id, action, subject, details, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        Uuid::new_v4(),
+        user_id.into(),
+        action,
+        subject,
+        details
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Newest first, with what's needed to describe and link each entry in the same query
+#[tracing::instrument(skip(pool, pii_cipher))]
+pub async fn recent_audit_entries(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            u.username AS "username?",
+            a.action AS "action!",
+            a.subject AS "subject!",
+            a.details,
+            a.created_at AS "created_at!",
+            s.email AS "subscriber_email?",
+            s.email_encrypted AS "subscriber_email_encrypted?",
+            i.title AS "issue_title?"
+        FROM (
+            -- Subjects are text, cast to a uuid so the joins below use the primary keys
+            SELECT *,
+                CASE WHEN subject ~* '^[0-9a-f]{8}-([0-9a-f]{4}-){3}[0-9a-f]{12}$'
+                    THEN subject::uuid
+                END AS subject_id
+            FROM audit_log
+            ORDER BY created_at DESC
+            LIMIT $1
+        ) a
+        LEFT JOIN users u ON u.user_id = a.user_id
+        LEFT JOIN subscriptions s
+            ON a.action LIKE 'subscriber.%' AND s.id = a.subject_id
+        LEFT JOIN newsletter_issues i
+            ON a.action LIKE 'issue.%' AND i.newsletter_issue_id = a.subject_id
+            AND i.deleted_at IS NULL
+        ORDER BY a.created_at DESC
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditEntry {
+            username: row.username,
+            action: row.action,
+            subject: row.subject,
+            details: row.details,
+            created_at: row.created_at,
+            subscriber_email: pii_cipher
+                .reveal(row.subscriber_email, row.subscriber_email_encrypted)
+                .ok(),
+            issue_title: row.issue_title,
+        })
+        .collect())
+}
@@ -0,0 +1,238 @@
+use std::{future::Future, ops::Deref, pin::Pin};
+
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse, ResponseError,
+    dev::Payload,
+    http::{StatusCode, header::WWW_AUTHENTICATE},
+    web,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::{Rng, distributions::Alphanumeric, thread_rng};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::password::{compute_password_hash, verify_password_hash};
+use crate::{clock::Clock, telemetry::spawn_blocking_with_tracing};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiTokenError {
+    #[error("Invalid API token.")]
+    InvalidToken(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for ApiTokenError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiTokenError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            ApiTokenError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let ApiTokenError::InvalidToken(_) = self {
+            response.insert_header((WWW_AUTHENTICATE, r#"Bearer realm="admin""#));
+        }
+        response.body(self.to_string())
+    }
+}
+
+// A user authenticated with `Authorization: Bearer <token>` rather than a session cookie
+#[derive(Copy, Clone, Debug)]
+pub struct BearerUser(Uuid);
+
+impl Deref for BearerUser {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for BearerUser {
+    type Error = ApiTokenError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| Secret::new(token.trim().to_owned()));
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        let now = req
+            .app_data::<web::Data<dyn Clock>>()
+            .map(|clock| clock.now());
+        Box::pin(async move {
+            let token = token
+                .context("The request has no bearer token.")
+                .map_err(ApiTokenError::InvalidToken)?;
+            let pool = pool.context("The connection pool is not registered.")?;
+            let now = now.context("The clock is not registered.")?;
+            validate_api_token(token, now, &pool).await.map(BearerUser)
+        })
+    }
+}
+
+// Tokens are `<token_id>.<secret>`: the id finds the stored hash, only the secret is hashed
+#[tracing::instrument(name = "Validate API token", skip(token, pool))]
+async fn validate_api_token(
+    token: Secret<String>,
+    now: DateTime<Utc>,
+    pool: &PgPool,
+) -> Result<Uuid, ApiTokenError> {
+    let (token_id, secret) = token
+        .expose_secret()
+        .split_once('.')
+        .and_then(|(token_id, secret)| Some((Uuid::parse_str(token_id).ok()?, secret.to_owned())))
+        .context("The API token is malformed.")
+        .map_err(ApiTokenError::InvalidToken)?;
+    let stored = sqlx::query!(
+        r#"
+        SELECT user_id, token_hash
+        FROM api_tokens
+        WHERE token_id = $1 AND (expires_at IS NULL OR expires_at > $2)
+        "#,
+        token_id,
+        now
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the stored API token.")?
+    .context("The API token does not exist or has expired.")
+    .map_err(ApiTokenError::InvalidToken)?;
+
+    let stored_hash = stored.token_hash;
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(Secret::new(stored_hash), Secret::new(secret))
+    })
+    .await
+    .context("Failed to spawn blocking task.")?
+    .map_err(|e| ApiTokenError::InvalidToken(e.into()))?;
+    sqlx::query!(
+        r#"UPDATE api_tokens SET last_used_at = $2 WHERE token_id = $1"#,
+        token_id,
+        now
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the API token's use.")?;
+    Ok(stored.user_id)
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub masked_token: String,
+}
+
+// The plaintext is only ever returned from here. Left without `expires_at` the token lasts until
+// it is revoked.
+#[tracing::instrument(name = "Create API token", skip(pool))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    expires_at: Option<DateTime<Utc>>,
+    pool: &PgPool,
+) -> Result<(Uuid, Secret<String>), anyhow::Error> {
+    let token_id = Uuid::new_v4();
+    let secret: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .map(char::from)
+        .take(40)
+        .collect();
+    let token = Secret::new(format!("{token_id}.{secret}"));
+    let last_four = secret[secret.len() - 4..].to_owned();
+    let token_hash = spawn_blocking_with_tracing(move || compute_password_hash(secret.as_bytes()))
+        .await?
+        .context("Failed to hash the API token.")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (token_id, user_id, token_hash, last_four, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, now(), $5)
+        "#,
+        token_id,
+        user_id,
+        token_hash.expose_secret(),
+        last_four,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the API token.")?;
+    Ok((token_id, token))
+}
+
+// Replaces every previous token, which stop working straight away
+#[tracing::instrument(name = "Generate API token", skip(pool))]
+pub async fn generate_api_token(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Secret<String>, anyhow::Error> {
+    revoke_api_token(user_id, pool).await?;
+    let (_, token) = create_api_token(user_id, None, pool).await?;
+    Ok(token)
+}
+
+#[tracing::instrument(name = "List API tokens", skip(pool))]
+pub async fn list_api_tokens(user_id: Uuid, pool: &PgPool) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let tokens = sqlx::query!(
+        r#"
+        SELECT token_id, created_at, expires_at, last_used_at, last_four
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the API tokens.")?
+    .into_iter()
+    .map(|r| ApiToken {
+        id: r.token_id,
+        created_at: r.created_at,
+        expires_at: r.expires_at,
+        last_used_at: r.last_used_at,
+        // Tokens from before the last four were kept show nothing of their secret
+        masked_token: format!("****{}", r.last_four.unwrap_or_default()),
+    })
+    .collect();
+    Ok(tokens)
+}
+
+// Revokes every token the user holds
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_api_token(user_id: Uuid, pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(r#"DELETE FROM api_tokens WHERE user_id = $1"#, user_id)
+        .execute(pool)
+        .await
+        .context("Failed to revoke the API tokens.")?;
+    Ok(())
+}
+
+// Returns false if the user holds no token with this id
+#[tracing::instrument(name = "Revoke API token by id", skip(pool))]
+pub async fn revoke_api_token_by_id(
+    user_id: Uuid,
+    token_id: Uuid,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM api_tokens WHERE token_id = $1 AND user_id = $2"#,
+        token_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the API token.")?
+    .rows_affected();
+    Ok(deleted > 0)
+}
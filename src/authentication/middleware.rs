@@ -2,13 +2,14 @@ use std::ops::Deref;
 
 use actix_web::{
     FromRequest, HttpMessage,
-    body::MessageBody,
+    body::{BoxBody, MessageBody},
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
 };
 use actix_web_lab::middleware::Next;
 use uuid::Uuid;
 
+use super::BearerUser;
 use crate::{
     session_state::TypedSession,
     utils::{e500, see_other},
@@ -35,6 +36,7 @@ impl Deref for UserId {
     }
 }
 
+// Accepts either a logged in session or, for programmatic access, an API token
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
@@ -49,6 +51,14 @@ pub async fn reject_anonymous_users(
             req.extensions_mut().insert(UserId(user_id));
             next.call(req).await
         }
+        None if req.headers().contains_key("Authorization") => {
+            let bearer_user = {
+                let (http_request, payload) = req.parts_mut();
+                BearerUser::from_request(http_request, payload).await
+            }?;
+            req.extensions_mut().insert(UserId(*bearer_user));
+            next.call(req).await
+        }
         None => {
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
@@ -56,3 +66,24 @@ pub async fn reject_anonymous_users(
         }
     }
 }
+
+// For the JSON API, which has no sessions: only an API token will do
+pub async fn reject_requests_without_api_token(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let bearer_user = {
+        let (http_request, payload) = req.parts_mut();
+        BearerUser::from_request(http_request, payload).await
+    };
+    match bearer_user {
+        Ok(bearer_user) => {
+            req.extensions_mut().insert(UserId(*bearer_user));
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_boxed_body)
+        }
+        // Answered here rather than returned, so the API's error handlers get to see it
+        Err(e) => Ok(req.error_response(e)),
+    }
+}
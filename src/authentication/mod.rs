@@ -1,5 +1,14 @@
+mod api_token;
 mod middleware;
 mod password;
+mod throttle;
 
-pub use middleware::{UserId, reject_anonymous_users};
-pub use password::{AuthError, Credentials, change_password, validate_credentials};
+pub use api_token::{
+    ApiToken, ApiTokenError, BearerUser, create_api_token, generate_api_token, list_api_tokens,
+    revoke_api_token, revoke_api_token_by_id,
+};
+pub use middleware::{UserId, reject_anonymous_users, reject_requests_without_api_token};
+pub use password::{
+    AuthError, Credentials, PasswordHistoryDepth, change_password, validate_credentials,
+};
+pub use throttle::LoginThrottle;
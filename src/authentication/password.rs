@@ -4,9 +4,11 @@ use argon2::{
     password_hash::SaltString,
 };
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 
-use crate::{routes::ValidNewPassword, telemetry::spawn_blocking_with_tracing};
+use crate::{
+    audit::record_audit_event, routes::ValidNewPassword, telemetry::spawn_blocking_with_tracing,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -71,7 +73,7 @@ pub async fn get_stored_credentials(
 }
 
 #[tracing::instrument(name = "Verify password hash", skip(database_phc, password_candidate))]
-fn verify_password_hash(
+pub(crate) fn verify_password_hash(
     database_phc: Secret<String>,
     password_candidate: Secret<String>,
 ) -> Result<(), AuthError> {
@@ -85,15 +87,41 @@ fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials)
 }
 
+// Application state can only hold one value of a type, hence the wrapper
+#[derive(Clone, Copy, Debug)]
+pub struct PasswordHistoryDepth(pub u8);
+
+// A password matching one of the last `history_depth` ones is refused as invalid credentials
 #[tracing::instrument(name = "Change password", skip(password, pool))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: ValidNewPassword,
+    history_depth: PasswordHistoryDepth,
     pool: &PgPool,
-) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+) -> Result<(), AuthError> {
+    let recent_hashes = get_recent_password_hashes(user_id, history_depth, pool).await?;
+    let password_hash = spawn_blocking_with_tracing(move || {
+        for recent_hash in recent_hashes {
+            let candidate = Secret::new(password.as_str().to_owned());
+            if verify_password_hash(recent_hash, candidate).is_ok() {
+                return Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+                    "The password was used recently."
+                )));
+            }
+        }
+        compute_password_hash(password.as_bytes())
+            .context("Failed to hash password")
+            .map_err(AuthError::UnexpectedError)
+    })
+    .await
+    .context("Failed to spawn blocking task.")??;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    seed_password_history(&mut transaction, user_id)
+        .await
+        .context("Failed to start the user's password history.")?;
     sqlx::query!(
         r#"
         UPDATE users
@@ -103,20 +131,130 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id
     )
-    .execute(pool)
+    .execute(&mut transaction)
     .await
     .context("Failed to change user's password in the database.")?;
+    record_password_history(&mut transaction, user_id, &password_hash, history_depth)
+        .await
+        .context("Failed to record the password in the user's history.")?;
+    record_audit_event(
+        &mut transaction,
+        user_id,
+        "password.changed",
+        &user_id.to_string(),
+        None,
+    )
+    .await
+    .context("Failed to audit the password change.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the password change.")?;
+    Ok(())
+}
+
+// The current password counts as one of them, it may predate the history
+#[tracing::instrument(skip(pool))]
+async fn get_recent_password_hashes(
+    user_id: uuid::Uuid,
+    history_depth: PasswordHistoryDepth,
+    pool: &PgPool,
+) -> Result<Vec<Secret<String>>, anyhow::Error> {
+    if history_depth.0 == 0 {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query!(
+        r#"
+        SELECT password_hash AS "password_hash!" FROM users WHERE user_id = $1
+        UNION ALL
+        (
+            SELECT password_hash FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+        )
+        "#,
+        user_id,
+        i64::from(history_depth.0)
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the user's recent passwords.")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Secret::new(row.password_hash))
+        .collect())
+}
+
+// Users whose password was set before the history existed start it with the one being replaced
+#[tracing::instrument(skip(transaction))]
+async fn seed_password_history(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO password_history (id, user_id, password_hash, created_at)
+        SELECT $1, user_id, password_hash, clock_timestamp()
+        FROM users
+        WHERE
+            user_id = $2 AND
+            NOT EXISTS (SELECT 1 FROM password_history WHERE user_id = $2)
+        "#,
+        uuid::Uuid::new_v4(),
+        user_id
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+// Only the last `history_depth` entries are kept
+#[tracing::instrument(skip(transaction, password_hash))]
+async fn record_password_history(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+    password_hash: &Secret<String>,
+    history_depth: PasswordHistoryDepth,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO password_history (id, user_id, password_hash, created_at)
+        VALUES ($1, $2, $3, clock_timestamp())
+        "#,
+        uuid::Uuid::new_v4(),
+        user_id,
+        password_hash.expose_secret()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM password_history
+        WHERE user_id = $1 AND id NOT IN (
+            SELECT id FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+        )
+        "#,
+        user_id,
+        i64::from(history_depth.0)
+    )
+    .execute(transaction)
+    .await?;
     Ok(())
 }
 
-fn compute_password_hash(password: ValidNewPassword) -> Result<Secret<String>, anyhow::Error> {
+// Also used for API tokens, which are stored the same way as passwords
+pub(crate) fn compute_password_hash(password: &[u8]) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         Algorithm::Argon2id,
         Version::V0x13,
         Params::new(15000, 2, 1, None).unwrap(),
     )
-    .hash_password(password.as_bytes(), &salt)?
+    .hash_password(password, &salt)?
     .to_string();
 
     Ok(Secret::new(password_hash))
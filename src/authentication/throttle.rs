@@ -0,0 +1,185 @@
+use std::net::IpAddr;
+
+use actix_web::HttpRequest;
+use anyhow::Context;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions, aio::ConnectionManager};
+
+use crate::configuration::LoginThrottleSettings;
+
+// Counts failed logins per client IP in Redis, regardless of which username was tried. Keys expire
+// on their own once the window has passed so no clean up is needed.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    connection: ConnectionManager,
+    key_prefix: String,
+    max_attempts: u64,
+    window_seconds: u64,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl LoginThrottle {
+    pub async fn new(
+        redis_uri: &str,
+        database_name: &str,
+        settings: &LoginThrottleSettings,
+    ) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri).context("Invalid Redis URI.")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis.")?;
+        Ok(Self {
+            connection,
+            key_prefix: format!("login_attempts:{database_name}"),
+            max_attempts: settings.max_attempts,
+            window_seconds: settings.window_seconds,
+            trusted_proxies: settings.trusted_proxies.clone(),
+        })
+    }
+
+    // The address the request came from. `X-Forwarded-For` is only believed when the connection
+    // comes from a trusted proxy, otherwise a client could pick a new address for every attempt.
+    pub fn client_ip(&self, request: &HttpRequest) -> String {
+        let forwarded_for = request
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok());
+        client_ip(
+            request.peer_addr().map(|address| address.ip()),
+            forwarded_for,
+            &self.trusted_proxies,
+        )
+        .map_or_else(|| "unknown".into(), |ip| ip.to_string())
+    }
+
+    // How many seconds are left before the IP may try again, `None` while it isn't throttled
+    #[tracing::instrument(name = "Check login throttle", skip(self))]
+    pub async fn retry_after(&self, ip: &str) -> Result<Option<u64>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let key = self.key(ip);
+        let attempts: Option<u64> = connection
+            .get(&key)
+            .await
+            .context("Failed to read failed login attempts from Redis.")?;
+        if attempts.unwrap_or(0) < self.max_attempts {
+            return Ok(None);
+        }
+        let ttl: i64 = connection
+            .ttl(&key)
+            .await
+            .context("Failed to read the login throttle window from Redis.")?;
+        Ok(match ttl {
+            // Expired since it was read
+            -2 => None,
+            // Without an expiry the IP would stay locked out for good, start a fresh window
+            -1 => {
+                connection
+                    .expire::<_, ()>(&key, self.window_seconds as i64)
+                    .await
+                    .context("Failed to set the login throttle window in Redis.")?;
+                Some(self.window_seconds)
+            }
+            ttl => Some(ttl.max(1) as u64),
+        })
+    }
+
+    #[tracing::instrument(name = "Record failed login attempt", skip(self))]
+    pub async fn record_failure(&self, ip: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let key = self.key(ip);
+        // The window starts at the first failure, later failures don't extend it. Creating the key
+        // with its expiry in the same transaction means it can never be left without one.
+        redis::pipe()
+            .atomic()
+            .set_options(
+                &key,
+                0,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(self.window_seconds)),
+            )
+            .ignore()
+            .incr(&key, 1)
+            .ignore()
+            .query_async::<()>(&mut connection)
+            .await
+            .context("Failed to record a failed login attempt in Redis.")?;
+        Ok(())
+    }
+
+    fn key(&self, ip: &str) -> String {
+        format!("{}:{ip}", self.key_prefix)
+    }
+}
+
+// Walks `X-Forwarded-For` from the closest hop back, the first address that isn't one of our own
+// proxies is the client. Anything further left was written by the client and can't be trusted.
+fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+    let Some(forwarded_for) = forwarded_for else {
+        return Some(peer);
+    };
+    let mut hops = forwarded_for.rsplit(',').map(str::trim);
+    let mut client = peer;
+    while trusted_proxies.contains(&client) {
+        match hops.next().map(str::parse::<IpAddr>) {
+            Some(Ok(hop)) => client = hop,
+            // A malformed hop means the chain can't be followed any further
+            Some(Err(_)) | None => break,
+        }
+    }
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::client_ip;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_an_untrusted_peer() {
+        let client = client_ip(Some(ip("203.0.113.7")), Some("10.1.2.3"), &[]);
+
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn a_trusted_proxy_passes_on_the_address_it_saw() {
+        let proxy = ip("10.0.0.1");
+
+        let client = client_ip(Some(proxy), Some("1.2.3.4, 203.0.113.7"), &[proxy]);
+
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn every_trusted_hop_is_skipped() {
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        let client = client_ip(
+            Some(proxies[0]),
+            Some("1.2.3.4, 203.0.113.7, 10.0.0.2"),
+            &proxies,
+        );
+
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn a_trusted_proxy_without_the_header_is_the_client() {
+        let proxy = ip("10.0.0.1");
+
+        assert_eq!(client_ip(Some(proxy), None, &[proxy]), Some(proxy));
+    }
+}
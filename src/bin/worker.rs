@@ -0,0 +1,62 @@
+use std::{path::PathBuf, sync::Arc};
+
+use clap::Parser;
+
+use zero_to_prod::{
+    build_info::BUILD_INFO,
+    clock::SystemClock,
+    configuration::{DEFAULT_CONFIGURATION_DIRECTORY, get_configuration},
+    issue_delivery_worker::run_worker_until_stopped,
+    shutdown::{report_exit, shutdown_signal},
+    supervisor::Supervisor,
+    telemetry::{Redaction, get_subscriber, init_subscriber},
+};
+
+// Delivers newsletters without serving the API, for running as many workers as the queue needs.
+// Pair it with `worker.embedded: false` so the API process leaves delivery to it.
+#[derive(Parser)]
+struct Cli {
+    /// Directory holding base.yaml and the per-environment configuration files
+    #[arg(long, env = "APP_CONFIG_DIR", default_value = DEFAULT_CONFIGURATION_DIRECTORY)]
+    config_dir: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let redaction = Redaction::default();
+    let subscriber = get_subscriber(
+        "zero_to_prod_worker".into(),
+        "info".into(),
+        std::io::stdout,
+        redaction.clone(),
+    );
+    init_subscriber(subscriber);
+
+    let configuration = BUILD_INFO.startup_span().in_scope(|| {
+        tracing::info!("Starting the delivery worker");
+        let configuration =
+            get_configuration(&cli.config_dir).expect("Failed to read configuration");
+        // The default redacted fields covered the logs written while loading it
+        redaction.set(configuration.telemetry.redacted_fields.clone());
+        configuration
+    });
+    let supervisor = Supervisor::new(configuration.supervisor.clone());
+    let worker_task = tokio::spawn(supervisor.supervise("Background worker", move || {
+        run_worker_until_stopped(
+            configuration.worker.clone(),
+            configuration.clone(),
+            Arc::new(SystemClock),
+        )
+    }));
+
+    // A delivery cut off here is rolled back and picked up again, by this worker or another one.
+    // The mail provider drops the repeat by its idempotency key.
+    tokio::select! {
+        o = worker_task => report_exit("Background worker", o),
+        _ = shutdown_signal() => tracing::info!("Shutting down"),
+    };
+
+    Ok(())
+}
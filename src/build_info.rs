@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+
+// What a running instance was built from, embedded by `build.rs`
+#[derive(Clone, Copy)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    // `unknown` when built without git or a `GIT_SHA`
+    pub git_sha: &'static str,
+    built_at: &'static str,
+}
+
+pub const BUILD_INFO: BuildInfo = BuildInfo {
+    version: env!("CARGO_PKG_VERSION"),
+    git_sha: env!("GIT_SHA"),
+    built_at: env!("BUILD_TIMESTAMP"),
+};
+
+impl BuildInfo {
+    pub fn short_sha(&self) -> &'static str {
+        self.git_sha.get(..7).unwrap_or(self.git_sha)
+    }
+
+    pub fn built_at(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.built_at.parse().ok()?, 0)
+    }
+
+    // Added to every log line, e.g. `0.1.0+1a2b3c4`
+    pub fn log_version(&self) -> String {
+        format!("{}+{}", self.version, self.short_sha())
+    }
+
+    // Entered by the binaries while they start up, so the lines logged then carry the full details
+    pub fn startup_span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "Starting up",
+            crate_version = self.version,
+            git_sha = self.git_sha,
+            built_at = self.built_at().map(|t| t.to_rfc3339()).as_deref(),
+        )
+    }
+}
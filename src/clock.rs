@@ -0,0 +1,56 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+
+// Where the app and the delivery worker get the current time from, so tests can move it around
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// Follows the system time until it is set or advanced, then stays put
+#[derive(Default)]
+pub struct TestClock(Mutex<Option<DateTime<Utc>>>);
+
+impl TestClock {
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = Some(now);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.0.lock().unwrap();
+        *now = Some(now.unwrap_or_else(Utc::now) + by);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0.lock().unwrap().unwrap_or_else(Utc::now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{Clock, TestClock};
+
+    #[test]
+    fn a_test_clock_stays_where_it_was_moved_to() {
+        let clock = TestClock::default();
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+
+        clock.set(start);
+        clock.advance(Duration::days(2));
+
+        assert_eq!(clock.now(), start + Duration::days(2));
+        assert_eq!(clock.now(), start + Duration::days(2));
+    }
+}
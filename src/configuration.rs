@@ -1,11 +1,26 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    num::NonZeroUsize,
+    path::Path,
+};
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono_tz::Tz;
 use secrecy::{ExposeSecret, Secret};
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde::Deserialize;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::{
     ConnectOptions,
     postgres::{PgConnectOptions, PgSslMode},
 };
+use url::Url;
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::{EmailStrictness, PiiCipher, SubscriberEmail},
+    email_client::{EmailClient, OutboundProxy},
+};
 
 #[derive(Clone, serde::Deserialize)]
 pub struct Settings {
@@ -13,6 +28,415 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    // How many of a user's previous passwords they can't change back to, 0 to allow any
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub password_history_depth: u8,
+    // Longer idempotency keys are turned away rather than stored
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idempotency_key_max_length: usize,
+    // A repeated request waits this long for the first one to finish before giving up
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idempotency_wait_seconds: u64,
+    pub login_throttle: LoginThrottleSettings,
+    pub notifications: NotificationSettings,
+    pub webhooks: WebhookSettings,
+    pub delivery: DeliverySettings,
+    pub site: SiteSettings,
+    pub caching: CacheSettings,
+    pub subscriptions: SubscriptionSettings,
+    pub session: SessionSettings,
+    pub link_check: LinkCheckSettings,
+    pub supervisor: SupervisorSettings,
+    pub display: DisplaySettings,
+    #[serde(default)]
+    pub worker: WorkerSettings,
+    pub public_stats: PublicStatsSettings,
+    pub telemetry: TelemetrySettings,
+    // Left out to keep subscriber emails and names in plain text
+    pub pii_encryption: Option<PiiEncryptionSettings>,
+}
+
+impl Settings {
+    // Without `pii_encryption` the cipher leaves everything in plain text
+    pub fn pii_cipher(&self) -> Result<PiiCipher, anyhow::Error> {
+        match &self.pii_encryption {
+            Some(pii_encryption) => PiiCipher::new(
+                &pii_encryption.key,
+                &pii_encryption.previous_keys,
+                &pii_encryption.hash_key,
+            )
+            .map_err(anyhow::Error::msg),
+            None => Ok(PiiCipher::default()),
+        }
+    }
+}
+
+// Each key is 32 random bytes, base64 encoded, e.g. from `openssl rand -base64 32`
+#[derive(Clone, serde::Deserialize)]
+pub struct PiiEncryptionSettings {
+    // Encrypts everything written from now on
+    pub key: Secret<String>,
+    // Keys being rotated out, only read until `zero2prod encrypt-pii` has re-encrypted their values
+    #[serde(default)]
+    pub previous_keys: Vec<Secret<String>>,
+    // Keys the email hash. Changing it loses every encrypted subscriber's hash, so it isn't rotated.
+    pub hash_key: Secret<String>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct TelemetrySettings {
+    // Values of these fields, and query string parameters of the same name, are masked in the logs
+    pub redacted_fields: Vec<String>,
+}
+
+// Where the delivery worker runs and how it paces itself. With `embedded` off it only runs as the
+// `zero2prod-worker` binary, so it can be scaled apart from the API. Several workers can share a
+// queue. Anything left out keeps its default.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct WorkerSettings {
+    pub embedded: bool,
+    // Deliveries sent side by side by one process, each loop claims its own task
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub concurrency: u16,
+    // Longest wait before looking at an empty queue again
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_milliseconds: u64,
+    // Wait after a failed pass, most errors are transient
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub error_backoff_milliseconds: u64,
+    // Shortest time between heartbeats, keep it well under `delivery.worker_stale_after_seconds`
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub heartbeat_interval_milliseconds: u64,
+    // How long a delivery to a paused domain waits before it is looked at again. Failed deliveries
+    // aren't retried on their own, an admin requeues them from the delivery report.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub paused_domain_retry_seconds: u64,
+    // Left out to send every issue to every subscriber straight away
+    pub frequency_cap: Option<FrequencyCap>,
+    // Left out to deliver around the clock
+    pub sending_window: Option<SendingWindow>,
+    // Per recipient domain, empty to send as fast as the worker goes
+    pub domain_rate_limits: Vec<DomainRateLimit>,
+    // Across every domain, left out to send as fast as the provider accepts
+    #[serde(deserialize_with = "deserialize_emails_per_second")]
+    pub max_emails_per_second: Option<f64>,
+    // Counted from midnight UTC, left out for no daily limit
+    pub max_emails_per_day: Option<u32>,
+}
+
+impl Default for WorkerSettings {
+    fn default() -> Self {
+        Self {
+            embedded: true,
+            concurrency: 1,
+            poll_interval_milliseconds: 10_000,
+            error_backoff_milliseconds: 1000,
+            heartbeat_interval_milliseconds: 10_000,
+            paused_domain_retry_seconds: 300,
+            frequency_cap: None,
+            sending_window: None,
+            domain_rate_limits: Vec::new(),
+            max_emails_per_second: None,
+            max_emails_per_day: None,
+        }
+    }
+}
+
+impl WorkerSettings {
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.poll_interval_milliseconds)
+    }
+
+    pub fn error_backoff(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.error_backoff_milliseconds)
+    }
+
+    pub fn heartbeat_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.heartbeat_interval_milliseconds)
+    }
+}
+
+// How the admin pages show timestamps, they are always stored in UTC
+#[derive(Clone, serde::Deserialize)]
+pub struct DisplaySettings {
+    pub timezone: Tz,
+}
+
+impl DisplaySettings {
+    pub fn format_timestamp(&self, at: DateTime<Utc>) -> String {
+        at.with_timezone(&self.timezone)
+            .format("%Y-%m-%d %H:%M %Z")
+            .to_string()
+    }
+}
+
+// How hard the background tasks are kept alive before the process gives up on them
+#[derive(Clone, serde::Deserialize)]
+pub struct SupervisorSettings {
+    // Failures in a row, a task that stays up for a while before failing starts counting again
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_restarts: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub initial_backoff_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_milliseconds: u64,
+}
+
+// Bounds how long checking an issue's links can hold up publishing
+#[derive(Clone, serde::Deserialize)]
+pub struct LinkCheckSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    // For every link in an issue together
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub deadline_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_concurrency: usize,
+    // Only for local development and tests, where the linked pages are served locally
+    #[serde(default)]
+    pub allow_private_targets: bool,
+}
+
+// Lets the session cookie live alongside other apps' cookies on a shared domain
+#[derive(Clone, serde::Deserialize)]
+pub struct SessionSettings {
+    pub cookie_name: String,
+    pub cookie_path: String,
+    // Left out to scope the cookie to the host that set it
+    pub cookie_domain: Option<String>,
+    pub cookie_secure: bool,
+    // Changing the password skips asking for the current one this soon after signing in, 0 always
+    // asks for it
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub reauthentication_window_minutes: u32,
+}
+
+impl SessionSettings {
+    pub fn reauthentication_window(&self) -> Duration {
+        Duration::minutes(self.reauthentication_window_minutes.into())
+    }
+}
+
+// Domains are matched case-insensitively against everything after the `@`. An empty allowlist
+// lets every domain through that isn't denied.
+#[derive(Clone, serde::Deserialize)]
+pub struct SubscriptionSettings {
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    #[serde(default)]
+    pub denied_domains: Vec<String>,
+    // How long the link confirming a subscriber's new address stays valid
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub email_change_token_ttl_hours: u32,
+    // Where subscribers are sent once confirmed, left out to show our own page
+    #[serde(default, deserialize_with = "deserialize_absolute_url")]
+    pub confirmation_redirect_url: Option<Url>,
+    // Applied to the addresses people sign up or change to
+    #[serde(default)]
+    pub email_strictness: EmailStrictness,
+}
+
+// Only absolute http(s) URLs, anything else would send subscribers somewhere unexpected
+fn deserialize_absolute_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(url) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let parsed = Url::parse(&url)
+        .map_err(|e| serde::de::Error::custom(format!("'{url}' is not an absolute URL: {e}")))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(serde::de::Error::custom(format!(
+            "'{url}' is not an http(s) URL"
+        )));
+    }
+    Ok(Some(parsed))
+}
+
+impl SubscriptionSettings {
+    pub fn accepts_domain(&self, domain: &str) -> bool {
+        let listed = |domains: &[String]| domains.iter().any(|d| d.eq_ignore_ascii_case(domain));
+        !listed(&self.denied_domains)
+            && (self.allowed_domains.is_empty() || listed(&self.allowed_domains))
+    }
+}
+
+// The subscriber count shown on other sites, e.g. "Join 12,430 readers"
+#[derive(Clone, serde::Deserialize)]
+pub struct PublicStatsSettings {
+    // The counts are redone at most this often
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub refresh_seconds: u64,
+    // The subscriber count is rounded to the nearest multiple of this, 1 shows it exactly
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub round_to: u64,
+    // Origins whose pages may fetch the stats from a browser, e.g. "https://example.com"
+    pub allowed_origins: Vec<String>,
+}
+
+impl PublicStatsSettings {
+    pub fn refresh_interval(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.refresh_seconds as i64)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct CacheSettings {
+    // How long browsers and CDNs may reuse a public page without revalidating it
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_seconds: u64,
+}
+
+// Public metadata about the newsletter, shown in the archive feed
+#[derive(Clone, serde::Deserialize)]
+pub struct SiteSettings {
+    pub title: String,
+    pub subtitle: String,
+    pub author: String,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct DeliverySettings {
+    // How many subscribers are queued per transaction when an issue is published
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub enqueue_chunk_size: NonZeroUsize,
+    // Left out to send links exactly as they were written
+    pub utm_injection: Option<UtmSettings>,
+    // Whether the publish form starts with CSS inlining ticked
+    pub inline_css: bool,
+    // Guards against picking a sender the mail provider won't accept. Empty to allow every sender
+    // verified on the senders page.
+    #[serde(default)]
+    pub verified_senders: Vec<String>,
+    // How long the worker can go without a heartbeat before the health check reports it as down
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub worker_stale_after_seconds: u64,
+    // The queue metrics warn once a due delivery has waited longer than this
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub queue_age_warning_seconds: u64,
+}
+
+// Anything slower belongs in `max_emails_per_day`, and the wait between sends would overflow
+const MIN_EMAILS_PER_SECOND: f64 = 0.001;
+
+fn deserialize_emails_per_second<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(rate) = deserialize_option_number_from_string::<f64, D>(deserializer)? else {
+        return Ok(None);
+    };
+    if !(rate.is_finite() && rate >= MIN_EMAILS_PER_SECOND) {
+        return Err(serde::de::Error::custom(format!(
+            "max_emails_per_second must be at least {MIN_EMAILS_PER_SECOND}, not {rate}"
+        )));
+    }
+    Ok(Some(rate))
+}
+
+impl DeliverySettings {
+    pub fn worker_stale_after(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.worker_stale_after_seconds as i64)
+    }
+
+    pub fn accepts_sender(&self, email: &str) -> bool {
+        self.verified_senders.is_empty()
+            || self
+                .verified_senders
+                .iter()
+                .any(|sender| sender.eq_ignore_ascii_case(email))
+    }
+}
+
+// At most `per_second` deliveries to `domain`, or to each domain without its own limit for `*`
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct DomainRateLimit {
+    pub domain: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub per_second: f64,
+}
+
+// Deliveries only go out between `start` and `end` in `timezone`, e.g. 08:00 to 20:00 in
+// Europe/London. A window with `end` before `start` runs over midnight.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct SendingWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: Tz,
+}
+
+impl SendingWindow {
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.timezone).time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+// Google Analytics campaign parameters added to every external link in an issue
+#[derive(Clone, serde::Deserialize)]
+pub struct UtmSettings {
+    pub utm_source: String,
+    pub utm_medium: String,
+    pub utm_campaign: String,
+}
+
+// At most `max_issues` deliveries to a subscriber within any rolling `window_seconds`
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct FrequencyCap {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_issues: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct WebhookSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    // Deliveries that keep failing are dropped after this many attempts
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    // Optional CRM endpoint told about every confirmed subscriber
+    pub confirmation_callback: Option<CallbackSettings>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct CallbackSettings {
+    pub url: String,
+    pub secret: Secret<String>,
+}
+
+impl WebhookSettings {
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(self.timeout_milliseconds))
+            .build()
+            .unwrap()
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct NotificationSettings {
+    // Issues sent to fewer subscribers than this don't trigger a completion summary
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub completion_min_recipients: u64,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct LoginThrottleSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+    // Load balancers whose `X-Forwarded-For` is believed, empty to count the connecting address
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -21,6 +445,12 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub authorisation_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    // Requests to the mail API go out through this proxy, e.g. "http://proxy.internal:3128"
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<Secret<String>>,
+    // Without `proxy_url` the HTTPS_PROXY variable is used, off to ignore the environment
+    pub use_env_proxy: bool,
 }
 
 impl EmailClientSettings {
@@ -32,14 +462,39 @@ impl EmailClientSettings {
         std::time::Duration::from_millis(self.timeout_milliseconds)
     }
 
-    pub fn client(self) -> EmailClient {
+    // `proxy_url`, else HTTPS_PROXY (or https_proxy) when the environment may be used
+    pub fn proxy(&self) -> Result<Option<OutboundProxy>, String> {
+        let from_env = || {
+            ["HTTPS_PROXY", "https_proxy"]
+                .into_iter()
+                .find_map(|name| std::env::var(name).ok())
+                .filter(|url| !url.is_empty())
+        };
+        let url = match &self.proxy_url {
+            Some(url) => Some(url.clone()),
+            None if self.use_env_proxy => from_env(),
+            None => None,
+        };
+        url.map(|url| {
+            OutboundProxy::parse(
+                &url,
+                self.proxy_username.as_deref(),
+                self.proxy_password.as_ref(),
+            )
+        })
+        .transpose()
+    }
+
+    pub fn client(&self) -> EmailClient {
         let sender_email = self.sender().expect("Invalid sender email address.");
         let timeout = self.timeout();
-        EmailClient::new(
-            self.base_url,
+        let proxy = self.proxy().expect("Invalid email client proxy.");
+        EmailClient::with_proxy(
+            self.base_url.clone(),
             sender_email,
-            self.authorisation_token,
+            self.authorisation_token.clone(),
             timeout,
+            proxy,
         )
     }
 }
@@ -51,6 +506,28 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    // Serves an interactive Swagger UI for the OpenAPI spec at /api/docs
+    #[serde(default)]
+    pub swagger_ui: bool,
+    // Keeps /version behind the admin login instead of open to anyone
+    #[serde(default)]
+    pub version_admin_only: bool,
+    // Requests for any other Host are refused. Empty to only allow the host of `base_url`.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    #[serde(default)]
+    pub flash_store: FlashStore,
+}
+
+// Where a flash message waits for the page after a redirect
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashStore {
+    // A signed cookie, capped at 2KB and sent along with the next request
+    #[default]
+    Cookie,
+    // The visitor's session in Redis, any length and never leaves the server
+    Session,
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -62,6 +539,11 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    // Slow queries and lock waits are cancelled by Postgres after these limits
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub statement_timeout_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lock_timeout_milliseconds: u64,
 }
 
 impl DatabaseSettings {
@@ -79,10 +561,17 @@ impl DatabaseSettings {
             .ssl_mode(ssl_mode)
     }
     pub fn with_db(&self) -> PgConnectOptions {
-        let mut options = self.without_db().database(&self.database_name);
+        let mut options = self.with_timeouts(self.without_db().database(&self.database_name));
         options.log_statements(tracing::log::LevelFilter::Trace);
         options
     }
+
+    pub fn with_timeouts(&self, options: PgConnectOptions) -> PgConnectOptions {
+        options.options([
+            ("statement_timeout", self.statement_timeout_milliseconds),
+            ("lock_timeout", self.lock_timeout_milliseconds),
+        ])
+    }
 }
 
 pub enum Environment {
@@ -91,6 +580,8 @@ pub enum Environment {
 }
 
 impl Environment {
+    const ALL: [Environment; 2] = [Environment::Local, Environment::Production];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Environment::Local => "local",
@@ -102,34 +593,389 @@ impl Environment {
 impl TryFrom<String> for Environment {
     type Error = String;
     fn try_from(s: String) -> Result<Self, Self::Error> {
-        match s.to_lowercase().as_str() {
-            "local" => Ok(Self::Local),
-            "production" => Ok(Self::Production),
-            other => Err(format!(
-                "{other} is not a supported environment. Use either 'local' or 'production'."
-            )),
-        }
+        Self::ALL
+            .into_iter()
+            .find(|environment| environment.as_str() == s.to_lowercase())
+            .ok_or_else(|| {
+                let supported: Vec<&str> = Self::ALL.iter().map(Environment::as_str).collect();
+                format!(
+                    "{s} is not a supported environment. Use one of: {}.",
+                    supported.join(", ")
+                )
+            })
     }
 }
 
-pub fn get_configuration() -> Result<Settings, config::ConfigError> {
-    let mut settings = config::Config::default();
-    let base_path = std::env::current_dir().expect("Failed to determine the current directory");
-    let configuration_directory = base_path.join("configuration");
+// Relative to the working directory, overridden with `--config-dir` or `APP_CONFIG_DIR`
+pub const DEFAULT_CONFIGURATION_DIRECTORY: &str = "configuration";
+// Optional and ignored by git, for settings that only make sense on one machine, e.g. the port of a
+// second instance. `APP_CONFIG_LOCAL` points at a different file, which then has to exist.
+pub const LOCAL_OVERRIDES_FILE: &str = "overrides.yaml";
+// Variables that pick the layers rather than override a setting
+const LOADER_VARIABLES: [&str; 3] = ["APP_ENVIRONMENT", "APP_CONFIG_DIR", "APP_CONFIG_LOCAL"];
+// Used to be under `delivery`, still set there they would quietly stop applying
+const MOVED_TO_WORKER: [&str; 6] = [
+    "paused_domain_retry_seconds",
+    "frequency_cap",
+    "sending_window",
+    "domain_rate_limits",
+    "max_emails_per_second",
+    "max_emails_per_day",
+];
 
-    // Load base.yaml into the default settings
-    settings.merge(config::File::from(configuration_directory.join("base")).required(true))?;
+pub fn get_configuration(configuration_directory: &Path) -> Result<Settings, config::ConfigError> {
+    load_configuration(configuration_directory, std::env::vars())
+}
 
-    let environment: Environment = std::env::var("APP_ENVIRONMENT")
-        .unwrap_or_else(|_| "local".into())
+// Each layer overrides the ones before it: base.yaml, the environment's file, the local overrides,
+// then `APP_` environment variables such as `APP_DATABASE__PORT`. Takes the variables as an argument
+// so tests don't have to change the process's environment.
+pub fn load_configuration(
+    configuration_directory: &Path,
+    variables: impl IntoIterator<Item = (String, String)>,
+) -> Result<Settings, config::ConfigError> {
+    let variables: HashMap<String, String> = variables.into_iter().collect();
+    let environment: Environment = variables
+        .get("APP_ENVIRONMENT")
+        .cloned()
+        .unwrap_or_else(|| "local".into())
         .try_into()
-        .expect("Failed to parse APP_ENVIRONMENT.");
+        .map_err(config::ConfigError::Message)?;
+    let local_overrides = match variables.get("APP_CONFIG_LOCAL") {
+        Some(path) => configuration_directory.join(path),
+        None => configuration_directory.join(LOCAL_OVERRIDES_FILE),
+    };
 
-    settings.merge(
-        config::File::from(configuration_directory.join(environment.as_str())).required(true),
-    )?;
-
-    settings.merge(config::Environment::with_prefix("app").separator("__"))?;
+    let layers: Vec<(String, Box<dyn config::Source + Send + Sync>)> = vec![
+        (
+            "base.yaml".into(),
+            Box::new(config::File::from(configuration_directory.join("base")).required(true)),
+        ),
+        (
+            format!("{}.yaml", environment.as_str()),
+            Box::new(
+                config::File::from(configuration_directory.join(environment.as_str()))
+                    .required(true),
+            ),
+        ),
+        (
+            local_overrides.display().to_string(),
+            Box::new(
+                config::File::from(local_overrides.as_path())
+                    .required(variables.contains_key("APP_CONFIG_LOCAL")),
+            ),
+        ),
+        (
+            "environment variables".into(),
+            Box::new(EnvironmentOverrides::new(&variables)),
+        ),
+    ];
+    let mut settings = config::Config::default();
+    // Where each setting's final value came from, for working out why a value isn't what was expected
+    let mut origins = BTreeMap::new();
+    for (layer, source) in layers {
+        for (key, value) in flatten(source.collect()?) {
+            origins.insert(key, (layer.clone(), value));
+        }
+        settings.merge(vec![source])?;
+    }
+    for (key, (layer, value)) in &origins {
+        tracing::debug!(setting = %key, from = %layer, value = %value, "Loaded a setting");
+    }
+    for moved in MOVED_TO_WORKER {
+        let old_key = format!("delivery.{moved}");
+        if let Some((_, (layer, _))) = origins
+            .iter()
+            .find(|(key, _)| **key == old_key || key.starts_with(&format!("{old_key}.")))
+        {
+            return Err(config::ConfigError::Message(format!(
+                "{old_key}, set in {layer}, is now worker.{moved}"
+            )));
+        }
+    }
 
     settings.try_into()
 }
+
+// `APP_DATABASE__PORT=5433` sets `database.port`
+#[derive(Clone, Debug)]
+struct EnvironmentOverrides(HashMap<String, config::Value>);
+
+impl EnvironmentOverrides {
+    fn new(variables: &HashMap<String, String>) -> Self {
+        let origin = "the environment".to_string();
+        let overrides = variables
+            .iter()
+            .filter(|(name, _)| !LOADER_VARIABLES.contains(&name.to_uppercase().as_str()))
+            .filter_map(|(name, value)| {
+                let prefix = name.get(..4).filter(|p| p.eq_ignore_ascii_case("APP_"))?;
+                let key = name[prefix.len()..].replace("__", ".").to_lowercase();
+                Some((key, config::Value::new(Some(&origin), value.as_str())))
+            })
+            .collect();
+        Self(overrides)
+    }
+}
+
+impl config::Source for EnvironmentOverrides {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<HashMap<String, config::Value>, config::ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+// Dotted keys with their values as logged, secrets are redacted
+fn flatten(table: HashMap<String, config::Value>) -> Vec<(String, String)> {
+    let mut settings = Vec::new();
+    for (key, value) in table {
+        if let Ok(table) = value.clone().into_table() {
+            settings.extend(
+                flatten(table)
+                    .into_iter()
+                    .map(|(nested, value)| (format!("{key}.{nested}"), value)),
+            );
+        } else if let Ok(items) = value.clone().into_array() {
+            settings.push((key, format!("[{} items]", items.len())));
+        } else if is_secret(&key) {
+            settings.push((key, "[REDACTED]".into()));
+        } else {
+            settings.push((key, value.to_string()));
+        }
+    }
+    settings
+}
+
+// Matches every `Secret<String>` setting by name
+fn is_secret(key: &str) -> bool {
+    ["password", "secret", "token", "redis_uri"]
+        .iter()
+        .any(|word| key.contains(word))
+        || key.ends_with("key")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use chrono::{TimeZone, Utc};
+    use serde_aux::field_attributes::deserialize_number_from_string;
+
+    use super::{
+        DisplaySettings, EmailStrictness, Environment, SendingWindow, SubscriptionSettings,
+        WorkerSettings, deserialize_emails_per_second, flatten,
+    };
+
+    #[test]
+    fn an_unknown_environment_lists_the_supported_ones() {
+        let error = Environment::try_from("staging".to_string()).err().unwrap();
+
+        assert_eq!(
+            error,
+            "staging is not a supported environment. Use one of: local, production."
+        );
+    }
+
+    #[test]
+    fn secrets_are_redacted_when_settings_are_logged() {
+        let file = config::File::from_str(
+            "database:\n  port: 5432\n  password: hunter2\napplication:\n  hmac_secret: shh\n\
+            pii_encryption:\n  key: c2VjcmV0\n",
+            config::FileFormat::Yaml,
+        );
+
+        let mut settings = flatten(config::Source::collect(&file).unwrap());
+        settings.sort();
+
+        assert_eq!(
+            settings,
+            vec![
+                ("application.hmac_secret".into(), "[REDACTED]".into()),
+                ("database.password".into(), "[REDACTED]".into()),
+                ("database.port".into(), "5432".into()),
+                ("pii_encryption.key".into(), "[REDACTED]".into()),
+            ]
+        );
+    }
+
+    fn worker(yaml: &str) -> WorkerSettings {
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from_str(yaml, config::FileFormat::Yaml))
+            .unwrap();
+        settings.try_into().unwrap()
+    }
+
+    #[test]
+    fn worker_settings_left_out_keep_their_defaults() {
+        let settings = worker("embedded: false\nconcurrency: 4\nmax_emails_per_day: 500\n");
+
+        assert_eq!(
+            settings,
+            WorkerSettings {
+                embedded: false,
+                concurrency: 4,
+                max_emails_per_day: Some(500),
+                ..WorkerSettings::default()
+            }
+        );
+        assert_eq!(settings.poll_interval().as_secs(), 10);
+        assert_eq!(settings.error_backoff().as_millis(), 1000);
+        assert_eq!(settings.paused_domain_retry_seconds, 300);
+        assert_eq!(settings.sending_window, None);
+        assert!(settings.domain_rate_limits.is_empty());
+    }
+
+    #[test]
+    fn worker_settings_can_come_from_environment_strings() {
+        let settings = worker("poll_interval_milliseconds: \"250\"\n");
+
+        assert!(settings.embedded);
+        assert_eq!(settings.concurrency, 1);
+        assert_eq!(settings.poll_interval_milliseconds, 250);
+    }
+
+    #[test]
+    fn timestamps_are_shown_in_the_display_timezone() {
+        let display = DisplaySettings {
+            timezone: "Australia/Sydney".parse().unwrap(),
+        };
+        // Sydney is on daylight saving time, UTC+11, in January
+        let timestamp = Utc.with_ymd_and_hms(2025, 1, 10, 20, 30, 0).unwrap();
+
+        assert_eq!(display.format_timestamp(timestamp), "2025-01-11 07:30 AEDT");
+    }
+
+    #[test]
+    fn timestamps_are_shown_in_utc_by_default() {
+        let display = DisplaySettings {
+            timezone: "UTC".parse().unwrap(),
+        };
+        let timestamp = Utc.with_ymd_and_hms(2025, 1, 10, 20, 30, 0).unwrap();
+
+        assert_eq!(display.format_timestamp(timestamp), "2025-01-10 20:30 UTC");
+    }
+
+    fn window(start: &str, end: &str, timezone: &str) -> SendingWindow {
+        SendingWindow {
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+            timezone: timezone.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn deliveries_inside_the_window_are_not_held_back() {
+        let window = window("08:00", "20:00", "UTC");
+        let noon = Utc.with_ymd_and_hms(2025, 7, 10, 12, 0, 0).unwrap();
+
+        assert!(window.is_open(noon));
+    }
+
+    #[test]
+    fn deliveries_before_the_window_are_held_back() {
+        let window = window("08:00", "20:00", "UTC");
+        let three_am = Utc.with_ymd_and_hms(2025, 7, 10, 3, 0, 0).unwrap();
+
+        assert!(!window.is_open(three_am));
+    }
+
+    #[test]
+    fn deliveries_after_the_window_are_held_back() {
+        let window = window("08:00", "20:00", "UTC");
+        let ten_pm = Utc.with_ymd_and_hms(2025, 7, 10, 22, 0, 0).unwrap();
+
+        assert!(!window.is_open(ten_pm));
+    }
+
+    #[test]
+    fn the_window_is_in_the_configured_timezone() {
+        // 07:00 UTC is 09:00 in Berlin during summer time
+        let window = window("08:00", "20:00", "Europe/Berlin");
+        let seven_am_utc = Utc.with_ymd_and_hms(2025, 7, 10, 7, 0, 0).unwrap();
+        let five_am_utc = Utc.with_ymd_and_hms(2025, 7, 10, 5, 0, 0).unwrap();
+
+        assert!(window.is_open(seven_am_utc));
+        assert!(!window.is_open(five_am_utc));
+    }
+
+    #[test]
+    fn windows_can_run_over_midnight() {
+        let window = window("22:00", "06:00", "UTC");
+        let one_am = Utc.with_ymd_and_hms(2025, 7, 10, 1, 0, 0).unwrap();
+        let noon = Utc.with_ymd_and_hms(2025, 7, 10, 12, 0, 0).unwrap();
+
+        assert!(window.is_open(one_am));
+        assert!(!window.is_open(noon));
+    }
+
+    fn domains(allowed: &[&str], denied: &[&str]) -> SubscriptionSettings {
+        SubscriptionSettings {
+            allowed_domains: allowed.iter().map(|d| d.to_string()).collect(),
+            denied_domains: denied.iter().map(|d| d.to_string()).collect(),
+            email_change_token_ttl_hours: 48,
+            confirmation_redirect_url: None,
+            email_strictness: EmailStrictness::Lenient,
+        }
+    }
+
+    #[test]
+    fn empty_domain_lists_accept_everyone() {
+        assert!(domains(&[], &[]).accepts_domain("example.com"));
+    }
+
+    #[test]
+    fn denied_domains_are_rejected_regardless_of_case() {
+        let settings = domains(&[], &["mailinator.com"]);
+
+        assert!(!settings.accepts_domain("Mailinator.COM"));
+        assert!(settings.accepts_domain("example.com"));
+    }
+
+    #[test]
+    fn an_allowlist_rejects_every_other_domain() {
+        let settings = domains(&["corp.example"], &[]);
+
+        assert!(settings.accepts_domain("corp.example"));
+        assert!(!settings.accepts_domain("example.com"));
+    }
+
+    fn with_redirect(url: &str) -> Result<SubscriptionSettings, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "email_change_token_ttl_hours": 48,
+            "confirmation_redirect_url": url,
+        }))
+    }
+
+    #[test]
+    fn the_per_second_limit_must_be_a_usable_rate() {
+        let rate = |value: serde_json::Value| deserialize_emails_per_second(value);
+
+        assert_eq!(rate(serde_json::json!(10)).unwrap(), Some(10.0));
+        assert_eq!(rate(serde_json::json!("0.5")).unwrap(), Some(0.5));
+        assert_eq!(rate(serde_json::Value::Null).unwrap(), None);
+        assert!(rate(serde_json::json!(0)).is_err());
+        assert!(rate(serde_json::json!(1e-300)).is_err());
+        assert!(rate(serde_json::json!(-1)).is_err());
+    }
+
+    #[test]
+    fn an_empty_enqueue_chunk_is_rejected() {
+        let chunk_size = |value: serde_json::Value| -> Result<NonZeroUsize, _> {
+            deserialize_number_from_string(value)
+        };
+
+        assert_eq!(chunk_size(serde_json::json!("1000")).unwrap().get(), 1000);
+        assert!(chunk_size(serde_json::json!(0)).is_err());
+        assert!(chunk_size(serde_json::json!("0")).is_err());
+    }
+
+    #[test]
+    fn the_confirmation_redirect_must_be_an_absolute_url() {
+        assert!(with_redirect("https://example.com/thank-you").is_ok());
+        assert!(with_redirect("/thank-you").is_err());
+        assert!(with_redirect("javascript:alert(1)").is_err());
+    }
+}
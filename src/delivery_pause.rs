@@ -0,0 +1,51 @@
+use anyhow::Context;
+use redis::{AsyncCommands, aio::ConnectionManager};
+
+// A switch in Redis that holds back newsletter deliveries without taking the API down, e.g. while
+// the email provider is having an incident. The key is scoped to the database whose queue it holds
+// back, so deployments sharing a Redis don't pause each other.
+#[derive(Clone)]
+pub struct DeliveryPause {
+    connection: ConnectionManager,
+    key: String,
+}
+
+impl DeliveryPause {
+    pub async fn new(redis_uri: &str, database_name: &str) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri).context("Invalid Redis URI.")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis.")?;
+        Ok(Self {
+            connection,
+            key: format!("deliveries_paused:{database_name}"),
+        })
+    }
+
+    #[tracing::instrument(name = "Check whether deliveries are paused", skip(self))]
+    pub async fn is_paused(&self) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .exists(&self.key)
+            .await
+            .context("Failed to read the delivery pause from Redis.")
+    }
+
+    #[tracing::instrument(name = "Pause deliveries", skip(self))]
+    pub async fn pause(&self) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .set::<_, _, ()>(&self.key, 1)
+            .await
+            .context("Failed to store the delivery pause in Redis.")
+    }
+
+    #[tracing::instrument(name = "Resume deliveries", skip(self))]
+    pub async fn resume(&self) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(&self.key)
+            .await
+            .context("Failed to remove the delivery pause from Redis.")
+    }
+}
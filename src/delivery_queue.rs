@@ -0,0 +1,209 @@
+use std::num::NonZeroUsize;
+
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    delivery_throttle::recipient_domain,
+    domain::PiiCipher,
+    webhooks::{WebhookEvent, enqueue_webhook_event},
+};
+
+// Who an issue published now would go to, and who is left out
+pub struct RecipientCounts {
+    pub recipients: i64,
+    pub suppressed: i64,
+    pub unsubscribed: i64,
+}
+
+// Counts the same `newsletter_recipients` that enqueueing reads, without writing anything
+#[tracing::instrument(skip(pool))]
+pub async fn count_recipients(pool: &PgPool) -> Result<RecipientCounts, anyhow::Error> {
+    sqlx::query_as!(
+        RecipientCounts,
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM newsletter_recipients) AS "recipients!",
+            (
+                SELECT COUNT(*) FROM subscriptions s
+                WHERE s.status = 'confirmed'
+                  AND EXISTS (
+                      SELECT 1 FROM suppressed_emails e
+                      WHERE e.email = lower(s.email) OR e.email_hash = s.email_hash
+                  )
+            ) AS "suppressed!",
+            (SELECT COUNT(*) FROM subscriptions WHERE status = 'unsubscribed') AS "unsubscribed!"
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count the issue's recipients.")
+}
+
+// Queues a delivery for every one of the `newsletter_recipients`, `chunk_size` at a time with each
+// chunk in its own transaction, so a big list never holds one huge transaction open. Returns the
+// issue's audience once every chunk is in.
+#[tracing::instrument(skip(pool, pii_cipher))]
+pub async fn enqueue_deliveries(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    newsletter_issue_id: Uuid,
+    chunk_size: NonZeroUsize,
+) -> Result<u64, anyhow::Error> {
+    while enqueue_next_chunk(pool, pii_cipher, newsletter_issue_id, chunk_size).await? {}
+    let issue = sqlx::query!(
+        r#"SELECT estimated_audience FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to read the issue's audience.")?;
+    Ok(issue.estimated_audience as u64)
+}
+
+// Picks up issues whose enqueueing was cut short, e.g. by a crash halfway through publishing
+#[tracing::instrument(skip(pool, pii_cipher))]
+pub async fn resume_interrupted_enqueues(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    chunk_size: NonZeroUsize,
+) -> Result<(), anyhow::Error> {
+    let issues = sqlx::query!(
+        r#"SELECT newsletter_issue_id FROM newsletter_issues WHERE status = 'enqueuing'"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look for issues that are still enqueuing.")?;
+    for issue in issues {
+        tracing::info!(
+            newsletter_issue_id = %issue.newsletter_issue_id,
+            "Resuming an interrupted enqueue."
+        );
+        enqueue_deliveries(pool, pii_cipher, issue.newsletter_issue_id, chunk_size).await?;
+    }
+    Ok(())
+}
+
+// `false` once the issue has left 'enqueuing'. The issue row is locked for the chunk, so a resumed
+// enqueue and the original one never queue the same chunk twice. Addresses are only decrypted for
+// their domain, queued for the throttle, a recipient whose address can't be is left out and
+// audited rather than failing the chunk over and over.
+async fn enqueue_next_chunk(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    newsletter_issue_id: Uuid,
+    chunk_size: NonZeroUsize,
+) -> Result<bool, anyhow::Error> {
+    let chunk_size = chunk_size.get();
+    let mut transaction = pool.begin().await?;
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, enqueue_cursor, estimated_audience, published_by
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'enqueuing'
+        FOR UPDATE
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to lock the issue being enqueued.")?;
+    let Some(issue) = issue else {
+        return Ok(false);
+    };
+    let chunk = sqlx::query!(
+        r#"
+        SELECT id AS "id!", email, email_encrypted
+        FROM newsletter_recipients
+        WHERE $1::uuid IS NULL OR id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        issue.enqueue_cursor,
+        chunk_size as i64
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to read a chunk of recipients.")?;
+    // Only the domain is queued with each recipient, the worker reads their address when it sends
+    let mut subscriber_ids = Vec::with_capacity(chunk.len());
+    let mut domains = Vec::with_capacity(chunk.len());
+    for recipient in &chunk {
+        match pii_cipher.reveal(recipient.email.clone(), recipient.email_encrypted.clone()) {
+            Ok(email) => {
+                subscriber_ids.push(recipient.id);
+                domains.push(recipient_domain(&email).unwrap_or_default());
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.message = %e,
+                    subscriber_id = %recipient.id,
+                    "Failed to decrypt a recipient's email. Leaving them out of the issue.",
+                );
+                record_audit_event(
+                    &mut transaction,
+                    issue.published_by,
+                    "issue.recipient_skipped",
+                    &newsletter_issue_id.to_string(),
+                    Some(&recipient.id.to_string()),
+                )
+                .await
+                .context("Failed to audit a recipient left out of the issue.")?;
+            }
+        }
+    }
+    let n_enqueued = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_id, recipient_domain)
+        SELECT $1, * FROM UNNEST($2::uuid[], $3::text[])
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        &subscriber_ids,
+        &domains
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to enqueue a chunk of deliveries.")?
+    .rows_affected();
+    let audience = issue.estimated_audience + n_enqueued as i32;
+    let last_id = chunk.last().map(|recipient| recipient.id);
+    // A short chunk means every subscriber has been read
+    let finished = chunk.len() < chunk_size;
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            estimated_audience = $2,
+            enqueue_cursor = CASE WHEN $3 THEN NULL ELSE $4::uuid END,
+            -- Nobody to deliver to means the worker will never get to mark it as sent
+            status = CASE WHEN NOT $3::bool THEN 'enqueuing' WHEN $2 > 0 THEN 'sending' ELSE 'sent' END,
+            completed_at = CASE WHEN $3 AND $2 = 0 THEN now() END
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        audience,
+        finished,
+        last_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to record the enqueue's progress.")?;
+    if finished {
+        let event = WebhookEvent::IssuePublished {
+            newsletter_issue_id,
+            title: issue.title,
+            recipients: audience as u64,
+        };
+        enqueue_webhook_event(&mut transaction, &event)
+            .await
+            .context("Failed to enqueue the issue.published webhook event")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit a chunk of deliveries.")?;
+    Ok(!finished)
+}
@@ -0,0 +1,127 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::configuration::DomainRateLimit;
+
+// Spaces out deliveries to the same recipient domain so big providers don't start deferring us.
+// Lives in the worker's memory, a restart lets every domain send straight away again.
+pub struct DeliveryThrottle {
+    limits: Vec<DomainRateLimit>,
+    // When each recently sent to domain may be sent to again
+    next_send_at: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl DeliveryThrottle {
+    pub fn new(limits: &[DomainRateLimit]) -> Self {
+        Self {
+            limits: limits.to_vec(),
+            next_send_at: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Domains that can't be sent to at `now`, their deliveries are left queued for later
+    pub fn saturated_domains(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut next_send_at = self.next_send_at.lock().unwrap();
+        next_send_at.retain(|_, at| *at > now);
+        next_send_at.keys().cloned().collect()
+    }
+
+    // How long until the first saturated domain can be sent to again
+    pub fn wait(&self, now: DateTime<Utc>) -> Option<std::time::Duration> {
+        let next_send_at = self.next_send_at.lock().unwrap();
+        let earliest = next_send_at.values().filter(|at| **at > now).min()?;
+        (*earliest - now).to_std().ok()
+    }
+
+    pub fn record_send(&self, email: &str, now: DateTime<Utc>) {
+        let Some(domain) = recipient_domain(email) else {
+            return;
+        };
+        if let Some(interval) = self.interval(&domain) {
+            self.next_send_at
+                .lock()
+                .unwrap()
+                .insert(domain, now + interval);
+        }
+    }
+
+    // An exact match beats the `*` fallback, a domain matching neither isn't throttled
+    fn interval(&self, domain: &str) -> Option<Duration> {
+        let limit = self
+            .limits
+            .iter()
+            .find(|l| l.domain.eq_ignore_ascii_case(domain))
+            .or_else(|| self.limits.iter().find(|l| l.domain == "*"))?;
+        if limit.per_second <= 0.0 {
+            return None;
+        }
+        Some(Duration::microseconds(
+            (1_000_000.0 / limit.per_second) as i64,
+        ))
+    }
+}
+
+pub fn recipient_domain(email: &str) -> Option<String> {
+    email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::DeliveryThrottle;
+    use crate::configuration::DomainRateLimit;
+
+    fn limit(domain: &str, per_second: f64) -> DomainRateLimit {
+        DomainRateLimit {
+            domain: domain.into(),
+            per_second,
+        }
+    }
+
+    #[test]
+    fn a_domain_is_saturated_until_its_interval_has_passed() {
+        let throttle = DeliveryThrottle::new(&[limit("gmail.com", 5.0)]);
+        let now = Utc.with_ymd_and_hms(2025, 7, 10, 12, 0, 0).unwrap();
+
+        throttle.record_send("someone@Gmail.com", now);
+
+        assert_eq!(throttle.saturated_domains(now), ["gmail.com"]);
+        assert_eq!(
+            throttle.wait(now),
+            Some(std::time::Duration::from_millis(200))
+        );
+        assert!(
+            throttle
+                .saturated_domains(now + Duration::milliseconds(200))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn the_fallback_limit_applies_to_each_domain_separately() {
+        let throttle = DeliveryThrottle::new(&[limit("gmail.com", 5.0), limit("*", 20.0)]);
+        let now = Utc.with_ymd_and_hms(2025, 7, 10, 12, 0, 0).unwrap();
+
+        throttle.record_send("someone@gmail.com", now);
+        throttle.record_send("someone@example.com", now);
+        throttle.record_send("someone@example.org", now);
+
+        let later = now + Duration::milliseconds(100);
+        assert_eq!(throttle.saturated_domains(later), ["gmail.com"]);
+    }
+
+    #[test]
+    fn domains_without_a_limit_are_never_saturated() {
+        let throttle = DeliveryThrottle::new(&[limit("gmail.com", 5.0)]);
+        let now = Utc.with_ymd_and_hms(2025, 7, 10, 12, 0, 0).unwrap();
+
+        throttle.record_send("someone@example.com", now);
+
+        assert!(throttle.saturated_domains(now).is_empty());
+        assert_eq!(throttle.wait(now), None);
+    }
+}
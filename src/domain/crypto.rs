@@ -0,0 +1,308 @@
+use std::sync::Arc;
+
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce, aead::Aead};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PiiError {
+    #[error("The value is encrypted but no pii_encryption key is configured.")]
+    NoKeys,
+    #[error("The value was encrypted with key {0}, which is no longer configured.")]
+    UnknownKey(String),
+    #[error("The value could not be decrypted.")]
+    Undecryptable,
+    #[error("Neither a plain text nor an encrypted value is stored.")]
+    Missing,
+}
+
+// What `encrypted` columns hold: `<key id>:<base64 of the nonce and ciphertext>`. Every value gets
+// a fresh nonce, so the same address never encrypts the same way twice and can't be looked up by
+// its ciphertext. That's what the email hash is for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedString(String);
+
+impl EncryptedString {
+    fn key_id(&self) -> &str {
+        self.0.split_once(':').map_or("", |(key_id, _)| key_id)
+    }
+}
+
+impl From<String> for EncryptedString {
+    fn from(stored: String) -> Self {
+        Self(stored)
+    }
+}
+
+impl AsRef<str> for EncryptedString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<EncryptedString> for String {
+    fn from(value: EncryptedString) -> Self {
+        value.0
+    }
+}
+
+// A value as it goes into its pair of columns, exactly one of the two is set
+#[derive(Debug)]
+pub struct StoredPii {
+    pub plain: Option<String>,
+    pub encrypted: Option<String>,
+}
+
+// One AES-256-GCM key, known by a fingerprint that's stored alongside everything it encrypts
+struct PiiKey {
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl PiiKey {
+    // 32 bytes, base64 encoded
+    fn parse(encoded: &Secret<String>) -> Result<PiiKey, String> {
+        let bytes = decode_key(encoded)?;
+        Ok(Self {
+            id: hex::encode(&Sha256::digest(&bytes)[..4]),
+            cipher: Aes256Gcm::new_from_slice(&bytes).expect("AES-256 takes a 32 byte key"),
+        })
+    }
+}
+
+fn decode_key(encoded: &Secret<String>) -> Result<Vec<u8>, String> {
+    let bytes = base64::decode(encoded.expose_secret().trim())
+        .map_err(|_| "A pii_encryption key is not valid base64.".to_string())?;
+    if bytes.len() != KEY_LENGTH {
+        return Err(format!(
+            "A pii_encryption key has to be {KEY_LENGTH} bytes, not {}.",
+            bytes.len()
+        ));
+    }
+    Ok(bytes)
+}
+
+struct PiiKeys {
+    current: PiiKey,
+    previous: Vec<PiiKey>,
+    email_hasher: Hmac<Sha256>,
+}
+
+// Encrypts subscriber emails and names with the current key and still reads anything encrypted
+// with a previous one, so keys can be rotated while `zero2prod encrypt-pii` catches up. The default
+// has no keys and leaves everything in plain text.
+#[derive(Clone, Default)]
+pub struct PiiCipher(Option<Arc<PiiKeys>>);
+
+// Only says which keys are in use, never the keys themselves
+impl std::fmt::Debug for PiiCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(keys) => f
+                .debug_struct("PiiCipher")
+                .field("current", &keys.current.id)
+                .field(
+                    "previous",
+                    &keys.previous.iter().map(|key| &key.id).collect::<Vec<_>>(),
+                )
+                .finish(),
+            None => f.write_str("PiiCipher(disabled)"),
+        }
+    }
+}
+
+impl PiiCipher {
+    pub fn new(
+        key: &Secret<String>,
+        previous_keys: &[Secret<String>],
+        hash_key: &Secret<String>,
+    ) -> Result<PiiCipher, String> {
+        let current = PiiKey::parse(key)?;
+        let previous = previous_keys
+            .iter()
+            .map(PiiKey::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        let email_hasher = <Hmac<Sha256> as Mac>::new_from_slice(&decode_key(hash_key)?)
+            .expect("HMAC takes a key of any length");
+        Ok(Self(Some(Arc::new(PiiKeys {
+            current,
+            previous,
+            email_hasher,
+        }))))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    // Prefixes every value encrypted with the current key, `None` without keys
+    pub fn current_key_prefix(&self) -> Option<String> {
+        self.0.as_ref().map(|keys| format!("{}:", keys.current.id))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Option<EncryptedString> {
+        let key = &self.0.as_ref()?.current;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = key
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .expect("Encrypting into memory doesn't fail");
+        let sealed = [nonce.as_slice(), &ciphertext].concat();
+        Some(EncryptedString(format!(
+            "{}:{}",
+            key.id,
+            base64::encode(sealed)
+        )))
+    }
+
+    pub fn decrypt(&self, value: &EncryptedString) -> Result<String, PiiError> {
+        let keys = self.0.as_ref().ok_or(PiiError::NoKeys)?;
+        let key_id = value.key_id();
+        let key = std::iter::once(&keys.current)
+            .chain(&keys.previous)
+            .find(|key| key.id == key_id)
+            .ok_or_else(|| PiiError::UnknownKey(key_id.to_owned()))?;
+        let sealed = value
+            .0
+            .split_once(':')
+            .and_then(|(_, sealed)| base64::decode(sealed).ok())
+            .filter(|sealed| sealed.len() > NONCE_LENGTH)
+            .ok_or(PiiError::Undecryptable)?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let plaintext = key
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| PiiError::Undecryptable)?;
+        String::from_utf8(plaintext).map_err(|_| PiiError::Undecryptable)
+    }
+
+    // Encrypted once keys are configured, as is until then
+    pub fn store(&self, value: &str) -> StoredPii {
+        match self.encrypt(value) {
+            Some(encrypted) => StoredPii {
+                plain: None,
+                encrypted: Some(encrypted.into()),
+            },
+            None => StoredPii {
+                plain: Some(value.to_owned()),
+                encrypted: None,
+            },
+        }
+    }
+
+    // Whichever of a value's two columns is set, rows written before encryption was turned on
+    // still have the plain text
+    pub fn reveal(
+        &self,
+        plain: Option<String>,
+        encrypted: Option<String>,
+    ) -> Result<String, PiiError> {
+        match (plain, encrypted) {
+            (Some(plain), _) => Ok(plain),
+            (None, Some(encrypted)) => self.decrypt(&EncryptedString(encrypted)),
+            (None, None) => Err(PiiError::Missing),
+        }
+    }
+
+    // Stands in for the address wherever it's compared, `None` without keys. Keyed with its own
+    // key so rotating the encryption key doesn't change it, and case-insensitive like suppressions.
+    pub fn email_hash(&self, email: &str) -> Option<String> {
+        let mut hasher = self.0.as_ref()?.email_hasher.clone();
+        hasher.update(email.to_lowercase().as_bytes());
+        Some(hex::encode(hasher.finalize().into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_none, assert_ok};
+    use secrecy::Secret;
+
+    use crate::domain::{EncryptedString, PiiCipher, PiiError};
+
+    fn key(byte: u8) -> Secret<String> {
+        Secret::new(base64::encode([byte; 32]))
+    }
+
+    fn cipher(current: u8, previous: &[u8]) -> PiiCipher {
+        let previous: Vec<_> = previous.iter().map(|byte| key(*byte)).collect();
+        PiiCipher::new(&key(current), &previous, &key(0)).unwrap()
+    }
+
+    #[test]
+    fn a_value_decrypts_to_what_was_encrypted() {
+        let cipher = cipher(1, &[]);
+        let encrypted = cipher.encrypt("ursula@example.com").unwrap();
+
+        assert_ne!(encrypted.as_ref(), "ursula@example.com");
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "ursula@example.com");
+    }
+
+    #[test]
+    fn the_same_value_never_encrypts_the_same_way_twice() {
+        let cipher = cipher(1, &[]);
+
+        assert_ne!(
+            cipher.encrypt("ursula@example.com"),
+            cipher.encrypt("ursula@example.com")
+        );
+    }
+
+    #[test]
+    fn values_under_a_previous_key_are_still_read() {
+        let encrypted = cipher(1, &[]).encrypt("Ursula").unwrap();
+
+        assert_eq!(cipher(2, &[1]).decrypt(&encrypted).unwrap(), "Ursula");
+        assert!(matches!(
+            cipher(2, &[]).decrypt(&encrypted),
+            Err(PiiError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn a_tampered_value_is_rejected() {
+        let cipher = cipher(1, &[]);
+        let mut encrypted: Vec<char> = String::from(cipher.encrypt("Ursula").unwrap())
+            .chars()
+            .collect();
+        // Inside the ciphertext, past the key id and nonce
+        encrypted[30] = if encrypted[30] == 'A' { 'B' } else { 'A' };
+        let tampered: String = encrypted.into_iter().collect();
+
+        assert!(matches!(
+            cipher.decrypt(&EncryptedString::from(tampered)),
+            Err(PiiError::Undecryptable)
+        ));
+    }
+
+    #[test]
+    fn the_email_hash_ignores_case_and_survives_a_key_rotation() {
+        let hash = cipher(1, &[]).email_hash("Ursula@Example.com");
+
+        assert_eq!(hash, cipher(2, &[1]).email_hash("ursula@example.com"));
+        assert_ne!(hash, cipher(1, &[]).email_hash("le.guin@example.com"));
+    }
+
+    #[test]
+    fn without_keys_values_stay_in_plain_text() {
+        let cipher = PiiCipher::default();
+
+        assert_none!(cipher.email_hash("ursula@example.com"));
+        let stored = cipher.store("Ursula");
+        assert_eq!(stored.plain.as_deref(), Some("Ursula"));
+        assert_ok!(cipher.reveal(stored.plain, stored.encrypted));
+    }
+
+    #[test]
+    fn a_key_has_to_be_32_bytes() {
+        let short = Secret::new(base64::encode([1u8; 16]));
+
+        assert_err!(PiiCipher::new(&short, &[], &key(0)));
+    }
+}
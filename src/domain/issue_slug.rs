@@ -0,0 +1,78 @@
+use uuid::Uuid;
+
+// URL path segment for an issue in the public archive, e.g. `hello-world`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueSlug(String);
+
+impl IssueSlug {
+    // Lowercases the title and joins its words with dashes. Titles with nothing left after that,
+    // like "!!!", fall back to the issue's id.
+    pub fn from_title(title: &str, newsletter_issue_id: Uuid) -> IssueSlug {
+        let slug = title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("-");
+        if slug.is_empty() {
+            Self(newsletter_issue_id.to_string())
+        } else {
+            Self(slug)
+        }
+    }
+
+    // Used to tell apart issues that share a title, the first one keeps the bare slug
+    pub fn with_suffix(&self, n: u32) -> IssueSlug {
+        Self(format!("{}-{n}", self.0))
+    }
+
+    // The number used by `with_suffix` to produce `other`, 1 for the bare slug itself
+    pub fn suffix_of(&self, other: &str) -> Option<u32> {
+        if other == self.0 {
+            return Some(1);
+        }
+        other.strip_prefix(&self.0)?.strip_prefix('-')?.parse().ok()
+    }
+}
+
+impl AsRef<str> for IssueSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_none, assert_some_eq};
+    use uuid::Uuid;
+
+    use crate::domain::IssueSlug;
+
+    #[test]
+    fn punctuation_and_spacing_collapse_into_single_dashes() {
+        let slug = IssueSlug::from_title("  Hello,   World! ", Uuid::new_v4());
+        assert_eq!(slug.as_ref(), "hello-world");
+    }
+
+    #[test]
+    fn unicode_letters_are_kept_and_lowercased() {
+        let slug = IssueSlug::from_title("Ünïcode Straße — 東京", Uuid::new_v4());
+        assert_eq!(slug.as_ref(), "ünïcode-straße-東京");
+    }
+
+    #[test]
+    fn a_title_without_any_letters_falls_back_to_the_id() {
+        let id = Uuid::new_v4();
+        let slug = IssueSlug::from_title("!!! ???", id);
+        assert_eq!(slug.as_ref(), id.to_string());
+    }
+
+    #[test]
+    fn suffixes_are_recognised_only_for_the_same_slug() {
+        let slug = IssueSlug::from_title("Hello world", Uuid::new_v4());
+        assert_some_eq!(slug.suffix_of("hello-world"), 1);
+        assert_some_eq!(slug.suffix_of(slug.with_suffix(3).as_ref()), 3);
+        assert_none!(slug.suffix_of("hello-worlds"));
+        assert_none!(slug.suffix_of("hello-world-again"));
+    }
+}
@@ -0,0 +1,69 @@
+// Label for grouping issues in the archive, e.g. `rust` or `release-notes`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueTag(String);
+
+impl IssueTag {
+    // Normalised like a slug so `Rust`, ` rust ` and `RUST` are the same tag. `None` when nothing
+    // is left.
+    pub fn parse(tag: &str) -> Option<IssueTag> {
+        let tag = tag
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("-");
+        (!tag.is_empty()).then_some(Self(tag))
+    }
+
+    // A comma separated list as typed into the publish form, duplicates dropped
+    pub fn parse_list(tags: &str) -> Vec<IssueTag> {
+        Self::parse_all(tags.split(','))
+    }
+
+    pub fn parse_all<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<IssueTag> {
+        let mut parsed: Vec<IssueTag> = Vec::new();
+        for tag in tags.into_iter().filter_map(Self::parse) {
+            if !parsed.contains(&tag) {
+                parsed.push(tag);
+            }
+        }
+        parsed
+    }
+}
+
+impl AsRef<str> for IssueTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<IssueTag> for String {
+    fn from(tag: IssueTag) -> Self {
+        tag.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_none;
+
+    use crate::domain::IssueTag;
+
+    #[test]
+    fn tags_are_lowercased_and_joined_with_dashes() {
+        let tag = IssueTag::parse("  Release Notes ").unwrap();
+        assert_eq!(tag.as_ref(), "release-notes");
+    }
+
+    #[test]
+    fn a_tag_without_any_letters_is_rejected() {
+        assert_none!(IssueTag::parse(" -- "));
+    }
+
+    #[test]
+    fn a_list_drops_blanks_and_duplicates() {
+        let tags = IssueTag::parse_list("Rust, backend,, rust ,BACKEND");
+        let tags: Vec<&str> = tags.iter().map(AsRef::as_ref).collect();
+        assert_eq!(tags, ["rust", "backend"]);
+    }
+}
@@ -1,7 +1,13 @@
+mod crypto;
+mod issue_slug;
+mod issue_tag;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
 
+pub use crypto::{EncryptedString, PiiCipher, PiiError, StoredPii};
+pub use issue_slug::IssueSlug;
+pub use issue_tag::IssueTag;
 pub use new_subscriber::NewSubscriber;
-pub use subscriber_email::SubscriberEmail;
+pub use subscriber_email::{EmailStrictness, SubscriberEmail};
 pub use subscriber_name::SubscriberName;
@@ -1,8 +1,45 @@
 use validator::validate_email;
 
-#[derive(Debug)]
+// Top level domains that are reserved and never deliver mail (RFC 2606 and RFC 6761)
+const RESERVED_TLDS: [&str; 5] = ["example", "invalid", "local", "localhost", "test"];
+
+#[derive(Clone, Debug)]
 pub struct SubscriberEmail(String);
 
+// How picky new addresses are checked. Addresses already stored are always parsed leniently, so
+// turning strict mode on doesn't stop deliveries to existing subscribers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailStrictness {
+    // Anything `validator` accepts, e.g. `ursula@localhost`
+    #[default]
+    Lenient,
+    // The domain also needs a dot and a top level domain that can receive mail
+    Strict,
+}
+
+impl EmailStrictness {
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        if *self == EmailStrictness::Lenient {
+            return Ok(());
+        }
+        let domain = email.domain();
+        let labels: Vec<&str> = domain.split('.').collect();
+        if labels.len() < 2 || labels.iter().any(|label| label.is_empty()) {
+            return Err(format!("'{email}' needs a domain like example.com."));
+        }
+        let tld = labels[labels.len() - 1].to_lowercase();
+        let plausible = tld.starts_with("xn--")
+            || (tld.chars().count() >= 2 && tld.chars().all(char::is_alphabetic));
+        if !plausible || RESERVED_TLDS.contains(&tld.as_str()) {
+            return Err(format!(
+                "'{email}' is not at a domain that can receive email."
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl SubscriberEmail {
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
         if validate_email(&s) {
@@ -11,6 +48,17 @@ impl SubscriberEmail {
             Err(format!("'{s}' is not a valid subscriber email."))
         }
     }
+
+    pub fn parse_with(s: String, strictness: EmailStrictness) -> Result<SubscriberEmail, String> {
+        let email = Self::parse(s)?;
+        strictness.check(&email)?;
+        Ok(email)
+    }
+
+    // Everything after the last `@`, which a valid address always has
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map_or("", |(_, domain)| domain)
+    }
 }
 
 impl std::fmt::Display for SubscriberEmail {
@@ -27,8 +75,8 @@ impl AsRef<str> for SubscriberEmail {
 
 #[cfg(test)]
 mod tests {
-    use super::SubscriberEmail;
-    use claim::assert_err;
+    use super::{EmailStrictness, SubscriberEmail};
+    use claim::{assert_err, assert_ok};
     use fake::{Fake, faker::internet::en::SafeEmail};
     use rand::{SeedableRng, rngs::StdRng};
 
@@ -65,6 +113,57 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn a_domain_without_a_dot_is_only_accepted_leniently() {
+        let email = || "ursula@localhost".to_string();
+        assert_ok!(SubscriberEmail::parse_with(
+            email(),
+            EmailStrictness::Lenient
+        ));
+        assert_err!(SubscriberEmail::parse_with(
+            email(),
+            EmailStrictness::Strict
+        ));
+    }
+
+    #[test]
+    fn reserved_top_level_domains_are_only_accepted_leniently() {
+        for email in [
+            "ursula@earthsea.test",
+            "ursula@mail.INVALID",
+            "ursula@box.local",
+        ] {
+            assert_ok!(SubscriberEmail::parse_with(
+                email.to_string(),
+                EmailStrictness::Lenient
+            ));
+            assert_err!(SubscriberEmail::parse_with(
+                email.to_string(),
+                EmailStrictness::Strict
+            ));
+        }
+    }
+
+    #[test]
+    fn numeric_top_level_domains_are_rejected_in_strict_mode() {
+        let email = "ursula@mail.123".to_string();
+        assert_err!(SubscriberEmail::parse_with(email, EmailStrictness::Strict));
+    }
+
+    #[test]
+    fn ordinary_and_internationalised_domains_pass_strict_mode() {
+        for email in [
+            "ursula@earthsea.org",
+            "ursula@mail.co.uk",
+            "ursula@xn--p1ai.xn--p1ai",
+        ] {
+            assert_ok!(SubscriberEmail::parse_with(
+                email.to_string(),
+                EmailStrictness::Strict
+            ));
+        }
+    }
+
     // Calls function default 100 times and if error is met narrows it down
     #[quickcheck_macros::quickcheck]
     fn valid_emails_are_parsed_successfully(valid_email: ValidEmailFixture) -> bool {
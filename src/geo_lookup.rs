@@ -0,0 +1,16 @@
+use std::net::IpAddr;
+
+// Where a client IP is, as an ISO 3166-1 alpha-2 country code such as "NZ". Plugged into
+// `Application::build` so a GeoIP database or service can be dropped in without touching signup.
+pub trait GeoLookup: Send + Sync {
+    fn country(&self, ip: IpAddr) -> Option<String>;
+}
+
+// The default: no provider, so signups only record their referer and `utm_source`
+pub struct NoGeoLookup;
+
+impl GeoLookup for NoGeoLookup {
+    fn country(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+}
@@ -1,16 +1,33 @@
+// Bounds the keys clients can make us store, see `idempotency_key_max_length`
+#[derive(Clone, Copy)]
+pub struct IdempotencyKeyMaxLength(pub usize);
+
 #[derive(Debug)]
 pub struct IdempotencyKey(String);
 
-impl TryFrom<String> for IdempotencyKey {
-    type Error = anyhow::Error;
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum IdempotencyKeyError {
+    #[error("The idempotency key cannot be empty")]
+    Empty,
+    #[error("The idempotency key must be at most {0} characters long")]
+    TooLong(usize),
+    #[error("The idempotency key can only contain printable characters")]
+    NotPrintable,
+}
 
-    fn try_from(s: String) -> Result<Self, Self::Error> {
+impl IdempotencyKey {
+    pub fn parse(
+        s: String,
+        max_length: IdempotencyKeyMaxLength,
+    ) -> Result<Self, IdempotencyKeyError> {
         if s.is_empty() {
-            anyhow::bail!("The idempotency key cannot be empty");
+            return Err(IdempotencyKeyError::Empty);
         }
-        let max_length = 50;
-        if s.len() >= max_length {
-            anyhow::bail!("The idempotency key must be shorter than {max_length} characters");
+        if s.chars().count() > max_length.0 {
+            return Err(IdempotencyKeyError::TooLong(max_length.0));
+        }
+        if s.chars().any(char::is_control) {
+            return Err(IdempotencyKeyError::NotPrintable);
         }
         Ok(Self(s))
     }
@@ -27,3 +44,45 @@ impl AsRef<str> for IdempotencyKey {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_ok;
+
+    use super::{IdempotencyKey, IdempotencyKeyError, IdempotencyKeyMaxLength};
+
+    const MAX_LENGTH: IdempotencyKeyMaxLength = IdempotencyKeyMaxLength(10);
+
+    #[test]
+    fn a_key_up_to_the_max_length_is_accepted() {
+        assert_ok!(IdempotencyKey::parse("ä".repeat(10), MAX_LENGTH));
+    }
+
+    #[test]
+    fn an_over_long_key_is_rejected() {
+        assert_eq!(
+            IdempotencyKey::parse("a".repeat(11), MAX_LENGTH).unwrap_err(),
+            IdempotencyKeyError::TooLong(10)
+        );
+    }
+
+    #[test]
+    fn an_empty_key_is_rejected() {
+        assert_eq!(
+            IdempotencyKey::parse(String::new(), MAX_LENGTH).unwrap_err(),
+            IdempotencyKeyError::Empty
+        );
+    }
+
+    #[test]
+    fn a_key_with_a_control_character_is_rejected() {
+        assert_eq!(
+            IdempotencyKey::parse("abc\u{0}def".into(), MAX_LENGTH).unwrap_err(),
+            IdempotencyKeyError::NotPrintable
+        );
+        assert_eq!(
+            IdempotencyKey::parse("abc\ndef".into(), MAX_LENGTH).unwrap_err(),
+            IdempotencyKeyError::NotPrintable
+        );
+    }
+}
@@ -1,5 +1,7 @@
 mod key;
 mod persistence;
 
-pub use key::IdempotencyKey;
-pub use persistence::{NextAction, get_saved_response, save_response, try_processing};
+pub use key::{IdempotencyKey, IdempotencyKeyError, IdempotencyKeyMaxLength};
+pub use persistence::{
+    IdempotencyWait, NextAction, get_saved_response, save_response, try_processing,
+};
@@ -1,5 +1,14 @@
-use actix_web::{HttpResponse, body::to_bytes, http::StatusCode};
-use sqlx::{PgPool, Postgres, Transaction, postgres::PgHasArrayType};
+use actix_web::{
+    HttpResponse,
+    body::to_bytes,
+    http::{
+        StatusCode,
+        header::{self, HeaderMap, HeaderName},
+    },
+};
+use std::time::Duration;
+
+use sqlx::{Executor, Postgres, Transaction, postgres::PgHasArrayType};
 use uuid::Uuid;
 
 use super::IdempotencyKey;
@@ -11,24 +20,136 @@ struct HeaderPairRecord {
     value: Vec<u8>,
 }
 
+// Every response we currently save is a bodiless redirect, so only its target gets persisted.
+// Anything else falls back to storing the full response.
+#[derive(Debug)]
+enum SavedResponse {
+    Redirect {
+        status_code: StatusCode,
+        location: String,
+    },
+    Full {
+        status_code: StatusCode,
+        headers: Vec<HeaderPairRecord>,
+        body: Vec<u8>,
+    },
+}
+
+// Headers that only make sense for the original connection or caller and must not be replayed
+const UNSAVED_HEADERS: [HeaderName; 9] = [
+    header::SET_COOKIE,
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+impl SavedResponse {
+    fn new(status_code: StatusCode, headers: &HeaderMap, body: &[u8]) -> Self {
+        if status_code.is_redirection() && body.is_empty() {
+            let location = headers
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok());
+            if let Some(location) = location {
+                return Self::Redirect {
+                    status_code,
+                    location: location.to_owned(),
+                };
+            }
+        }
+        let headers = headers
+            .iter()
+            .filter(|(name, _)| !UNSAVED_HEADERS.contains(name))
+            .map(|(name, value)| HeaderPairRecord {
+                name: name.as_str().to_owned(),
+                value: value.as_bytes().to_owned(),
+            })
+            .collect();
+        Self::Full {
+            status_code,
+            headers,
+            body: body.to_owned(),
+        }
+    }
+
+    fn into_response(self) -> HttpResponse {
+        match self {
+            Self::Redirect {
+                status_code,
+                location,
+            } => HttpResponse::build(status_code)
+                .insert_header((header::LOCATION, location))
+                .finish(),
+            Self::Full {
+                status_code,
+                headers,
+                body,
+            } => {
+                let mut response = HttpResponse::build(status_code);
+                for HeaderPairRecord { name, value } in headers {
+                    response.append_header((name, value));
+                }
+                // Setting the response body and returning a HttpResponse
+                response.body(body)
+            }
+        }
+    }
+}
+
+// How long a duplicate request waits on one still in flight, see `idempotency_wait_seconds`
+#[derive(Clone, Copy)]
+pub struct IdempotencyWait(pub Duration);
+
 pub enum NextAction {
-    StartProcessing(Box<Transaction<'static, Postgres>>),
+    StartProcessing,
     ReturnSavedResponse(HttpResponse),
+    // The request holding the key didn't finish within the wait
+    StillProcessing,
 }
 
+// Raised by Postgres when a lock or statement timeout gives up on the claim
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+const QUERY_CANCELED: &str = "57014";
+
 impl PgHasArrayType for HeaderPairRecord {
     fn array_type_info() -> sqlx::postgres::PgTypeInfo {
         sqlx::postgres::PgTypeInfo::with_name("_header_pair")
     }
 }
 
+// Claims the key in the caller's transaction. Every write the request makes belongs in that same
+// transaction, which is only committed once `save_response` has stored the response, so either all
+// of them persist or the key is still free for a retry.
+//
+// `user_id` is whoever the key belongs to: the signed in user, or the subscriber asking for their
+// confirmation email again.
+//
+// A duplicate of a request still in flight queues behind its uncommitted claim. It gives up after
+// `wait` rather than hanging on a request that is stuck, the connection's own timeouts apply again
+// once the claim is settled.
 pub async fn try_processing(
-    pool: &PgPool,
+    transaction: &mut Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    wait: IdempotencyWait,
 ) -> Result<NextAction, anyhow::Error> {
-    let mut transaction = pool.begin().await?;
-    let n_inserted_rows = sqlx::query!(
+    let timeouts = sqlx::query!(
+        r#"
+        SELECT
+            current_setting('lock_timeout') AS "lock_timeout!",
+            current_setting('statement_timeout') AS "statement_timeout!"
+        "#
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    // A zero timeout would turn waiting off altogether
+    let wait_milliseconds = wait.0.as_millis().max(1).to_string();
+    set_local_timeouts(transaction, &wait_milliseconds, &wait_milliseconds).await?;
+    let inserted = sqlx::query!(
         r#"
     INSERT INTO idempotency (
         user_id,
@@ -41,30 +162,69 @@ pub async fn try_processing(
         user_id,
         idempotency_key.as_ref()
     )
-    .execute(&mut transaction)
-    .await?
-    .rows_affected();
+    .execute(&mut *transaction)
+    .await;
+    let n_inserted_rows = match inserted {
+        Err(sqlx::Error::Database(e))
+            if matches!(
+                e.code().as_deref(),
+                Some(LOCK_NOT_AVAILABLE | QUERY_CANCELED)
+            ) =>
+        {
+            return Ok(NextAction::StillProcessing);
+        }
+        inserted => inserted?.rows_affected(),
+    };
+    set_local_timeouts(
+        transaction,
+        &timeouts.lock_timeout,
+        &timeouts.statement_timeout,
+    )
+    .await?;
     if n_inserted_rows > 0 {
-        Ok(NextAction::StartProcessing(Box::new(transaction)))
+        Ok(NextAction::StartProcessing)
     } else {
-        let saved_response = get_saved_response(pool, idempotency_key, user_id)
+        let saved_response = get_saved_response(&mut *transaction, idempotency_key, user_id)
             .await?
             .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
         Ok(NextAction::ReturnSavedResponse(saved_response))
     }
 }
 
-pub async fn get_saved_response(
-    pool: &PgPool,
+async fn set_local_timeouts(
+    transaction: &mut Transaction<'static, Postgres>,
+    lock_timeout: &str,
+    statement_timeout: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT
+            set_config('lock_timeout', $1, true) AS lock_timeout,
+            set_config('statement_timeout', $2, true) AS statement_timeout
+        "#,
+        lock_timeout,
+        statement_timeout
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_saved_response<'c, E>(
+    executor: E,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
-) -> Result<Option<HttpResponse>, anyhow::Error> {
+) -> Result<Option<HttpResponse>, anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
     let saved_response = sqlx::query!(
         r#"
     SELECT
         response_status_code as "response_status_code!",
-        response_headers as "response_headers!: Vec<HeaderPairRecord>",
-        response_body as "response_body!"
+        response_location,
+        response_headers as "response_headers: Vec<HeaderPairRecord>",
+        response_body
     FROM idempotency
     WHERE
         user_id = $1 AND
@@ -73,60 +233,172 @@ pub async fn get_saved_response(
         user_id,
         idempotency_key.as_ref()
     )
-    .fetch_optional(pool)
+    .fetch_optional(executor)
     .await?;
     if let Some(r) = saved_response {
         let status_code = StatusCode::from_u16(r.response_status_code.try_into()?)?;
-        let mut response = HttpResponse::build(status_code);
-        for HeaderPairRecord { name, value } in r.response_headers {
-            response.append_header((name, value));
-        }
-        // Setting the response body and returning a HttpResponse
-        Ok(Some(response.body(r.response_body)))
+        let saved_response = match r.response_location {
+            Some(location) => SavedResponse::Redirect {
+                status_code,
+                location,
+            },
+            None => SavedResponse::Full {
+                status_code,
+                headers: r.response_headers.unwrap_or_default(),
+                body: r.response_body.unwrap_or_default(),
+            },
+        };
+        Ok(Some(saved_response.into_response()))
     } else {
         Ok(None)
     }
 }
 
+// Leaves committing to the caller
 pub async fn save_response(
-    mut transaction: Transaction<'static, Postgres>,
+    transaction: &mut Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
     http_response: HttpResponse,
 ) -> Result<HttpResponse, anyhow::Error> {
     let (response_head, body) = http_response.into_parts();
     let body = to_bytes(body).await.map_err(|e| anyhow::anyhow!("{}", e))?;
-    let status_code = response_head.status().as_u16() as i16;
-    let headers = {
-        let mut h = Vec::with_capacity(response_head.headers().len());
-        for (name, value) in response_head.headers().iter() {
-            let name = name.as_str().to_owned();
-            let value = value.as_bytes().to_owned();
-            h.push(HeaderPairRecord { name, value });
+    let saved_response = SavedResponse::new(response_head.status(), response_head.headers(), &body);
+    match saved_response {
+        SavedResponse::Redirect {
+            status_code,
+            location,
+        } => {
+            sqlx::query!(
+                r#"
+                UPDATE idempotency
+                SET
+                    response_status_code = $3,
+                    response_location = $4
+                WHERE
+                    user_id = $1 AND
+                    idempotency_key = $2
+                "#,
+                user_id,
+                idempotency_key.as_ref(),
+                status_code.as_u16() as i16,
+                location,
+            )
+            .execute(&mut *transaction)
+            .await?;
         }
-        h
-    };
-    sqlx::query_unchecked!(
-        r#"
-        UPDATE idempotency
-        SET
-            response_status_code = $3,
-            response_headers = $4,
-            response_body = $5
-        WHERE
-            user_id = $1 AND
-            idempotency_key = $2
-        "#,
-        user_id,
-        idempotency_key.as_ref(),
-        status_code,
-        headers,
-        body.as_ref(),
-    )
-    .execute(&mut transaction)
-    .await?;
-    transaction.commit().await?;
-
+        SavedResponse::Full {
+            status_code,
+            headers,
+            body,
+        } => {
+            sqlx::query_unchecked!(
+                r#"
+                UPDATE idempotency
+                SET
+                    response_status_code = $3,
+                    response_headers = $4,
+                    response_body = $5
+                WHERE
+                    user_id = $1 AND
+                    idempotency_key = $2
+                "#,
+                user_id,
+                idempotency_key.as_ref(),
+                status_code.as_u16() as i16,
+                headers,
+                body,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
     let http_response = response_head.set_body(body).map_into_boxed_body();
     Ok(http_response)
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{
+        StatusCode,
+        header::{self, HeaderMap, HeaderValue},
+    };
+
+    use super::SavedResponse;
+
+    fn headers(pairs: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(name.clone(), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn a_bodiless_redirect_is_saved_as_its_location() {
+        let headers = headers(&[
+            (header::LOCATION, "/admin/newsletter"),
+            (header::SET_COOKIE, "_flash=stale"),
+        ]);
+
+        let saved = SavedResponse::new(StatusCode::SEE_OTHER, &headers, b"");
+
+        match saved {
+            SavedResponse::Redirect {
+                status_code,
+                location,
+            } => {
+                assert_eq!(status_code, StatusCode::SEE_OTHER);
+                assert_eq!(location, "/admin/newsletter");
+            }
+            SavedResponse::Full { .. } => panic!("Expected a redirect to be saved"),
+        }
+    }
+
+    #[test]
+    fn a_redirect_with_a_body_is_saved_in_full() {
+        let headers = headers(&[(header::LOCATION, "/admin/newsletter")]);
+
+        let saved = SavedResponse::new(StatusCode::SEE_OTHER, &headers, b"Redirecting");
+
+        assert!(matches!(saved, SavedResponse::Full { .. }));
+    }
+
+    #[test]
+    fn cookies_and_hop_by_hop_headers_are_not_saved() {
+        let headers = headers(&[
+            (header::CONTENT_TYPE, "text/plain"),
+            (header::SET_COOKIE, "_flash=stale"),
+            (header::CONNECTION, "keep-alive"),
+            (header::TRANSFER_ENCODING, "chunked"),
+        ]);
+
+        let saved = SavedResponse::new(StatusCode::OK, &headers, b"body");
+
+        match saved {
+            SavedResponse::Full { headers, body, .. } => {
+                let names: Vec<_> = headers.iter().map(|h| h.name.as_str()).collect();
+                assert_eq!(names, vec!["content-type"]);
+                assert_eq!(body, b"body");
+            }
+            SavedResponse::Redirect { .. } => panic!("Expected a full response to be saved"),
+        }
+    }
+
+    #[test]
+    fn a_replayed_redirect_carries_no_cookies() {
+        let headers = headers(&[
+            (header::LOCATION, "/admin/newsletter"),
+            (header::SET_COOKIE, "_flash=stale"),
+        ]);
+
+        let response = SavedResponse::new(StatusCode::SEE_OTHER, &headers, b"").into_response();
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "/admin/newsletter"
+        );
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+    }
+}
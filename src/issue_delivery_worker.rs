@@ -1,65 +1,306 @@
-use std::time::Duration;
+use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
+use css_inline::CSSInliner;
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, Postgres, Transaction};
 use tracing::{Span, field::display};
 use uuid::Uuid;
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
-    startup::get_connection_pool,
+    clock::Clock,
+    configuration::{
+        DeliverySettings, FrequencyCap, NotificationSettings, Settings, UtmSettings, WorkerSettings,
+    },
+    delivery_pause::DeliveryPause,
+    delivery_queue::resume_interrupted_enqueues,
+    delivery_throttle::{DeliveryThrottle, recipient_domain},
+    domain::{PiiCipher, SubscriberEmail},
+    email_client::{EmailClient, mailbox},
+    routes::{Rating, add_utm_parameters, feedback_link, rewrite_links, store_links},
+    sending_rate::SendingRate,
+    startup::{ApplicationBaseUrl, HmacSecret, get_connection_pool},
+    suppressions::is_suppressed,
+    webhooks::{WebhookEvent, enqueue_webhook_event},
+    worker_heartbeat::{Heartbeat, worker_id},
 };
 
 type PgTransaction = Transaction<'static, Postgres>;
+// The locked queue row's issue, subscriber and resend id
+type DequeuedTask = (PgTransaction, Uuid, Uuid, Option<Uuid>);
 
 struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    utm_injection: bool,
+    inline_css: bool,
+    // Set when the issue was published from a sender that is still verified
+    sender_email: Option<String>,
+    sender_name: Option<String>,
+    // Shown in place of the sender's display name
+    from_name: Option<String>,
+}
+
+impl NewsletterIssue {
+    fn sender_address(&self) -> Option<String> {
+        let email = self.sender_email.as_deref()?;
+        let name = self.from_name.as_deref().or(self.sender_name.as_deref());
+        Some(mailbox(name, email))
+    }
+
+    // The archive keeps the issue as it was written
+    fn inline_css(&mut self) {
+        match inline_css(&self.html_content) {
+            Ok(html_content) => self.html_content = html_content,
+            Err(e) => tracing::warn!(
+                error.message = %e,
+                "Failed to inline the issue's CSS. Sending it as written.",
+            ),
+        }
+    }
+
+    // Runs before click tracking, so the tracker redirects to the tagged URL
+    fn add_utm_parameters(&mut self, utm: &UtmSettings, base_url: &ApplicationBaseUrl) {
+        self.html_content = add_utm_parameters(&self.html_content, utm, base_url);
+    }
+
+    // Only the issue's own links are tracked, so this runs before anything else is appended
+    fn add_click_tracking(
+        &mut self,
+        base_url: &ApplicationBaseUrl,
+        delivery_id: Uuid,
+    ) -> Vec<String> {
+        let (html_content, links) = rewrite_links(&self.html_content, base_url, delivery_id);
+        self.html_content = html_content;
+        links
+    }
+
+    fn add_feedback_links(&mut self, up: &str, down: &str) {
+        self.html_content.push_str(&format!(
+            "<p>Was this issue useful? \
+            <a href=\"{up}\">Yes</a> / <a href=\"{down}\">No</a></p>"
+        ));
+        self.text_content.push_str(&format!(
+            "\n\nWas this issue useful?\nYes: {up}\nNo: {down}"
+        ));
+    }
+
+    // Goes last so the rest of the email has loaded by the time it is requested
+    fn add_open_tracking_pixel(&mut self, base_url: &ApplicationBaseUrl, delivery_id: Uuid) {
+        let pixel_url = base_url.join_path(&format!("track/open/{delivery_id}.gif"));
+        self.html_content.push_str(&format!(
+            "<img src=\"{pixel_url}\" \
+            width=\"1\" height=\"1\" alt=\"\" />"
+        ));
+    }
+}
+
+// Media queries can't be inlined, so they stay behind in the head for clients that support them
+pub fn inline_css(html: &str) -> Result<String, css_inline::InlineError> {
+    CSSInliner::options()
+        .keep_at_rules(true)
+        .build()
+        .inline(html)
 }
 
 pub enum ExecutionOutcome {
+    // An email went out to a subscriber
+    Delivered,
+    // Anything else that took a task off the queue or moved it along: a failed, skipped or
+    // deferred delivery, or a notification
     TaskCompleted,
     EmptyQueue,
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     skip_all,
     fields(
         newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty
+        subscriber_id=tracing::field::Empty,
+        recipient_domain=tracing::field::Empty
     ),
     err
 )]
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    notification_settings: &NotificationSettings,
+    delivery_settings: &DeliverySettings,
+    worker_settings: &WorkerSettings,
+    throttle: &DeliveryThrottle,
+    pause: &DeliveryPause,
+    sending_rate: &SendingRate,
+    pii_cipher: &PiiCipher,
+    clock: &dyn Clock,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
+    let now = clock.now();
+    // Paused by an admin, e.g. during an email provider incident. Notifications keep going out.
+    if pause.is_paused().await? {
+        return try_send_notification(pool, email_client).await;
+    }
+    // Issue deliveries stay queued until the sending window opens, notifications keep going out
+    if let Some(window) = &worker_settings.sending_window
+        && !window.is_open(now)
+    {
+        return try_send_notification(pool, email_client).await;
+    }
+    // The provider's daily limit, deliveries stay queued until the count starts over at midnight UTC
+    if sending_rate.daily_limit_reached(now).await? {
+        tracing::warn!("Reached the daily sending limit, holding deliveries until midnight UTC.");
+        return try_send_notification(pool, email_client).await;
+    }
+    // Deliveries to a domain that was sent to too recently wait their turn behind other domains
+    let saturated_domains = throttle.saturated_domains(now);
+    let task = dequeue_task(pool, now, &saturated_domains).await?;
+    // Notifications are only sent once there are no subscriber deliveries waiting
     if task.is_none() {
-        return Ok(ExecutionOutcome::EmptyQueue);
+        return try_send_notification(pool, email_client).await;
     }
-    let (transaction, issue_id, email) = task.unwrap();
-
+    let (transaction, issue_id, subscriber_id, resend_id) = task.unwrap();
     Span::current()
         .record("newsletter_issue_id", display(issue_id))
-        .record("subscriber_email", display(&email));
+        .record("subscriber_id", display(subscriber_id));
+    let Some(email) = get_subscriber_email(pool, pii_cipher, subscriber_id).await? else {
+        // Left queued it would be picked again on every pass, holding up everyone behind it
+        let delivery_id = get_delivery_id(pool, issue_id, subscriber_id)
+            .await?
+            .unwrap_or_else(Uuid::new_v4);
+        delete_task(
+            transaction,
+            issue_id,
+            subscriber_id,
+            delivery_id,
+            false,
+            clock.now(),
+        )
+        .await?;
+        complete_issue_if_done(pool, issue_id, notification_settings).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
 
+    Span::current().record(
+        "recipient_domain",
+        display(recipient_domain(&email).unwrap_or_default()),
+    );
+
+    // A subscription row may outlive a suppression, the suppression always wins
+    if is_suppressed(pool, &email).await? {
+        tracing::info!("Skipping a delivery to a suppressed address.");
+        skip_task(transaction, issue_id, subscriber_id).await?;
+        complete_issue_if_done(pool, issue_id, notification_settings).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    if is_domain_paused(pool, &email).await? {
+        tracing::info!("Deferring a delivery to a paused domain.");
+        defer_task(
+            transaction,
+            issue_id,
+            subscriber_id,
+            now,
+            worker_settings.paused_domain_retry_seconds,
+        )
+        .await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    if let Some(cap) = &worker_settings.frequency_cap
+        && let Some(wait_seconds) = frequency_cap_wait(pool, subscriber_id, cap, now).await?
+    {
+        tracing::info!("Deferring a delivery to a subscriber who has reached their cap.");
+        defer_task(transaction, issue_id, subscriber_id, now, wait_seconds).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    let delivery_id = get_delivery_id(pool, issue_id, subscriber_id)
+        .await?
+        .unwrap_or_else(Uuid::new_v4);
+    let mut delivered = false;
     match SubscriberEmail::parse(email.clone()) {
         Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
+            let mut issue = get_issue(pool, issue_id).await?;
+            if issue.inline_css {
+                issue.inline_css();
+            }
+            if issue.utm_injection
+                && let Some(utm) = &delivery_settings.utm_injection
             {
+                issue.add_utm_parameters(utm, base_url);
+            }
+            let links = issue.add_click_tracking(base_url, delivery_id);
+            store_links(pool, issue_id, &links).await?;
+            let up = feedback_link(
+                base_url,
+                &hmac_secret.0,
+                issue_id,
+                subscriber_id,
+                Rating::Up,
+            );
+            let down = feedback_link(
+                base_url,
+                &hmac_secret.0,
+                issue_id,
+                subscriber_id,
+                Rating::Down,
+            );
+            issue.add_feedback_links(&up, &down);
+            issue.add_open_tracking_pixel(base_url, delivery_id);
+            // Another worker may have taken the day's last send since the limit was checked
+            if !sending_rate.claim_send(now).await? {
+                tracing::warn!("Reached the daily sending limit, leaving the delivery queued.");
+                return Ok(ExecutionOutcome::TaskCompleted);
+            }
+            throttle.record_send(email.as_ref(), clock.now());
+            sending_rate.acquire().await;
+            // A resend asked for by an admin is a new email, not a retry of the first copy
+            let idempotency_key = match resend_id {
+                Some(resend_id) => resend_id.to_string(),
+                None => delivery_idempotency_key(issue_id, subscriber_id),
+            };
+            let sent = match issue.sender_address() {
+                Some(from) => {
+                    email_client
+                        .send_email_from(
+                            &from,
+                            &email,
+                            &issue.title,
+                            &issue.html_content,
+                            &issue.text_content,
+                            Some(&idempotency_key),
+                        )
+                        .await
+                }
+                None => {
+                    email_client
+                        .send_email(
+                            issue.from_name.as_deref(),
+                            &email,
+                            &issue.title,
+                            &issue.html_content,
+                            &issue.text_content,
+                            Some(&idempotency_key),
+                        )
+                        .await
+                }
+            };
+            if let Err(e) = sent {
                 tracing::error!(
                     error.message = %e,
                     "Failed to deliver issue to a confirmed subscriber. Skipping.",
                 );
+                // The failure is recorded either way, a send counted twice only ends the day early
+                if let Err(e) = sending_rate.release_send(now).await {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to give back an unsent email to the daily limit",
+                    );
+                }
+            } else {
+                delivered = true;
             }
         }
         Err(e) => {
@@ -69,9 +310,29 @@ pub async fn try_execute_task(
             );
         }
     }
-    delete_task(transaction, issue_id, &email).await?;
+    delete_task(
+        transaction,
+        issue_id,
+        subscriber_id,
+        delivery_id,
+        delivered,
+        clock.now(),
+    )
+    .await?;
+    complete_issue_if_done(pool, issue_id, notification_settings).await?;
 
-    Ok(ExecutionOutcome::TaskCompleted)
+    if delivered {
+        Ok(ExecutionOutcome::Delivered)
+    } else {
+        Ok(ExecutionOutcome::TaskCompleted)
+    }
+}
+
+// The same for every attempt at sending an issue to a subscriber, so the provider can tell a retry
+// from a new email even when we crashed before recording that the first attempt went through. Not
+// tied to the address, a subscriber who changes theirs in between still gets a single copy.
+pub fn delivery_idempotency_key(issue_id: Uuid, subscriber_id: Uuid) -> String {
+    hex::encode(Sha256::digest(format!("{issue_id}:{subscriber_id}")))
 }
 
 #[tracing::instrument(skip_all)]
@@ -79,10 +340,19 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content
-        FROM newsletter_issues
+        SELECT
+            i.title,
+            i.text_content,
+            i.html_content,
+            i.utm_injection,
+            i.inline_css,
+            s.email AS "sender_email?",
+            s.display_name AS "sender_name?",
+            i.from_name
+        FROM newsletter_issues i
+        LEFT JOIN senders s ON s.sender_id = i.sender_id AND s.verified_at IS NOT NULL
         WHERE
-            newsletter_issue_id = $1
+            i.newsletter_issue_id = $1
         "#,
         issue_id
     )
@@ -91,19 +361,84 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     Ok(issue)
 }
 
+// The queue only knows who the delivery is for, the address is read when it's sent. `None` when it
+// can't be decrypted, e.g. it was encrypted under a key that has since been dropped.
+#[tracing::instrument(skip_all)]
+async fn get_subscriber_email(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT email, email_encrypted FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await?;
+    match pii_cipher.reveal(row.email, row.email_encrypted) {
+        Ok(email) => Ok(Some(email)),
+        Err(e) => {
+            tracing::error!(
+                error.message = %e,
+                "Failed to decrypt a confirmed subscriber's email. Skipping.",
+            );
+            Ok(None)
+        }
+    }
+}
+
+// A resent delivery keeps its original id, so pixels in the earlier attempt still count
+#[tracing::instrument(skip_all)]
+async fn get_delivery_id(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT delivery_id
+    FROM newsletter_deliveries
+    WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_id = $2
+    "#,
+        issue_id,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.delivery_id))
+}
+
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+    now: DateTime<Utc>,
+    saturated_domains: &[String],
+) -> Result<Option<DequeuedTask>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let r = sqlx::query!(
         r#"
-    SELECT newsletter_issue_id, subscriber_email
+    SELECT newsletter_issue_id, subscriber_id AS "subscriber_id!", resend_id
     FROM issue_delivery_queue
+    WHERE
+        execute_after <= $1 AND
+        recipient_domain <> ALL($2) AND
+        -- Queued before deliveries were keyed by subscriber, waiting on `zero2prod encrypt-pii`
+        subscriber_id IS NOT NULL AND
+        -- Held back until every chunk is in, so the issue can't be marked as sent halfway
+        NOT EXISTS (
+            SELECT 1 FROM newsletter_issues i
+            WHERE
+                i.newsletter_issue_id = issue_delivery_queue.newsletter_issue_id AND
+                i.status = 'enqueuing'
+        )
     FOR UPDATE
     SKIP LOCKED
     LIMIT 1
     "#,
+        now,
+        saturated_domains
     )
     .fetch_optional(&mut transaction)
     .await?;
@@ -111,52 +446,491 @@ async fn dequeue_task(
         Ok(Some((
             transaction,
             r.newsletter_issue_id,
-            r.subscriber_email,
+            r.subscriber_id,
+            r.resend_id,
         )))
     } else {
         Ok(None)
     }
 }
 
+#[tracing::instrument(skip_all)]
+async fn is_domain_paused(pool: &PgPool, email: &str) -> Result<bool, anyhow::Error> {
+    let Some((_, domain)) = email.rsplit_once('@') else {
+        return Ok(false);
+    };
+    let row = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM paused_domains WHERE domain = $1) AS "paused!""#,
+        domain.to_lowercase()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.paused)
+}
+
+// How long until the subscriber drops back under the cap, `None` if they can be emailed now
+#[tracing::instrument(skip_all)]
+async fn frequency_cap_wait(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    cap: &FrequencyCap,
+    now: DateTime<Utc>,
+) -> Result<Option<u64>, anyhow::Error> {
+    let window = sqlx::query!(
+        r#"
+    SELECT
+        COUNT(*) AS "n_delivered!",
+        EXTRACT(EPOCH FROM MIN(attempted_at) + make_interval(secs => $2) - $3::timestamptz)::FLOAT8
+            AS wait_seconds
+    FROM newsletter_deliveries
+    WHERE
+        subscriber_id = $1 AND
+        status = 'delivered' AND
+        attempted_at > $3::timestamptz - make_interval(secs => $2)
+    "#,
+        subscriber_id,
+        cap.window_seconds as f64,
+        now
+    )
+    .fetch_one(pool)
+    .await?;
+    if window.n_delivered < cap.max_issues as i64 {
+        return Ok(None);
+    }
+    Ok(Some(
+        window.wait_seconds.unwrap_or(0.0).ceil().max(1.0) as u64
+    ))
+}
+
+// Leaves the task in the queue untouched apart from pushing it back, so it isn't counted as failed
+#[tracing::instrument(skip_all)]
+async fn defer_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    now: DateTime<Utc>,
+    delay_seconds: u64,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+    UPDATE issue_delivery_queue
+    SET execute_after = $3::timestamptz + make_interval(secs => $4)
+    WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_id = $2
+    "#,
+        issue_id,
+        subscriber_id,
+        now,
+        delay_seconds as f64
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+// Drops the task without recording a delivery attempt, there is nothing to retry
+#[tracing::instrument(skip_all)]
+async fn skip_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+    DELETE FROM issue_delivery_queue
+    WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_id = $2
+    "#,
+        issue_id,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 async fn delete_task(
     mut transaction: PgTransaction,
     issue_id: Uuid,
-    email: &str,
+    subscriber_id: Uuid,
+    delivery_id: Uuid,
+    delivered: bool,
+    attempted_at: DateTime<Utc>,
 ) -> Result<(), anyhow::Error> {
     sqlx::query!(
         r#"
     DELETE FROM issue_delivery_queue
     WHERE
         newsletter_issue_id = $1 AND
-        subscriber_email = $2
+        subscriber_id = $2
+    "#,
+        issue_id,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+    INSERT INTO newsletter_deliveries (
+        newsletter_issue_id,
+        subscriber_id,
+        status,
+        attempted_at,
+        delivery_id
+    )
+    VALUES ($1, $2, $3, $5, $4)
+    ON CONFLICT (newsletter_issue_id, subscriber_id) DO UPDATE
+    SET status = EXCLUDED.status, attempted_at = EXCLUDED.attempted_at
+    "#,
+        issue_id,
+        subscriber_id,
+        if delivered { "delivered" } else { "failed" },
+        delivery_id,
+        attempted_at
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET
+        n_delivered = n_delivered + CASE WHEN $2 THEN 1 ELSE 0 END,
+        n_failed = n_failed + CASE WHEN $2 THEN 0 ELSE 1 END
+    WHERE newsletter_issue_id = $1
     "#,
         issue_id,
-        email
+        delivered
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+// Runs after the task's transaction has committed, otherwise two workers finishing the last two
+// tasks at the same time would each still see the other's task and neither would mark the issue.
+#[tracing::instrument(skip_all)]
+async fn complete_issue_if_done(
+    pool: &PgPool,
+    issue_id: Uuid,
+    notification_settings: &NotificationSettings,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let completed = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'sent', completed_at = now()
+    WHERE
+        newsletter_issue_id = $1 AND
+        status = 'sending' AND
+        NOT EXISTS (
+            SELECT 1 FROM issue_delivery_queue WHERE newsletter_issue_id = $1
+        )
+    RETURNING
+        title,
+        n_delivered,
+        n_failed,
+        published_by,
+        EXTRACT(EPOCH FROM completed_at - published_at::timestamptz)::BIGINT as "duration_seconds!"
+    "#,
+        issue_id
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let Some(completed) = completed else {
+        return Ok(());
+    };
+    // One write per issue rather than one per delivery
+    sqlx::query!(
+        r#"
+    UPDATE subscriptions s
+    SET last_emailed_at = d.attempted_at
+    FROM newsletter_deliveries d
+    WHERE
+        d.newsletter_issue_id = $1 AND
+        d.status = 'delivered' AND
+        s.id = d.subscriber_id AND
+        (s.last_emailed_at IS NULL OR s.last_emailed_at < d.attempted_at)
+    "#,
+        issue_id
     )
     .execute(&mut transaction)
     .await?;
+    let event = WebhookEvent::IssueCompleted {
+        newsletter_issue_id: issue_id,
+        title: completed.title.clone(),
+        delivered: completed.n_delivered,
+        failed: completed.n_failed,
+    };
+    enqueue_webhook_event(&mut transaction, &event).await?;
+    let n_recipients = (completed.n_delivered + completed.n_failed) as u64;
+    if n_recipients < notification_settings.completion_min_recipients {
+        transaction.commit().await?;
+        return Ok(());
+    }
+    let recipient = match completed.published_by {
+        Some(user_id) => get_completion_notification_recipient(&mut transaction, user_id).await?,
+        None => None,
+    };
+    if let Some(recipient) = recipient {
+        let summary = format!(
+            "Issue: {}\nDelivered: {}\nFailed: {}\nDuration: {}s",
+            completed.title, completed.n_delivered, completed.n_failed, completed.duration_seconds
+        );
+        let html_summary = format!(
+            "<p>{}</p>",
+            htmlescape::encode_minimal(&summary).replace('\n', "<br />")
+        );
+        enqueue_notification(
+            &mut transaction,
+            &recipient,
+            &format!("Finished delivering \"{}\"", completed.title),
+            &html_summary,
+            &summary,
+        )
+        .await?;
+    }
     transaction.commit().await?;
     Ok(())
 }
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+#[tracing::instrument(skip_all)]
+async fn get_completion_notification_recipient(
+    transaction: &mut PgTransaction,
+    user_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT email
+    FROM users
+    WHERE user_id = $1 AND notify_on_completion
+    "#,
+        user_id
+    )
+    .fetch_optional(transaction)
+    .await?;
+    Ok(row.and_then(|r| r.email))
+}
+
+#[tracing::instrument(skip_all)]
+async fn enqueue_notification(
+    transaction: &mut PgTransaction,
+    recipient: &str,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO notification_queue (
+        notification_id,
+        recipient,
+        subject,
+        html_content,
+        text_content,
+        enqueued_at
+    )
+    VALUES ($1, $2, $3, $4, $5, now())
+    "#,
+        Uuid::new_v4(),
+        recipient,
+        subject,
+        html_content,
+        text_content
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, fields(recipient=tracing::field::Empty))]
+async fn try_send_notification(
+    pool: &PgPool,
+    email_client: &EmailClient,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let notification = sqlx::query!(
+        r#"
+    SELECT notification_id, recipient, subject, html_content, text_content
+    FROM notification_queue
+    ORDER BY enqueued_at
+    FOR UPDATE
+    SKIP LOCKED
+    LIMIT 1
+    "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let Some(notification) = notification else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("recipient", display(&notification.recipient));
+
+    match SubscriberEmail::parse(notification.recipient) {
+        Ok(recipient) => {
+            if let Err(e) = email_client
+                .send_email(
+                    None,
+                    &recipient,
+                    &notification.subject,
+                    &notification.html_content,
+                    &notification.text_content,
+                    Some(&notification.notification_id.to_string()),
+                )
+                .await
+            {
+                tracing::error!(
+                    error.message = %e,
+                    "Failed to deliver a notification. Skipping.",
+                );
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.message = %e,
+                "Skipping a notification. The recipient's address is invalid",
+            );
+        }
+    }
+    sqlx::query!(
+        r#"DELETE FROM notification_queue WHERE notification_id = $1"#,
+        notification.notification_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+// `settings` decides how the worker paces itself, `configuration` is only read for the connections
+// and the issue settings it shares with the API. `clock` is the one the API is built with when the
+// worker is embedded, so the health check and the heartbeats agree on the time.
+pub async fn run_worker_until_stopped(
+    settings: WorkerSettings,
+    configuration: Settings,
+    clock: Arc<dyn Clock>,
+) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database).await;
+    let pii_cipher = configuration.pii_cipher()?;
     let email_client = configuration.email_client.client();
-    worker_loop(&connection_pool, email_client).await
+    let base_url = ApplicationBaseUrl::parse(&configuration.application.base_url)?;
+    let hmac_secret = HmacSecret(configuration.application.hmac_secret);
+    let throttle = DeliveryThrottle::new(&settings.domain_rate_limits);
+    let pause = DeliveryPause::new(
+        configuration.redis_uri.expose_secret(),
+        &configuration.database.database_name,
+    )
+    .await?;
+    let sending_rate = SendingRate::new(
+        configuration.redis_uri.expose_secret(),
+        &configuration.database.database_name,
+        &settings,
+    )
+    .await?;
+    resume_interrupted_enqueues(
+        &connection_pool,
+        &pii_cipher,
+        configuration.delivery.enqueue_chunk_size,
+    )
+    .await?;
+    let heartbeat = Heartbeat::new(worker_id(), settings.heartbeat_interval());
+    // The loops share the throttle and sending rate, so together they keep to the same limits
+    let loops = (0..settings.concurrency.max(1)).map(|_| {
+        worker_loop(
+            &connection_pool,
+            &email_client,
+            &base_url,
+            &hmac_secret,
+            &configuration.notifications,
+            &configuration.delivery,
+            &settings,
+            &throttle,
+            &pause,
+            &sending_rate,
+            &pii_cipher,
+            &heartbeat,
+            clock.as_ref(),
+        )
+    });
+    futures_util::future::try_join_all(loops).await?;
+    Ok(())
 }
 
-async fn worker_loop(pool: &PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+#[allow(clippy::too_many_arguments)]
+async fn worker_loop(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    notification_settings: &NotificationSettings,
+    delivery_settings: &DeliverySettings,
+    worker_settings: &WorkerSettings,
+    throttle: &DeliveryThrottle,
+    pause: &DeliveryPause,
+    sending_rate: &SendingRate,
+    pii_cipher: &PiiCipher,
+    heartbeat: &Heartbeat,
+    clock: &dyn Clock,
+) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(pool, &email_client).await {
+        let outcome = try_execute_task(
+            pool,
+            email_client,
+            base_url,
+            hmac_secret,
+            notification_settings,
+            delivery_settings,
+            worker_settings,
+            throttle,
+            pause,
+            sending_rate,
+            pii_cipher,
+            clock,
+        )
+        .await;
+        if let Err(e) = heartbeat
+            .beat(pool, outcome.as_ref().ok(), clock.now())
+            .await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to record the worker's heartbeat"
+            );
+        }
+        match outcome {
+            // Deliveries to a throttled domain may still be waiting, so look again once it frees up
             Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+                if let Err(e) = resume_interrupted_enqueues(
+                    pool,
+                    pii_cipher,
+                    delivery_settings.enqueue_chunk_size,
+                )
+                .await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to resume an interrupted enqueue"
+                    );
+                }
+                let poll_interval = worker_settings.poll_interval();
+                let wait = throttle
+                    .wait(clock.now())
+                    .map_or(poll_interval, |w| w.min(poll_interval));
+                tokio::time::sleep(wait).await;
             }
-            // Most errors are transient/temporary, 1 second wait to reduce load on errors
+            // Most errors are transient/temporary, back off a little to reduce load on errors
             Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                tokio::time::sleep(worker_settings.error_backoff()).await;
             }
-            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Ok(ExecutionOutcome::Delivered | ExecutionOutcome::TaskCompleted) => {}
         }
     }
 }
@@ -1,11 +1,31 @@
+pub mod audit;
 pub mod authentication;
+pub mod build_info;
+pub mod clock;
 pub mod configuration;
+pub mod delivery_pause;
+pub mod delivery_queue;
+pub mod delivery_throttle;
 pub mod domain;
 pub mod email_client;
+pub mod geo_lookup;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod link_checker;
+pub mod mail_domain_checker;
+pub mod middleware;
+pub mod newsletter;
+pub mod pii_migration;
+pub mod public_stats;
+pub mod readiness;
 pub mod routes;
+pub mod sending_rate;
 pub mod session_state;
+pub mod shutdown;
 pub mod startup;
+pub mod supervisor;
+pub mod suppressions;
 pub mod telemetry;
 pub mod utils;
+pub mod webhooks;
+pub mod worker_heartbeat;
@@ -0,0 +1,261 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    StatusCode, Url,
+    dns::{Addrs, Resolve, Resolving},
+    redirect,
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::configuration::LinkCheckSettings;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    Ok,
+    // The server answered with a 4xx or 5xx
+    Broken(StatusCode),
+    TimedOut,
+    Unreachable,
+    // Points at a loopback, private or link-local address, which we never request
+    Private,
+}
+
+#[derive(Debug)]
+pub struct LinkReport {
+    pub url: String,
+    pub status: LinkStatus,
+}
+
+impl LinkReport {
+    pub fn describe(&self) -> String {
+        match &self.status {
+            LinkStatus::Ok => "OK".into(),
+            LinkStatus::Broken(status) => format!("returned {status}"),
+            LinkStatus::TimedOut => "timed out".into(),
+            LinkStatus::Unreachable => "could not be reached".into(),
+            LinkStatus::Private => "points at a private address and was not checked".into(),
+        }
+    }
+}
+
+// Checks the links in an issue before it goes out. Only error responses count against a link, a
+// server that is slow or unreachable might just be having a moment.
+// The links are written by whoever composes the issue, so requests never go to this machine or
+// its network, whether directly, through DNS or through a redirect.
+#[derive(Clone)]
+pub struct LinkChecker {
+    client: reqwest::Client,
+    max_concurrency: usize,
+    deadline: Duration,
+    allow_private_targets: bool,
+}
+
+impl LinkChecker {
+    pub fn new(settings: &LinkCheckSettings) -> Self {
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_millis(settings.timeout_milliseconds));
+        if !settings.allow_private_targets {
+            builder = builder.dns_resolver(Arc::new(PublicOnlyResolver)).redirect(
+                redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= MAX_REDIRECTS {
+                        attempt.stop()
+                    } else if is_private_target(attempt.url()) {
+                        attempt.error("Redirected to a private address")
+                    } else {
+                        attempt.follow()
+                    }
+                }),
+            );
+        }
+        Self {
+            client: builder.build().unwrap(),
+            max_concurrency: settings.max_concurrency.max(1),
+            deadline: Duration::from_millis(settings.deadline_milliseconds),
+            allow_private_targets: settings.allow_private_targets,
+        }
+    }
+
+    // One report per distinct URL, in the order they were given
+    #[tracing::instrument(name = "Check links", skip_all, fields(n_links = urls.len()))]
+    pub async fn check(&self, urls: &[String]) -> Vec<LinkReport> {
+        let mut unique: Vec<String> = Vec::new();
+        for url in urls {
+            if !unique.contains(url) {
+                unique.push(url.clone());
+            }
+        }
+        let permits = Arc::new(Semaphore::new(self.max_concurrency));
+        let mut checks = JoinSet::new();
+        let mut statuses: Vec<Option<LinkStatus>> = unique.iter().map(|_| None).collect();
+        for (i, url) in unique.iter().enumerate() {
+            if !self.allow_private_targets
+                && Url::parse(url).is_ok_and(|url| is_private_target(&url))
+            {
+                statuses[i] = Some(LinkStatus::Private);
+                continue;
+            }
+            let client = self.client.clone();
+            let permits = permits.clone();
+            let url = url.clone();
+            checks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                (i, check_link(&client, &url).await)
+            });
+        }
+        // Queued behind the concurrency limit, each link's own timeout doesn't bound the total
+        let deadline = tokio::time::sleep(self.deadline);
+        tokio::pin!(deadline);
+        let mut out_of_time = false;
+        loop {
+            tokio::select! {
+                result = checks.join_next() => match result {
+                    Some(Ok((i, status))) => statuses[i] = Some(status),
+                    Some(Err(_)) => {}
+                    None => break,
+                },
+                _ = &mut deadline => {
+                    tracing::warn!("Ran out of time checking links, the rest count as timed out.");
+                    checks.abort_all();
+                    out_of_time = true;
+                    break;
+                }
+            }
+        }
+        unique
+            .into_iter()
+            .zip(statuses)
+            .map(|(url, status)| LinkReport {
+                url,
+                status: status.unwrap_or(if out_of_time {
+                    LinkStatus::TimedOut
+                } else {
+                    LinkStatus::Unreachable
+                }),
+            })
+            .collect()
+    }
+}
+
+const MAX_REDIRECTS: usize = 10;
+
+// Only a literal address can be judged without a lookup, names are filtered by the resolver
+fn is_private_target(url: &Url) -> bool {
+    match url.host() {
+        Some(url::Host::Ipv4(ip)) => !is_public(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => !is_public(IpAddr::V6(ip)),
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        None => true,
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // Carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+// Drops any private address a name resolves to, the check happens on the address actually
+// connected to so a name can't pass a lookup and then change its answer
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Some servers don't answer HEAD properly, so an error there is double checked with a GET
+async fn check_link(client: &reqwest::Client, url: &str) -> LinkStatus {
+    match classify(client.head(url).send().await) {
+        LinkStatus::Broken(_) => classify(client.get(url).send().await),
+        status => status,
+    }
+}
+
+fn classify(response: Result<reqwest::Response, reqwest::Error>) -> LinkStatus {
+    match response {
+        Ok(response)
+            if response.status().is_client_error() || response.status().is_server_error() =>
+        {
+            LinkStatus::Broken(response.status())
+        }
+        Ok(_) => LinkStatus::Ok,
+        Err(e) if e.is_timeout() => LinkStatus::TimedOut,
+        Err(_) => LinkStatus::Unreachable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::is_private_target;
+
+    #[test]
+    fn links_to_this_machine_or_its_network_are_private() {
+        for url in [
+            "http://localhost/admin",
+            "http://127.0.0.1:8000/",
+            "http://10.0.0.1/",
+            "http://172.16.5.4/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+        ] {
+            assert!(is_private_target(&Url::parse(url).unwrap()), "{url}");
+        }
+    }
+
+    #[test]
+    fn links_elsewhere_are_checked() {
+        for url in [
+            "https://example.com/",
+            "http://93.184.216.34/",
+            "http://[2606:2800:220:1::]/",
+        ] {
+            assert!(!is_private_target(&Url::parse(url).unwrap()), "{url}");
+        }
+    }
+}
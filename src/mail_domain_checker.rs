@@ -0,0 +1,174 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use hickory_resolver::{
+    TokioAsyncResolver,
+    config::{ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::op::ResponseCode,
+};
+
+// Lookups are repeated for every subscriber at a domain, so answers are kept this long
+const CACHE_FOR: chrono::Duration = chrono::Duration::minutes(10);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Whether a domain has somewhere to deliver mail to: its MX hosts, or the domain's own address
+// when it has no MX record, which is what mail servers fall back to (RFC 5321 section 5.1).
+pub struct MailDomainChecker {
+    resolver: TokioAsyncResolver,
+    // Each domain's answer and when it was looked up. Only definite answers are kept, a lookup
+    // that timed out or couldn't reach a nameserver is tried again next time.
+    answers: Mutex<HashMap<String, (bool, DateTime<Utc>)>>,
+}
+
+enum Answer {
+    Deliverable,
+    Undeliverable,
+    // The nameserver couldn't be asked or didn't answer, which says nothing about the domain
+    Unknown,
+}
+
+impl Default for MailDomainChecker {
+    fn default() -> Self {
+        let (config, mut options) = hickory_resolver::system_conf::read_system_conf()
+            .unwrap_or_else(|_| (ResolverConfig::default(), ResolverOpts::default()));
+        options.timeout = LOOKUP_TIMEOUT;
+        Self::with_resolver(TokioAsyncResolver::tokio(config, options))
+    }
+}
+
+impl MailDomainChecker {
+    fn with_resolver(resolver: TokioAsyncResolver) -> Self {
+        Self {
+            resolver,
+            answers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[tracing::instrument(name = "Check a mail domain", skip(self))]
+    pub async fn resolves(&self, domain: &str, now: DateTime<Utc>) -> bool {
+        let domain = domain.to_lowercase();
+        if let Some(resolves) = self.cached(&domain, now) {
+            return resolves;
+        }
+        let resolves = match self.look_up(&domain).await {
+            Answer::Deliverable => true,
+            Answer::Undeliverable => false,
+            // Saying a subscriber can't be emailed because our own DNS is down would be wrong
+            Answer::Unknown => return true,
+        };
+        self.answers.lock().unwrap().insert(domain, (resolves, now));
+        resolves
+    }
+
+    async fn look_up(&self, domain: &str) -> Answer {
+        // A fully qualified name, so the resolver doesn't try the host's search domains
+        let name = format!("{}.", domain.trim_end_matches('.'));
+        match self.resolver.mx_lookup(name.as_str()).await {
+            // A single MX of "." is a domain saying it takes no mail at all (RFC 7505)
+            Ok(hosts) => {
+                if hosts.iter().all(|mx| mx.exchange().is_root()) {
+                    Answer::Undeliverable
+                } else {
+                    Answer::Deliverable
+                }
+            }
+            Err(e) if is_nonexistent_domain(&e) => Answer::Undeliverable,
+            Err(e) if is_no_records(&e) => match self.resolver.lookup_ip(name.as_str()).await {
+                Ok(addresses) if addresses.iter().next().is_some() => Answer::Deliverable,
+                Ok(_) => Answer::Undeliverable,
+                Err(e) if is_no_records(&e) => Answer::Undeliverable,
+                Err(e) => unknown(domain, &e),
+            },
+            Err(e) => unknown(domain, &e),
+        }
+    }
+
+    fn cached(&self, domain: &str, now: DateTime<Utc>) -> Option<bool> {
+        let mut answers = self.answers.lock().unwrap();
+        answers.retain(|_, (_, looked_up_at)| now - *looked_up_at < CACHE_FOR);
+        answers.get(domain).map(|(resolves, _)| *resolves)
+    }
+}
+
+fn unknown(domain: &str, e: &ResolveError) -> Answer {
+    tracing::warn!(error.message = %e, "Couldn't look up the mail hosts for {domain}");
+    Answer::Unknown
+}
+
+// The nameserver answered, and there's nothing of the type asked for (or nothing at all)
+fn is_no_records(e: &ResolveError) -> bool {
+    matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+fn is_nonexistent_domain(e: &ResolveError) -> bool {
+    matches!(
+        e.kind(),
+        ResolveErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NXDomain,
+            ..
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use chrono::{Duration, Utc};
+    use hickory_resolver::{
+        TokioAsyncResolver,
+        config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    };
+
+    use super::MailDomainChecker;
+
+    #[tokio::test]
+    async fn answers_are_cached_for_ten_minutes() {
+        let checker = MailDomainChecker::default();
+        let now = Utc::now();
+        // Nothing under .invalid ever resolves
+        assert!(!checker.resolves("example.invalid", now).await);
+        checker
+            .answers
+            .lock()
+            .unwrap()
+            .insert("example.invalid".into(), (true, now));
+
+        assert!(
+            checker
+                .resolves("EXAMPLE.invalid", now + Duration::minutes(9))
+                .await
+        );
+        assert!(
+            !checker
+                .resolves("example.invalid", now + Duration::minutes(10))
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn a_domain_without_mx_records_falls_back_to_its_address() {
+        let checker = MailDomainChecker::default();
+
+        // localhost never has an MX record but always has an address
+        assert!(checker.resolves("localhost", Utc::now()).await);
+    }
+
+    #[tokio::test]
+    async fn a_lookup_that_gets_no_answer_is_not_remembered() {
+        // Nothing listens on this port, so every query goes unanswered
+        let nameservers =
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::LOCALHOST)], 1, true);
+        let mut options = ResolverOpts::default();
+        options.timeout = std::time::Duration::from_millis(100);
+        options.attempts = 1;
+        let checker = MailDomainChecker::with_resolver(TokioAsyncResolver::tokio(
+            ResolverConfig::from_parts(None, vec![], nameservers),
+            options,
+        ));
+
+        assert!(checker.resolves("example.com", Utc::now()).await);
+        assert!(checker.answers.lock().unwrap().is_empty());
+    }
+}
@@ -1,54 +1,107 @@
-use std::fmt::{Debug, Display};
+use std::{path::PathBuf, sync::Arc};
 
-use tokio::task::JoinError;
+use clap::{Parser, Subcommand};
+use tracing::Instrument;
 
 use zero_to_prod::{
-    configuration::get_configuration,
-    issue_delivery_worker::run_worker_until_stopped,
-    startup::{Application, get_connection_pool},
-    telemetry::{get_subscriber, init_subscriber},
+    build_info::BUILD_INFO,
+    clock::{Clock, SystemClock},
+    configuration::{DEFAULT_CONFIGURATION_DIRECTORY, get_configuration},
+    geo_lookup::NoGeoLookup,
+    pii_migration::encrypt_stored_pii,
+    shutdown::{report_exit, shutdown_signal},
+    startup::{Application, get_connection_pool, spawn_background_tasks},
+    telemetry::{Redaction, get_subscriber, init_subscriber},
 };
 
+#[derive(Parser)]
+struct Cli {
+    /// Directory holding base.yaml and the per-environment configuration files
+    #[arg(long, env = "APP_CONFIG_DIR", default_value = DEFAULT_CONFIGURATION_DIRECTORY)]
+    config_dir: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Encrypt subscriber emails and names still stored in plain text or under a previous key,
+    /// match deliveries that still carry an address to their subscriber, then exit
+    EncryptPii {
+        /// Subscribers encrypted per transaction
+        #[arg(long, default_value_t = 500)]
+        batch_size: i64,
+    },
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
     // Subscriber receives all span and event data and decides how to process it for output
-    let subscriber = get_subscriber("zero_to_prod".into(), "info".into(), std::io::stdout);
+    let redaction = Redaction::default();
+    let subscriber = get_subscriber(
+        "zero_to_prod".into(),
+        "info".into(),
+        std::io::stdout,
+        redaction.clone(),
+    );
     init_subscriber(subscriber);
 
-    let configuration = get_configuration().expect("Failed to read configuration");
-    let connection_pool = get_connection_pool(&configuration.database).await;
-    let application = Application::build(configuration.clone(), connection_pool).await?;
+    if let Some(Command::EncryptPii { batch_size }) = cli.command {
+        let configuration =
+            get_configuration(&cli.config_dir).expect("Failed to read configuration");
+        redaction.set(configuration.telemetry.redacted_fields.clone());
+        let pii_cipher = configuration.pii_cipher()?;
+        let connection_pool = get_connection_pool(&configuration.database).await;
+        let report = encrypt_stored_pii(&connection_pool, &pii_cipher, batch_size).await?;
+        tracing::info!(
+            subscribers = report.subscribers,
+            suppressions = report.suppressions,
+            deliveries = report.deliveries,
+            "Encrypted the stored subscriber details."
+        );
+        return Ok(());
+    }
+
+    // The API and the embedded worker keep the same time
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    // So the lines logged while starting up say exactly which build it is
+    let (configuration, application) = async {
+        tracing::info!("Starting the API");
+        let configuration =
+            get_configuration(&cli.config_dir).expect("Failed to read configuration");
+        // The default redacted fields covered the logs written while loading it
+        redaction.set(configuration.telemetry.redacted_fields.clone());
+        let connection_pool = get_connection_pool(&configuration.database).await;
+        let application = Application::build(
+            configuration.clone(),
+            connection_pool,
+            clock.clone(),
+            Arc::new(NoGeoLookup),
+        )
+        .await?;
+        anyhow::Ok((configuration, application))
+    }
+    .instrument(BUILD_INFO.startup_span())
+    .await?;
+    let server = application.handle();
+    let tasks = spawn_background_tasks(&configuration, clock, application.supervisor());
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tasks
+        .worker
+        .unwrap_or_else(|| tokio::spawn(std::future::pending()));
 
     // Coordinate shutdown
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = tasks.webhook_dispatcher => report_exit("Webhook dispatcher", o),
+        o = tasks.stats_refresher => report_exit("Stats refresher", o),
+        _ = shutdown_signal() => tracing::info!("Shutting down"),
     };
+    // Lets in-flight requests finish when it was a background task that stopped
+    server.stop(true).await;
 
     Ok(())
 }
-
-// Error reporting, informs which component failed first, why it failed, and what the error was
-fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
-    match outcome {
-        Ok(Ok(())) => {
-            tracing::info!("{} has exited", task_name)
-        }
-        Ok(Err(e)) => {
-            tracing::error!(
-                error.name = %e,
-                    "{} failed",
-                    task_name
-            )
-        }
-        Err(e) => {
-            tracing::error!(
-                error.name = %e,
-                    "{}' task failed to complete",
-                    task_name
-            )
-        }
-    }
-}
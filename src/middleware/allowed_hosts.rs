@@ -0,0 +1,136 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{StatusCode, header::HOST},
+    web::Data,
+};
+use actix_web_lab::middleware::Next;
+
+use crate::{routes::error_response, startup::ApplicationBaseUrl};
+
+// The hosts this site may be reached at. Links built from a forged Host header would otherwise
+// point at whoever forged it.
+#[derive(Clone, Debug)]
+pub struct AllowedHosts(Vec<String>);
+
+impl AllowedHosts {
+    // Falls back to the host of the base URL when none are configured
+    pub fn new(allowed_hosts: Vec<String>, base_url: &ApplicationBaseUrl) -> Self {
+        let allowed_hosts = if allowed_hosts.is_empty() {
+            base_url
+                .url
+                .host_str()
+                .map(str::to_owned)
+                .into_iter()
+                .collect()
+        } else {
+            allowed_hosts
+        };
+        Self(
+            allowed_hosts
+                .into_iter()
+                .map(|host| host.to_lowercase())
+                .collect(),
+        )
+    }
+
+    // An allowed host without a port accepts any port, one with a port only that port
+    fn allows(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        let hostname = strip_port(&host);
+        self.0
+            .iter()
+            .any(|allowed| *allowed == host || *allowed == hostname)
+    }
+}
+
+// Probes reach the instance by its own address, which isn't one the site is published at
+fn is_health_check(path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    path == "/health_check" || path.starts_with("/health_check/")
+}
+
+pub async fn reject_unknown_hosts(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if is_health_check(req.path()) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    }
+    // HTTP/2 requests carry the host in the URI rather than a header
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()));
+    let allowed = match (req.app_data::<Data<AllowedHosts>>(), host) {
+        (Some(allowed_hosts), Some(host)) => allowed_hosts.allows(host),
+        (None, _) => true,
+        (Some(_), None) => false,
+    };
+    if !allowed {
+        tracing::warn!(host, "Rejected a request for an unknown host");
+        let response = error_response(
+            req.request(),
+            StatusCode::BAD_REQUEST,
+            "This site can't be reached at that address.",
+        );
+        return Ok(req.into_response(response));
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+// `example.com:8000` -> `example.com`, `[::1]:8000` -> `[::1]`
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((hostname, port))
+            if port.chars().all(|c| c.is_ascii_digit())
+                && (!hostname.contains(':') || hostname.ends_with(']')) =>
+        {
+            hostname
+        }
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AllowedHosts, strip_port};
+    use crate::startup::ApplicationBaseUrl;
+
+    fn allowed_hosts(hosts: &[&str]) -> AllowedHosts {
+        let base_url = ApplicationBaseUrl::parse("https://newsletter.example.com").unwrap();
+        AllowedHosts::new(hosts.iter().map(|h| h.to_string()).collect(), &base_url)
+    }
+
+    #[test]
+    fn the_port_is_stripped_from_hostnames_and_ip_addresses() {
+        assert_eq!(strip_port("example.com:8000"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:8000"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn the_base_url_host_is_allowed_when_none_are_configured() {
+        let allowed = allowed_hosts(&[]);
+        assert!(allowed.allows("newsletter.example.com"));
+        assert!(allowed.allows("Newsletter.Example.com:443"));
+        assert!(!allowed.allows("attacker.example"));
+    }
+
+    #[test]
+    fn a_configured_port_must_match() {
+        let allowed = allowed_hosts(&["example.com:8000", "www.example.com"]);
+        assert!(allowed.allows("example.com:8000"));
+        assert!(!allowed.allows("example.com:9000"));
+        assert!(!allowed.allows("example.com"));
+        assert!(allowed.allows("www.example.com:9000"));
+        assert!(!allowed.allows("newsletter.example.com"));
+    }
+}
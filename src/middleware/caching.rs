@@ -0,0 +1,100 @@
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, SET_COOKIE},
+    },
+    web::Data,
+};
+use actix_web_lab::middleware::Next;
+use sha2::{Digest, Sha256};
+
+use crate::{configuration::CacheSettings, utils::e500};
+
+// Adds a weak ETag and a public Cache-Control header to successful GETs, and answers with a bare
+// 304 when the client already holds the current version. Only wrap pages that look the same to
+// every visitor; anything that sets a cookie or its own Cache-Control is passed through untouched.
+pub async fn cache_public_pages(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let max_age_seconds = req
+        .app_data::<Data<CacheSettings>>()
+        .map(|settings| settings.max_age_seconds)
+        .unwrap_or(0);
+    let if_none_match = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let is_get = matches!(*req.method(), Method::GET | Method::HEAD);
+
+    let response = next.call(req).await?;
+    let headers = response.headers();
+    if !is_get
+        || response.status() != StatusCode::OK
+        || headers.contains_key(SET_COOKIE)
+        || headers.contains_key(CACHE_CONTROL)
+    {
+        return Ok(response.map_into_boxed_body());
+    }
+
+    let (req, response) = response.into_parts();
+    let (mut response, body) = response.into_parts();
+    let body = body::to_bytes(body)
+        .await
+        .map_err(|e| e500(e.into().to_string()))?;
+    let etag = weak_etag(&body);
+    let headers = response.headers_mut();
+    headers.insert(ETAG, etag.parse().unwrap());
+    headers.insert(
+        CACHE_CONTROL,
+        format!("public, max-age={max_age_seconds}")
+            .parse()
+            .unwrap(),
+    );
+
+    let response = if if_none_match.is_some_and(|tags| matches_etag(&tags, &etag)) {
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response.set_body(BoxBody::new(()))
+    } else {
+        response.set_body(BoxBody::new(body))
+    };
+    Ok(ServiceResponse::new(req, response))
+}
+
+fn weak_etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("W/\"{}\"", hex::encode(&digest[..16]))
+}
+
+// If-None-Match uses weak comparison and may list several tags, or `*` for any
+fn matches_etag(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_etag, weak_etag};
+
+    #[test]
+    fn the_same_body_always_gets_the_same_etag() {
+        assert_eq!(weak_etag(b"<p>Issue</p>"), weak_etag(b"<p>Issue</p>"));
+        assert_ne!(weak_etag(b"<p>Issue</p>"), weak_etag(b"<p>Issue 2</p>"));
+    }
+
+    #[test]
+    fn if_none_match_is_compared_weakly() {
+        let etag = weak_etag(b"body");
+        let strong = etag.trim_start_matches("W/");
+        assert!(matches_etag(&etag, &etag));
+        assert!(matches_etag(strong, &etag));
+        assert!(matches_etag(&format!("\"other\", {etag}"), &etag));
+        assert!(matches_etag("*", &etag));
+        assert!(!matches_etag("W/\"other\"", &etag));
+    }
+}
@@ -0,0 +1,90 @@
+use actix_web::{
+    HttpResponse,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method,
+        header::{
+            ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+            HeaderMap, HeaderValue, ORIGIN, VARY,
+        },
+    },
+    web::Data,
+};
+use actix_web_lab::middleware::Next;
+
+// Other sites whose pages may read a resource from the visitor's browser
+#[derive(Clone, Debug)]
+pub struct AllowedOrigins(Vec<String>);
+
+impl AllowedOrigins {
+    pub fn new(origins: Vec<String>) -> Self {
+        Self(
+            origins
+                .into_iter()
+                .map(|origin| origin.trim_end_matches('/').to_lowercase())
+                .collect(),
+        )
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.0
+            .iter()
+            .any(|allowed| allowed == &origin.to_lowercase())
+    }
+}
+
+// Lets the allowed origins make simple GET requests, answering their preflights here. Any other
+// origin gets the response without CORS headers, so the browser keeps it from the page.
+pub async fn allow_cross_origin_reads(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let origin = req
+        .headers()
+        .get(ORIGIN)
+        .filter(|origin| {
+            let allowed_origins = req.app_data::<Data<AllowedOrigins>>();
+            match (allowed_origins, origin.to_str()) {
+                (Some(allowed_origins), Ok(origin)) => allowed_origins.allows(origin),
+                _ => false,
+            }
+        })
+        .cloned();
+    if *req.method() == Method::OPTIONS {
+        let mut response = HttpResponse::NoContent().finish();
+        add_cors_headers(response.headers_mut(), origin);
+        return Ok(req.into_response(response));
+    }
+    let mut response = next.call(req).await?.map_into_boxed_body();
+    add_cors_headers(response.headers_mut(), origin);
+    Ok(response)
+}
+
+fn add_cors_headers(headers: &mut HeaderMap, origin: Option<HeaderValue>) {
+    // The answer depends on who is asking, shared caches must keep them apart
+    headers.append(VARY, HeaderValue::from_static("Origin"));
+    if let Some(origin) = origin {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.insert(
+            ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static("GET, OPTIONS"),
+        );
+        headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static("86400"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AllowedOrigins;
+
+    #[test]
+    fn origins_match_exactly_ignoring_case_and_a_trailing_slash() {
+        let origins = AllowedOrigins::new(vec!["https://Example.com/".into()]);
+
+        assert!(origins.allows("https://example.com"));
+        assert!(!origins.allows("http://example.com"));
+        assert!(!origins.allows("https://example.com:8443"));
+        assert!(!origins.allows("https://evil-example.com"));
+    }
+}
@@ -0,0 +1,7 @@
+mod allowed_hosts;
+mod caching;
+mod cors;
+
+pub use allowed_hosts::{AllowedHosts, reject_unknown_hosts};
+pub use caching::cache_public_pages;
+pub use cors::{AllowedOrigins, allow_cross_origin_reads};
@@ -0,0 +1,166 @@
+// How an issue's line in the inbox reads in the major clients, before it is sent. The limits are
+// what each client shows in a default desktop layout, a narrower window cuts it shorter.
+pub struct InboxPreviewSimulator {
+    // `None` shows the whole line
+    clients: Vec<(&'static str, Option<usize>)>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct InboxPreview {
+    pub client: &'static str,
+    pub preview: String,
+    pub truncated: bool,
+}
+
+impl Default for InboxPreviewSimulator {
+    fn default() -> Self {
+        Self {
+            clients: vec![
+                ("Gmail", Some(77)),
+                ("Outlook", Some(50)),
+                ("Apple Mail", None),
+            ],
+        }
+    }
+}
+
+impl InboxPreviewSimulator {
+    // Clients show the sender's address when there is no display name, and skip an empty preheader
+    pub fn preview(
+        &self,
+        from_name: Option<&str>,
+        from_email: &str,
+        subject: &str,
+        preheader: Option<&str>,
+    ) -> Vec<InboxPreview> {
+        let from = from_name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(from_email.trim());
+        let mut parts = vec![from, subject.trim()];
+        if let Some(preheader) = preheader.map(str::trim).filter(|p| !p.is_empty()) {
+            parts.push(preheader);
+        }
+        let line = parts.join(" – ");
+        self.clients
+            .iter()
+            .map(|(client, limit)| {
+                let (preview, truncated) = match limit {
+                    Some(limit) => truncate(&line, *limit),
+                    None => (line.clone(), false),
+                };
+                InboxPreview {
+                    client,
+                    preview,
+                    truncated,
+                }
+            })
+            .collect()
+    }
+}
+
+// Counts characters rather than bytes, the ellipsis takes up the last one
+fn truncate(line: &str, limit: usize) -> (String, bool) {
+    if line.chars().count() <= limit {
+        return (line.to_owned(), false);
+    }
+    let kept: String = line.chars().take(limit.saturating_sub(1)).collect();
+    (format!("{}…", kept.trim_end()), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InboxPreviewSimulator;
+
+    fn preview_for<'a>(
+        previews: &'a [super::InboxPreview],
+        client: &str,
+    ) -> &'a super::InboxPreview {
+        previews.iter().find(|p| p.client == client).unwrap()
+    }
+
+    #[test]
+    fn a_short_line_is_shown_whole_everywhere() {
+        let previews = InboxPreviewSimulator::default().preview(
+            Some("Ursula"),
+            "ursula@example.com",
+            "Issue 12",
+            Some("Dragons"),
+        );
+
+        for preview in &previews {
+            assert_eq!(preview.preview, "Ursula – Issue 12 – Dragons");
+            assert!(!preview.truncated);
+        }
+    }
+
+    #[test]
+    fn gmail_truncates_at_77_characters_and_outlook_at_50() {
+        let subject = "s".repeat(40);
+        let preheader = "p".repeat(40);
+
+        let previews = InboxPreviewSimulator::default().preview(
+            Some("Ursula"),
+            "ursula@example.com",
+            &subject,
+            Some(&preheader),
+        );
+
+        let gmail = preview_for(&previews, "Gmail");
+        assert!(gmail.truncated);
+        assert_eq!(gmail.preview.chars().count(), 77);
+        assert!(gmail.preview.ends_with("pp…"));
+        let outlook = preview_for(&previews, "Outlook");
+        assert!(outlook.truncated);
+        assert_eq!(outlook.preview.chars().count(), 50);
+        assert!(outlook.preview.starts_with(&format!("Ursula – {subject}")));
+        let apple_mail = preview_for(&previews, "Apple Mail");
+        assert!(!apple_mail.truncated);
+        assert_eq!(
+            apple_mail.preview,
+            format!("Ursula – {subject} – {preheader}")
+        );
+    }
+
+    #[test]
+    fn a_line_exactly_at_the_limit_is_not_truncated() {
+        // "U – " is 4 characters, 46 more make 50
+        let subject = "s".repeat(46);
+
+        let previews =
+            InboxPreviewSimulator::default().preview(Some("U"), "u@example.com", &subject, None);
+
+        let outlook = preview_for(&previews, "Outlook");
+        assert!(!outlook.truncated);
+        assert_eq!(outlook.preview.chars().count(), 50);
+    }
+
+    #[test]
+    fn the_sender_alone_can_push_the_subject_out_of_view() {
+        let from_name = "A".repeat(60);
+
+        let previews = InboxPreviewSimulator::default().preview(
+            Some(&from_name),
+            "a@example.com",
+            "Subject",
+            None,
+        );
+
+        let outlook = preview_for(&previews, "Outlook");
+        assert!(outlook.truncated);
+        assert_eq!(outlook.preview, format!("{}…", "A".repeat(49)));
+        assert!(!preview_for(&previews, "Gmail").truncated);
+    }
+
+    #[test]
+    fn the_address_stands_in_for_a_missing_name_and_an_empty_preheader_is_skipped() {
+        let previews = InboxPreviewSimulator::default().preview(
+            Some("  "),
+            "ursula@example.com",
+            "Issue 12",
+            Some(""),
+        );
+
+        assert_eq!(previews[0].preview, "ursula@example.com – Issue 12");
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::domain::PiiCipher;
+
+// What one run of `zero2prod encrypt-pii` changed
+#[derive(Debug, Default)]
+pub struct PiiMigrationReport {
+    pub subscribers: u64,
+    pub suppressions: u64,
+    // Queued and recorded deliveries that still had an address instead of a subscriber
+    pub deliveries: u64,
+}
+
+// Brings every stored subscriber up to the current key: plain text is encrypted and cleared, values
+// under a previous key are re-encrypted, and missing email hashes are filled in. Deliveries that
+// still carry an address are then matched to their subscriber by its hash. Runs `batch_size`
+// rows per transaction alongside the API and worker, and can be stopped and run again at any point.
+// Once it's done the previous keys can be dropped from the configuration.
+#[tracing::instrument(skip(pool, pii_cipher))]
+pub async fn encrypt_stored_pii(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    batch_size: i64,
+) -> Result<PiiMigrationReport, anyhow::Error> {
+    let Some(prefix) = pii_cipher.current_key_prefix() else {
+        anyhow::bail!("pii_encryption has to be configured to encrypt stored subscriber details.");
+    };
+    let mut report = PiiMigrationReport::default();
+    loop {
+        let n_migrated = encrypt_next_batch(pool, pii_cipher, &prefix, batch_size).await?;
+        if n_migrated == 0 {
+            break;
+        }
+        report.subscribers += n_migrated;
+        tracing::info!(
+            n_migrated = report.subscribers,
+            "Encrypted a batch of subscribers."
+        );
+    }
+    report.suppressions = hash_suppressions(pool, pii_cipher).await?;
+    report.deliveries = match_deliveries_to_subscribers(pool, pii_cipher).await?;
+    Ok(report)
+}
+
+async fn encrypt_next_batch(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    prefix: &str,
+    batch_size: i64,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            email,
+            email_encrypted,
+            name,
+            name_encrypted,
+            pending_email,
+            pending_email_encrypted
+        FROM subscriptions
+        WHERE
+            email IS NOT NULL OR
+            name IS NOT NULL OR
+            pending_email IS NOT NULL OR
+            email_hash IS NULL OR
+            NOT starts_with(email_encrypted, $1) OR
+            NOT starts_with(name_encrypted, $1) OR
+            NOT starts_with(pending_email_encrypted, $1)
+        ORDER BY id
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+        "#,
+        prefix,
+        batch_size
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to read a batch of subscribers to encrypt.")?;
+    for row in &rows {
+        let email = pii_cipher
+            .reveal(row.email.clone(), row.email_encrypted.clone())
+            .with_context(|| format!("Failed to read subscriber {}'s email.", row.id))?;
+        let name = pii_cipher
+            .reveal(row.name.clone(), row.name_encrypted.clone())
+            .with_context(|| format!("Failed to read subscriber {}'s name.", row.id))?;
+        let pending_email = match (
+            row.pending_email.clone(),
+            row.pending_email_encrypted.clone(),
+        ) {
+            (None, None) => None,
+            (plain, encrypted) => Some(pii_cipher.reveal(plain, encrypted).with_context(|| {
+                format!("Failed to read subscriber {}'s pending email.", row.id)
+            })?),
+        };
+        sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET
+                email = NULL,
+                email_encrypted = $2,
+                email_hash = $3,
+                name = NULL,
+                name_encrypted = $4,
+                pending_email = NULL,
+                pending_email_encrypted = $5
+            WHERE id = $1
+            "#,
+            row.id,
+            pii_cipher.encrypt(&email).map(String::from),
+            pii_cipher.email_hash(&email),
+            pii_cipher.encrypt(&name).map(String::from),
+            pending_email
+                .and_then(|pending_email| pii_cipher.encrypt(&pending_email))
+                .map(String::from)
+        )
+        .execute(&mut transaction)
+        .await
+        .with_context(|| format!("Failed to encrypt subscriber {}.", row.id))?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit a batch of encrypted subscribers.")?;
+    Ok(rows.len() as u64)
+}
+
+// Suppressions keep their address in plain text, they only need the hash to match encrypted
+// subscribers
+async fn hash_suppressions(pool: &PgPool, pii_cipher: &PiiCipher) -> Result<u64, anyhow::Error> {
+    let emails =
+        sqlx::query_scalar!(r#"SELECT email FROM suppressed_emails WHERE email_hash IS NULL"#)
+            .fetch_all(pool)
+            .await
+            .context("Failed to read the suppressions without an email hash.")?;
+    let email_hashes: Vec<String> = emails
+        .iter()
+        .filter_map(|email| pii_cipher.email_hash(email))
+        .collect();
+    let n_hashed = sqlx::query!(
+        r#"
+        UPDATE suppressed_emails e
+        SET email_hash = h.email_hash
+        FROM UNNEST($1::text[], $2::text[]) AS h(email, email_hash)
+        WHERE e.email = h.email
+        "#,
+        &emails,
+        &email_hashes
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the suppressions' email hashes.")?
+    .rows_affected();
+    Ok(n_hashed)
+}
+
+// Deliveries queued or recorded before they were keyed by subscriber, whose address only matches
+// an encrypted subscriber. Queued ones for an address nobody has anymore are dropped, recorded ones
+// stay counted without it.
+async fn match_deliveries_to_subscribers(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let emails = sqlx::query_scalar!(
+        r#"
+        SELECT subscriber_email AS "subscriber_email!"
+        FROM issue_delivery_queue
+        WHERE subscriber_id IS NULL
+        UNION
+        SELECT subscriber_email AS "subscriber_email!"
+        FROM newsletter_deliveries
+        WHERE subscriber_email IS NOT NULL
+        "#
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to read the deliveries that still have an address.")?;
+    let email_hashes: Vec<String> = emails
+        .iter()
+        .filter_map(|email| pii_cipher.email_hash(email))
+        .collect();
+    let n_queued = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue q
+        SET subscriber_id = s.id, subscriber_email = NULL
+        FROM UNNEST($1::text[], $2::text[]) AS h(email, email_hash)
+        JOIN subscriptions s ON s.email = h.email OR s.email_hash = h.email_hash
+        WHERE
+            q.subscriber_id IS NULL AND
+            q.subscriber_email = h.email AND
+            NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue o
+                WHERE o.newsletter_issue_id = q.newsletter_issue_id AND o.subscriber_id = s.id
+            )
+        "#,
+        &emails,
+        &email_hashes
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to match queued deliveries to their subscribers.")?
+    .rows_affected();
+    let n_dropped = sqlx::query!(r#"DELETE FROM issue_delivery_queue WHERE subscriber_id IS NULL"#)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to drop queued deliveries for addresses without a subscriber.")?
+        .rows_affected();
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_deliveries d
+        SET subscriber_id = s.id
+        FROM UNNEST($1::text[], $2::text[]) AS h(email, email_hash)
+        JOIN subscriptions s ON s.email = h.email OR s.email_hash = h.email_hash
+        WHERE
+            d.subscriber_id IS NULL AND
+            d.subscriber_email = h.email AND
+            NOT EXISTS (
+                SELECT 1 FROM newsletter_deliveries o
+                WHERE o.newsletter_issue_id = d.newsletter_issue_id AND o.subscriber_id = s.id
+            )
+        "#,
+        &emails,
+        &email_hashes
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to match recorded deliveries to their subscribers.")?;
+    let n_recorded = sqlx::query!(
+        r#"UPDATE newsletter_deliveries SET subscriber_email = NULL WHERE subscriber_email IS NOT NULL"#
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to clear the addresses of recorded deliveries.")?
+    .rows_affected();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deliveries matched to their subscribers.")?;
+    Ok(n_queued + n_dropped + n_recorded)
+}
@@ -0,0 +1,168 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+
+use crate::{clock::Clock, configuration::Settings, startup::get_connection_pool};
+
+// Kept in a table rather than in memory so every API process shares one refresh
+#[derive(Clone, Debug)]
+pub struct PublicStats {
+    pub confirmed_subscribers: i64,
+    pub issues_published: i64,
+    pub refreshed_at: DateTime<Utc>,
+}
+
+// Any number will do as long as nothing else takes the same advisory lock
+pub const RECOUNT_LOCK: i64 = 0x7075_626c_6963;
+
+// Counts are redone at most once per `max_age`, even while the stats refresher isn't running.
+// Once they are stale one request recounts and the others keep serving the old counts meanwhile,
+// so a burst of traffic doesn't run the counts in parallel. Only before the very first count do
+// requests wait for it.
+#[tracing::instrument(skip(pool))]
+pub async fn cached_public_stats(
+    pool: &PgPool,
+    max_age: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Result<PublicStats, anyhow::Error> {
+    let cached = stored_public_stats(pool).await?;
+    let stale = match cached {
+        Some(stats) if now - stats.refreshed_at < max_age => return Ok(stats),
+        stale => stale,
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    if let Some(stale) = stale {
+        let locked = sqlx::query!(
+            r#"SELECT pg_try_advisory_xact_lock($1) AS "locked!""#,
+            RECOUNT_LOCK
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .context("Failed to take the public stats recount lock.")?
+        .locked;
+        if !locked {
+            return Ok(stale);
+        }
+    } else {
+        sqlx::query!("SELECT pg_advisory_xact_lock($1)", RECOUNT_LOCK)
+            .execute(&mut transaction)
+            .await
+            .context("Failed to wait for the public stats recount lock.")?;
+    }
+    // Whoever held the lock before may have just recounted
+    if let Some(stats) = stored_public_stats(&mut transaction).await?
+        && now - stats.refreshed_at < max_age
+    {
+        return Ok(stats);
+    }
+    let stats = refresh_public_stats(&mut transaction, now).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the recounted public stats.")?;
+    Ok(stats)
+}
+
+async fn stored_public_stats<'c, E>(executor: E) -> Result<Option<PublicStats>, anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as!(
+        PublicStats,
+        "SELECT confirmed_subscribers, issues_published, refreshed_at FROM public_stats"
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to read the cached public stats.")
+}
+
+#[tracing::instrument(skip(executor))]
+pub async fn refresh_public_stats<'c, E>(
+    executor: E,
+    now: DateTime<Utc>,
+) -> Result<PublicStats, anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as!(
+        PublicStats,
+        r#"
+        INSERT INTO public_stats (id, confirmed_subscribers, issues_published, refreshed_at)
+        VALUES (
+            TRUE,
+            (SELECT COUNT(*) FROM subscriptions WHERE status = 'confirmed'),
+            (SELECT COUNT(*) FROM newsletter_issues WHERE deleted_at IS NULL),
+            $1
+        )
+        ON CONFLICT (id) DO UPDATE
+        SET confirmed_subscribers = EXCLUDED.confirmed_subscribers,
+            issues_published = EXCLUDED.issues_published,
+            refreshed_at = EXCLUDED.refreshed_at
+        RETURNING confirmed_subscribers, issues_published, refreshed_at
+        "#,
+        now
+    )
+    .fetch_one(executor)
+    .await
+    .context("Failed to refresh the public stats.")
+}
+
+// Recounts on a schedule so requests are served from the cache rather than counting the tables.
+// Requests still recount when the cache has gone stale, e.g. while this isn't running.
+pub async fn run_stats_refresher_until_stopped(
+    configuration: Settings,
+    clock: Arc<dyn Clock>,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    refresher_loop(
+        &connection_pool,
+        configuration.public_stats.refresh_seconds,
+        clock.as_ref(),
+    )
+    .await
+}
+
+async fn refresher_loop(
+    pool: &PgPool,
+    refresh_seconds: u64,
+    clock: &dyn Clock,
+) -> Result<(), anyhow::Error> {
+    // Half the refresh interval, so the cache is renewed before a request finds it stale. The
+    // first tick is immediate, a freshly started app has counts to serve straight away.
+    let mut interval = tokio::time::interval(Duration::from_millis(refresh_seconds.max(1) * 500));
+    loop {
+        interval.tick().await;
+        if let Err(e) = refresh_public_stats(pool, clock.now()).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to refresh the public stats",
+            );
+        }
+    }
+}
+
+// To the nearest multiple of `granularity`, halves round up. 0 and 1 leave the count exact.
+pub fn round_to_nearest(count: i64, granularity: u64) -> i64 {
+    let granularity = granularity.max(1) as i64;
+    (count + granularity / 2) / granularity * granularity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::round_to_nearest;
+
+    #[test]
+    fn counts_round_to_the_nearest_multiple() {
+        assert_eq!(round_to_nearest(12_434, 10), 12_430);
+        assert_eq!(round_to_nearest(12_435, 10), 12_440);
+        assert_eq!(round_to_nearest(4, 10), 0);
+        assert_eq!(round_to_nearest(12_434, 1), 12_434);
+        assert_eq!(round_to_nearest(12_434, 0), 12_434);
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::Context;
+use redis::aio::ConnectionManager;
+use sqlx::PgPool;
+
+// Matches the pool's acquire timeout, so an unreachable Redis fails the probe as quickly as an
+// unreachable database does
+const REDIS_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Whether the application can take traffic: it has finished starting up and both Postgres and
+// Redis answer. Kept apart from liveness so a dependency outage takes the instance out of the load
+// balancer instead of getting it restarted.
+pub struct Readiness {
+    redis: ConnectionManager,
+    started: AtomicBool,
+}
+
+impl Readiness {
+    pub async fn new(redis_uri: &str) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri).context("Invalid Redis URI.")?;
+        let redis = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis.")?;
+        Ok(Self {
+            redis,
+            started: AtomicBool::new(false),
+        })
+    }
+
+    // Called once `Application::build` has everything in place
+    pub fn mark_started(&self) {
+        self.started.store(true, Ordering::Release);
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::Acquire)
+    }
+
+    #[tracing::instrument(name = "Check the database is reachable", skip_all)]
+    pub async fn check_database(&self, pool: &PgPool) -> Result<(), anyhow::Error> {
+        sqlx::query!("SELECT 1 AS ping")
+            .fetch_one(pool)
+            .await
+            .context("Failed to reach Postgres.")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Check Redis is reachable", skip_all)]
+    pub async fn check_redis(&self) -> Result<(), anyhow::Error> {
+        let mut connection = self.redis.clone();
+        tokio::time::timeout(
+            REDIS_PING_TIMEOUT,
+            redis::cmd("PING").query_async::<()>(&mut connection),
+        )
+        .await
+        .context("Timed out pinging Redis.")?
+        .context("Failed to reach Redis.")
+    }
+}
@@ -0,0 +1,109 @@
+use actix_web::{HttpResponse, web};
+use chrono::Duration;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{
+        UserId, create_api_token, generate_api_token, list_api_tokens, revoke_api_token,
+        revoke_api_token_by_id,
+    },
+    clock::Clock,
+    utils::{e400, e404, e500},
+};
+
+// Ten years, anything longer may as well not expire
+const MAX_API_TOKEN_DAYS: u32 = 3650;
+
+#[derive(serde::Serialize)]
+struct ApiTokenResponse<'a> {
+    token: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct CreatedApiTokenResponse<'a> {
+    id: Uuid,
+    token: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ApiTokenFormData {
+    // Left out for a token that lasts until it is revoked
+    expires_in_days: Option<u32>,
+}
+
+#[tracing::instrument(name = "Regenerate API token", skip_all, fields(user_id=%&*user_id))]
+pub async fn regenerate_api_token(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = generate_api_token(**user_id, &pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok().json(ApiTokenResponse {
+        token: token.expose_secret(),
+    }))
+}
+
+#[tracing::instrument(name = "Delete API token", skip_all, fields(user_id=%&*user_id))]
+pub async fn delete_api_token(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_api_token(**user_id, &pool).await.map_err(e500)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Only the current user's tokens, with everything but the last four characters masked
+pub async fn api_tokens(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tokens = list_api_tokens(**user_id, &pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+// Unlike regenerating, the user's other tokens keep working
+#[tracing::instrument(name = "Add API token", skip_all, fields(user_id=%&*user_id))]
+pub async fn add_api_token(
+    form: web::Form<ApiTokenFormData>,
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let expires_at = match form.expires_in_days {
+        Some(days @ 1..=MAX_API_TOKEN_DAYS) => Some(
+            clock
+                .now()
+                .checked_add_signed(Duration::days(days.into()))
+                .ok_or_else(|| e400("The expiry is too far in the future"))?,
+        ),
+        Some(_) => {
+            return Err(e400(format!(
+                "A token must expire within 1 to {MAX_API_TOKEN_DAYS} days"
+            )));
+        }
+        None => None,
+    };
+    let (id, token) = create_api_token(**user_id, expires_at, &pool)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Created().json(CreatedApiTokenResponse {
+        id,
+        token: token.expose_secret(),
+    }))
+}
+
+#[tracing::instrument(name = "Remove API token", skip(pool, user_id), fields(user_id=%&*user_id))]
+pub async fn remove_api_token(
+    token_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !revoke_api_token_by_id(**user_id, *token_id, &pool)
+        .await
+        .map_err(e500)?
+    {
+        return Err(e404("No such API token"));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,16 +1,116 @@
+use std::fmt::Write;
+
 use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
+use htmlescape::encode_minimal;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, utils::e500};
+use crate::{
+    audit::{AuditEntry, recent_audit_entries},
+    authentication::UserId,
+    clock::Clock,
+    configuration::{DeliverySettings, DisplaySettings, WorkerSettings},
+    delivery_pause::DeliveryPause,
+    domain::PiiCipher,
+    utils::{e500, time_ago},
+    worker_heartbeat::latest_heartbeat,
+};
+
+// How many audit entries the activity feed shows
+const ACTIVITY_FEED_LENGTH: i64 = 10;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn admin_dashboard(
+    flash_messages: IncomingFlashMessages,
     user_id: web::ReqData<UserId>,
     pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    worker: web::Data<WorkerSettings>,
+    clock: web::Data<dyn Clock>,
+    delivery_pause: web::Data<DeliveryPause>,
+    display: web::Data<DisplaySettings>,
+    pii_cipher: web::Data<PiiCipher>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let (email, notify_on_completion) = get_notification_settings(*user_id, &pool)
+        .await
+        .map_err(e500)?;
+    let email = htmlescape::encode_attribute(email.as_deref().unwrap_or_default());
+    let checked = if notify_on_completion { "checked" } else { "" };
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    let deliveries_paused = delivery_pause.is_paused().await.map_err(e500)?;
+    let worker_form = if deliveries_paused {
+        writeln!(
+            msg_html,
+            "<p><b>Newsletter deliveries are paused. Queued emails go out once they are resumed.</b></p>"
+        )
+        .unwrap();
+        r#"<form action="/admin/worker/resume" method="post">
+            <button type="submit">Resume newsletter deliveries</button>
+        </form>"#
+    } else {
+        r#"<form action="/admin/worker/pause" method="post">
+            <button type="submit">Pause newsletter deliveries</button>
+        </form>"#
+    };
+    if let Some(window) = &worker.sending_window
+        && !window.is_open(clock.now())
+    {
+        writeln!(
+            msg_html,
+            "<p><b>Newsletter deliveries are paused outside the sending window. \
+            They resume at {} ({}).</b></p>",
+            window.start.format("%H:%M"),
+            window.timezone
+        )
+        .unwrap();
+    }
+    // The same figures as /health_check/worker
+    let now = clock.now();
+    let worker_status = match latest_heartbeat(&pool).await.map_err(e500)? {
+        Some(heartbeat) => {
+            let age = heartbeat.age(now);
+            let status = if age <= delivery.worker_stale_after() {
+                "up"
+            } else {
+                "<b>not responding</b>"
+            };
+            let last_task = heartbeat
+                .last_task_at
+                .map_or("never".into(), |at| display.format_timestamp(at));
+            format!(
+                "Delivery worker {status}: last heartbeat {}s ago from {}, last delivery {last_task}, \
+                {} emails queued.",
+                age.num_seconds(),
+                encode_minimal(&heartbeat.worker_id),
+                heartbeat.queue_depth
+            )
+        }
+        None => "<b>The delivery worker has never reported in.</b>".into(),
+    };
+    let mut activity_html = String::new();
+    for entry in recent_audit_entries(&pool, &pii_cipher, ACTIVITY_FEED_LENGTH)
+        .await
+        .context("Failed to retrieve the recent audit entries.")
+        .map_err(e500)?
+    {
+        writeln!(
+            activity_html,
+            "<li>{} {}</li>",
+            describe_audit_entry(&entry),
+            time_ago(now - entry.created_at)
+        )
+        .unwrap();
+    }
+    if activity_html.is_empty() {
+        activity_html = "<li>Nothing yet.</li>".into();
+    }
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -22,22 +122,97 @@ pub async fn admin_dashboard(
                     <title>Admin dashboard</title>
                 </head>
                 <body>
+                    {msg_html}
                     <p>Welcome {username}!</p>
+                    <p>{worker_status}</p>
                     <p>Available actions:</p>
                     <ol>
                         <li><a href="/admin/password"> Change password</a></li>
                         <li><a href="/admin/newsletter"> Send newsletter issue</a></li>
+                        <li><a href="/admin/webhooks"> Manage webhooks</a></li>
+                        <li><a href="/admin/paused-domains"> Paused domains</a></li>
+                        <li><a href="/admin/suppressions"> Suppressed addresses</a></li>
+                        <li><a href="/admin/senders"> Senders</a></li>
+                        <li><a href="/admin/subscribers"> Subscribers</a></li>
+                        <li><a href="/admin/emails/confirmation/preview"> Preview the confirmation email</a></li>
+                        <li>{worker_form}</li>
                         <li>
                             <form name="logoutForm" action="/admin/logout" method="post">
                                 <input type="submit" value="Logout">
                             </form>
                         </li>
                     </ol>
+                    <p>Notification settings:</p>
+                    <form action="/admin/notifications" method="post">
+                        <label>Email
+                            <input type="email" name="email" value="{email}">
+                        </label>
+                        <label>
+                            <input type="checkbox" name="notify_on_completion" {checked}>
+                            Email me a summary when an issue finishes delivering
+                        </label>
+                        <button type="submit">Save</button>
+                    </form>
+                    <p>Recent activity:</p>
+                    <ul>
+                        {activity_html}
+                    </ul>
                 </body>
             </html>"#
         )))
 }
 
+// e.g. "alice published 'March issue'". Subscribers and issues are linked while they still exist.
+fn describe_audit_entry(entry: &AuditEntry) -> String {
+    let who = entry
+        .username
+        .as_deref()
+        .map_or("A deleted user".into(), encode_minimal);
+    let subject = encode_minimal(&entry.subject);
+    let details = entry.details.as_deref().map(encode_minimal);
+    let subscriber = match &entry.subscriber_email {
+        Some(email) => format!(
+            r#"<a href="/admin/subscribers/{subject}">{}</a>"#,
+            encode_minimal(email)
+        ),
+        None => "a removed subscriber".into(),
+    };
+    // The title is kept in the details of the entry that published it, for once it's gone
+    let issue = match (&entry.issue_title, &details) {
+        (Some(title), _) => format!(
+            r#"<a href="/admin/newsletter/{subject}/delivery-report">'{}'</a>"#,
+            encode_minimal(title)
+        ),
+        (None, Some(title)) if entry.action == "issue.published" => format!("'{title}'"),
+        (None, _) => "a deleted issue".into(),
+    };
+    let what = match entry.action.as_str() {
+        "issue.published" => format!("published {issue}"),
+        "issue.archived" => format!("archived {issue}"),
+        "issue.unarchived" => format!("unarchived {issue}"),
+        "issue.recipient_skipped" => {
+            format!("published {issue} without a subscriber whose address can't be decrypted")
+        }
+        "password.changed" => "changed their password".into(),
+        "worker.paused" => "paused newsletter deliveries".into(),
+        "worker.resumed" => "resumed newsletter deliveries".into(),
+        "suppression.added" => format!("suppressed {subject}"),
+        "suppression.removed" => format!("lifted the suppression of {subject}"),
+        "sender.verified" => format!("verified the sender {subject}"),
+        "sender.unverified" => format!("unverified the sender {subject}"),
+        "subscriber.note_changed" => format!("changed the note on {subscriber}"),
+        "subscriber.email_change_requested" => {
+            format!("asked {subscriber} to confirm a new address")
+        }
+        "subscriber.email_changed" => format!("changed the address of {subscriber}"),
+        "subscriber.exported" => format!("exported {subscriber}"),
+        "subscriber.merged" => format!("merged a duplicate into {subscriber}"),
+        "subscribers.exported" => format!("exported {subject} subscribers"),
+        action => format!("{} {subject}", encode_minimal(action)),
+    };
+    format!("{who} {what}")
+}
+
 #[tracing::instrument(name = "Get username", skip(pool))]
 pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
     let row = sqlx::query!(
@@ -53,3 +228,22 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
     .context("Failed to perform a query to retrieve a username.")?;
     Ok(row.username)
 }
+
+#[tracing::instrument(name = "Get notification settings", skip(pool))]
+async fn get_notification_settings(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<(Option<String>, bool), anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email, notify_on_completion
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to retrieve notification settings.")?;
+    Ok((row.email, row.notify_on_completion))
+}
@@ -0,0 +1,38 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use htmlescape::encode_minimal;
+
+use crate::{authentication::UserId, routes::confirmation_email, startup::ApplicationBaseUrl};
+
+// Stands in for a real token, so the link in the preview can't confirm anyone
+const SAMPLE_TOKEN: &str = "sample-confirmation-token";
+
+// Renders the confirmation email the way a new subscriber would get it, without sending anything
+#[tracing::instrument(name = "Preview the confirmation email", skip(base_url, _user_id))]
+pub async fn confirmation_email_preview(
+    base_url: web::Data<ApplicationBaseUrl>,
+    _user_id: web::ReqData<UserId>,
+) -> HttpResponse {
+    let email = confirmation_email("Ursula", &base_url, SAMPLE_TOKEN);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Confirmation email preview</title>
+            </head>
+            <body>
+                <p>Subject: <strong>{}</strong></p>
+                <h2>HTML</h2>
+                <div>{}</div>
+                <h2>Plain text</h2>
+                <pre>{}</pre>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+            encode_minimal(email.subject),
+            email.html_body,
+            encode_minimal(&email.plain_body),
+        ))
+}
@@ -0,0 +1,328 @@
+use std::fmt::Write;
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Months, Utc};
+use htmlescape::encode_minimal;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    clock::Clock,
+    configuration::DisplaySettings,
+    domain::PiiCipher,
+    utils::{e400, e500, see_other},
+    webhooks::{WebhookEvent, enqueue_webhook_event},
+};
+
+const DEFAULT_MONTHS: u32 = 12;
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct InactiveQuery {
+    months: Option<u32>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeInactiveFormData {
+    months: u32,
+}
+
+struct InactiveSubscriber {
+    email: String,
+    name: String,
+    subscribed_at: DateTime<Utc>,
+    last_emailed_at: Option<DateTime<Utc>>,
+    last_opened_at: Option<DateTime<Utc>>,
+}
+
+// Confirmed subscribers who haven't opened anything since the cutoff. Anyone who subscribed after
+// it hasn't had the chance yet, so they are left out.
+#[tracing::instrument(
+    name = "List inactive subscribers",
+    skip(flash_messages, pool, display, clock, pii_cipher, _user_id)
+)]
+pub async fn inactive_subscribers_page(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<InactiveQuery>,
+    pool: web::Data<PgPool>,
+    display: web::Data<DisplaySettings>,
+    clock: web::Data<dyn Clock>,
+    pii_cipher: web::Data<PiiCipher>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let months = query.months.unwrap_or(DEFAULT_MONTHS);
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let cutoff = inactivity_cutoff(clock.now(), months)?;
+    let (subscribers, n_subscribers) =
+        get_inactive_subscribers(&pool, &pii_cipher, cutoff, page, per_page)
+            .await
+            .map_err(e500)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    let never = || "Never".to_owned();
+    let mut subscribers_html = String::new();
+    for subscriber in &subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+            </tr>"#,
+            encode_minimal(&subscriber.email),
+            encode_minimal(&subscriber.name),
+            display.format_timestamp(subscriber.subscribed_at),
+            subscriber
+                .last_emailed_at
+                .map_or_else(never, |at| display.format_timestamp(at)),
+            subscriber
+                .last_opened_at
+                .map_or_else(never, |at| display.format_timestamp(at)),
+        )
+        .unwrap();
+    }
+    let shown = (page - 1).saturating_mul(per_page) + subscribers.len() as i64;
+    let next_page_html = if shown < n_subscribers {
+        format!(
+            r#"<p><a href="/admin/subscribers/inactive?months={months}&page={}&per_page={per_page}">Next page -&gt;</a></p>"#,
+            page.saturating_add(1),
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Inactive subscribers</title>
+            </head>
+            <body>
+                {msg_html}
+                <form action="/admin/subscribers/inactive" method="get">
+                    <label>No opens in the last
+                        <input type="number" name="months" min="1" value="{months}">
+                        months
+                    </label>
+                    <button type="submit">Show</button>
+                </form>
+                <p>{n_subscribers} confirmed subscribers haven't opened an issue since {}.</p>
+                <table>
+                    <tr><th>Email</th><th>Name</th><th>Subscribed</th><th>Last emailed</th><th>Last opened</th></tr>
+                    {subscribers_html}
+                </table>
+                {next_page_html}
+                <form action="/admin/subscribers/inactive" method="post">
+                    <input type="hidden" name="months" value="{months}">
+                    <button type="submit">Unsubscribe all {n_subscribers}</button>
+                </form>
+                <p><a href="/admin/subscribers">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+            display.format_timestamp(cutoff),
+        )))
+}
+
+// Unsubscribes everyone the report lists for the same cutoff, not only the page that was shown.
+// Anyone who opened an issue since the page was loaded is no longer inactive and is kept.
+#[tracing::instrument(
+    name = "Unsubscribe inactive subscribers",
+    skip(form, pool, clock, pii_cipher, user_id),
+    fields(months = form.months, user_id=%&*user_id)
+)]
+pub async fn unsubscribe_inactive_subscribers(
+    form: web::Form<UnsubscribeInactiveFormData>,
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let months = form.0.months;
+    let cutoff = inactivity_cutoff(clock.now(), months)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let unsubscribed = unsubscribe_inactive(&mut transaction, &pii_cipher, cutoff)
+        .await
+        .map_err(e500)?;
+    for (subscriber_id, email) in &unsubscribed {
+        if let Some(email) = email {
+            let event = WebhookEvent::SubscriberUnsubscribed {
+                subscriber_id: *subscriber_id,
+                email: email.clone(),
+            };
+            enqueue_webhook_event(&mut transaction, &event)
+                .await
+                .context("Failed to enqueue the subscriber.unsubscribed webhook event.")
+                .map_err(e500)?;
+        }
+        record_audit_event(
+            &mut transaction,
+            **user_id,
+            "subscriber.unsubscribed",
+            &subscriber_id.to_string(),
+            Some(&format!("No opens in the last {months} months")),
+        )
+        .await
+        .context("Failed to audit the unsubscribe.")
+        .map_err(e500)?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to unsubscribe inactive subscribers.")
+        .map_err(e500)?;
+    FlashMessage::info(format!(
+        "{} inactive subscribers have been unsubscribed.",
+        unsubscribed.len()
+    ))
+    .send();
+    Ok(see_other(&format!(
+        "/admin/subscribers/inactive?months={months}"
+    )))
+}
+
+fn inactivity_cutoff(now: DateTime<Utc>, months: u32) -> Result<DateTime<Utc>, actix_web::Error> {
+    now.checked_sub_months(Months::new(months))
+        .ok_or_else(|| e400(format!("{months} months is too far back.")))
+}
+
+// Their queued deliveries are dropped and any confirmation link still lying around stops working,
+// so nothing resubscribes or emails them behind the admin's back. The address comes back for the
+// webhook event, or `None` if it can't be decrypted, which mustn't keep them subscribed.
+async fn unsubscribe_inactive(
+    transaction: &mut Transaction<'_, Postgres>,
+    pii_cipher: &PiiCipher,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<(Uuid, Option<String>)>, anyhow::Error> {
+    let unsubscribed = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE
+            status = 'confirmed' AND
+            subscribed_at < $1 AND
+            (last_opened_at IS NULL OR last_opened_at < $1)
+        RETURNING id, email, email_encrypted
+        "#,
+        cutoff
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to unsubscribe inactive subscribers.")?
+    .into_iter()
+    .map(
+        |row| match pii_cipher.reveal(row.email, row.email_encrypted) {
+            Ok(email) => (row.id, Some(email)),
+            Err(e) => {
+                tracing::error!(
+                    error.message = %e,
+                    "Failed to decrypt an unsubscribed subscriber's email. No webhook event is sent.",
+                );
+                (row.id, None)
+            }
+        },
+    )
+    .collect::<Vec<_>>();
+    let ids: Vec<Uuid> = unsubscribed.iter().map(|(id, _)| *id).collect();
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_id = ANY($1)",
+        &ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to drop the unsubscribed subscribers' queued deliveries.")?;
+    sqlx::query!(
+        "UPDATE subscription_tokens SET used = TRUE WHERE subscriber_id = ANY($1)",
+        &ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to use up the unsubscribed subscribers' tokens.")?;
+    Ok(unsubscribed)
+}
+
+#[tracing::instrument(skip(pool, pii_cipher))]
+async fn get_inactive_subscribers(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    cutoff: DateTime<Utc>,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<InactiveSubscriber>, i64), anyhow::Error> {
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT
+            email,
+            email_encrypted,
+            name,
+            name_encrypted,
+            subscribed_at,
+            last_emailed_at,
+            last_opened_at
+        FROM subscriptions
+        WHERE
+            status = 'confirmed' AND
+            subscribed_at < $1 AND
+            (last_opened_at IS NULL OR last_opened_at < $1)
+        ORDER BY last_opened_at NULLS FIRST, subscribed_at, id
+        LIMIT $2
+        OFFSET $3
+        "#,
+        cutoff,
+        per_page,
+        (page - 1).saturating_mul(per_page)
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve inactive subscribers.")?
+    .into_iter()
+    .map(|row| {
+        Ok(InactiveSubscriber {
+            email: pii_cipher.reveal(row.email, row.email_encrypted)?,
+            name: pii_cipher.reveal(row.name, row.name_encrypted)?,
+            subscribed_at: row.subscribed_at,
+            last_emailed_at: row.last_emailed_at,
+            last_opened_at: row.last_opened_at,
+        })
+    })
+    .collect::<Result<Vec<_>, anyhow::Error>>()
+    .context("Failed to read an inactive subscriber's details.")?;
+    let total = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "total!"
+        FROM subscriptions
+        WHERE
+            status = 'confirmed' AND
+            subscribed_at < $1 AND
+            (last_opened_at IS NULL OR last_opened_at < $1)
+        "#,
+        cutoff
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count inactive subscribers.")?
+    .total;
+    Ok((subscribers, total))
+}
@@ -1,9 +1,43 @@
+mod api_token;
 mod dashboard;
+mod email_previews;
+mod inactive_subscribers;
 mod logout;
 mod newsletter;
+mod notifications;
 mod password;
+mod paused_domains;
+mod senders;
+mod subscriber_deliverability;
+mod subscriber_email;
+mod subscriber_merge;
+mod subscriber_note;
+mod subscribers;
+mod suppressions;
+mod unsubscribed_subscribers;
+mod webhooks;
+mod worker;
 
+pub use api_token::{
+    add_api_token, api_tokens, delete_api_token, regenerate_api_token, remove_api_token,
+};
 pub use dashboard::admin_dashboard;
+pub use email_previews::confirmation_email_preview;
+pub use inactive_subscribers::{inactive_subscribers_page, unsubscribe_inactive_subscribers};
 pub use logout::log_out;
 pub use newsletter::*;
+pub use notifications::update_notification_settings;
 pub use password::{ValidNewPassword, change_password, change_password_form};
+pub use paused_domains::{pause_domain, paused_domains_page, resume_domain};
+pub use senders::{add_sender, get_senders, senders_page, unverify_sender, verify_sender};
+pub use subscriber_deliverability::can_receive_email;
+pub use subscriber_email::request_email_change;
+pub use subscriber_merge::merge_subscribers;
+pub use subscriber_note::{get_subscriber, update_subscriber_note};
+pub use subscribers::{
+    export_subscriber, export_subscribers, search_subscribers, subscribers_page,
+};
+pub use suppressions::{add_suppression, remove_suppression, suppressions_page};
+pub use unsubscribed_subscribers::unsubscribed_subscribers;
+pub use webhooks::*;
+pub use worker::{pause_worker, resume_worker};
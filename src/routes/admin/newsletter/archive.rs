@@ -0,0 +1,102 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    utils::{e500, see_other},
+};
+
+// Only hides the issue, its deliveries and reports are kept as they are
+#[tracing::instrument(
+    name = "Archive a newsletter issue",
+    skip(pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn archive_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_issue_archived(&pool, *issue_id, **user_id, true).await
+}
+
+#[tracing::instrument(
+    name = "Unarchive a newsletter issue",
+    skip(pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn unarchive_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_issue_archived(&pool, *issue_id, **user_id, false).await
+}
+
+async fn set_issue_archived(
+    pool: &PgPool,
+    issue_id: Uuid,
+    user_id: Uuid,
+    archived: bool,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            archived_at = CASE WHEN $2 THEN now() ELSE NULL END,
+            archive_changed_at = now()
+        WHERE newsletter_issue_id = $1 AND deleted_at IS NULL
+        RETURNING title
+        "#,
+        issue_id,
+        archived
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to update the issue's archived state.")
+    .map_err(e500)?;
+    let Some(updated) = updated else {
+        FlashMessage::error("No such newsletter issue.").send();
+        return Ok(see_other("/admin/newsletter"));
+    };
+    let action = if archived {
+        "issue.archived"
+    } else {
+        "issue.unarchived"
+    };
+    record_audit_event(
+        &mut transaction,
+        user_id,
+        action,
+        &issue_id.to_string(),
+        None,
+    )
+    .await
+    .context("Failed to audit the issue's archived state.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the issue's archived state.")
+        .map_err(e500)?;
+    let message = if archived {
+        format!("\"{}\" has been archived.", encode_minimal(&updated.title))
+    } else {
+        format!(
+            "\"{}\" is no longer archived.",
+            encode_minimal(&updated.title)
+        )
+    };
+    FlashMessage::info(message).send();
+    Ok(see_other("/admin/newsletter"))
+}
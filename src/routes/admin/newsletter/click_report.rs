@@ -0,0 +1,63 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    utils::{e404, e500},
+};
+
+#[derive(serde::Serialize)]
+struct ClickReportResponse {
+    links: Vec<LinkClicks>,
+}
+
+#[derive(serde::Serialize)]
+struct LinkClicks {
+    url: String,
+    clicks: i64,
+    unique_clicks: i64,
+}
+
+#[tracing::instrument(
+    name = "Get the click report of a newsletter issue",
+    skip(pool, _user_id)
+)]
+pub async fn get_click_report(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        r#"SELECT newsletter_issue_id FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        *issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the issue.")
+    .map_err(e500)?;
+    if issue.is_none() {
+        return Err(e404("No such issue"));
+    }
+
+    let links = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT
+            original_url AS url,
+            COUNT(*) AS "clicks!",
+            COUNT(DISTINCT subscriber_id) AS "unique_clicks!"
+        FROM newsletter_clicks
+        WHERE newsletter_issue_id = $1
+        GROUP BY original_url
+        ORDER BY COUNT(*) DESC, original_url
+        "#,
+        *issue_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to count the issue's clicks.")
+    .map_err(e500)?;
+    Ok(HttpResponse::Ok().json(ClickReportResponse { links }))
+}
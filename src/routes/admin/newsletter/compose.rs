@@ -0,0 +1,452 @@
+use actix_web::{
+    HttpResponse, ResponseError,
+    http::{StatusCode, header},
+    web,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{
+    IssueContent, IssueError, apply_template, check_links, check_sender, content_mismatch,
+    normalise_from_name, preview_html, store_issue,
+};
+use crate::{
+    authentication::UserId,
+    configuration::DeliverySettings,
+    delivery_queue::{count_recipients, enqueue_deliveries},
+    domain::{IssueTag, PiiCipher},
+    idempotency::{
+        IdempotencyKey, IdempotencyKeyMaxLength, IdempotencyWait, NextAction, save_response,
+        try_processing,
+    },
+    link_checker::{LinkChecker, LinkStatus},
+    routes::ErrorBody,
+    startup::ApplicationBaseUrl,
+};
+
+// An issue as the compose page has it, every endpoint below takes the same fields
+#[derive(serde::Deserialize)]
+pub struct ComposeData {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub text_content: String,
+    #[serde(default)]
+    pub html_content: String,
+    // Optional layout whose `{{content}}` placeholders are filled with the content above
+    pub template_id: Option<Uuid>,
+    // A verified sender to send from instead of the configured one
+    pub sender_id: Option<Uuid>,
+    // Replaces the sender's display name for this issue
+    pub from_name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub inline_css: bool,
+}
+
+impl ComposeData {
+    fn tags(&self) -> Vec<String> {
+        IssueTag::parse_all(self.tags.iter().map(String::as_str))
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct SaveDraftData {
+    // Left out to start a new draft
+    draft_id: Option<Uuid>,
+    #[serde(flatten)]
+    issue: ComposeData,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ComposePublishData {
+    pub idempotency_key: String,
+    // The draft the issue was written in, removed once it's published
+    pub draft_id: Option<Uuid>,
+    // Publishes despite warnings, errors still stop it
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(flatten)]
+    pub issue: ComposeData,
+}
+
+#[derive(serde::Serialize)]
+struct Draft {
+    draft_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    template_id: Option<Uuid>,
+    sender_id: Option<Uuid>,
+    from_name: Option<String>,
+    tags: Vec<String>,
+    inline_css: bool,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct SavedDraft {
+    draft_id: Uuid,
+    updated_at: DateTime<Utc>,
+}
+
+// Errors stop an issue from being published, warnings only until it's confirmed
+#[derive(serde::Serialize, Debug, Default)]
+pub struct ValidationReport {
+    valid: bool,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct RenderedPreview {
+    subject: String,
+    html: String,
+    text: String,
+}
+
+#[derive(serde::Serialize)]
+struct AudienceEstimate {
+    recipients: i64,
+    suppressed: i64,
+    unsubscribed: i64,
+}
+
+#[derive(serde::Serialize)]
+struct PublishedIssue {
+    issue_id: Uuid,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ComposeError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("The issue can't be published as it is.")]
+    Rejected(ValidationReport),
+    #[error("The issue is still being published, try again shortly.")]
+    StillProcessing(u64),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<IssueError> for ComposeError {
+    fn from(e: IssueError) -> Self {
+        match e {
+            IssueError::Invalid(message) => ComposeError::ValidationError(message),
+            IssueError::Unexpected(e) => ComposeError::UnexpectedError(e),
+        }
+    }
+}
+
+// Always JSON, the compose page reads every response the same way
+impl ResponseError for ComposeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ComposeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ComposeError::Rejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ComposeError::StillProcessing(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ComposeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            ComposeError::Rejected(report) => response.json(report),
+            ComposeError::StillProcessing(retry_after) => response
+                .insert_header((header::RETRY_AFTER, *retry_after))
+                .json(ErrorBody {
+                    error: self.to_string(),
+                }),
+            ComposeError::ValidationError(_) => response.json(ErrorBody {
+                error: self.to_string(),
+            }),
+            ComposeError::UnexpectedError(_) => response.json(ErrorBody {
+                error: "Something went wrong on our side.".into(),
+            }),
+        }
+    }
+}
+
+// Every draft, the most recently edited first
+#[tracing::instrument(name = "List newsletter drafts", skip_all)]
+pub async fn list_drafts(
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ComposeError> {
+    let drafts = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT
+            draft_id,
+            title,
+            text_content,
+            html_content,
+            template_id,
+            sender_id,
+            from_name,
+            tags,
+            inline_css,
+            updated_at
+        FROM newsletter_drafts
+        ORDER BY updated_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the drafts.")?;
+    Ok(HttpResponse::Ok().json(drafts))
+}
+
+// Creates the draft or overwrites it, nothing is checked until the issue is validated or published
+#[tracing::instrument(name = "Save a newsletter draft", skip_all, fields(user_id=%&*user_id))]
+pub async fn save_draft(
+    body: web::Json<SaveDraftData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ComposeError> {
+    let SaveDraftData { draft_id, issue } = body.into_inner();
+    let tags = issue.tags();
+    let saved = sqlx::query_as!(
+        SavedDraft,
+        r#"
+        INSERT INTO newsletter_drafts (
+            draft_id,
+            title,
+            text_content,
+            html_content,
+            template_id,
+            sender_id,
+            from_name,
+            tags,
+            inline_css,
+            updated_by,
+            updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, now())
+        ON CONFLICT (draft_id) DO UPDATE SET
+            title = EXCLUDED.title,
+            text_content = EXCLUDED.text_content,
+            html_content = EXCLUDED.html_content,
+            template_id = EXCLUDED.template_id,
+            sender_id = EXCLUDED.sender_id,
+            from_name = EXCLUDED.from_name,
+            tags = EXCLUDED.tags,
+            inline_css = EXCLUDED.inline_css,
+            updated_by = EXCLUDED.updated_by,
+            updated_at = EXCLUDED.updated_at
+        RETURNING draft_id, updated_at
+        "#,
+        draft_id.unwrap_or_else(Uuid::new_v4),
+        issue.title,
+        issue.text_content,
+        issue.html_content,
+        issue.template_id,
+        issue.sender_id,
+        normalise_from_name(issue.from_name),
+        &tags,
+        issue.inline_css,
+        **user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to save the draft.")?;
+    Ok(HttpResponse::Ok().json(saved))
+}
+
+// The same checks publishing makes, without storing anything
+#[tracing::instrument(name = "Validate a newsletter issue", skip_all)]
+pub async fn validate_issue(
+    body: web::Json<ComposeData>,
+    pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    link_checker: web::Data<LinkChecker>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ComposeError> {
+    let (report, _) = review(&pool, &delivery, &link_checker, body.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// The issue's HTML as subscribers get it, template applied, CSS inlined and links tagged
+#[tracing::instrument(name = "Render a newsletter preview", skip_all)]
+pub async fn render_preview(
+    body: web::Json<ComposeData>,
+    pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ComposeError> {
+    let issue = body.into_inner();
+    let (html_content, text_content) = apply_template(
+        &pool,
+        issue.template_id,
+        issue.html_content,
+        issue.text_content,
+    )
+    .await?;
+    let html = preview_html(
+        html_content,
+        issue.inline_css,
+        delivery.utm_injection.as_ref(),
+        &base_url,
+    );
+    Ok(HttpResponse::Ok().json(RenderedPreview {
+        subject: issue.title,
+        html,
+        text: text_content,
+    }))
+}
+
+// Who an issue published now would reach
+#[tracing::instrument(name = "Estimate a newsletter's audience", skip_all)]
+pub async fn estimate_audience(
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ComposeError> {
+    let counts = count_recipients(&pool).await?;
+    Ok(HttpResponse::Ok().json(AudienceEstimate {
+        recipients: counts.recipients,
+        suppressed: counts.suppressed,
+        unsubscribed: counts.unsubscribed,
+    }))
+}
+
+// Publishes like the form does, idempotency key included. A rejected issue gets its validation
+// report back with a 422. The key is claimed before reviewing, so a replay gets the saved response
+// even if a link has broken since, and doesn't check the links again. A rejection rolls the claim
+// back, leaving the key free for a corrected issue.
+#[tracing::instrument(name = "Publish a composed newsletter issue", skip_all, fields(user_id=%&*user_id))]
+#[allow(clippy::too_many_arguments)]
+pub async fn publish_composed_issue(
+    body: web::Json<ComposePublishData>,
+    pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    link_checker: web::Data<LinkChecker>,
+    idempotency_key_max_length: web::Data<IdempotencyKeyMaxLength>,
+    idempotency_wait: web::Data<IdempotencyWait>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ComposeError> {
+    let ComposePublishData {
+        idempotency_key,
+        draft_id,
+        confirm,
+        issue,
+    } = body.into_inner();
+    let idempotency_key = IdempotencyKey::parse(idempotency_key, **idempotency_key_max_length)
+        .map_err(|e| ComposeError::ValidationError(e.to_string()))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    match try_processing(
+        &mut transaction,
+        &idempotency_key,
+        **user_id,
+        **idempotency_wait,
+    )
+    .await?
+    {
+        NextAction::StartProcessing => {}
+        NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+        NextAction::StillProcessing => {
+            return Err(ComposeError::StillProcessing(
+                idempotency_wait.0.as_secs().max(1),
+            ));
+        }
+    }
+    let (report, issue) = review(&pool, &delivery, &link_checker, issue).await?;
+    let Some(issue) = issue.filter(|_| confirm || report.warnings.is_empty()) else {
+        return Err(ComposeError::Rejected(report));
+    };
+    let issue_id = store_issue(&mut transaction, &issue, **user_id, &delivery).await?;
+    if let Some(draft_id) = draft_id {
+        sqlx::query!(
+            "DELETE FROM newsletter_drafts WHERE draft_id = $1",
+            draft_id
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to remove the published draft.")?;
+    }
+    let response = HttpResponse::Created().json(PublishedIssue { issue_id });
+    let response = save_response(&mut transaction, &idempotency_key, **user_id, response).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the published issue")?;
+    enqueue_deliveries(&pool, &pii_cipher, issue_id, delivery.enqueue_chunk_size)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+    Ok(response)
+}
+
+// Checks the issue the way the publish form does: a missing template or an unusable sender is an
+// error, a forgotten content part or a broken link a warning. The issue comes back ready to store
+// unless there were errors.
+async fn review(
+    pool: &PgPool,
+    delivery: &DeliverySettings,
+    link_checker: &LinkChecker,
+    issue: ComposeData,
+) -> Result<(ValidationReport, Option<IssueContent>), anyhow::Error> {
+    let mut report = ValidationReport::default();
+    if issue.title.trim().is_empty() {
+        report.errors.push("The issue needs a title.".into());
+    }
+    if let Some(warning) = content_mismatch(&issue.html_content, &issue.text_content) {
+        report.warnings.push(warning);
+    }
+    if let Err(e) = check_sender(pool, delivery, issue.sender_id).await {
+        match e {
+            IssueError::Invalid(message) => report.errors.push(message),
+            IssueError::Unexpected(e) => return Err(e),
+        }
+    }
+    let tags = issue.tags();
+    let rendered = match apply_template(
+        pool,
+        issue.template_id,
+        issue.html_content,
+        issue.text_content,
+    )
+    .await
+    {
+        Ok(rendered) => Some(rendered),
+        Err(IssueError::Invalid(message)) => {
+            report.errors.push(message);
+            None
+        }
+        Err(IssueError::Unexpected(e)) => return Err(e),
+    };
+    if let Some((html_content, _)) = &rendered {
+        let reports = check_links(link_checker, html_content).await;
+        for link in reports
+            .iter()
+            .filter(|r| matches!(r.status, LinkStatus::Broken(_)))
+        {
+            report
+                .warnings
+                .push(format!("{} {}.", link.url, link.describe()));
+        }
+    }
+    report.valid = report.errors.is_empty();
+    let issue = rendered
+        .filter(|_| report.valid)
+        .map(|(html_content, text_content)| IssueContent {
+            title: issue.title,
+            text_content,
+            html_content,
+            inline_css: issue.inline_css,
+            sender_id: issue.sender_id,
+            from_name: normalise_from_name(issue.from_name),
+            tags,
+        });
+    Ok((report, issue))
+}
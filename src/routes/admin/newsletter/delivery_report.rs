@@ -0,0 +1,49 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    utils::{e404, e500},
+};
+
+#[derive(serde::Serialize)]
+struct DeliveryReportResponse {
+    status: String,
+    // Confirmed subscribers when the issue was published, later unsubscribes don't change it
+    audience_at_send: i32,
+    delivered: i32,
+    failed: i32,
+}
+
+#[tracing::instrument(
+    name = "Get the delivery report of a newsletter issue",
+    skip(pool, _user_id)
+)]
+pub async fn get_delivery_report(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = sqlx::query!(
+        r#"
+        SELECT status, estimated_audience, n_delivered, n_failed
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        *issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the issue's delivery counts.")
+    .map_err(e500)?
+    .ok_or_else(|| e404("No such issue"))?;
+
+    Ok(HttpResponse::Ok().json(DeliveryReportResponse {
+        status: report.status,
+        audience_at_send: report.estimated_audience,
+        delivered: report.n_delivered,
+        failed: report.n_failed,
+    }))
+}
@@ -2,17 +2,93 @@ use std::fmt::Write;
 
 use actix_web::{HttpResponse, http::header::ContentType, web};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::authentication::UserId;
+use crate::{
+    authentication::UserId,
+    configuration::{DeliverySettings, DisplaySettings},
+    routes::get_senders,
+    utils::e500,
+};
+
+#[derive(serde::Deserialize)]
+pub struct NewsletterListQuery {
+    include_archived: Option<bool>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct IssueSummary {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub published_at: chrono::DateTime<chrono::Utc>,
+    pub status: String,
+    pub archived: bool,
+    pub tags: Vec<String>,
+}
 
 pub async fn send_newsletter_form(
     flash_messages: IncomingFlashMessages,
+    query: web::Query<NewsletterListQuery>,
+    pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    display: web::Data<DisplaySettings>,
     _user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let mut sender_options = String::new();
+    for sender in get_senders(&pool).await.map_err(e500)? {
+        if sender.verified && delivery.accepts_sender(&sender.email) {
+            writeln!(
+                sender_options,
+                r#"<option value="{}">{} &lt;{}&gt;</option>"#,
+                sender.sender_id,
+                encode_minimal(&sender.display_name),
+                encode_minimal(&sender.email),
+            )
+            .unwrap();
+        }
+    }
+    let include_archived = query.include_archived.unwrap_or(false);
+    let mut issues_html = String::new();
+    for issue in get_issues(&pool, include_archived, &[])
+        .await
+        .map_err(e500)?
+    {
+        let (action, label) = if issue.archived {
+            ("unarchive", "Unarchive")
+        } else {
+            ("archive", "Archive")
+        };
+        writeln!(
+            issues_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/newsletter/{}/{action}" method="post">
+                        <button type="submit">{label}</button>
+                    </form>
+                </td>
+            </tr>"#,
+            encode_minimal(&issue.title),
+            display.format_timestamp(issue.published_at),
+            issue.status,
+            issue.newsletter_issue_id,
+        )
+        .unwrap();
+    }
+    let archived_toggle = if include_archived {
+        r#"<a href="/admin/newsletter">Hide archived issues</a>"#
+    } else {
+        r#"<a href="/admin/newsletter?include_archived=true">Show archived issues</a>"#
+    };
+    let inline_css_checked = if delivery.inline_css { "checked" } else { "" };
     let idempotency_key = uuid::Uuid::new_v4();
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -56,12 +132,74 @@ pub async fn send_newsletter_form(
                             wrap="soft"
                         ></textarea>
                     </label>
+                    <label>Sender:
+                        <select name="sender_id">
+                            <option value="">Default sender</option>
+                            {sender_options}
+                        </select>
+                    </label>
+                    <label>From name:
+                        <input
+                            type="text"
+                            placeholder="The sender's name"
+                            name="from_name"
+                        >
+                    </label>
+                    <br>
+                    <label>Tags:
+                        <input
+                            type="text"
+                            placeholder="Comma separated, e.g. rust, backend"
+                            name="tags"
+                        >
+                    </label>
+                    <br>
+                    <label>
+                        <input type="checkbox" name="inline_css" {inline_css_checked}>
+                        Move &lt;style&gt; rules into style attributes when sending
+                    </label>
+                    <br>
                     <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
                     <button type="submit">Publish</button>
+                    <button type="submit" name="action" value="dry_run">Dry run</button>
                 </form>
+                <h2>Issues</h2>
+                <p>{archived_toggle}</p>
+                <table>
+                    <tr><th>Title</th><th>Published</th><th>Status</th><th></th></tr>
+                    {issues_html}
+                </table>
                 <br>
                 <p><a href="/admin/dashboard">&lt;- Back</a></p>
             </body>
         </html>"#,
         )))
 }
+
+// Archived issues are left out unless asked for, deleted ones never show up
+// Only issues carrying every one of `tags`, all of them when it's empty
+pub async fn get_issues(
+    pool: &PgPool,
+    include_archived: bool,
+    tags: &[String],
+) -> Result<Vec<IssueSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            published_at::timestamptz AS "published_at!",
+            status,
+            archived_at IS NOT NULL AS "archived!",
+            tags
+        FROM newsletter_issues
+        WHERE deleted_at IS NULL AND ($1 OR archived_at IS NULL) AND tags @> $2
+        ORDER BY published_at DESC
+        "#,
+        include_archived,
+        tags
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,38 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    issue_delivery_worker::inline_css,
+    utils::{e404, e500},
+};
+
+// The issue as an email client that strips <style> blocks would render it. Inlined whether or not
+// the issue was published with CSS inlining, and without the tracking the worker adds per delivery.
+#[tracing::instrument(
+    name = "Preview a newsletter issue with inlined CSS",
+    skip(pool, _user_id)
+)]
+pub async fn preview_inlined_html(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        "SELECT html_content FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        *issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch the issue's HTML.")
+    .map_err(e500)?
+    .ok_or_else(|| e404("No such issue"))?;
+    let html = inline_css(&issue.html_content)
+        .context("Failed to inline the issue's CSS.")
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html))
+}
@@ -0,0 +1,26 @@
+use actix_web::{HttpResponse, web};
+
+use crate::{authentication::UserId, newsletter::InboxPreviewSimulator};
+
+#[derive(serde::Deserialize)]
+pub struct InboxPreviewQuery {
+    subject: String,
+    from_name: Option<String>,
+    from_email: String,
+    preheader: Option<String>,
+}
+
+#[tracing::instrument(name = "Preview an issue in the inbox", skip_all)]
+pub async fn inbox_preview(
+    query: web::Query<InboxPreviewQuery>,
+    _user_id: web::ReqData<UserId>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let previews = InboxPreviewSimulator::default().preview(
+        query.from_name.as_deref(),
+        &query.from_email,
+        &query.subject,
+        query.preheader.as_deref(),
+    );
+    HttpResponse::Ok().json(previews)
+}
@@ -1,5 +1,31 @@
+mod archive;
+mod click_report;
+mod compose;
+mod delivery_report;
 mod get;
+mod html_inline;
+mod inbox_preview;
+mod open_rate;
 mod post;
+mod preview_to_me;
+mod queue_metrics;
+mod resend;
+mod resend_failed;
+mod sending_rate;
+mod templates;
 
+pub use archive::*;
+pub use click_report::*;
+pub use compose::*;
+pub use delivery_report::*;
 pub use get::*;
+pub use html_inline::*;
+pub use inbox_preview::*;
+pub use open_rate::*;
 pub use post::*;
+pub use preview_to_me::*;
+pub use queue_metrics::*;
+pub use resend::*;
+pub use resend_failed::*;
+pub use sending_rate::*;
+pub use templates::*;
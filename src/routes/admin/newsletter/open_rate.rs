@@ -0,0 +1,54 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    utils::{e404, e500},
+};
+
+#[derive(serde::Serialize)]
+struct OpenRateResponse {
+    opens: i64,
+    unique_opens: i64,
+    // Share of delivered emails that were opened at least once
+    rate: f64,
+}
+
+#[tracing::instrument(name = "Get the open rate of a newsletter issue", skip(pool, _user_id))]
+pub async fn get_open_rate(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let stats = sqlx::query!(
+        r#"
+        SELECT
+            i.n_delivered,
+            COUNT(o.id) AS "opens!",
+            COUNT(DISTINCT o.delivery_id) AS "unique_opens!"
+        FROM newsletter_issues i
+        LEFT JOIN newsletter_opens o ON o.newsletter_issue_id = i.newsletter_issue_id
+        WHERE i.newsletter_issue_id = $1
+        GROUP BY i.newsletter_issue_id
+        "#,
+        *issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to count the issue's opens.")
+    .map_err(e500)?
+    .ok_or_else(|| e404("No such issue"))?;
+
+    let rate = if stats.n_delivered > 0 {
+        stats.unique_opens as f64 / stats.n_delivered as f64
+    } else {
+        0.0
+    };
+    Ok(HttpResponse::Ok().json(OpenRateResponse {
+        opens: stats.opens,
+        unique_opens: stats.unique_opens,
+        rate,
+    }))
+}
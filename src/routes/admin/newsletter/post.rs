@@ -1,21 +1,143 @@
-use actix_web::{HttpResponse, web};
+use std::fmt::Write;
+
+use actix_web::{
+    Either, HttpResponse,
+    http::header::{self, ContentType},
+    web,
+};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use sqlx::{PgPool, Postgres, Transaction};
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use super::{ComposeData, ComposePublishData, estimate_audience, publish_composed_issue};
 use crate::{
+    audit::record_audit_event,
     authentication::UserId,
-    idempotency::{IdempotencyKey, NextAction, save_response, try_processing},
-    utils::{e400, e500, see_other},
+    configuration::DeliverySettings,
+    delivery_queue::{RecipientCounts, count_recipients, enqueue_deliveries},
+    domain::{IssueSlug, IssueTag, PiiCipher},
+    idempotency::{
+        IdempotencyKey, IdempotencyKeyMaxLength, IdempotencyWait, NextAction, save_response,
+        try_processing,
+    },
+    link_checker::{LinkChecker, LinkReport, LinkStatus},
+    routes::{extract_links, find_template},
+    utils::{e400, e500, group_thousands, see_other},
 };
 
 #[derive(serde::Deserialize)]
+pub struct PublishQuery {
+    // Set by the confirmation page once the author has seen the broken links
+    skip_link_check: Option<u8>,
+}
+
+#[derive(Clone, serde::Deserialize)]
 pub struct NewsletterFormData {
     title: String,
     text_content: String,
     html_content: String,
     idempotency_key: String,
+    // Optional layout whose `{{content}}` placeholders are filled with the content above
+    template_id: Option<Uuid>,
+    // A verified sender to send from instead of the configured one. The form's default option
+    // submits an empty value.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    sender_id: Option<Uuid>,
+    // Replaces the sender's display name for this issue, left blank to keep it
+    from_name: Option<String>,
+    // Typed as one comma separated field, stored normalised
+    #[serde(default, deserialize_with = "comma_separated_tags")]
+    tags: Vec<String>,
+    // Unchecked checkboxes are left out of the form entirely
+    inline_css: Option<String>,
+    // Set by the confirmation page once the author has seen the content warning
+    confirm: Option<String>,
+    // `dry_run` to only count who the issue would go to. Never carried over to a resubmission.
+    action: Option<String>,
+}
+
+// An issue ready to be stored, with its template already applied
+pub struct IssueContent {
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub inline_css: bool,
+    pub sender_id: Option<Uuid>,
+    pub from_name: Option<String>,
+    pub tags: Vec<String>,
+}
+
+// Why an issue can't be published as it is. Shared by the form and the compose API, which each
+// report it their own way.
+#[derive(thiserror::Error, Debug)]
+pub enum IssueError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl From<IssueError> for actix_web::Error {
+    fn from(e: IssueError) -> Self {
+        match e {
+            IssueError::Invalid(message) => e400(message),
+            IssueError::Unexpected(e) => e500(e),
+        }
+    }
+}
+
+// Below this many visible characters a short part is more likely a short issue than a mistake
+const MIN_LENGTH_FOR_RATIO: usize = 200;
+// The shorter part has to be at least this fraction of the longer one
+const MIN_LENGTH_RATIO: f64 = 0.2;
+// Each attempt loses a race for the slug to an issue published at the same moment
+const MAX_SLUG_ATTEMPTS: u32 = 5;
+
+fn empty_as_none<'de, D>(deserializer: D) -> Result<Option<Uuid>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(id) => id.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+fn comma_separated_tags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(value
+        .map(|tags| {
+            IssueTag::parse_list(&tags)
+                .into_iter()
+                .map(Into::into)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+// The same fields as the form, answered with the compose API's JSON responses instead of pages
+fn as_compose_data(form: NewsletterFormData, skip_link_check: bool) -> ComposePublishData {
+    ComposePublishData {
+        idempotency_key: form.idempotency_key,
+        draft_id: None,
+        confirm: form.confirm.is_some() || skip_link_check,
+        issue: ComposeData {
+            title: form.title,
+            text_content: form.text_content,
+            html_content: form.html_content,
+            template_id: form.template_id,
+            sender_id: form.sender_id,
+            from_name: form.from_name,
+            tags: form.tags,
+            inline_css: form.inline_css.is_some(),
+        },
+    }
 }
 
 #[tracing::instrument(
@@ -23,54 +145,456 @@ pub struct NewsletterFormData {
     skip_all
     fields(user_id=%&*user_id)
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn publish_newsletter(
-    form: web::Form<NewsletterFormData>,
+    body: Either<web::Json<NewsletterFormData>, web::Form<NewsletterFormData>>,
+    query: web::Query<PublishQuery>,
     pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    link_checker: web::Data<LinkChecker>,
+    idempotency_key_max_length: web::Data<IdempotencyKeyMaxLength>,
+    idempotency_wait: web::Data<IdempotencyWait>,
+    pii_cipher: web::Data<PiiCipher>,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let form = match body {
+        // API clients get the new issue's id back, or why it wasn't published, as JSON
+        Either::Left(json) => {
+            let form = json.into_inner();
+            if form.action.as_deref() == Some("dry_run") {
+                return Ok(estimate_audience(pool, user_id).await?);
+            }
+            let data = as_compose_data(form, query.skip_link_check == Some(1));
+            return Ok(publish_composed_issue(
+                web::Json(data),
+                pool,
+                delivery,
+                link_checker,
+                idempotency_key_max_length,
+                idempotency_wait,
+                pii_cipher,
+                user_id,
+            )
+            .await?);
+        }
+        Either::Right(form) => form.into_inner(),
+    };
     let user_id = user_id.into_inner();
+    // Kept as submitted so a confirmation page can send it again
+    let submission = form.clone();
     let NewsletterFormData {
         title,
         text_content,
         html_content,
         idempotency_key,
-    } = form.0;
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
-        .await
-        .map_err(e500)?
+        template_id,
+        sender_id,
+        from_name,
+        tags,
+        inline_css,
+        confirm,
+        action,
+    } = form;
+    if action.as_deref() == Some("dry_run") {
+        let counts = count_recipients(&pool).await.map_err(e500)?;
+        return Ok(dry_run_page(&submission, &counts));
+    }
+    if confirm.is_none()
+        && let Some(warning) = content_mismatch(&html_content, &text_content)
     {
-        NextAction::StartProcessing(t) => t,
-        NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
-            return Ok(saved_response);
+        return Ok(content_warning_page(&submission, &warning));
+    }
+    let idempotency_key =
+        IdempotencyKey::parse(idempotency_key, **idempotency_key_max_length).map_err(e400)?;
+    let (html_content, text_content) =
+        apply_template(&pool, template_id, html_content, text_content).await?;
+    check_sender(&pool, &delivery, sender_id).await?;
+    if query.skip_link_check != Some(1) {
+        let reports = check_links(&link_checker, &html_content).await;
+        if has_broken_links(&reports) {
+            return Ok(broken_links_page(&submission, &reports));
         }
+    }
+    let issue = IssueContent {
+        title,
+        text_content,
+        html_content,
+        inline_css: inline_css.is_some(),
+        sender_id,
+        from_name: normalise_from_name(from_name),
+        tags,
     };
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
+    let mut transaction = pool
+        .begin()
         .await
-        .context("Failed to store newsletter issue details")
+        .context("Failed to acquire a Postgres connection from the pool.")
         .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    match try_processing(
+        &mut transaction,
+        &idempotency_key,
+        *user_id,
+        **idempotency_wait,
+    )
+    .await
+    .map_err(e500)?
+    {
+        NextAction::StartProcessing => {}
+        NextAction::ReturnSavedResponse(saved_response) => {
+            // The audience isn't part of the saved response, so a replay can't repeat it
+            FlashMessage::info("The newsletter issue has already been queued.").send();
+            return Ok(saved_response);
+        }
+        NextAction::StillProcessing => {
+            return Ok(HttpResponse::ServiceUnavailable()
+                .insert_header((header::RETRY_AFTER, idempotency_wait.0.as_secs().max(1)))
+                .body("The newsletter issue is still being published, try again shortly."));
+        }
+    }
+    let issue_id = store_issue(&mut transaction, &issue, *user_id, &delivery)
         .await
-        .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
     tracing::Span::current().record("user_id", tracing::field::display(&user_id));
     let response = see_other("/admin/newsletter");
-    let response = save_response(*transaction, &idempotency_key, *user_id, response)
+    let response = save_response(&mut transaction, &idempotency_key, *user_id, response)
+        .await
+        .map_err(e500)?;
+    // The key, the issue and the saved response land together or not at all. The issue is stored
+    // as 'enqueuing', the worker carries on queueing its deliveries if we don't get to finish.
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the published issue")
+        .map_err(e500)?;
+    let n_enqueued = enqueue_deliveries(&pool, &pii_cipher, issue_id, delivery.enqueue_chunk_size)
         .await
+        .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
-    success_message().send();
+    FlashMessage::info(format!("Newsletter queued for {n_enqueued} subscribers.")).send();
     Ok(response)
 }
 
+// Stored as 'enqueuing' and audited, in the caller's transaction. Queueing the deliveries is left
+// to `enqueue_deliveries` once it has committed.
+pub async fn store_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue: &IssueContent,
+    published_by: Uuid,
+    delivery: &DeliverySettings,
+) -> Result<Uuid, anyhow::Error> {
+    let issue_id = insert_newsletter_issue(
+        transaction,
+        issue,
+        published_by,
+        delivery.utm_injection.is_some(),
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+    record_audit_event(
+        &mut **transaction,
+        published_by,
+        "issue.published",
+        &issue_id.to_string(),
+        Some(&issue.title),
+    )
+    .await
+    .context("Failed to audit the published issue")?;
+    Ok(issue_id)
+}
+
+// Fills the template's `{{content}}` placeholders, the content is returned as is without one
+pub async fn apply_template(
+    pool: &PgPool,
+    template_id: Option<Uuid>,
+    html_content: String,
+    text_content: String,
+) -> Result<(String, String), IssueError> {
+    let Some(template_id) = template_id else {
+        return Ok((html_content, text_content));
+    };
+    let template = find_template(pool, template_id)
+        .await?
+        .ok_or_else(|| IssueError::Invalid("The selected template does not exist.".into()))?;
+    Ok(template.render(&html_content, &text_content))
+}
+
+// Only verified senders that the email provider also accepts can be sent from
+pub async fn check_sender(
+    pool: &PgPool,
+    delivery: &DeliverySettings,
+    sender_id: Option<Uuid>,
+) -> Result<(), IssueError> {
+    let Some(sender_id) = sender_id else {
+        return Ok(());
+    };
+    let email = verified_sender_email(pool, sender_id)
+        .await?
+        .ok_or_else(|| IssueError::Invalid("Only verified senders can be selected.".into()))?;
+    if !delivery.accepts_sender(&email) {
+        return Err(IssueError::Invalid(format!(
+            "{email} is not one of the configured verified senders."
+        )));
+    }
+    Ok(())
+}
+
+// One report per link in the issue, anything that isn't OK is logged
+pub async fn check_links(link_checker: &LinkChecker, html_content: &str) -> Vec<LinkReport> {
+    let reports = link_checker.check(&extract_links(html_content)).await;
+    for report in reports.iter().filter(|r| r.status != LinkStatus::Ok) {
+        tracing::warn!(url = %report.url, "A link in the issue {}.", report.describe());
+    }
+    reports
+}
+
+// Slow or unreachable links don't hold an issue back, error responses do
+pub fn has_broken_links(reports: &[LinkReport]) -> bool {
+    reports
+        .iter()
+        .any(|r| matches!(r.status, LinkStatus::Broken(_)))
+}
+
+// A blank display name keeps the sender's own
+pub fn normalise_from_name(from_name: Option<String>) -> Option<String> {
+    from_name
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+}
+
+// Lists what is wrong with the issue's links and offers to publish it anyway with the same
+// submission, idempotency key included
+fn broken_links_page(form: &NewsletterFormData, reports: &[LinkReport]) -> HttpResponse {
+    let n_broken = reports
+        .iter()
+        .filter(|r| matches!(r.status, LinkStatus::Broken(_)))
+        .count();
+    let summary = if n_broken == 1 {
+        "1 link returned an error".to_owned()
+    } else {
+        format!("{n_broken} links returned errors")
+    };
+    let mut reports_html = String::new();
+    for report in reports.iter().filter(|r| r.status != LinkStatus::Ok) {
+        writeln!(
+            reports_html,
+            "<li>{} {}</li>",
+            encode_minimal(&report.url),
+            report.describe()
+        )
+        .unwrap();
+    }
+    let hidden_html = hidden_fields(form);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Check links</title>
+            </head>
+            <body>
+                <p><i>{summary}</i></p>
+                <ul>
+                    {reports_html}
+                </ul>
+                <form action="/admin/newsletter?skip_link_check=1" method="post">
+                    {hidden_html}
+                    <button type="submit">Publish anyway</button>
+                </form>
+                <p><a href="/admin/newsletter">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        ))
+}
+
+// Reports who publishing would reach and offers to go ahead with the same submission. Nothing is
+// stored and the idempotency key is left unused.
+fn dry_run_page(form: &NewsletterFormData, counts: &RecipientCounts) -> HttpResponse {
+    let summary = format!(
+        "Would send to {} confirmed subscribers; {} suppressed, {} unsubscribed excluded.",
+        group_thousands(counts.recipients),
+        group_thousands(counts.suppressed),
+        group_thousands(counts.unsubscribed)
+    );
+    let hidden_html = hidden_fields(form);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Dry run</title>
+            </head>
+            <body>
+                <p><i>{summary}</i></p>
+                <form action="/admin/newsletter" method="post">
+                    {hidden_html}
+                    <button type="submit">Publish</button>
+                </form>
+                <p><a href="/admin/newsletter">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        ))
+}
+
+// Warns about an issue where one part looks forgotten: left empty, or much shorter than the other
+pub fn content_mismatch(html_content: &str, text_content: &str) -> Option<String> {
+    let html = visible_length(html_content);
+    let text = text_content.chars().filter(|c| !c.is_whitespace()).count();
+    match (html, text) {
+        (0, 0) => None,
+        (0, _) => Some("The HTML content is empty but the plain text content isn't.".into()),
+        (_, 0) => Some("The plain text content is empty but the HTML content isn't.".into()),
+        (html, text)
+            if html.max(text) >= MIN_LENGTH_FOR_RATIO
+                && (html.min(text) as f64) < html.max(text) as f64 * MIN_LENGTH_RATIO =>
+        {
+            let (shorter, longer) = if html < text {
+                ("HTML", "plain text")
+            } else {
+                ("plain text", "HTML")
+            };
+            Some(format!(
+                "The {shorter} content is much shorter than the {longer} content."
+            ))
+        }
+        _ => None,
+    }
+}
+
+// Only counts what a reader would see, so markup doesn't make the HTML look longer
+fn visible_length(html: &str) -> usize {
+    let mut in_tag = false;
+    html.chars()
+        .filter(|c| match c {
+            '<' => {
+                in_tag = true;
+                false
+            }
+            '>' => {
+                in_tag = false;
+                false
+            }
+            c => !in_tag && !c.is_whitespace(),
+        })
+        .count()
+}
+
+fn content_warning_page(form: &NewsletterFormData, warning: &str) -> HttpResponse {
+    let hidden_html = hidden_fields(form);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Check content</title>
+            </head>
+            <body>
+                <p><i>{}</i></p>
+                <form action="/admin/newsletter" method="post">
+                    {hidden_html}
+                    <input type="hidden" name="confirm" value="1">
+                    <button type="submit">Publish anyway</button>
+                </form>
+                <p><a href="/admin/newsletter">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+            encode_minimal(warning)
+        ))
+}
+
+// The submission as hidden inputs, idempotency key included, so it can be posted again as it was
+fn hidden_fields(form: &NewsletterFormData) -> String {
+    let mut fields = vec![
+        ("title", form.title.clone()),
+        ("text_content", form.text_content.clone()),
+        ("html_content", form.html_content.clone()),
+        ("idempotency_key", form.idempotency_key.clone()),
+    ];
+    if let Some(template_id) = form.template_id {
+        fields.push(("template_id", template_id.to_string()));
+    }
+    if let Some(sender_id) = form.sender_id {
+        fields.push(("sender_id", sender_id.to_string()));
+    }
+    if let Some(from_name) = &form.from_name {
+        fields.push(("from_name", from_name.clone()));
+    }
+    if !form.tags.is_empty() {
+        fields.push(("tags", form.tags.join(", ")));
+    }
+    if let Some(inline_css) = &form.inline_css {
+        fields.push(("inline_css", inline_css.clone()));
+    }
+    if let Some(confirm) = &form.confirm {
+        fields.push(("confirm", confirm.clone()));
+    }
+    let mut hidden_html = String::new();
+    for (name, value) in fields {
+        writeln!(
+            hidden_html,
+            r#"<input type="hidden" name="{name}" value="{}">"#,
+            encode_attribute(&value)
+        )
+        .unwrap();
+    }
+    hidden_html
+}
+
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
+    issue: &IssueContent,
+    published_by: Uuid,
+    utm_injection: bool,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
+    let base_slug = IssueSlug::from_title(&issue.title, newsletter_issue_id);
+    // An issue with the same title published at the same time can take the slug between looking
+    // and inserting. The insert then fails on the unique constraint and the next suffix is tried.
+    let mut attempt = 1;
+    loop {
+        let slug = unique_slug(transaction, &base_slug).await?;
+        let mut savepoint = transaction.begin().await?;
+        match insert_issue_row(
+            &mut savepoint,
+            newsletter_issue_id,
+            issue,
+            published_by,
+            &slug,
+            utm_injection,
+        )
+        .await
+        {
+            Ok(()) => {
+                savepoint.commit().await?;
+                return Ok(newsletter_issue_id);
+            }
+            Err(e) if is_slug_conflict(&e) && attempt < MAX_SLUG_ATTEMPTS => {
+                savepoint.rollback().await?;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn is_slug_conflict(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|e| e.constraint() == Some("newsletter_issues_slug_key"))
+}
+
+async fn insert_issue_row(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    issue: &IssueContent,
+    published_by: Uuid,
+    slug: &IssueSlug,
+    utm_injection: bool,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
     INSERT INTO newsletter_issues (
@@ -78,42 +602,70 @@ async fn insert_newsletter_issue(
         title,
         text_content,
         html_content,
-        published_at
+        published_at,
+        published_by,
+        status,
+        slug,
+        utm_injection,
+        inline_css,
+        sender_id,
+        from_name,
+        tags
     )
-    VALUES ($1, $2, $3, $4, now())
+    VALUES ($1, $2, $3, $4, now(), $5, 'enqueuing', $6, $7, $8, $9, $10, $11)
     "#,
         newsletter_issue_id,
-        title,
-        text_content,
-        html_content
+        issue.title,
+        issue.text_content,
+        issue.html_content,
+        published_by,
+        slug.as_ref(),
+        utm_injection,
+        issue.inline_css,
+        issue.sender_id,
+        issue.from_name,
+        &issue.tags
     )
-    .execute(transaction)
+    .execute(&mut **transaction)
     .await?;
-    Ok(newsletter_issue_id)
+    Ok(())
 }
 
+// `None` for senders that don't exist or haven't been verified
+#[tracing::instrument(skip(pool))]
+async fn verified_sender_email(
+    pool: &PgPool,
+    sender_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let sender = sqlx::query!(
+        r#"SELECT email FROM senders WHERE sender_id = $1 AND verified_at IS NOT NULL"#,
+        sender_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the sender.")?;
+    Ok(sender.map(|s| s.email))
+}
+
+// Issues sharing a title get `-2`, `-3`, ... appended in publishing order
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+async fn unique_slug(
     transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+    slug: &IssueSlug,
+) -> Result<IssueSlug, sqlx::Error> {
+    let taken = sqlx::query!(
         r#"
-    INSERT INTO issue_delivery_queue (
-        newsletter_issue_id,
-        subscriber_email
-    )
-    SELECT $1, email
-    FROM subscriptions
-    WHERE status = 'confirmed'
+    SELECT slug
+    FROM newsletter_issues
+    WHERE slug = $1 OR slug LIKE $1 || '-%'
     "#,
-        newsletter_issue_id,
+        slug.as_ref()
     )
-    .execute(transaction)
+    .fetch_all(&mut **transaction)
     .await?;
-    Ok(())
-}
-
-fn success_message() -> FlashMessage {
-    FlashMessage::info("The newsletter issue has been published!")
+    let last_suffix = taken.iter().filter_map(|r| slug.suffix_of(&r.slug)).max();
+    Ok(match last_suffix {
+        Some(n) => slug.with_suffix(n + 1),
+        None => slug.clone(),
+    })
 }
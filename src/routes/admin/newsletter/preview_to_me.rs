@@ -0,0 +1,116 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    configuration::{DeliverySettings, UtmSettings},
+    domain::SubscriberEmail,
+    email_client::EmailClientPool,
+    issue_delivery_worker::inline_css,
+    routes::add_utm_parameters,
+    startup::ApplicationBaseUrl,
+    utils::{e500, see_other},
+};
+
+// Sends the issue to the signed in admin's own address, styled and tagged as subscribers get it
+// but without the tracking and feedback links that belong to a delivery. Nothing is recorded, so
+// it can be sent as often as needed.
+#[tracing::instrument(
+    name = "Send a newsletter preview to the admin",
+    skip(pool, email_clients, delivery, base_url, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn send_preview_to_me(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_clients: web::Data<EmailClientPool>,
+    delivery: web::Data<DeliverySettings>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = sqlx::query!("SELECT email FROM users WHERE user_id = $1", **user_id)
+        .fetch_one(pool.get_ref())
+        .await
+        .context("Failed to retrieve the admin's email address.")
+        .map_err(e500)?
+        .email;
+    let Some(Ok(recipient)) = email.map(SubscriberEmail::parse) else {
+        FlashMessage::error(
+            "Add a valid email address to your notification settings to send yourself previews.",
+        )
+        .send();
+        return Ok(see_other("/admin/newsletter"));
+    };
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, text_content, html_content, utm_injection, inline_css, from_name
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND deleted_at IS NULL
+        "#,
+        *issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the newsletter issue.")
+    .map_err(e500)?;
+    let Some(issue) = issue else {
+        FlashMessage::error("No such newsletter issue.").send();
+        return Ok(see_other("/admin/newsletter"));
+    };
+
+    let utm = delivery
+        .utm_injection
+        .as_ref()
+        .filter(|_| issue.utm_injection);
+    let html_content = preview_html(issue.html_content, issue.inline_css, utm, &base_url);
+    let sent = email_clients
+        .send(
+            issue.from_name.as_deref(),
+            &recipient,
+            &format!("[Preview] {}", issue.title),
+            &html_content,
+            &issue.text_content,
+        )
+        .await;
+    match sent {
+        Ok(()) => FlashMessage::info(format!(
+            "A preview of \"{}\" has been sent to {}.",
+            encode_minimal(&issue.title),
+            encode_minimal(recipient.as_ref())
+        ))
+        .send(),
+        Err(e) => {
+            tracing::error!(error.message = %e, "Failed to send a newsletter preview.");
+            FlashMessage::error("The preview couldn't be sent, please try again.").send();
+        }
+    }
+    Ok(see_other("/admin/newsletter"))
+}
+
+// The issue's HTML styled and tagged the way the worker sends it, without the tracking and feedback
+// links that belong to a delivery
+pub fn preview_html(
+    html_content: String,
+    inline: bool,
+    utm: Option<&UtmSettings>,
+    base_url: &ApplicationBaseUrl,
+) -> String {
+    let mut html_content = html_content;
+    if inline {
+        match inline_css(&html_content) {
+            Ok(inlined) => html_content = inlined,
+            Err(e) => tracing::warn!(
+                error.message = %e,
+                "Failed to inline the issue's CSS. Previewing it as written.",
+            ),
+        }
+    }
+    match utm {
+        Some(utm) => add_utm_parameters(&html_content, utm, base_url),
+        None => html_content,
+    }
+}
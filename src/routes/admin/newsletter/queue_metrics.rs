@@ -0,0 +1,137 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{authentication::UserId, clock::Clock, configuration::DeliverySettings, utils::e500};
+
+#[derive(serde::Serialize)]
+struct QueueMetricsResponse {
+    // Due now and waiting for a worker
+    pending_tasks: i64,
+    // Locked by a worker that is sending them right now
+    in_progress_tasks: i64,
+    // Since midnight UTC
+    completed_today: i64,
+    failed_today: i64,
+    // From publishing an issue to delivering it, over today's deliveries. Left out when there were none.
+    average_delivery_time_ms: Option<i64>,
+    // How long the longest waiting due task has been due, 0 with nothing due
+    oldest_pending_age_seconds: i64,
+    // Set when that wait is longer than `delivery.queue_age_warning_seconds`
+    warning: Option<String>,
+}
+
+// For monitoring dashboards to poll
+#[tracing::instrument(name = "Get the delivery queue metrics", skip_all)]
+pub async fn get_queue_metrics(
+    pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    clock: web::Data<dyn Clock>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let now = clock.now();
+    let queue = queue_counts(&pool, now).await.map_err(e500)?;
+    let deliveries = deliveries_today(&pool, now).await.map_err(e500)?;
+    let warning = (queue.oldest_pending_age_seconds > delivery.queue_age_warning_seconds as i64)
+        .then(|| {
+            format!(
+                "The oldest pending delivery has been waiting for {} seconds.",
+                queue.oldest_pending_age_seconds
+            )
+        });
+    Ok(HttpResponse::Ok().json(QueueMetricsResponse {
+        pending_tasks: queue.pending_tasks,
+        in_progress_tasks: queue.in_progress_tasks,
+        completed_today: deliveries.completed,
+        failed_today: deliveries.failed,
+        average_delivery_time_ms: deliveries.average_delivery_time_ms,
+        oldest_pending_age_seconds: queue.oldest_pending_age_seconds,
+        warning,
+    }))
+}
+
+struct QueueCounts {
+    pending_tasks: i64,
+    in_progress_tasks: i64,
+    oldest_pending_age_seconds: i64,
+}
+
+// A worker holds one task's row lock for as long as it's sending it, and those locks are the
+// only ones taken on the queue. Row locks aren't listed anywhere short of reading the rows, so
+// the workers' transactions holding a lock on the table stand in for them. Nothing is locked
+// here, so a dashboard polling away never gets in a worker's way.
+async fn queue_counts(pool: &PgPool, now: DateTime<Utc>) -> Result<QueueCounts, anyhow::Error> {
+    let counts = sqlx::query!(
+        r#"
+        WITH in_progress AS (
+            SELECT COUNT(DISTINCT virtualtransaction) AS n
+            FROM pg_locks
+            WHERE
+                locktype = 'relation' AND
+                mode = 'RowShareLock' AND
+                granted AND
+                database = (SELECT oid FROM pg_database WHERE datname = current_database()) AND
+                relation = 'issue_delivery_queue'::regclass AND
+                pid <> pg_backend_pid()
+        )
+        SELECT
+            GREATEST(
+                (SELECT COUNT(*) FROM issue_delivery_queue WHERE execute_after <= $1) -
+                    (SELECT n FROM in_progress),
+                0
+            ) AS "pending_tasks!",
+            (SELECT n FROM in_progress) AS "in_progress_tasks!",
+            COALESCE(
+                (SELECT EXTRACT(EPOCH FROM $1 - MIN(execute_after))::BIGINT
+                 FROM issue_delivery_queue
+                 WHERE execute_after <= $1),
+                0
+            ) AS "oldest_pending_age_seconds!"
+        "#,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count the queued deliveries.")?;
+    Ok(QueueCounts {
+        pending_tasks: counts.pending_tasks,
+        in_progress_tasks: counts.in_progress_tasks,
+        oldest_pending_age_seconds: counts.oldest_pending_age_seconds,
+    })
+}
+
+struct DeliveriesToday {
+    completed: i64,
+    failed: i64,
+    average_delivery_time_ms: Option<i64>,
+}
+
+async fn deliveries_today(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+) -> Result<DeliveriesToday, anyhow::Error> {
+    let midnight = now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE d.status = 'delivered') AS "completed!",
+            COUNT(*) FILTER (WHERE d.status = 'failed') AS "failed!",
+            (AVG(EXTRACT(EPOCH FROM d.attempted_at - i.published_at::timestamptz))
+                FILTER (WHERE d.status = 'delivered') * 1000)::BIGINT AS average_delivery_time_ms
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE d.attempted_at >= $1 AND d.attempted_at <= $2
+        "#,
+        midnight,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count today's deliveries.")?;
+    Ok(DeliveriesToday {
+        completed: counts.completed,
+        failed: counts.failed,
+        average_delivery_time_ms: counts.average_delivery_time_ms,
+    })
+}
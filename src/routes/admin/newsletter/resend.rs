@@ -0,0 +1,187 @@
+use actix_web::{HttpResponse, error::ErrorConflict, web};
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    delivery_throttle::recipient_domain,
+    domain::{PiiCipher, SubscriberEmail},
+    utils::{e400, e404, e500},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ResendFormData {
+    email: String,
+}
+
+#[derive(serde::Serialize)]
+struct ResendResponse {
+    resend_id: Uuid,
+}
+
+// For a subscriber who never got their copy. The worker renders the stored issue again, so they
+// get the same content as everyone else.
+#[tracing::instrument(
+    name = "Resend a newsletter issue to a single subscriber",
+    skip(form, pool, pii_cipher, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn resend_to_subscriber(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<ResendFormData>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    // None of the errors repeat the address, they end up in logs
+    let email = SubscriberEmail::parse(form.0.email)
+        .map_err(|_| e400("That is not a valid email address."))?;
+    if !issue_exists(&pool, issue_id).await.map_err(e500)? {
+        return Err(e404("No such newsletter issue."));
+    }
+    let Some(subscriber_id) = confirmed_subscriber_id(&pool, email.as_ref(), &pii_cipher)
+        .await
+        .map_err(e500)?
+    else {
+        return Err(e400("That address is not a confirmed subscriber."));
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let resend_id = Uuid::new_v4();
+    let queued = enqueue_resend(
+        &mut transaction,
+        issue_id,
+        subscriber_id,
+        &recipient_domain(email.as_ref()).unwrap_or_default(),
+        resend_id,
+    )
+    .await
+    .context("Failed to queue the resend")
+    .map_err(e500)?;
+    if !queued {
+        return Err(ErrorConflict(
+            "A copy for that subscriber is already queued.",
+        ));
+    }
+    forget_previous_outcome(&mut transaction, issue_id, subscriber_id)
+        .await
+        .context("Failed to update the issue's delivery counts")
+        .map_err(e500)?;
+    record_audit_event(
+        &mut transaction,
+        *user_id.into_inner(),
+        "issue.resent",
+        &issue_id.to_string(),
+        Some(&subscriber_id.to_string()),
+    )
+    .await
+    .context("Failed to audit the resend")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to queue the resend.")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(ResendResponse { resend_id }))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn issue_exists(pool: &PgPool, issue_id: Uuid) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT 1 AS "exists"
+    FROM newsletter_issues
+    WHERE newsletter_issue_id = $1 AND deleted_at IS NULL
+    "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.is_some())
+}
+
+#[tracing::instrument(skip(pool, pii_cipher))]
+async fn confirmed_subscriber_id(
+    pool: &PgPool,
+    email: &str,
+    pii_cipher: &PiiCipher,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT id
+    FROM subscriptions
+    WHERE (email = $1 OR email_hash = $2) AND status = 'confirmed'
+    "#,
+        email,
+        pii_cipher.email_hash(email)
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.id))
+}
+
+// `false` when a delivery to the subscriber is still waiting in the queue
+#[tracing::instrument(skip(transaction))]
+async fn enqueue_resend(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    recipient_domain: &str,
+    resend_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let queued = sqlx::query!(
+        r#"
+    INSERT INTO issue_delivery_queue (
+        newsletter_issue_id,
+        subscriber_id,
+        recipient_domain,
+        resend_id
+    )
+    VALUES ($1, $2, $3, $4)
+    ON CONFLICT DO NOTHING
+    "#,
+        issue_id,
+        subscriber_id,
+        recipient_domain,
+        resend_id
+    )
+    .execute(transaction)
+    .await?
+    .rows_affected();
+    Ok(queued > 0)
+}
+
+// The worker counts the resend's outcome once it's done, so the first attempt stops counting and
+// the subscriber is still counted once
+#[tracing::instrument(skip(transaction))]
+async fn forget_previous_outcome(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    UPDATE newsletter_issues i
+    SET
+        n_delivered = GREATEST(i.n_delivered - (d.status = 'delivered')::INT, 0),
+        n_failed = GREATEST(i.n_failed - (d.status = 'failed')::INT, 0)
+    FROM newsletter_deliveries d
+    WHERE
+        i.newsletter_issue_id = $1 AND
+        d.newsletter_issue_id = i.newsletter_issue_id AND
+        d.subscriber_id = $2
+    "#,
+        issue_id,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId, delivery_throttle::recipient_domain, domain::PiiCipher, utils::e500,
+};
+
+#[derive(serde::Serialize)]
+struct ResendFailedResponse {
+    requeued: u64,
+}
+
+#[tracing::instrument(
+    name = "Requeue failed deliveries of a newsletter issue",
+    skip(pool, pii_cipher, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn resend_failed_deliveries(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let requeued = requeue_failed_deliveries(&mut transaction, &pii_cipher, issue_id)
+        .await
+        .context("Failed to requeue failed deliveries")
+        .map_err(e500)?;
+    if requeued > 0 {
+        reopen_issue(&mut transaction, issue_id, requeued)
+            .await
+            .context("Failed to reopen the newsletter issue for delivery")
+            .map_err(e500)?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to requeue failed deliveries.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("{requeued} failed deliveries have been requeued.")).send();
+    Ok(HttpResponse::Ok().json(ResendFailedResponse { requeued }))
+}
+
+// The queue needs each recipient's domain, which only their decrypted address gives away
+#[tracing::instrument(skip_all)]
+async fn requeue_failed_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    pii_cipher: &PiiCipher,
+    issue_id: Uuid,
+) -> Result<u64, anyhow::Error> {
+    let failed = sqlx::query!(
+        r#"
+    SELECT s.id, s.email, s.email_encrypted
+    FROM newsletter_deliveries d
+    JOIN subscriptions s ON s.id = d.subscriber_id
+    WHERE
+        d.newsletter_issue_id = $1 AND
+        d.status = 'failed'
+    "#,
+        issue_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    let domains = failed
+        .iter()
+        .map(|row| {
+            pii_cipher
+                .reveal(row.email.clone(), row.email_encrypted.clone())
+                .map(|email| recipient_domain(&email).unwrap_or_default())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to decrypt a recipient's email.")?;
+    let subscriber_ids: Vec<Uuid> = failed.iter().map(|row| row.id).collect();
+    let requeued = sqlx::query!(
+        r#"
+    INSERT INTO issue_delivery_queue (
+        newsletter_issue_id,
+        subscriber_id,
+        recipient_domain,
+        retry_count
+    )
+    SELECT $1, *, 0 FROM UNNEST($2::uuid[], $3::text[])
+    ON CONFLICT DO NOTHING
+    "#,
+        issue_id,
+        &subscriber_ids,
+        &domains
+    )
+    .execute(transaction)
+    .await?
+    .rows_affected();
+    Ok(requeued)
+}
+
+// The requeued deliveries no longer count as failures, the worker records their new outcome and
+// marks the issue as sent again once they are done.
+#[tracing::instrument(skip_all)]
+async fn reopen_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    requeued: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET
+        status = 'sending',
+        completed_at = NULL,
+        n_failed = GREATEST(n_failed - $2, 0)
+    WHERE newsletter_issue_id = $1
+    "#,
+        issue_id,
+        requeued as i32,
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use actix_web::{HttpResponse, web};
+
+use crate::{authentication::UserId, clock::Clock, sending_rate::SendingRate, utils::e500};
+
+#[derive(serde::Serialize)]
+struct SendingRateResponse {
+    emails_sent_today: u64,
+    // Left out when there is no limit
+    daily_limit: Option<u32>,
+    emails_per_second_limit: Option<f64>,
+    // Deliveries are held until midnight UTC
+    is_rate_limited: bool,
+}
+
+#[tracing::instrument(name = "Get the sending rate", skip_all)]
+pub async fn get_sending_rate(
+    rate: web::Data<SendingRate>,
+    clock: web::Data<dyn Clock>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let now = clock.now();
+    let emails_sent_today = rate.sent_today(now).await.map_err(e500)?;
+    let is_rate_limited = rate
+        .per_day()
+        .is_some_and(|per_day| emails_sent_today >= per_day as u64);
+    Ok(HttpResponse::Ok().json(SendingRateResponse {
+        emails_sent_today,
+        daily_limit: rate.per_day(),
+        emails_per_second_limit: rate.per_second(),
+        is_rate_limited,
+    }))
+}
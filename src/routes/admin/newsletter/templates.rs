@@ -0,0 +1,225 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+
+// Replaced with the issue's own content when a newsletter is published with a template
+pub const CONTENT_PLACEHOLDER: &str = "{{content}}";
+
+#[derive(serde::Deserialize)]
+pub struct TemplateData {
+    name: String,
+    html_body: String,
+    text_body: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct NewsletterTemplate {
+    pub id: Uuid,
+    pub name: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NewsletterTemplate {
+    // Returns the (html, text) bodies with the placeholders swapped for the given content
+    pub fn render(&self, html_content: &str, text_content: &str) -> (String, String) {
+        (
+            self.html_body.replace(CONTENT_PLACEHOLDER, html_content),
+            self.text_body.replace(CONTENT_PLACEHOLDER, text_content),
+        )
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TemplateError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("Template not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for TemplateError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TemplateError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            TemplateError::NotFound => StatusCode::NOT_FOUND,
+            TemplateError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl TemplateData {
+    fn validate(&self) -> Result<(), TemplateError> {
+        if self.name.trim().is_empty() {
+            return Err(TemplateError::ValidationError(
+                "A template needs a name.".into(),
+            ));
+        }
+        if !self.html_body.contains(CONTENT_PLACEHOLDER)
+            || !self.text_body.contains(CONTENT_PLACEHOLDER)
+        {
+            return Err(TemplateError::ValidationError(format!(
+                "Both bodies must contain the {CONTENT_PLACEHOLDER} placeholder."
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[tracing::instrument(name = "Create a newsletter template", skip_all, fields(user_id=%&*user_id))]
+pub async fn create_template(
+    template: web::Json<TemplateData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, TemplateError> {
+    template.validate()?;
+    let template = sqlx::query_as!(
+        NewsletterTemplate,
+        r#"
+        INSERT INTO newsletter_templates (id, name, html_body, text_body, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        RETURNING id, name, html_body, text_body, created_by, created_at
+        "#,
+        Uuid::new_v4(),
+        template.name.trim(),
+        template.html_body,
+        template.text_body,
+        **user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to store the newsletter template.")?;
+    Ok(HttpResponse::Created().json(template))
+}
+
+#[tracing::instrument(name = "List newsletter templates", skip_all)]
+pub async fn list_templates(
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, TemplateError> {
+    let templates = sqlx::query_as!(
+        NewsletterTemplate,
+        r#"
+        SELECT id, name, html_body, text_body, created_by, created_at
+        FROM newsletter_templates
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve newsletter templates.")?;
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+#[tracing::instrument(name = "Get a newsletter template", skip(pool, _user_id))]
+pub async fn get_template(
+    template_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, TemplateError> {
+    let template = find_template(&pool, *template_id)
+        .await?
+        .ok_or(TemplateError::NotFound)?;
+    Ok(HttpResponse::Ok().json(template))
+}
+
+#[tracing::instrument(name = "Update a newsletter template", skip(template, pool, _user_id))]
+pub async fn update_template(
+    template_id: web::Path<Uuid>,
+    template: web::Json<TemplateData>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, TemplateError> {
+    template.validate()?;
+    let template = sqlx::query_as!(
+        NewsletterTemplate,
+        r#"
+        UPDATE newsletter_templates
+        SET name = $2, html_body = $3, text_body = $4
+        WHERE id = $1
+        RETURNING id, name, html_body, text_body, created_by, created_at
+        "#,
+        *template_id,
+        template.name.trim(),
+        template.html_body,
+        template.text_body
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update the newsletter template.")?
+    .ok_or(TemplateError::NotFound)?;
+    Ok(HttpResponse::Ok().json(template))
+}
+
+#[tracing::instrument(name = "Delete a newsletter template", skip(pool, _user_id))]
+pub async fn delete_template(
+    template_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, TemplateError> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM newsletter_templates WHERE id = $1"#,
+        *template_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete the newsletter template.")?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(TemplateError::NotFound);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn find_template(
+    pool: &PgPool,
+    template_id: Uuid,
+) -> Result<Option<NewsletterTemplate>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        NewsletterTemplate,
+        r#"
+        SELECT id, name, html_body, text_body, created_by, created_at
+        FROM newsletter_templates
+        WHERE id = $1
+        "#,
+        template_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter template.")?;
+    Ok(template)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::NewsletterTemplate;
+
+    #[test]
+    fn rendering_wraps_the_content_in_the_layout() {
+        let template = NewsletterTemplate {
+            id: Uuid::new_v4(),
+            name: "Layout".into(),
+            html_body: "<header/>{{content}}<footer/>".into(),
+            text_body: "Hello\n{{content}}\nBye".into(),
+            created_by: Uuid::new_v4(),
+            created_at: Utc::now(),
+        };
+
+        let (html, text) = template.render("<p>Body</p>", "Body");
+
+        assert_eq!(html, "<header/><p>Body</p><footer/>");
+        assert_eq!(text, "Hello\nBody\nBye");
+    }
+}
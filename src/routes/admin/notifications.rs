@@ -0,0 +1,75 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    domain::SubscriberEmail,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct NotificationSettingsFormData {
+    email: String,
+    // Unchecked checkboxes are left out of the form entirely
+    notify_on_completion: Option<String>,
+}
+
+pub async fn update_notification_settings(
+    form: web::Form<NotificationSettingsFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let NotificationSettingsFormData {
+        email,
+        notify_on_completion,
+    } = form.0;
+    let notify_on_completion = notify_on_completion.is_some();
+    let email = if email.trim().is_empty() {
+        None
+    } else {
+        match SubscriberEmail::parse(email.trim().to_owned()) {
+            Ok(email) => Some(email),
+            Err(e) => {
+                FlashMessage::error(e).send();
+                return Ok(see_other("/admin/dashboard"));
+            }
+        }
+    };
+    if notify_on_completion && email.is_none() {
+        FlashMessage::error("An email address is required to receive notifications.").send();
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    store_notification_settings(*user_id, email.as_ref(), notify_on_completion, &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Your notification settings have been updated.").send();
+    Ok(see_other("/admin/dashboard"))
+}
+
+#[tracing::instrument(name = "Store notification settings", skip(email, pool))]
+async fn store_notification_settings(
+    user_id: Uuid,
+    email: Option<&SubscriberEmail>,
+    notify_on_completion: bool,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $1, notify_on_completion = $2
+        WHERE user_id = $3
+        "#,
+        email.map(|e| e.as_ref()),
+        notify_on_completion,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the user's notification settings.")?;
+    Ok(())
+}
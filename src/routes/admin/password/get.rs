@@ -3,16 +3,30 @@ use std::fmt::Write;
 use actix_web::{HttpResponse, http::header::ContentType, web};
 use actix_web_flash_messages::IncomingFlashMessages;
 
-use crate::authentication::UserId;
+use crate::{
+    authentication::UserId, clock::Clock, configuration::SessionSettings,
+    session_state::TypedSession, utils::e500,
+};
 
 pub async fn change_password_form(
     flash_messages: IncomingFlashMessages,
+    session_settings: web::Data<SessionSettings>,
+    session: TypedSession,
+    clock: web::Data<dyn Clock>,
     _user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let recently_signed_in = session
+        .authenticated_within(session_settings.reauthentication_window(), clock.now())
+        .map_err(e500)?;
+    let current_password_placeholder = if recently_signed_in {
+        "Not needed, you signed in recently"
+    } else {
+        "Enter current password"
+    };
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -29,7 +43,7 @@ pub async fn change_password_form(
                     <label>Current password
                     <input
                         type="password"
-                        placeholder="Enter current password"
+                        placeholder="{current_password_placeholder}"
                         name="current_password"
                     >
                 </label>
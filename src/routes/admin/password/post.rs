@@ -4,8 +4,11 @@ use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{AuthError, Credentials, UserId, validate_credentials},
+    authentication::{AuthError, Credentials, PasswordHistoryDepth, UserId, validate_credentials},
+    clock::Clock,
+    configuration::SessionSettings,
     routes::admin::dashboard::get_username,
+    session_state::TypedSession,
     utils::{e500, see_other},
 };
 
@@ -14,7 +17,8 @@ pub struct ValidNewPassword(String);
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
-    current_password: Secret<String>,
+    // May be left empty shortly after signing in, see `SessionSettings`
+    current_password: Option<Secret<String>>,
     new_password: Secret<String>,
     new_password_check: Secret<String>,
 }
@@ -30,62 +34,84 @@ impl ValidNewPassword {
         Ok(ValidNewPassword(s.to_string()))
     }
 
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         self.0.as_bytes()
     }
 }
 
+// Every applicable problem is reported at once, so the form only has to be fixed once
 pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
+    history_depth: web::Data<PasswordHistoryDepth>,
+    session_settings: web::Data<SessionSettings>,
+    session: TypedSession,
+    clock: web::Data<dyn Clock>,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
-    let new_password = match ValidNewPassword::parse(form.new_password.expose_secret()) {
-        Ok(password) => password,
-        Err(error) => {
-            FlashMessage::error(&error).send();
-            return Ok(see_other("/admin/password"));
-        }
-    };
-    let new_password_check = match ValidNewPassword::parse(form.new_password_check.expose_secret())
-    {
-        Ok(password) => password,
-        Err(e) => {
-            FlashMessage::error(&e).send();
-            return Ok(see_other("/admin/password"));
-        }
-    };
+    let mut errors = Vec::new();
 
-    if new_password.0 != new_password_check.0 {
-        FlashMessage::error(
-            "You entered two different new passwords - the field values must match.",
-        )
-        .send();
-        return Ok(see_other("/admin/password"));
+    let new_password = ValidNewPassword::parse(form.new_password.expose_secret())
+        .map_err(|e| errors.push(e))
+        .ok();
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        errors
+            .push("You entered two different new passwords - the field values must match.".into());
     }
 
-    // Get the username from the session
-    let username = get_username(*user_id, &pool).await.map_err(e500)?;
-
-    // User must prove they know the current password/reauthenticate
-    let credentials = Credentials {
-        username,
-        password: form.0.current_password,
-    };
-    if let Err(e) = validate_credentials(credentials, &pool).await {
-        return match e {
-            AuthError::InvalidCredentials(_) => {
-                FlashMessage::error("The current password is incorrect.").send();
-                Ok(see_other("/admin/password"))
+    // User must prove they know the current password/reauthenticate, unless they signed in
+    // recently enough that the session itself is proof. A password that is given is always checked.
+    let current_password = form
+        .0
+        .current_password
+        .filter(|password| !password.expose_secret().is_empty());
+    let now = clock.now();
+    match current_password {
+        Some(password) => {
+            let username = get_username(*user_id, &pool).await.map_err(e500)?;
+            let credentials = Credentials { username, password };
+            match validate_credentials(credentials, &pool).await {
+                Ok(_) => session.set_authenticated_at(now).map_err(e500)?,
+                Err(AuthError::InvalidCredentials(_)) => {
+                    errors.push("The current password is incorrect.".into());
+                }
+                Err(e @ AuthError::UnexpectedError(_)) => return Err(e500(e)),
             }
-            AuthError::UnexpectedError(_) => Err(e500(e)),
-        };
+        }
+        None if session
+            .authenticated_within(session_settings.reauthentication_window(), now)
+            .map_err(e500)? => {}
+        None => errors.push("Enter your current password to change it.".into()),
     }
 
-    crate::authentication::change_password(*user_id, new_password, &pool)
+    let new_password = match new_password {
+        Some(new_password) if errors.is_empty() => new_password,
+        _ => {
+            for error in errors {
+                FlashMessage::error(error).send();
+            }
+            return Ok(see_other("/admin/password"));
+        }
+    };
+    match crate::authentication::change_password(*user_id, new_password, **history_depth, &pool)
         .await
-        .map_err(e500)?;
+    {
+        Ok(()) => {}
+        Err(AuthError::InvalidCredentials(_)) => {
+            FlashMessage::error(format!(
+                "The new password must differ from your last {} passwords.",
+                history_depth.0
+            ))
+            .send();
+            return Ok(see_other("/admin/password"));
+        }
+        Err(e @ AuthError::UnexpectedError(_)) => return Err(e500(e)),
+    }
     FlashMessage::info("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
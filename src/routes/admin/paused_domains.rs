@@ -0,0 +1,127 @@
+use std::fmt::Write;
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::UserId,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct PausedDomainFormData {
+    domain: String,
+}
+
+pub async fn paused_domains_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let mut domains_html = String::new();
+    for domain in get_paused_domains(&pool).await.map_err(e500)? {
+        let domain = htmlescape::encode_minimal(&domain);
+        writeln!(
+            domains_html,
+            r#"<li>{domain}
+                <form action="/admin/paused-domains/{domain}/resume" method="post">
+                    <button type="submit">Resume</button>
+                </form>
+            </li>"#,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Paused domains</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>Deliveries to these domains are deferred:</p>
+                <ul>
+                    {domains_html}
+                </ul>
+                <form action="/admin/paused-domains" method="post">
+                    <label>Domain
+                        <input type="text" name="domain" placeholder="example.com">
+                    </label>
+                    <button type="submit">Pause</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        )))
+}
+
+pub async fn pause_domain(
+    form: web::Form<PausedDomainFormData>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let domain = form.0.domain.trim().to_lowercase();
+    let is_valid = domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    if !is_valid {
+        FlashMessage::error(format!("{domain:?} is not a valid domain.")).send();
+        return Ok(see_other("/admin/paused-domains"));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO paused_domains (domain, paused_at)
+        VALUES ($1, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        domain
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to pause the domain.")
+    .map_err(e500)?;
+    FlashMessage::info(format!("Deliveries to {domain} have been paused.")).send();
+    Ok(see_other("/admin/paused-domains"))
+}
+
+// Deferred deliveries are picked up again once their retry delay has passed
+pub async fn resume_domain(
+    domain: web::Path<String>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let domain = domain.into_inner();
+    sqlx::query!(r#"DELETE FROM paused_domains WHERE domain = $1"#, domain)
+        .execute(pool.get_ref())
+        .await
+        .context("Failed to resume the domain.")
+        .map_err(e500)?;
+    FlashMessage::info(format!("Deliveries to {domain} have been resumed.")).send();
+    Ok(see_other("/admin/paused-domains"))
+}
+
+#[tracing::instrument(name = "Get paused domains", skip(pool))]
+async fn get_paused_domains(pool: &PgPool) -> Result<Vec<String>, anyhow::Error> {
+    let rows = sqlx::query!(r#"SELECT domain FROM paused_domains ORDER BY domain"#)
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve paused domains.")?;
+    Ok(rows.into_iter().map(|r| r.domain).collect())
+}
@@ -0,0 +1,231 @@
+use std::fmt::Write;
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    domain::SubscriberEmail,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct SenderFormData {
+    email: String,
+    display_name: String,
+}
+
+pub struct Sender {
+    pub sender_id: Uuid,
+    pub email: String,
+    pub display_name: String,
+    pub verified: bool,
+}
+
+pub async fn senders_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    let mut senders_html = String::new();
+    for sender in get_senders(&pool).await.map_err(e500)? {
+        let (status, action, label) = if sender.verified {
+            ("Verified", "unverify", "Unverify")
+        } else {
+            ("Unverified", "verify", "Verify")
+        };
+        writeln!(
+            senders_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{status}</td>
+                <td>
+                    <form action="/admin/senders/{}/{action}" method="post">
+                        <button type="submit">{label}</button>
+                    </form>
+                </td>
+            </tr>"#,
+            encode_minimal(&sender.display_name),
+            encode_minimal(&sender.email),
+            sender.sender_id,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Senders</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>Verified senders can be picked when publishing an issue:</p>
+                <table>
+                    <tr><th>Name</th><th>Email</th><th>Status</th><th></th></tr>
+                    {senders_html}
+                </table>
+                <form action="/admin/senders" method="post">
+                    <label>Email
+                        <input type="email" name="email" placeholder="product@example.com">
+                    </label>
+                    <label>Display name
+                        <input type="text" name="display_name" placeholder="Product team">
+                    </label>
+                    <button type="submit">Add sender</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        )))
+}
+
+// New senders start out unverified
+#[tracing::instrument(name = "Add a sender", skip_all, fields(user_id=%&*user_id))]
+pub async fn add_sender(
+    form: web::Form<SenderFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let SenderFormData {
+        email,
+        display_name,
+    } = form.0;
+    let email = match SubscriberEmail::parse(email.trim().to_lowercase()) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/senders"));
+        }
+    };
+    let display_name = display_name.trim();
+    if display_name.is_empty() {
+        FlashMessage::error("A sender needs a display name.").send();
+        return Ok(see_other("/admin/senders"));
+    }
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO senders (sender_id, email, display_name, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        email.as_ref(),
+        display_name
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to add the sender.")
+    .map_err(e500)?
+    .rows_affected();
+    if inserted == 0 {
+        FlashMessage::error(format!("{email} is already a sender.")).send();
+    } else {
+        FlashMessage::info(format!(
+            "{email} has been added, verify it before sending from it."
+        ))
+        .send();
+    }
+    Ok(see_other("/admin/senders"))
+}
+
+#[tracing::instrument(name = "Verify a sender", skip(pool, user_id), fields(user_id=%&*user_id))]
+pub async fn verify_sender(
+    sender_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_sender_verified(&pool, *sender_id, **user_id, true).await
+}
+
+// Issues already queued from this sender fall back to the configured sender
+#[tracing::instrument(name = "Unverify a sender", skip(pool, user_id), fields(user_id=%&*user_id))]
+pub async fn unverify_sender(
+    sender_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_sender_verified(&pool, *sender_id, **user_id, false).await
+}
+
+async fn set_sender_verified(
+    pool: &PgPool,
+    sender_id: Uuid,
+    user_id: Uuid,
+    verified: bool,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE senders
+        SET verified_at = CASE WHEN $2 THEN now() ELSE NULL END
+        WHERE sender_id = $1
+        RETURNING email
+        "#,
+        sender_id,
+        verified
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to update the sender's verification.")
+    .map_err(e500)?;
+    let Some(updated) = updated else {
+        FlashMessage::error("No such sender.").send();
+        return Ok(see_other("/admin/senders"));
+    };
+    let action = if verified {
+        "sender.verified"
+    } else {
+        "sender.unverified"
+    };
+    record_audit_event(&mut transaction, user_id, action, &updated.email, None)
+        .await
+        .context("Failed to audit the sender's verification.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the sender's verification.")
+        .map_err(e500)?;
+    let message = if verified {
+        format!("{} has been verified.", updated.email)
+    } else {
+        format!("{} is no longer verified.", updated.email)
+    };
+    FlashMessage::info(message).send();
+    Ok(see_other("/admin/senders"))
+}
+
+#[tracing::instrument(name = "Get senders", skip(pool))]
+pub async fn get_senders(pool: &PgPool) -> Result<Vec<Sender>, anyhow::Error> {
+    let senders = sqlx::query_as!(
+        Sender,
+        r#"
+        SELECT sender_id, email, display_name, verified_at IS NOT NULL AS "verified!"
+        FROM senders
+        ORDER BY email
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve senders.")?;
+    Ok(senders)
+}
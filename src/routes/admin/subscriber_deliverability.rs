@@ -0,0 +1,76 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    clock::Clock,
+    domain::{PiiCipher, SubscriberEmail},
+    mail_domain_checker::MailDomainChecker,
+    suppressions::is_suppressed,
+    utils::{e404, e500},
+};
+
+#[derive(serde::Serialize)]
+struct Deliverability {
+    can_receive: bool,
+    // Everything standing in the way, empty when `can_receive` is true
+    reasons: Vec<String>,
+}
+
+// Checked before reactivating or emailing a subscriber by hand, so every problem is reported rather
+// than just the first
+#[tracing::instrument(name = "Check whether a subscriber can receive email", skip_all, fields(%subscriber_id))]
+pub async fn can_receive_email(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    mail_domains: web::Data<MailDomainChecker>,
+    clock: web::Data<dyn Clock>,
+    pii_cipher: web::Data<PiiCipher>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber = sqlx::query!(
+        "SELECT email, email_encrypted, status FROM subscriptions WHERE id = $1",
+        *subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber.")
+    .map_err(e500)?
+    .ok_or_else(|| e404("Subscriber not found"))?;
+    let email = pii_cipher
+        .reveal(subscriber.email, subscriber.email_encrypted)
+        .context("Failed to read the subscriber's address.")
+        .map_err(e500)?;
+
+    let mut reasons = Vec::new();
+    if is_suppressed(pool.get_ref(), &email)
+        .await
+        .context("Failed to check the suppression list.")
+        .map_err(e500)?
+    {
+        reasons.push("The address is on the suppression list.".to_string());
+    }
+    match SubscriberEmail::parse(email) {
+        Ok(email) => {
+            if !mail_domains.resolves(email.domain(), clock.now()).await {
+                reasons.push(format!(
+                    "The domain {} doesn't resolve, so mail to it can't be delivered.",
+                    email.domain()
+                ));
+            }
+        }
+        Err(e) => reasons.push(e),
+    }
+    if subscriber.status != "confirmed" {
+        reasons.push(format!(
+            "The subscriber is {}, only confirmed subscribers are emailed.",
+            subscriber.status
+        ));
+    }
+    Ok(HttpResponse::Ok().json(Deliverability {
+        can_receive: reasons.is_empty(),
+        reasons,
+    }))
+}
@@ -0,0 +1,225 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    clock::Clock,
+    configuration::SubscriptionSettings,
+    domain::{PiiCipher, SubscriberEmail},
+    email_client::{EmailClientError, EmailClientPool},
+    routes::{generate_subscription_token, greeting_name},
+    startup::ApplicationBaseUrl,
+};
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeData {
+    email: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmailChangeRequestError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("Subscriber not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for EmailChangeRequestError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            EmailChangeRequestError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            EmailChangeRequestError::NotFound => StatusCode::NOT_FOUND,
+            EmailChangeRequestError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// The subscriber's address stays as it is until the new one is confirmed from its own inbox, so
+// nobody is moved to an address they haven't agreed to receive the newsletter at. Asking again
+// replaces the pending address and voids the earlier link.
+#[tracing::instrument(
+    name = "Request a subscriber's email change",
+    skip(
+        body,
+        pool,
+        email_clients,
+        base_url,
+        settings,
+        clock,
+        pii_cipher,
+        user_id
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn request_email_change(
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<EmailChangeData>,
+    pool: web::Data<PgPool>,
+    email_clients: web::Data<EmailClientPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    clock: web::Data<dyn Clock>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, EmailChangeRequestError> {
+    let subscriber_id = subscriber_id.into_inner();
+    let new_email = SubscriberEmail::parse_with(body.into_inner().email, settings.email_strictness)
+        .map_err(EmailChangeRequestError::ValidationError)?;
+    let domain = new_email.domain();
+    if !settings.accepts_domain(domain) {
+        return Err(EmailChangeRequestError::ValidationError(format!(
+            "Email addresses at '{domain}' can't subscribe to this newsletter."
+        )));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let subscriber = sqlx::query!(
+        r#"SELECT name, name_encrypted FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to look up the subscriber.")?
+    .ok_or(EmailChangeRequestError::NotFound)?;
+    let name = pii_cipher
+        .reveal(subscriber.name, subscriber.name_encrypted)
+        .context("Failed to read the subscriber's name.")?;
+    if is_address_taken(&mut transaction, new_email.as_ref(), &pii_cipher)
+        .await
+        .context("Failed to check whether the address is in use.")?
+    {
+        return Err(EmailChangeRequestError::ValidationError(format!(
+            "'{}' is already subscribed.",
+            new_email.as_ref()
+        )));
+    }
+    let pending_email = pii_cipher.store(new_email.as_ref());
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET pending_email = $2, pending_email_encrypted = $3
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        pending_email.plain,
+        pending_email.encrypted
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the pending address.")?;
+    let token = generate_subscription_token();
+    store_email_change_token(
+        &mut transaction,
+        subscriber_id,
+        **user_id,
+        &token,
+        clock.now(),
+    )
+    .await
+    .context("Failed to store the email change token.")?;
+    record_audit_event(
+        &mut transaction,
+        **user_id,
+        "subscriber.email_change_requested",
+        &subscriber_id.to_string(),
+        None,
+    )
+    .await
+    .context("Failed to audit the email change request.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the email change request.")?;
+    send_email_change_confirmation(&email_clients, &new_email, &name, &base_url, &token)
+        .await
+        .context("Failed to send the email change confirmation.")?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[tracing::instrument(skip(transaction, pii_cipher))]
+async fn is_address_taken(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    pii_cipher: &PiiCipher,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM subscriptions WHERE email = $1 OR email_hash = $2) AS "taken!"
+        "#,
+        email,
+        pii_cipher.email_hash(email)
+    )
+    .fetch_one(transaction)
+    .await?;
+    Ok(row.taken)
+}
+
+#[tracing::instrument(skip(transaction, token))]
+async fn store_email_change_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    requested_by: Uuid,
+    token: &str,
+    now: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM email_change_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO email_change_tokens (email_change_token, subscriber_id, requested_by, created_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        token,
+        subscriber_id,
+        requested_by,
+        now
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_email_change_confirmation(
+    email_clients: &EmailClientPool,
+    email: &SubscriberEmail,
+    name: &str,
+    base_url: &ApplicationBaseUrl,
+    token: &str,
+) -> Result<(), EmailClientError> {
+    let mut confirmation_link = base_url.join_path("subscriptions/confirm-email");
+    confirmation_link
+        .query_pairs_mut()
+        .append_pair("email_change_token", token);
+    let name = greeting_name(name);
+    let html_body = &format!(
+        "Hello {}, please confirm your new address.<br />\
+            Click <a href=\"{confirmation_link}\">here</a> to receive the newsletter here from now on.",
+        htmlescape::encode_minimal(name)
+    );
+    let plain_body = &format!(
+        "Hello {name}, please confirm your new address.\n\
+            Visit {confirmation_link} to receive the newsletter here from now on."
+    );
+    email_clients
+        .send(
+            None,
+            email,
+            "Confirm your new address",
+            html_body,
+            plain_body,
+        )
+        .await
+}
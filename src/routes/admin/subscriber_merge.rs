@@ -0,0 +1,282 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    delivery_throttle::recipient_domain,
+    domain::PiiCipher,
+    webhooks::{WebhookEvent, enqueue_webhook_event},
+};
+
+#[derive(serde::Deserialize)]
+pub struct MergeData {
+    subscriber_ids: [Uuid; 2],
+}
+
+#[derive(serde::Serialize)]
+struct MergeResult {
+    // The record that was kept
+    subscriber_id: Uuid,
+    // The record that was folded into it and removed
+    merged_id: Uuid,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SubscriberMergeError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("Subscriber not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for SubscriberMergeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscriberMergeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscriberMergeError::NotFound => StatusCode::NOT_FOUND,
+            SubscriberMergeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+struct MergeCandidate {
+    id: Uuid,
+    email: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+// Folds one of two duplicate subscribers into the other. A confirmed record is kept over one that
+// isn't, otherwise the older of the two, so the surviving address is one the subscriber has
+// actually agreed to. An opt-out on either record carries over, the merged subscriber stays
+// unsubscribed. Everything happens in one transaction, a failure leaves both untouched.
+#[tracing::instrument(
+    name = "Merge duplicate subscribers",
+    skip(body, pool, pii_cipher, user_id)
+)]
+pub async fn merge_subscribers(
+    body: web::Json<MergeData>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, SubscriberMergeError> {
+    let [first_id, second_id] = body.into_inner().subscriber_ids;
+    if first_id == second_id {
+        return Err(SubscriberMergeError::ValidationError(
+            "A subscriber can't be merged with itself.".into(),
+        ));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let mut candidates = sqlx::query!(
+        r#"
+        SELECT id, email, email_encrypted, status, subscribed_at
+        FROM subscriptions
+        WHERE id = $1 OR id = $2
+        FOR UPDATE
+        "#,
+        first_id,
+        second_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to look up the subscribers.")?
+    .into_iter()
+    .map(|row| {
+        Ok(MergeCandidate {
+            id: row.id,
+            email: pii_cipher.reveal(row.email, row.email_encrypted)?,
+            status: row.status,
+            subscribed_at: row.subscribed_at,
+        })
+    })
+    .collect::<Result<Vec<_>, anyhow::Error>>()
+    .context("Failed to read the subscribers' addresses.")?;
+    if candidates.len() != 2 {
+        return Err(SubscriberMergeError::NotFound);
+    }
+    candidates.sort_by_key(|c| (c.status != "confirmed", c.subscribed_at));
+    let duplicate = candidates.pop().unwrap();
+    let survivor = candidates.pop().unwrap();
+
+    merge_into(&mut transaction, &survivor, &duplicate).await?;
+    // The surviving record only now picks up the duplicate's opt-out
+    if survivor.status != "unsubscribed" && duplicate.status == "unsubscribed" {
+        let event = WebhookEvent::SubscriberUnsubscribed {
+            subscriber_id: survivor.id,
+            email: survivor.email.clone(),
+        };
+        enqueue_webhook_event(&mut transaction, &event)
+            .await
+            .context("Failed to enqueue the subscriber.unsubscribed webhook event.")?;
+    }
+    record_audit_event(
+        &mut transaction,
+        **user_id,
+        "subscriber.merged",
+        &survivor.id.to_string(),
+        Some(&format!("Merged {}", duplicate.id)),
+    )
+    .await
+    .context("Failed to audit the merge.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the merge.")?;
+    Ok(HttpResponse::Ok().json(MergeResult {
+        subscriber_id: survivor.id,
+        merged_id: duplicate.id,
+    }))
+}
+
+async fn merge_into(
+    transaction: &mut Transaction<'_, Postgres>,
+    survivor: &MergeCandidate,
+    duplicate: &MergeCandidate,
+) -> Result<(), anyhow::Error> {
+    let unsubscribed = survivor.status == "unsubscribed" || duplicate.status == "unsubscribed";
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET status = CASE WHEN $3 THEN 'unsubscribed' ELSE s.status END,
+            subscribed_at = LEAST(s.subscribed_at, d.subscribed_at),
+            last_emailed_at = GREATEST(s.last_emailed_at, d.last_emailed_at),
+            last_opened_at = GREATEST(s.last_opened_at, d.last_opened_at),
+            admin_note = NULLIF(CONCAT_WS(E'\n', s.admin_note, d.admin_note), '')
+        FROM subscriptions d
+        WHERE s.id = $1 AND d.id = $2
+        "#,
+        survivor.id,
+        duplicate.id,
+        unsubscribed
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to merge the subscriber records.")?;
+
+    sqlx::query!(
+        "UPDATE newsletter_opens SET subscriber_id = $1 WHERE subscriber_id = $2",
+        survivor.id,
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the duplicate's opens.")?;
+    sqlx::query!(
+        "UPDATE newsletter_clicks SET subscriber_id = $1 WHERE subscriber_id = $2",
+        survivor.id,
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the duplicate's clicks.")?;
+    // One rating per issue, the survivor's own wins
+    sqlx::query!(
+        r#"
+        UPDATE issue_feedback f
+        SET subscriber_id = $1
+        WHERE f.subscriber_id = $2
+          AND NOT EXISTS (
+              SELECT 1 FROM issue_feedback s
+              WHERE s.newsletter_issue_id = f.newsletter_issue_id AND s.subscriber_id = $1
+          )
+        "#,
+        survivor.id,
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the duplicate's feedback.")?;
+    // Deliveries still waiting go to the surviving address, unless it already has the issue
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue q
+        SET subscriber_id = $1, recipient_domain = $3
+        WHERE q.subscriber_id = $2
+          AND NOT EXISTS (
+              SELECT 1 FROM issue_delivery_queue s
+              WHERE s.newsletter_issue_id = q.newsletter_issue_id AND s.subscriber_id = $1
+          )
+          AND NOT EXISTS (
+              SELECT 1 FROM newsletter_deliveries d
+              WHERE d.newsletter_issue_id = q.newsletter_issue_id AND d.subscriber_id = $1
+          )
+        "#,
+        survivor.id,
+        duplicate.id,
+        recipient_domain(&survivor.email).unwrap_or_default()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the duplicate's queued deliveries.")?;
+    // Past deliveries count towards the survivor's frequency cap, one per issue
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_deliveries d
+        SET subscriber_id = $1
+        WHERE d.subscriber_id = $2
+          AND NOT EXISTS (
+              SELECT 1 FROM newsletter_deliveries s
+              WHERE s.newsletter_issue_id = d.newsletter_issue_id AND s.subscriber_id = $1
+          )
+        "#,
+        survivor.id,
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the duplicate's deliveries.")?;
+
+    // Whatever couldn't be moved goes with the duplicate, its links stop working too. Deliveries
+    // left behind stay counted but no longer name a subscriber.
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_id = $1",
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to drop the duplicate's queued deliveries.")?;
+    if unsubscribed {
+        sqlx::query!(
+            "DELETE FROM issue_delivery_queue WHERE subscriber_id = $1",
+            survivor.id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to drop the unsubscribed survivor's queued deliveries.")?;
+    }
+    sqlx::query!(
+        "DELETE FROM issue_feedback WHERE subscriber_id = $1",
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to drop the duplicate's feedback.")?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to drop the duplicate's subscription tokens.")?;
+    sqlx::query!(
+        "DELETE FROM email_change_tokens WHERE subscriber_id = $1",
+        duplicate.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to drop the duplicate's email change tokens.")?;
+    sqlx::query!("DELETE FROM subscriptions WHERE id = $1", duplicate.id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to remove the duplicate subscriber.")?;
+    Ok(())
+}
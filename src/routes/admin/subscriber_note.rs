@@ -0,0 +1,162 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{audit::record_audit_event, authentication::UserId, domain::PiiCipher};
+
+const MAX_NOTE_LENGTH: usize = 500;
+
+#[derive(serde::Deserialize)]
+pub struct NoteData {
+    note: String,
+}
+
+// The admin's view of a subscriber. `admin_note` must stay out of anything the subscriber sees,
+// including their data export.
+#[derive(serde::Serialize)]
+pub struct SubscriberDetail {
+    id: Uuid,
+    email: String,
+    // Waiting for the subscriber to confirm it
+    pending_email: Option<String>,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    last_emailed_at: Option<DateTime<Utc>>,
+    last_opened_at: Option<DateTime<Utc>>,
+    admin_note: Option<String>,
+    signup_referer: Option<String>,
+    signup_utm_source: Option<String>,
+    signup_country: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SubscriberNoteError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("Subscriber not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for SubscriberNoteError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscriberNoteError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscriberNoteError::NotFound => StatusCode::NOT_FOUND,
+            SubscriberNoteError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get a subscriber", skip(pool, pii_cipher, _user_id))]
+pub async fn get_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, SubscriberNoteError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            email,
+            email_encrypted,
+            pending_email,
+            pending_email_encrypted,
+            name,
+            name_encrypted,
+            status,
+            subscribed_at,
+            last_emailed_at,
+            last_opened_at,
+            admin_note,
+            signup_referer,
+            signup_utm_source,
+            signup_country
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        *subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber.")?
+    .ok_or(SubscriberNoteError::NotFound)?;
+    let subscriber = SubscriberDetail {
+        id: row.id,
+        email: pii_cipher
+            .reveal(row.email, row.email_encrypted)
+            .context("Failed to read the subscriber's address.")?,
+        pending_email: match (row.pending_email, row.pending_email_encrypted) {
+            (None, None) => None,
+            (plain, encrypted) => Some(
+                pii_cipher
+                    .reveal(plain, encrypted)
+                    .context("Failed to read the subscriber's pending address.")?,
+            ),
+        },
+        name: pii_cipher
+            .reveal(row.name, row.name_encrypted)
+            .context("Failed to read the subscriber's name.")?,
+        status: row.status,
+        subscribed_at: row.subscribed_at,
+        last_emailed_at: row.last_emailed_at,
+        last_opened_at: row.last_opened_at,
+        admin_note: row.admin_note,
+        signup_referer: row.signup_referer,
+        signup_utm_source: row.signup_utm_source,
+        signup_country: row.signup_country,
+    };
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+// An empty note clears it
+#[tracing::instrument(name = "Update a subscriber's note", skip(note, pool, user_id))]
+pub async fn update_subscriber_note(
+    subscriber_id: web::Path<Uuid>,
+    note: web::Json<NoteData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, SubscriberNoteError> {
+    let note = note.note.trim();
+    if note.chars().count() > MAX_NOTE_LENGTH {
+        return Err(SubscriberNoteError::ValidationError(format!(
+            "A note can be at most {MAX_NOTE_LENGTH} characters long."
+        )));
+    }
+    let note = (!note.is_empty()).then_some(note);
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let updated = sqlx::query!(
+        r#"UPDATE subscriptions SET admin_note = $2 WHERE id = $1"#,
+        *subscriber_id,
+        note
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update the subscriber's note.")?
+    .rows_affected();
+    if updated == 0 {
+        return Err(SubscriberNoteError::NotFound);
+    }
+    record_audit_event(
+        &mut transaction,
+        **user_id,
+        "subscriber.note_changed",
+        &subscriber_id.to_string(),
+        note,
+    )
+    .await
+    .context("Failed to audit the note change.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the note change.")?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,540 @@
+use std::fmt::Write;
+
+use actix_web::{
+    HttpResponse,
+    http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType},
+    web::{self, Bytes},
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt, stream};
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    clock::Clock,
+    configuration::DisplaySettings,
+    domain::{PiiCipher, PiiError},
+    utils::{e404, e500},
+};
+
+// Everything we hold about one subscriber. The field names are handed to the subscriber as they
+// are, so treat them as a public format and only ever add to it.
+#[derive(serde::Serialize)]
+struct SubscriberExport {
+    exported_at: DateTime<Utc>,
+    subscription: ExportedSubscription,
+    suppression: Option<ExportedSuppression>,
+    deliveries: Vec<ExportedDelivery>,
+    opens: Vec<ExportedOpen>,
+    clicks: Vec<ExportedClick>,
+    feedback: Vec<ExportedFeedback>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscription {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+// A subscription as it's stored, either column of a pair may hold the value
+struct SubscriptionRow {
+    id: Uuid,
+    email: Option<String>,
+    email_encrypted: Option<String>,
+    name: Option<String>,
+    name_encrypted: Option<String>,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl SubscriptionRow {
+    fn reveal(self, pii_cipher: &PiiCipher) -> Result<ExportedSubscription, PiiError> {
+        Ok(ExportedSubscription {
+            id: self.id,
+            email: pii_cipher.reveal(self.email, self.email_encrypted)?,
+            name: pii_cipher.reveal(self.name, self.name_encrypted)?,
+            status: self.status,
+            subscribed_at: self.subscribed_at,
+        })
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExportQuery {
+    // e.g. `confirmed`, left out to export every subscriber
+    status: Option<String>,
+}
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+// The most common signup sources listed under the subscribers
+const TOP_SIGNUP_SOURCES: i64 = 20;
+// Searches stop here however large a page was asked for
+const MAX_SEARCH_RESULTS: i64 = 100;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SubscribersQuery {
+    // Matched against any part of the email or name, an empty search lists everyone. Encrypted
+    // subscribers only match their full email address.
+    search: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+// Confirmed subscribers by one kind of signup source: `utm_source`, referring site or country
+struct SignupSourceCount {
+    kind: String,
+    source: String,
+    subscribers: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SearchQuery {
+    q: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSuppression {
+    reason: String,
+    suppressed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedDelivery {
+    newsletter_issue_id: Uuid,
+    issue_title: String,
+    status: String,
+    attempted_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedOpen {
+    newsletter_issue_id: Uuid,
+    opened_at: DateTime<Utc>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedClick {
+    newsletter_issue_id: Uuid,
+    url: String,
+    clicked_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedFeedback {
+    newsletter_issue_id: Uuid,
+    rating: String,
+    submitted_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(
+    name = "Export a subscriber's data",
+    skip(pool, pii_cipher, clock, user_id)
+)]
+pub async fn export_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    clock: web::Data<dyn Clock>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let export = get_subscriber_export(&pool, &pii_cipher, subscriber_id, clock.now())
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("No such subscriber"))?;
+    let body = serde_json::to_vec_pretty(&export)
+        .context("Failed to serialise the subscriber export.")
+        .map_err(e500)?;
+    record_audit_event(
+        pool.get_ref(),
+        **user_id,
+        "subscriber.exported",
+        &subscriber_id.to_string(),
+        None,
+    )
+    .await
+    .context("Failed to audit the subscriber export.")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "subscriber-{subscriber_id}.json"
+            ))],
+        })
+        .body(body))
+}
+
+// One JSON object per line. Rows are written out as they come off the cursor and the channel only
+// holds a few lines at a time, so memory stays flat however many subscribers there are.
+#[tracing::instrument(name = "Export subscribers", skip(pool, pii_cipher, user_id))]
+pub async fn export_subscribers(
+    query: web::Query<ExportQuery>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ExportQuery { status } = query.into_inner();
+    record_audit_event(
+        pool.get_ref(),
+        **user_id,
+        "subscribers.exported",
+        status.as_deref().unwrap_or("all"),
+        None,
+    )
+    .await
+    .context("Failed to audit the subscribers export.")
+    .map_err(e500)?;
+
+    let (sender, receiver) = mpsc::channel::<Result<Bytes, anyhow::Error>>(64);
+    let pool = pool.into_inner();
+    let pii_cipher = pii_cipher.into_inner();
+    tokio::spawn(async move {
+        let mut rows = sqlx::query_as!(
+            SubscriptionRow,
+            r#"
+            SELECT id, email, email_encrypted, name, name_encrypted, status, subscribed_at
+            FROM subscriptions
+            WHERE $1::TEXT IS NULL OR status = $1
+            ORDER BY subscribed_at
+            "#,
+            status
+        )
+        .fetch(pool.as_ref())
+        .map_err(|e| anyhow::Error::new(e).context("Failed to read a subscriber."));
+        while let Some(row) = rows.next().await {
+            let line = row.and_then(|row| {
+                let subscription = row
+                    .reveal(&pii_cipher)
+                    .context("Failed to read a subscriber's details.")?;
+                let mut line = serde_json::to_vec(&subscription)
+                    .context("Failed to serialise a subscriber.")?;
+                line.push(b'\n');
+                Ok(Bytes::from(line))
+            });
+            let failed = line.is_err();
+            if let Err(e) = &line {
+                tracing::error!(error.message = %e, "The subscribers export was cut short.");
+            }
+            // A failed send means the client has gone away
+            if sender.send(line).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|line| (line, receiver))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscribers.jsonl".into())],
+        })
+        .streaming(body))
+}
+
+#[tracing::instrument(name = "List subscribers", skip(pool, display, pii_cipher, _user_id))]
+pub async fn subscribers_page(
+    query: web::Query<SubscribersQuery>,
+    pool: web::Data<PgPool>,
+    display: web::Data<DisplaySettings>,
+    pii_cipher: web::Data<PiiCipher>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let SubscribersQuery {
+        search,
+        page,
+        per_page,
+    } = query.into_inner();
+    let search = search
+        .map(|search| search.trim().to_owned())
+        .filter(|search| !search.is_empty());
+    let page = page.unwrap_or(1).max(1);
+    let mut per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    if search.is_some() {
+        per_page = per_page.min(MAX_SEARCH_RESULTS);
+    }
+    let subscribers = get_subscribers(&pool, &pii_cipher, search.as_deref(), page, per_page)
+        .await
+        .map_err(e500)?;
+
+    let mut subscribers_html = String::new();
+    for subscriber in &subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td><a href="/admin/subscribers/{}/export">Export</a></td>
+            </tr>"#,
+            encode_minimal(&subscriber.email),
+            encode_minimal(&subscriber.name),
+            subscriber.status,
+            display.format_timestamp(subscriber.subscribed_at),
+            subscriber.id,
+        )
+        .unwrap();
+    }
+    let mut sources_html = String::new();
+    for count in signup_source_counts(&pool).await.map_err(e500)? {
+        writeln!(
+            sources_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            count.kind,
+            encode_minimal(&count.source),
+            count.subscribers
+        )
+        .unwrap();
+    }
+    if sources_html.is_empty() {
+        sources_html = r#"<tr><td colspan="3">No signup sources recorded yet.</td></tr>"#.into();
+    }
+    let search_value = encode_attribute(search.as_deref().unwrap_or_default());
+    // A full page means there may be more after it
+    let next_page_html = if subscribers.len() as i64 == per_page {
+        format!(
+            r#"<p><a href="/admin/subscribers?search={}&page={}&per_page={per_page}">Next page -&gt;</a></p>"#,
+            urlencoding::encode(search.as_deref().unwrap_or_default()),
+            page.saturating_add(1),
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Subscribers</title>
+            </head>
+            <body>
+                <form action="/admin/subscribers" method="get">
+                    <input type="search" name="search" value="{search_value}" placeholder="Email or name">
+                    <button type="submit">Search</button>
+                </form>
+                <table>
+                    <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed</th><th></th></tr>
+                    {subscribers_html}
+                </table>
+                {next_page_html}
+                <h2>Signup sources</h2>
+                <table>
+                    <tr><th>By</th><th>Source</th><th>Confirmed subscribers</th></tr>
+                    {sources_html}
+                </table>
+                <p><a href="/admin/subscribers/export.jsonl">Export every subscriber</a></p>
+                <p><a href="/admin/subscribers/inactive">Subscribers who have stopped opening issues</a></p>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        )))
+}
+
+// `/admin/subscribers/search?q=example.com` is the same as `/admin/subscribers?search=example.com`
+pub async fn search_subscribers(
+    query: web::Query<SearchQuery>,
+    pool: web::Data<PgPool>,
+    display: web::Data<DisplaySettings>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let query = SubscribersQuery {
+        search: query.into_inner().q,
+        page: None,
+        per_page: None,
+    };
+    subscribers_page(web::Query(query), pool, display, pii_cipher, user_id).await
+}
+
+#[tracing::instrument(skip(pool, pii_cipher))]
+async fn get_subscribers(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    search: Option<&str>,
+    page: i64,
+    per_page: i64,
+) -> Result<Vec<ExportedSubscription>, anyhow::Error> {
+    // `%` and `_` in the search are meant literally
+    let pattern = search.map(|search| {
+        search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    });
+    let subscribers = sqlx::query_as!(
+        SubscriptionRow,
+        r#"
+        SELECT id, email, email_encrypted, name, name_encrypted, status, subscribed_at
+        FROM subscriptions
+        WHERE
+            $1::TEXT IS NULL OR
+            email ILIKE '%' || $1 || '%' OR
+            name ILIKE '%' || $1 || '%' OR
+            email_hash = $4
+        ORDER BY subscribed_at DESC, email
+        LIMIT $2
+        OFFSET $3
+        "#,
+        pattern,
+        per_page,
+        (page - 1).saturating_mul(per_page),
+        search.and_then(|search| pii_cipher.email_hash(search))
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscribers.")?
+    .into_iter()
+    .map(|row| row.reveal(pii_cipher))
+    .collect::<Result<Vec<_>, _>>()
+    .context("Failed to read a subscriber's details.")?;
+    Ok(subscribers)
+}
+
+// Referers are grouped by site, the full URL is kept on each subscriber
+#[tracing::instrument(skip(pool))]
+async fn signup_source_counts(pool: &PgPool) -> Result<Vec<SignupSourceCount>, anyhow::Error> {
+    sqlx::query_as!(
+        SignupSourceCount,
+        r#"
+        SELECT kind AS "kind!", source AS "source!", COUNT(*) AS "subscribers!"
+        FROM subscriptions,
+        LATERAL (VALUES
+            ('utm_source', signup_utm_source),
+            ('referer', substring(signup_referer FROM '^[A-Za-z][A-Za-z0-9+.-]*://([^/?#:]+)')),
+            ('country', signup_country)
+        ) AS sources (kind, source)
+        WHERE status = 'confirmed' AND source IS NOT NULL
+        GROUP BY kind, source
+        ORDER BY COUNT(*) DESC, kind, source
+        LIMIT $1
+        "#,
+        TOP_SIGNUP_SOURCES
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count the signup sources.")
+}
+
+// Deliveries are keyed by address rather than id, so they are looked up through the subscription
+#[tracing::instrument(skip(pool, pii_cipher))]
+async fn get_subscriber_export(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    subscriber_id: Uuid,
+    exported_at: DateTime<Utc>,
+) -> Result<Option<SubscriberExport>, anyhow::Error> {
+    let Some(row) = sqlx::query_as!(
+        SubscriptionRow,
+        r#"
+        SELECT id, email, email_encrypted, name, name_encrypted, status, subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscription.")?
+    else {
+        return Ok(None);
+    };
+    let subscription = row
+        .reveal(pii_cipher)
+        .context("Failed to read the subscriber's details.")?;
+    let suppression = sqlx::query_as!(
+        ExportedSuppression,
+        r#"
+        SELECT reason, suppressed_at
+        FROM suppressed_emails
+        WHERE email = lower($1) OR email_hash = $2
+        "#,
+        subscription.email,
+        pii_cipher.email_hash(&subscription.email)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the suppression.")?;
+    let deliveries = sqlx::query_as!(
+        ExportedDelivery,
+        r#"
+        SELECT d.newsletter_issue_id, i.title AS issue_title, d.status, d.attempted_at
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_id = $1
+        ORDER BY d.attempted_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the deliveries.")?;
+    let opens = sqlx::query_as!(
+        ExportedOpen,
+        r#"
+        SELECT newsletter_issue_id, opened_at, ip_address, user_agent
+        FROM newsletter_opens
+        WHERE subscriber_id = $1
+        ORDER BY opened_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the opens.")?;
+    let clicks = sqlx::query_as!(
+        ExportedClick,
+        r#"
+        SELECT newsletter_issue_id, original_url AS url, clicked_at
+        FROM newsletter_clicks
+        WHERE subscriber_id = $1
+        ORDER BY clicked_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the clicks.")?;
+    let feedback = sqlx::query_as!(
+        ExportedFeedback,
+        r#"
+        SELECT newsletter_issue_id, rating, submitted_at
+        FROM issue_feedback
+        WHERE subscriber_id = $1
+        ORDER BY submitted_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the feedback.")?;
+
+    Ok(Some(SubscriberExport {
+        exported_at,
+        subscription,
+        suppression,
+        deliveries,
+        opens,
+        clicks,
+        feedback,
+    }))
+}
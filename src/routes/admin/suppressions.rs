@@ -0,0 +1,221 @@
+use std::fmt::Write;
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::PgPool;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    configuration::DisplaySettings,
+    domain::{PiiCipher, SubscriberEmail},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct SuppressionFormData {
+    email: String,
+    reason: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RemoveSuppressionFormData {
+    email: String,
+}
+
+struct Suppression {
+    email: String,
+    reason: String,
+    suppressed_at: DateTime<Utc>,
+}
+
+pub async fn suppressions_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    display: web::Data<DisplaySettings>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    let mut suppressions_html = String::new();
+    for suppression in get_suppressions(&pool).await.map_err(e500)? {
+        writeln!(
+            suppressions_html,
+            r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>
+                    <form action="/admin/suppressions/remove" method="post">
+                        <input type="hidden" name="email" value="{}">
+                        <button type="submit">Remove</button>
+                    </form>
+                </td>
+            </tr>"#,
+            encode_minimal(&suppression.email),
+            encode_minimal(&suppression.reason),
+            display.format_timestamp(suppression.suppressed_at),
+            encode_attribute(&suppression.email),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Suppressed addresses</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>These addresses are never emailed:</p>
+                <table>
+                    <tr><th>Email</th><th>Reason</th><th>Suppressed</th><th></th></tr>
+                    {suppressions_html}
+                </table>
+                <form action="/admin/suppressions" method="post">
+                    <label>Email
+                        <input type="email" name="email" placeholder="someone@example.com">
+                    </label>
+                    <label>Reason
+                        <input type="text" name="reason" placeholder="Legal request">
+                    </label>
+                    <button type="submit">Suppress</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Suppress an address", skip_all, fields(user_id=%&*user_id))]
+pub async fn add_suppression(
+    form: web::Form<SuppressionFormData>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let SuppressionFormData { email, reason } = form.0;
+    let email = match SubscriberEmail::parse(email.trim().to_lowercase()) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+    let reason = reason.trim();
+    if reason.is_empty() {
+        FlashMessage::error("A reason is required to suppress an address.").send();
+        return Ok(see_other("/admin/suppressions"));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO suppressed_emails (email, reason, suppressed_at, suppressed_by, email_hash)
+        VALUES ($1, $2, now(), $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+        email.as_ref(),
+        reason,
+        **user_id,
+        pii_cipher.email_hash(email.as_ref())
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to suppress the address.")
+    .map_err(e500)?
+    .rows_affected();
+    if inserted == 0 {
+        FlashMessage::error(format!("{email} is already suppressed.")).send();
+        return Ok(see_other("/admin/suppressions"));
+    }
+    record_audit_event(
+        &mut transaction,
+        **user_id,
+        "suppression.added",
+        email.as_ref(),
+        Some(reason),
+    )
+    .await
+    .context("Failed to audit the suppression.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the suppression.")
+        .map_err(e500)?;
+    FlashMessage::info(format!("{email} will no longer be emailed.")).send();
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[tracing::instrument(name = "Remove a suppression", skip_all, fields(user_id=%&*user_id))]
+pub async fn remove_suppression(
+    form: web::Form<RemoveSuppressionFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = form.0.email;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    let removed = sqlx::query!(
+        r#"DELETE FROM suppressed_emails WHERE email = $1 RETURNING reason"#,
+        email
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to remove the suppression.")
+    .map_err(e500)?;
+    let Some(removed) = removed else {
+        FlashMessage::error(format!("{email} is not suppressed.")).send();
+        return Ok(see_other("/admin/suppressions"));
+    };
+    record_audit_event(
+        &mut transaction,
+        **user_id,
+        "suppression.removed",
+        &email,
+        Some(&removed.reason),
+    )
+    .await
+    .context("Failed to audit the removed suppression.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the removed suppression.")
+        .map_err(e500)?;
+    FlashMessage::info(format!("{email} can be emailed again.")).send();
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[tracing::instrument(name = "Get suppressed addresses", skip(pool))]
+async fn get_suppressions(pool: &PgPool) -> Result<Vec<Suppression>, anyhow::Error> {
+    let suppressions = sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT email, reason, suppressed_at
+        FROM suppressed_emails
+        ORDER BY suppressed_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve suppressed addresses.")?;
+    Ok(suppressions)
+}
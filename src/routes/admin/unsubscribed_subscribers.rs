@@ -0,0 +1,118 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{authentication::UserId, domain::PiiCipher, utils::e500};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UnsubscribedQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    // Only those who left on or after this day, from midnight UTC
+    from: Option<NaiveDate>,
+}
+
+#[derive(serde::Serialize)]
+struct UnsubscribedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    subscribed_at: DateTime<Utc>,
+    unsubscribed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct UnsubscribedPage {
+    subscribers: Vec<UnsubscribedSubscriber>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+}
+
+// Most recent first. Anyone who left before `unsubscribed_at` was recorded has no date and is
+// listed last, or not at all once `from` is given.
+#[tracing::instrument(
+    name = "List unsubscribed subscribers",
+    skip(pool, pii_cipher, _user_id)
+)]
+pub async fn unsubscribed_subscribers(
+    query: web::Query<UnsubscribedQuery>,
+    pool: web::Data<PgPool>,
+    pii_cipher: web::Data<PiiCipher>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let from = query
+        .from
+        .map(|day| day.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    let (subscribers, total) =
+        get_unsubscribed_subscribers(&pool, &pii_cipher, from, page, per_page)
+            .await
+            .map_err(e500)?;
+    Ok(HttpResponse::Ok().json(UnsubscribedPage {
+        subscribers,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[tracing::instrument(skip(pool, pii_cipher))]
+async fn get_unsubscribed_subscribers(
+    pool: &PgPool,
+    pii_cipher: &PiiCipher,
+    from: Option<DateTime<Utc>>,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<UnsubscribedSubscriber>, i64), anyhow::Error> {
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, email_encrypted, name, name_encrypted, subscribed_at, unsubscribed_at
+        FROM subscriptions
+        WHERE status = 'unsubscribed' AND ($1::timestamptz IS NULL OR unsubscribed_at >= $1)
+        ORDER BY unsubscribed_at DESC NULLS LAST, email
+        LIMIT $2
+        OFFSET $3
+        "#,
+        from,
+        per_page,
+        (page - 1).saturating_mul(per_page)
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve unsubscribed subscribers.")?
+    .into_iter()
+    .map(|row| {
+        Ok(UnsubscribedSubscriber {
+            id: row.id,
+            email: pii_cipher.reveal(row.email, row.email_encrypted)?,
+            name: pii_cipher.reveal(row.name, row.name_encrypted)?,
+            subscribed_at: row.subscribed_at,
+            unsubscribed_at: row.unsubscribed_at,
+        })
+    })
+    .collect::<Result<Vec<_>, anyhow::Error>>()
+    .context("Failed to read an unsubscribed subscriber's details.")?;
+    let total = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "total!"
+        FROM subscriptions
+        WHERE status = 'unsubscribed' AND ($1::timestamptz IS NULL OR unsubscribed_at >= $1)
+        "#,
+        from
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unsubscribed subscribers.")?
+    .total;
+    Ok((subscribers, total))
+}
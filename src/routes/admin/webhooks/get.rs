@@ -0,0 +1,103 @@
+use std::fmt::Write;
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{authentication::UserId, utils::e500, webhooks::EVENT_NAMES};
+
+struct Webhook {
+    webhook_id: Uuid,
+    url: String,
+    events: Vec<String>,
+}
+
+pub async fn webhooks_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let mut webhooks_html = String::new();
+    for webhook in get_webhooks(&pool).await.map_err(e500)? {
+        writeln!(
+            webhooks_html,
+            r#"<li>{} ({})
+                <form action="/admin/webhooks/{}/delete" method="post">
+                    <button type="submit">Delete</button>
+                </form>
+            </li>"#,
+            htmlescape::encode_minimal(&webhook.url),
+            webhook.events.join(", "),
+            webhook.webhook_id
+        )
+        .unwrap();
+    }
+    let mut events_html = String::new();
+    for event in EVENT_NAMES {
+        writeln!(
+            events_html,
+            r#"<label><input type="checkbox" name="{}"> {event}</label><br>"#,
+            event.replace('.', "_")
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Webhooks</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>Registered webhooks:</p>
+                <ul>
+                    {webhooks_html}
+                </ul>
+                <form action="/admin/webhooks" method="post">
+                    <label>URL
+                        <input type="url" name="url" placeholder="https://example.com/hook">
+                    </label>
+                    <br>
+                    <label>Signing secret
+                        <input type="password" name="secret">
+                    </label>
+                    <br>
+                    {events_html}
+                    <button type="submit">Add webhook</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+        </html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get webhooks", skip(pool))]
+async fn get_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, anyhow::Error> {
+    let webhooks = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT webhook_id, url, events
+        FROM webhooks
+        ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve webhooks.")?;
+    Ok(webhooks)
+}
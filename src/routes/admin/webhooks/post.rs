@@ -0,0 +1,106 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct WebhookFormData {
+    url: String,
+    secret: String,
+    // One checkbox per event, unchecked ones are left out of the form entirely
+    issue_published: Option<String>,
+    issue_completed: Option<String>,
+    subscriber_confirmed: Option<String>,
+    subscriber_unsubscribed: Option<String>,
+}
+
+impl WebhookFormData {
+    fn events(&self) -> Vec<&'static str> {
+        [
+            (&self.issue_published, "issue.published"),
+            (&self.issue_completed, "issue.completed"),
+            (&self.subscriber_confirmed, "subscriber.confirmed"),
+            (&self.subscriber_unsubscribed, "subscriber.unsubscribed"),
+        ]
+        .into_iter()
+        .filter(|(checked, _)| checked.is_some())
+        .map(|(_, event)| event)
+        .collect()
+    }
+}
+
+pub async fn create_webhook(
+    form: web::Form<WebhookFormData>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let url = match reqwest::Url::parse(&form.url) {
+        Ok(url) if ["http", "https"].contains(&url.scheme()) => url,
+        _ => {
+            FlashMessage::error("The webhook URL must be a valid http(s) URL.").send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+    };
+    if form.secret.is_empty() {
+        FlashMessage::error("A signing secret is required.").send();
+        return Ok(see_other("/admin/webhooks"));
+    }
+    let events = form.events();
+    if events.is_empty() {
+        FlashMessage::error("Select at least one event.").send();
+        return Ok(see_other("/admin/webhooks"));
+    }
+
+    insert_webhook(&pool, url.as_str(), &form.secret, &events)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The webhook has been added.").send();
+    Ok(see_other("/admin/webhooks"))
+}
+
+pub async fn delete_webhook(
+    webhook_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    sqlx::query!(
+        r#"DELETE FROM webhooks WHERE webhook_id = $1"#,
+        webhook_id.into_inner()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete the webhook.")
+    .map_err(e500)?;
+    FlashMessage::info("The webhook has been deleted.").send();
+    Ok(see_other("/admin/webhooks"))
+}
+
+#[tracing::instrument(name = "Store a new webhook", skip(pool, secret))]
+async fn insert_webhook(
+    pool: &PgPool,
+    url: &str,
+    secret: &str,
+    events: &[&str],
+) -> Result<(), anyhow::Error> {
+    let events: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO webhooks (webhook_id, url, secret, events, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        Uuid::new_v4(),
+        url,
+        secret,
+        &events
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the webhook.")?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    delivery_pause::DeliveryPause,
+    utils::{e500, see_other},
+};
+
+// Queued deliveries stay where they are, the worker picks them up again once resumed
+#[tracing::instrument(
+    name = "Pause the delivery worker",
+    skip(pool, delivery_pause, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn pause_worker(
+    pool: web::Data<PgPool>,
+    delivery_pause: web::Data<DeliveryPause>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    delivery_pause.pause().await.map_err(e500)?;
+    record_audit_event(
+        pool.get_ref(),
+        **user_id,
+        "worker.paused",
+        "deliveries",
+        None,
+    )
+    .await
+    .context("Failed to audit pausing deliveries.")
+    .map_err(e500)?;
+    FlashMessage::info("Newsletter deliveries have been paused.").send();
+    Ok(see_other("/admin/dashboard"))
+}
+
+#[tracing::instrument(
+    name = "Resume the delivery worker",
+    skip(pool, delivery_pause, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn resume_worker(
+    pool: web::Data<PgPool>,
+    delivery_pause: web::Data<DeliveryPause>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    delivery_pause.resume().await.map_err(e500)?;
+    record_audit_event(
+        pool.get_ref(),
+        **user_id,
+        "worker.resumed",
+        "deliveries",
+        None,
+    )
+    .await
+    .context("Failed to audit resuming deliveries.")
+    .map_err(e500)?;
+    FlashMessage::info("Newsletter deliveries have been resumed.").send();
+    Ok(see_other("/admin/dashboard"))
+}
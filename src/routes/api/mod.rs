@@ -0,0 +1,158 @@
+mod v1;
+
+use actix_web::{
+    HttpRequest, HttpResponse,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        StatusCode,
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+    },
+    middleware::ErrorHandlerResponse,
+    web,
+};
+use actix_web_lab::middleware::{Next, from_fn};
+
+use crate::{authentication::reject_requests_without_api_token, routes::ErrorBody};
+
+pub use v1::*;
+
+// Where requests without a version are pointed to
+const LATEST_VERSION: &str = "v1";
+
+// Mounts one version of the API under `/api/{version}`. Every version gets the same content
+// negotiation and authentication, so a v2 can sit next to v1 while clients move over. Only
+// `public_routes` can be reached without an API token.
+pub fn mount_api_version(
+    cfg: &mut web::ServiceConfig,
+    version: &str,
+    public_routes: fn(&mut web::ServiceConfig),
+    routes: fn(&mut web::ServiceConfig),
+) {
+    cfg.service(
+        web::scope(&format!("/{version}"))
+            .wrap(from_fn(reject_unacceptable_requests))
+            .configure(public_routes)
+            .service(
+                web::scope("")
+                    .wrap(from_fn(reject_requests_without_api_token))
+                    .configure(routes)
+                    .default_service(web::to(api_not_found)),
+            ),
+    );
+}
+
+// Every error under `/api` has a JSON body, whatever the handler or middleware put in it
+pub fn api_error<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if is_json {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+    let (req, res) = res.into_parts();
+    let status = res.status();
+    // Server errors can carry internal details, only client errors explain themselves
+    let message = match res.error() {
+        Some(e) if status.is_client_error() => e.to_string(),
+        _ if status.is_server_error() => "Something went wrong on our side.".to_owned(),
+        _ => status.canonical_reason().unwrap_or("Error").to_owned(),
+    };
+    let mut response = json_error_response(status, &message);
+    for (name, value) in res.headers() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+            response.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, response).map_into_right_body(),
+    ))
+}
+
+// `/api/...` without a version, or with one that doesn't exist
+pub async fn unversioned_api(req: HttpRequest) -> HttpResponse {
+    let rest = req
+        .path()
+        .trim_start_matches("/api")
+        .trim_start_matches('/');
+    let first_segment = rest.split('/').next().unwrap_or_default();
+    let message = if is_version(first_segment) {
+        format!("API version {first_segment} does not exist, the latest is {LATEST_VERSION}.")
+    } else {
+        format!("The API is versioned, try /api/{LATEST_VERSION}/{rest}")
+    };
+    json_error_response(StatusCode::NOT_FOUND, &message)
+}
+
+async fn api_not_found() -> HttpResponse {
+    json_error_response(StatusCode::NOT_FOUND, "This endpoint does not exist.")
+}
+
+// The API only speaks JSON, a client that can't take it is told so up front
+async fn reject_unacceptable_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let accept = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    // Without an `Accept` header anything goes
+    if accept.is_some_and(|accept| !accepts_json(accept)) {
+        let response = json_error_response(
+            StatusCode::NOT_ACCEPTABLE,
+            "This API only responds with application/json.",
+        );
+        return Ok(req.into_response(response));
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+fn accepts_json(accept: &str) -> bool {
+    accept.split(',').any(|media_range| {
+        let media_type = media_range.split(';').next().unwrap_or_default().trim();
+        ["application/json", "application/*", "*/*"]
+            .iter()
+            .any(|accepted| media_type.eq_ignore_ascii_case(accepted))
+    })
+}
+
+// `v1`, `v2`, ...
+fn is_version(segment: &str) -> bool {
+    segment
+        .strip_prefix('v')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn json_error_response(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(ErrorBody {
+        error: message.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accepts_json, is_version};
+
+    #[test]
+    fn json_and_wildcards_are_acceptable() {
+        assert!(accepts_json("application/json"));
+        assert!(accepts_json("text/html, application/json;q=0.9"));
+        assert!(accepts_json("application/*"));
+        assert!(accepts_json("*/*"));
+        assert!(!accepts_json("text/html"));
+        assert!(!accepts_json("application/xml, text/*"));
+    }
+
+    #[test]
+    fn versions_are_a_v_followed_by_digits() {
+        assert!(is_version("v1"));
+        assert!(is_version("v12"));
+        assert!(!is_version("v"));
+        assert!(!is_version("newsletters"));
+    }
+}
@@ -0,0 +1,24 @@
+mod newsletters;
+mod stats;
+
+use actix_web::web;
+use actix_web_lab::middleware::from_fn;
+
+use crate::middleware::allow_cross_origin_reads;
+
+pub use newsletters::*;
+pub use stats::*;
+
+// Open to anyone, e.g. the marketing site
+pub fn v1_public_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/stats")
+            .wrap(from_fn(allow_cross_origin_reads))
+            .route(web::get().to(get_stats)),
+    );
+}
+
+pub fn v1_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/newsletters").route(web::get().to(list_newsletters)))
+        .service(web::resource("/newsletters/tags").route(web::get().to(list_newsletter_tags)));
+}
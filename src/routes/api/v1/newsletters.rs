@@ -0,0 +1,64 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use sqlx::PgPool;
+
+use crate::{
+    authentication::UserId,
+    routes::{get_issues, issue_tag_counts, tags_from_query},
+    utils::e500,
+};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListNewslettersQuery {
+    // Archived issues are left out unless asked for
+    include_archived: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/newsletters",
+    tag = "newsletters",
+    params(
+        ListNewslettersQuery,
+        ("tag" = Option<Vec<String>>, Query, description = "Only issues carrying every given tag, e.g. `?tag=rust&tag=backend`"),
+    ),
+    security(("api_token" = [])),
+    responses(
+        (status = 200, description = "Published issues, newest first", body = [IssueSummary]),
+        (status = 401, description = "The API token is missing, unknown or expired", body = ErrorBody),
+        (status = 406, description = "The client doesn't accept JSON", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "List newsletter issues", skip(request, query, pool, _user_id))]
+pub async fn list_newsletters(
+    request: HttpRequest,
+    query: web::Query<ListNewslettersQuery>,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tags = tags_from_query(request.query_string());
+    let issues = get_issues(&pool, query.include_archived.unwrap_or(false), &tags)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().json(issues))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/newsletters/tags",
+    tag = "newsletters",
+    security(("api_token" = [])),
+    responses(
+        (status = 200, description = "Every tag on a published issue with how many issues carry it, most used first", body = [TagCount]),
+        (status = 401, description = "The API token is missing, unknown or expired", body = ErrorBody),
+        (status = 406, description = "The client doesn't accept JSON", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "List newsletter tags", skip_all)]
+pub async fn list_newsletter_tags(
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tags = issue_tag_counts(&pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok().json(tags))
+}
@@ -0,0 +1,44 @@
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    clock::Clock,
+    configuration::PublicStatsSettings,
+    public_stats::{cached_public_stats, round_to_nearest},
+    utils::e500,
+};
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct StatsResponse {
+    // Rounded when `public_stats.round_to` is set
+    confirmed_subscribers: i64,
+    issues_published: i64,
+    // When the counts were last taken, they are at most `public_stats.refresh_seconds` old
+    computed_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    tag = "stats",
+    responses(
+        (status = 200, description = "Counts for showing on other sites, up to a minute old", body = StatsResponse),
+        (status = 406, description = "The client doesn't accept JSON", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "Get public stats", skip_all)]
+pub async fn get_stats(
+    pool: web::Data<PgPool>,
+    settings: web::Data<PublicStatsSettings>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let stats = cached_public_stats(&pool, settings.refresh_interval(), clock.now())
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().json(StatsResponse {
+        confirmed_subscribers: round_to_nearest(stats.confirmed_subscribers, settings.round_to),
+        issues_published: stats.issues_published,
+        computed_at: stats.refreshed_at,
+    }))
+}
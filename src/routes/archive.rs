@@ -0,0 +1,375 @@
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::header::{ContentType, HttpDate, IfModifiedSince, LOCATION, LastModified},
+    web,
+};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::SiteSettings,
+    domain::IssueTag,
+    startup::ApplicationBaseUrl,
+    utils::{e404, e500},
+};
+
+const FEED_LENGTH: i64 = 20;
+
+struct ArchivedIssue {
+    newsletter_issue_id: Uuid,
+    slug: String,
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Serve the archive feed", skip_all)]
+pub async fn archive_feed(
+    if_modified_since: Option<web::Header<IfModifiedSince>>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    site: web::Data<SiteSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let last_modified = get_last_modified(&pool).await.map_err(e500)?;
+    // HTTP dates only have second precision, so compare at that granularity
+    if let (Some(last_modified), Some(web::Header(IfModifiedSince(since)))) =
+        (last_modified, if_modified_since)
+        && last_modified.timestamp() <= unix_seconds(since.into())
+    {
+        return Ok(HttpResponse::NotModified().finish());
+    }
+
+    let issues = get_recent_issues(&pool).await.map_err(e500)?;
+    let feed = render_feed(&base_url, &site, last_modified, &issues);
+
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/atom+xml; charset=utf-8");
+    if let Some(last_modified) = last_modified {
+        let last_modified =
+            SystemTime::UNIX_EPOCH + Duration::from_secs(last_modified.timestamp().max(0) as u64);
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    Ok(response.body(feed))
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn render_feed(
+    base_url: &ApplicationBaseUrl,
+    site: &SiteSettings,
+    updated: Option<DateTime<Utc>>,
+    issues: &[ArchivedIssue],
+) -> String {
+    let updated = updated.unwrap_or_else(Utc::now);
+    let feed_url = base_url.join_path("archive/feed.xml");
+    let home_url = base_url.join_path("");
+    let mut entries = String::new();
+    for issue in issues {
+        writeln!(
+            entries,
+            r#"  <entry>
+    <id>urn:uuid:{}</id>
+    <title>{}</title>
+    <link rel="alternate" type="text/html" href="{}"/>
+    <updated>{}</updated>
+    <content type="html">{}</content>
+  </entry>"#,
+            issue.newsletter_issue_id,
+            encode_minimal(&issue.title),
+            base_url.join_path(&format!("archive/{}", issue.slug)),
+            rfc3339(issue.published_at),
+            encode_minimal(&issue.html_content),
+        )
+        .unwrap();
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>{feed_url}</id>
+  <title>{}</title>
+  <subtitle>{}</subtitle>
+  <link rel="self" href="{feed_url}"/>
+  <link rel="alternate" href="{home_url}"/>
+  <updated>{}</updated>
+  <author><name>{}</name></author>
+{entries}</feed>
+"#,
+        encode_minimal(&site.title),
+        encode_minimal(&site.subtitle),
+        rfc3339(updated),
+        encode_minimal(&site.author),
+    )
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Soft-deleting, archiving or unarchiving an issue changes the feed too, so it counts as a
+// modification
+#[tracing::instrument(skip_all)]
+async fn get_last_modified(pool: &PgPool) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            GREATEST(
+                MAX(published_at::timestamptz),
+                MAX(deleted_at),
+                MAX(archive_changed_at)
+            ) AS last_modified
+        FROM newsletter_issues
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to determine when the archive last changed.")?;
+    Ok(row.last_modified)
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_recent_issues(pool: &PgPool) -> Result<Vec<ArchivedIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            slug,
+            title,
+            html_content,
+            published_at::timestamptz AS "published_at!"
+        FROM newsletter_issues
+        WHERE deleted_at IS NULL AND archived_at IS NULL
+        ORDER BY published_at::timestamptz DESC
+        LIMIT $1
+        "#,
+        FEED_LENGTH
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve archived issues.")?;
+    Ok(issues)
+}
+
+// `?tag=rust&tag=backend` narrows the list down to issues carrying every tag
+#[tracing::instrument(name = "Serve the archive index", skip_all)]
+pub async fn archive_index(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    site: web::Data<SiteSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tags = tags_from_query(request.query_string());
+    let mut tags_html = String::new();
+    for tag in issue_tag_counts(&pool).await.map_err(e500)? {
+        writeln!(
+            tags_html,
+            r#"<a href="/archive?tag={}">{} ({})</a>"#,
+            encode_attribute(&urlencoding::encode(&tag.tag)),
+            encode_minimal(&tag.tag),
+            tag.count
+        )
+        .unwrap();
+    }
+    let filter_html = if tags.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<p>Tagged {} - <a href="/archive">show all issues</a></p>"#,
+            encode_minimal(&tags.join(", "))
+        )
+    };
+    let mut issues_html = String::new();
+    for issue in get_published_slugs(&pool, &tags).await.map_err(e500)? {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/archive/{}">{}</a></li>"#,
+            encode_minimal(&issue.slug),
+            encode_minimal(&issue.title)
+        )
+        .unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>{title}</title>
+                <link rel="alternate" type="application/atom+xml" href="/archive/feed.xml">
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <p>{tags_html}</p>
+                {filter_html}
+                <ul>
+                    {issues_html}
+                </ul>
+            </body>
+        </html>"#,
+            title = encode_minimal(&site.title),
+        )))
+}
+
+// Old links use the issue's id, those are permanently redirected to the slug
+#[tracing::instrument(name = "Serve an archived issue", skip(pool))]
+pub async fn archive_issue(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let slug = slug.into_inner();
+    if let Ok(newsletter_issue_id) = Uuid::parse_str(&slug) {
+        let issue = get_slug_by_id(&pool, newsletter_issue_id)
+            .await
+            .map_err(e500)?;
+        // Issues published before slugs existed use their id as their slug
+        if let Some(issue_slug) = issue.filter(|s| *s != slug) {
+            return Ok(HttpResponse::MovedPermanently()
+                .insert_header((LOCATION, format!("/archive/{issue_slug}")))
+                .finish());
+        }
+    }
+    let issue = get_issue_by_slug(&pool, &slug)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("No such issue"))?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>{title}</title>
+            </head>
+            <body>
+                <h1>{title}</h1>
+                {}
+                <p><a href="/archive">&lt;- All issues</a></p>
+            </body>
+        </html>"#,
+            issue.html_content,
+            title = encode_minimal(&issue.title),
+        )))
+}
+
+pub struct PublishedSlug {
+    pub slug: String,
+    pub title: String,
+    pub published_at: DateTime<Utc>,
+}
+
+// Only issues carrying every one of `tags`, all of them when it's empty
+#[tracing::instrument(skip(pool))]
+pub async fn get_published_slugs(
+    pool: &PgPool,
+    tags: &[String],
+) -> Result<Vec<PublishedSlug>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        PublishedSlug,
+        r#"
+        SELECT slug, title, published_at::timestamptz AS "published_at!"
+        FROM newsletter_issues
+        WHERE deleted_at IS NULL AND archived_at IS NULL AND tags @> $1
+        ORDER BY published_at::timestamptz DESC
+        "#,
+        tags
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve archived issues.")?;
+    Ok(issues)
+}
+
+// Repeated `tag` parameters, which `web::Query` can't collect into a list
+pub fn tags_from_query(query_string: &str) -> Vec<String> {
+    let values: Vec<_> = url::form_urlencoded::parse(query_string.as_bytes())
+        .filter(|(name, _)| name == "tag")
+        .map(|(_, value)| value)
+        .collect();
+    IssueTag::parse_all(values.iter().map(AsRef::as_ref))
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct TagCount {
+    pub tag: String,
+    // Published issues carrying the tag, archived and deleted ones left out
+    pub count: i64,
+}
+
+// Most used first
+#[tracing::instrument(skip_all)]
+pub async fn issue_tag_counts(pool: &PgPool) -> Result<Vec<TagCount>, anyhow::Error> {
+    let counts = sqlx::query_as!(
+        TagCount,
+        r#"
+        SELECT tag AS "tag!", COUNT(*) AS "count!"
+        FROM newsletter_issues, unnest(tags) AS tag
+        WHERE deleted_at IS NULL AND archived_at IS NULL
+        GROUP BY tag
+        ORDER BY COUNT(*) DESC, tag
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count the issues' tags.")?;
+    Ok(counts)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_slug_by_id(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT slug
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND deleted_at IS NULL AND archived_at IS NULL
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the issue's slug.")?;
+    Ok(row.map(|r| r.slug))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_issue_by_slug(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<ArchivedIssue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            slug,
+            title,
+            html_content,
+            published_at::timestamptz AS "published_at!"
+        FROM newsletter_issues
+        WHERE slug = $1 AND deleted_at IS NULL AND archived_at IS NULL
+        "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the archived issue.")?;
+    Ok(issue)
+}
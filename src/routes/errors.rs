@@ -0,0 +1,83 @@
+use actix_web::{
+    HttpRequest, HttpResponse, ResponseError,
+    dev::ServiceResponse,
+    error::JsonPayloadError,
+    http::{
+        StatusCode,
+        header::{ACCEPT, ALLOW, ContentType},
+    },
+    middleware::ErrorHandlerResponse,
+};
+use htmlescape::encode_minimal;
+
+// What every JSON error response looks like
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    #[schema(example = "This page does not exist.")]
+    pub error: String,
+}
+
+// Anything that didn't match a route
+pub async fn not_found(req: HttpRequest) -> HttpResponse {
+    error_response(&req, StatusCode::NOT_FOUND, "This page does not exist.")
+}
+
+// The router's own 405 has an empty body, this keeps its `Allow` header and adds one like the 404's
+pub fn method_not_allowed<B>(
+    res: ServiceResponse<B>,
+) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let (req, res) = res.into_parts();
+    let mut response = error_response(
+        &req,
+        StatusCode::METHOD_NOT_ALLOWED,
+        "This method is not allowed here.",
+    );
+    if let Some(allow) = res.headers().get(ALLOW) {
+        response.headers_mut().insert(ALLOW, allow.clone());
+    }
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, response).map_into_right_body(),
+    ))
+}
+
+// A JSON body that doesn't parse gets a JSON error back rather than plain text
+pub fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = HttpResponse::build(err.status_code()).json(ErrorBody {
+        error: err.to_string(),
+    });
+    actix_web::error::InternalError::from_response(err, response).into()
+}
+
+// Browsers get a page, API clients asking for JSON get JSON
+pub fn error_response(req: &HttpRequest, status: StatusCode, message: &str) -> HttpResponse {
+    if wants_json(req) {
+        return HttpResponse::build(status).json(ErrorBody {
+            error: message.to_owned(),
+        });
+    }
+    HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>{status}</title>
+            </head>
+            <body>
+                <p>{}</p>
+                <p><a href="/">Home</a></p>
+            </body>
+        </html>"#,
+            encode_minimal(message),
+        ))
+}
+
+fn wants_json(req: &HttpRequest) -> bool {
+    let accept = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    accept.contains("application/json") && !accept.contains("text/html")
+}
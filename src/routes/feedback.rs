@@ -0,0 +1,188 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, http::header::ContentType, web};
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+impl Rating {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rating::Up => "up",
+            Rating::Down => "down",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct FeedbackParameters {
+    issue: Uuid,
+    token: String,
+    rating: Rating,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FeedbackError {
+    #[error("Invalid token")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for FeedbackError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            FeedbackError::InvalidToken => StatusCode::UNAUTHORIZED,
+            FeedbackError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// The token is `<subscriber_id>.<signature>`, signed over both the issue and the subscriber so a
+// link can't be replayed against another issue or edited to vote on someone else's behalf.
+pub fn feedback_token(hmac_secret: &Secret<String>, issue_id: Uuid, subscriber_id: Uuid) -> String {
+    let signature = hex::encode(
+        signer(hmac_secret, issue_id, subscriber_id)
+            .finalize()
+            .into_bytes(),
+    );
+    format!("{subscriber_id}.{signature}")
+}
+
+pub fn feedback_link(
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &Secret<String>,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    rating: Rating,
+) -> String {
+    let token = feedback_token(hmac_secret, issue_id, subscriber_id);
+    let mut link = base_url.join_path("feedback");
+    link.query_pairs_mut()
+        .append_pair("issue", &issue_id.to_string())
+        .append_pair("token", &token)
+        .append_pair("rating", rating.as_str());
+    link.into()
+}
+
+fn verify_feedback_token(
+    hmac_secret: &Secret<String>,
+    issue_id: Uuid,
+    token: &str,
+) -> Option<Uuid> {
+    let (subscriber_id, signature) = token.split_once('.')?;
+    let subscriber_id = Uuid::parse_str(subscriber_id).ok()?;
+    let signature = hex::decode(signature).ok()?;
+    signer(hmac_secret, issue_id, subscriber_id)
+        .verify_slice(&signature)
+        .ok()?;
+    Some(subscriber_id)
+}
+
+fn signer(hmac_secret: &Secret<String>, issue_id: Uuid, subscriber_id: Uuid) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes()).unwrap();
+    mac.update(format!("{issue_id}:{subscriber_id}").as_bytes());
+    mac
+}
+
+#[tracing::instrument(
+    name = "Record issue feedback",
+    skip(parameters, pool, hmac_secret),
+    fields(newsletter_issue_id = %parameters.issue, rating = ?parameters.rating)
+)]
+pub async fn submit_feedback(
+    parameters: web::Query<FeedbackParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, FeedbackError> {
+    let subscriber_id = verify_feedback_token(&hmac_secret.0, parameters.issue, &parameters.token)
+        .ok_or(FeedbackError::InvalidToken)?;
+    store_feedback(&pool, parameters.issue, subscriber_id, parameters.rating)
+        .await
+        .context("Failed to store issue feedback.")?;
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Thank you</title>
+            </head>
+            <body>
+                <p>Thanks for your feedback!</p>
+            </body>
+        </html>"#,
+    ))
+}
+
+// Subscribers can change their mind, the latest rating wins
+#[tracing::instrument(name = "Store issue feedback", skip(pool))]
+async fn store_feedback(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    rating: Rating,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_feedback (newsletter_issue_id, subscriber_id, rating, submitted_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_id)
+        DO UPDATE SET rating = EXCLUDED.rating, submitted_at = EXCLUDED.submitted_at
+        "#,
+        issue_id,
+        subscriber_id,
+        rating.as_str(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{feedback_token, verify_feedback_token};
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-secret-used-only-in-tests".to_string())
+    }
+
+    #[test]
+    fn a_token_verifies_for_the_issue_it_was_signed_for() {
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let token = feedback_token(&secret(), issue_id, subscriber_id);
+        assert_some_eq!(
+            verify_feedback_token(&secret(), issue_id, &token),
+            subscriber_id
+        );
+    }
+
+    #[test]
+    fn a_token_is_rejected_for_another_issue() {
+        let token = feedback_token(&secret(), Uuid::new_v4(), Uuid::new_v4());
+        assert_none!(verify_feedback_token(&secret(), Uuid::new_v4(), &token));
+    }
+
+    #[test]
+    fn a_token_with_a_swapped_subscriber_is_rejected() {
+        let issue_id = Uuid::new_v4();
+        let token = feedback_token(&secret(), issue_id, Uuid::new_v4());
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!("{}.{signature}", Uuid::new_v4());
+        assert_none!(verify_feedback_token(&secret(), issue_id, &forged));
+    }
+}
@@ -1,5 +1,171 @@
-use actix_web::HttpResponse;
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 
+use crate::{
+    build_info::BUILD_INFO, clock::Clock, configuration::DeliverySettings, readiness::Readiness,
+    supervisor::Supervisor, utils::e500, worker_heartbeat::latest_heartbeat,
+};
+
+// Kept for whatever was pointed here before liveness and readiness were split
+#[utoipa::path(
+    get,
+    path = "/health_check",
+    tag = "health",
+    responses((status = 200, description = "The application is up, same as /health_check/live"))
+)]
 pub async fn health_check() -> HttpResponse {
+    liveness_check().await
+}
+
+// Answering at all means the server loop is running, so it checks nothing else
+#[utoipa::path(
+    get,
+    path = "/health_check/live",
+    tag = "health",
+    responses((status = 200, description = "The application is up"))
+)]
+pub async fn liveness_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ReadinessReport {
+    ready: bool,
+    started: bool,
+    database: bool,
+    redis: bool,
+    // The short commit SHA, to tell which instances are on the new build during a rollout
+    git_sha: &'static str,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health_check/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "The application has started and reaches Postgres and Redis", body = ReadinessReport),
+        (status = 503, description = "Still starting up, or a dependency can't be reached", body = ReadinessReport),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn readiness_check(
+    pool: web::Data<PgPool>,
+    readiness: web::Data<Readiness>,
+) -> HttpResponse {
+    let started = readiness.is_started();
+    let (database, redis) = tokio::join!(readiness.check_database(&pool), readiness.check_redis());
+    for e in [&database, &redis]
+        .into_iter()
+        .filter_map(|r| r.as_ref().err())
+    {
+        tracing::warn!(error.cause_chain = ?e, "The readiness check failed.");
+    }
+    let body = ReadinessReport {
+        ready: started && database.is_ok() && redis.is_ok(),
+        started,
+        database: database.is_ok(),
+        redis: redis.is_ok(),
+        git_sha: BUILD_INFO.short_sha(),
+    };
+    if body.ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct VersionInfo {
+    // The crate version from Cargo.toml
+    version: &'static str,
+    // The full commit SHA, `unknown` when built without git or a `GIT_SHA`
+    git_sha: &'static str,
+    built_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "health",
+    responses((status = 200, description = "What the running instance was built from", body = VersionInfo))
+)]
+pub async fn version() -> HttpResponse {
+    HttpResponse::Ok().json(VersionInfo {
+        version: BUILD_INFO.version,
+        git_sha: BUILD_INFO.git_sha,
+        built_at: BUILD_INFO.built_at(),
+    })
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct WorkerHealth {
+    healthy: bool,
+    worker_id: Option<String>,
+    last_beat: Option<DateTime<Utc>>,
+    // Seconds since the newest heartbeat, left out if the worker has never beaten
+    age_seconds: Option<i64>,
+    last_task_at: Option<DateTime<Utc>>,
+    queue_depth: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health_check/worker",
+    tag = "health",
+    responses(
+        (status = 200, description = "The delivery worker beat recently", body = WorkerHealth),
+        (status = 503, description = "The delivery worker hasn't beaten within the staleness threshold", body = WorkerHealth),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn worker_health_check(
+    pool: web::Data<PgPool>,
+    delivery: web::Data<DeliverySettings>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let heartbeat = latest_heartbeat(&pool).await.map_err(e500)?;
+    let age = heartbeat.as_ref().map(|h| h.age(clock.now()));
+    let healthy = age.is_some_and(|age| age <= delivery.worker_stale_after());
+    let body = WorkerHealth {
+        healthy,
+        worker_id: heartbeat.as_ref().map(|h| h.worker_id.clone()),
+        last_beat: heartbeat.as_ref().map(|h| h.last_beat),
+        age_seconds: age.map(|age| age.num_seconds()),
+        last_task_at: heartbeat.as_ref().and_then(|h| h.last_task_at),
+        queue_depth: heartbeat.as_ref().map(|h| h.queue_depth),
+    };
+    if healthy {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct BackgroundTaskHealth {
+    name: &'static str,
+    // Since the process started, a number that keeps going up means the task keeps failing
+    restarts: u32,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct BackgroundTasksHealth {
+    tasks: Vec<BackgroundTaskHealth>,
+}
+
+// A task that has been given up on shuts the process down, so there's no unhealthy answer here
+#[utoipa::path(
+    get,
+    path = "/health_check/tasks",
+    tag = "health",
+    responses((status = 200, description = "How often each background task has been restarted", body = BackgroundTasksHealth))
+)]
+pub async fn background_tasks_health_check(supervisor: web::Data<Supervisor>) -> HttpResponse {
+    let tasks = supervisor
+        .restart_counts()
+        .into_iter()
+        .map(|(name, restarts)| BackgroundTaskHealth { name, restarts })
+        .collect();
+    HttpResponse::Ok().json(BackgroundTasksHealth { tasks })
+}
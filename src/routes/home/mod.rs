@@ -1,7 +1,63 @@
-use actix_web::{HttpResponse, http::header::ContentType};
+use std::fmt::Write;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+use actix_web::{
+    HttpResponse,
+    http::header::{CACHE_CONTROL, ContentType},
+    web,
+};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+
+use crate::configuration::SiteSettings;
+
+pub async fn home(
+    site: web::Data<SiteSettings>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let mut flash_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(flash_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    let mut response = HttpResponse::Ok();
+    response.content_type(ContentType::html());
+    // The flash message is meant for this visitor only, keep it out of shared caches
+    if !flash_html.is_empty() {
+        response.insert_header((CACHE_CONTROL, "no-store"));
+    }
+    response.body(format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>{title}</title>
+                <link rel="alternate" type="application/atom+xml" href="/archive/feed.xml">
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <p>{subtitle}</p>
+                {flash_html}
+                <form action="/subscriptions" method="post">
+                    <input type="hidden" name="redirect_to" value="/">
+                    <label>Name
+                        <input
+                            type="text"
+                            placeholder="Enter your name"
+                            name="name"
+                        >
+                    </label>
+                    <label>Email
+                        <input
+                            type="email"
+                            placeholder="Enter your email"
+                            name="email"
+                        >
+                    </label>
+                    <button type="submit">Subscribe</button>
+                </form>
+                <p><a href="/archive">Read past issues</a></p>
+            </body>
+        </html>"#,
+        title = encode_minimal(&site.title),
+        subtitle = encode_minimal(&site.subtitle),
+    ))
 }
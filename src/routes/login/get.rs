@@ -3,12 +3,24 @@ use std::fmt::Write;
 use actix_web::{HttpResponse, http::header::ContentType};
 use actix_web_flash_messages::IncomingFlashMessages;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+use crate::{
+    session_state::TypedSession,
+    utils::{e500, see_other},
+};
+
+// Someone who is already logged in has no use for the form
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_user_id().map_err(e500)?.is_some() {
+        return Ok(see_other("/admin/dashboard"));
+    }
     let mut error_html = String::new();
     for m in flash_messages.iter() {
         writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
             r#"<!DOCTYPE html>
@@ -38,5 +50,5 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
                     </form>
             </body>
         </html>"#,
-        ))
+        )))
 }
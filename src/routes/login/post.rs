@@ -1,10 +1,16 @@
-use actix_web::{HttpResponse, ResponseError, error::InternalError, http::StatusCode, web};
+use actix_web::{
+    HttpRequest, HttpResponse, ResponseError,
+    error::InternalError,
+    http::{StatusCode, header::RETRY_AFTER},
+    web,
+};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{AuthError, Credentials, validate_credentials},
+    authentication::{AuthError, Credentials, LoginThrottle, validate_credentials},
+    clock::Clock,
     session_state::TypedSession,
     utils::see_other,
 };
@@ -19,6 +25,8 @@ pub struct FormData {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, try again later")]
+    TooManyAttempts,
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -28,19 +36,38 @@ impl ResponseError for LoginError {
         match self {
             LoginError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             LoginError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            LoginError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(request, form, pool, session, throttle, clock),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    throttle: web::Data<LoginThrottle>,
+    clock: web::Data<dyn Clock>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let client_ip = throttle.client_ip(&request);
+    // Checked before touching the credentials so a sprayed IP can't keep hashing passwords
+    if let Some(retry_after) = throttle
+        .retry_after(&client_ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        let response = HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, retry_after))
+            .finish();
+        return Err(InternalError::from_response(
+            LoginError::TooManyAttempts,
+            response,
+        ));
+    }
     let credentials = Credentials {
         username: form.0.username,
         password: form.0.password,
@@ -53,9 +80,18 @@ pub async fn login(
             session
                 .insert_user_id(user_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .set_authenticated_at(clock.now())
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
             Ok(see_other("/admin/dashboard"))
         }
         Err(e) => {
+            if let AuthError::InvalidCredentials(_) = e {
+                throttle
+                    .record_failure(&client_ip)
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            }
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
@@ -1,13 +1,31 @@
 mod admin;
+mod api;
+mod archive;
+mod errors;
+mod feedback;
 mod health_check;
 mod home;
 mod login;
+mod openapi;
+mod sitemap;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_confirm_email;
+mod subscriptions_resend_confirmation;
+mod tracking;
 
 pub use admin::*;
+pub use api::*;
+pub use archive::*;
+pub use errors::*;
+pub use feedback::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use openapi::*;
+pub use sitemap::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_confirm_email::*;
+pub use subscriptions_resend_confirmation::*;
+pub use tracking::*;
@@ -0,0 +1,79 @@
+use actix_web::{HttpResponse, http::header::ContentType};
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+};
+
+use crate::routes::{
+    BackgroundTaskHealth, BackgroundTasksHealth, ErrorBody, IssueSummary, ReadinessReport,
+    ResendConfirmationFormData, StatsResponse, SubscribeResponse, SubscriptionsFormData, TagCount,
+    VersionInfo, WorkerHealth,
+};
+
+// The JSON API for partners. The admin pages are HTML for people and stay out of it.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Newsletter API"),
+    paths(
+        crate::routes::health_check,
+        crate::routes::liveness_check,
+        crate::routes::readiness_check,
+        crate::routes::worker_health_check,
+        crate::routes::background_tasks_health_check,
+        crate::routes::version,
+        crate::routes::subscribe,
+        crate::routes::confirm,
+        crate::routes::confirm_email_change,
+        crate::routes::resend_confirmation,
+        crate::routes::list_newsletters,
+        crate::routes::list_newsletter_tags,
+        crate::routes::get_stats,
+    ),
+    components(schemas(SubscriptionsFormData, ResendConfirmationFormData, SubscribeResponse, IssueSummary, TagCount, StatsResponse, ErrorBody, WorkerHealth, BackgroundTasksHealth, BackgroundTaskHealth, ReadinessReport, VersionInfo)),
+    modifiers(&ApiTokenAuth),
+    tags(
+        (name = "subscriptions", description = "Signing up and confirming addresses"),
+        (name = "newsletters", description = "Published issues, needs an API token"),
+        (name = "stats", description = "Public counts for showing on other sites"),
+        (name = "health", description = "Whether the application and its delivery worker are up"),
+    )
+)]
+pub struct ApiDoc;
+
+// `Authorization: Bearer <token>`, with a token from the admin's API tokens page
+struct ApiTokenAuth;
+
+impl Modify for ApiTokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+// Only served when `application.swagger_ui` is on. The UI itself is loaded from a CDN.
+pub async fn swagger_ui_page() -> HttpResponse {
+    HttpResponse::Ok().content_type(ContentType::html()).body(
+        r##"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Newsletter API</title>
+                <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+            </head>
+            <body>
+                <div id="swagger-ui"></div>
+                <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+                <script>
+                    SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+                </script>
+            </body>
+        </html>"##,
+    )
+}
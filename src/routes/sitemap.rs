@@ -0,0 +1,58 @@
+use std::fmt::Write;
+
+use actix_web::{HttpResponse, web};
+use chrono::SecondsFormat;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+
+use crate::{routes::get_published_slugs, startup::ApplicationBaseUrl, utils::e500};
+
+#[tracing::instrument(name = "Serve the sitemap", skip_all)]
+pub async fn sitemap(
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_published_slugs(&pool, &[]).await.map_err(e500)?;
+
+    let mut urls = String::new();
+    // Issues are newest first, so the index changed whenever the first one was published
+    let index_lastmod = issues
+        .first()
+        .map(|i| {
+            format!(
+                "<lastmod>{}</lastmod>",
+                i.published_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )
+        })
+        .unwrap_or_default();
+    writeln!(
+        urls,
+        "  <url><loc>{}</loc>{index_lastmod}</url>",
+        base_url.join_path("archive")
+    )
+    .unwrap();
+    for issue in &issues {
+        writeln!(
+            urls,
+            "  <url><loc>{}</loc><lastmod>{}</lastmod></url>",
+            encode_minimal(
+                base_url
+                    .join_path(&format!("archive/{}", urlencoding::encode(&issue.slug)))
+                    .as_str()
+            ),
+            issue
+                .published_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+{urls}</urlset>
+"#
+        )))
+}
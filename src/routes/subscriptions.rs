@@ -1,6 +1,16 @@
-use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::{
+    Either, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+    http::{
+        StatusCode,
+        header::{Accept, REFERER},
+    },
+    mime, web,
+};
+use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::{
     distributions::Alphanumeric,
     {Rng, thread_rng},
@@ -9,15 +19,87 @@ use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
-    email_client::EmailClient,
+    clock::Clock,
+    configuration::SubscriptionSettings,
+    domain::{NewSubscriber, PiiCipher, SubscriberEmail, SubscriberName},
+    email_client::{EmailClientError, EmailClientPool},
+    geo_lookup::GeoLookup,
+    routes::ErrorBody,
     startup::ApplicationBaseUrl,
+    suppressions::is_suppressed,
+    utils::see_other,
 };
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct SubscriptionsFormData {
+    #[schema(example = "ursula_le_guin@gmail.com")]
     email: String,
+    #[schema(example = "le guin")]
     name: String,
+    // Set by our own forms, the page to send the browser back to
+    redirect_to: Option<String>,
+    // Also taken from the query string, e.g. `/subscriptions?utm_source=podcast`
+    #[schema(example = "podcast")]
+    utm_source: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct SignupQuery {
+    utm_source: Option<String>,
+}
+
+// Longer referers and `utm_source`s are cut short rather than refused
+const MAX_SIGNUP_SOURCE_LENGTH: usize = 512;
+
+// Where a signup came from, for growth analytics
+pub struct SignupSource {
+    referer: Option<String>,
+    utm_source: Option<String>,
+    // Only known when a geo lookup is plugged in
+    country: Option<String>,
+}
+
+impl SignupSource {
+    // The form's `utm_source` wins over the query string's
+    fn from_request(
+        request: &HttpRequest,
+        utm_source: Option<String>,
+        geo: &dyn GeoLookup,
+    ) -> Self {
+        let utm_source = utm_source.or_else(|| {
+            web::Query::<SignupQuery>::from_query(request.query_string())
+                .ok()
+                .and_then(|query| query.into_inner().utm_source)
+        });
+        let referer = request
+            .headers()
+            .get(REFERER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let country = request
+            .connection_info()
+            .realip_remote_addr()
+            .and_then(parse_ip)
+            .and_then(|ip| geo.country(ip));
+        Self {
+            referer: referer.and_then(clean_source),
+            utm_source: utm_source.and_then(clean_source),
+            country,
+        }
+    }
+}
+
+fn clean_source(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.chars().take(MAX_SIGNUP_SOURCE_LENGTH).collect())
+}
+
+// The peer address comes with its port, a forwarded one usually without
+fn parse_ip(address: &str) -> Option<IpAddr> {
+    address
+        .parse::<IpAddr>()
+        .or_else(|_| address.parse::<SocketAddr>().map(|address| address.ip()))
+        .ok()
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -55,27 +137,184 @@ impl From<String> for SubscribeError {
     }
 }
 
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscribeResponse {
+    subscriber_id: Uuid,
+}
+
+// Accepts a JSON body as well as the form. JSON clients get the subscriber's id back.
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(
+        content = SubscriptionsFormData,
+        description = "Also accepted as application/x-www-form-urlencoded"
+    ),
+    responses(
+        (status = 200, description = "Subscribed, a confirmation email is on its way", body = SubscribeResponse),
+        (status = 303, description = "Browsers are sent back to the form"),
+        (status = 400, description = "The name or email is invalid", body = ErrorBody),
+        (status = 500, description = "Something went wrong on our side"),
+    )
+)]
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(request, body, pool, email_clients, base_url, settings, geo, pii_cipher, clock),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name,
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty,
     )
 )]
 pub async fn subscribe(
-    form: web::Form<SubscriptionsFormData>,
+    request: HttpRequest,
+    body: Either<web::Json<SubscriptionsFormData>, web::Form<SubscriptionsFormData>>,
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
+    email_clients: web::Data<EmailClientPool>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    geo: web::Data<dyn GeoLookup>,
+    pii_cipher: web::Data<PiiCipher>,
+    clock: web::Data<dyn Clock>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into()?;
+    let (mut form, is_json) = match body {
+        Either::Left(json) => (json.into_inner(), true),
+        Either::Right(form) => (form.into_inner(), false),
+    };
+    tracing::Span::current()
+        .record("subscriber_email", tracing::field::display(&form.email))
+        .record("subscriber_name", tracing::field::display(&form.name));
+    let redirect_to = form.redirect_to.take();
+    let source = SignupSource::from_request(&request, form.utm_source.take(), geo.get_ref());
+    let outcome = try_subscribe(
+        form,
+        &source,
+        &pool,
+        &email_clients,
+        &base_url,
+        &settings,
+        &pii_cipher,
+        clock.now(),
+    )
+    .await;
+    if is_json || prefers_json(&request) {
+        return match outcome {
+            Ok(subscriber_id) => Ok(HttpResponse::Ok().json(SubscribeResponse { subscriber_id })),
+            Err(SubscribeError::ValidationError(error)) => {
+                Ok(HttpResponse::BadRequest().json(ErrorBody { error }))
+            }
+            Err(e) => Err(e),
+        };
+    }
+    if redirect_to.is_none() && !prefers_html(&request) {
+        return outcome.map(|_| HttpResponse::Ok().finish());
+    }
+    // Post/Redirect/Get: browsers go back to the form with the outcome as a flash message, so a
+    // refresh doesn't submit the form again
+    match outcome {
+        Ok(_) => {
+            FlashMessage::info("Thanks for subscribing! Check your inbox to confirm your email.")
+                .send();
+        }
+        Err(SubscribeError::ValidationError(e)) => FlashMessage::error(e).send(),
+        Err(e) => return Err(e),
+    }
+    Ok(see_other(&redirect_target(&request, redirect_to)))
+}
+
+fn prefers_html(request: &HttpRequest) -> bool {
+    request
+        .get_header::<Accept>()
+        .is_some_and(|accept| accept.preference() == mime::TEXT_HTML)
+}
+
+fn prefers_json(request: &HttpRequest) -> bool {
+    request
+        .get_header::<Accept>()
+        .is_some_and(|accept| accept.preference() == mime::APPLICATION_JSON)
+}
+
+// Only ever a path on this site: the form's `redirect_to`, else the referring page, else home
+fn redirect_target(request: &HttpRequest, redirect_to: Option<String>) -> String {
+    if let Some(redirect_to) = redirect_to.as_deref().and_then(local_path) {
+        return redirect_to;
+    }
+    request
+        .headers()
+        .get(REFERER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|referer| reqwest::Url::parse(referer).ok())
+        .and_then(|referer| match referer.query() {
+            Some(query) => local_path(&format!("{}?{query}", referer.path())),
+            None => local_path(referer.path()),
+        })
+        .unwrap_or_else(|| "/".into())
+}
+
+// The path and query of `target` if it stays on this site. Browsers read `\` as `/`, so
+// `/\evil.com` is as much another site as `//evil.com`, and they drop tabs and newlines.
+fn local_path(target: &str) -> Option<String> {
+    if !target.starts_with('/') || target.contains('\\') || reqwest::Url::parse(target).is_ok() {
+        return None;
+    }
+    let base = reqwest::Url::parse("http://localhost/").unwrap();
+    let url = base.join(target).ok()?;
+    if url.host() != base.host() || url.port() != base.port() {
+        return None;
+    }
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    };
+    // Dot segments can collapse into a leading `//` once resolved
+    (!path.starts_with("//")).then_some(path)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn try_subscribe(
+    form: SubscriptionsFormData,
+    source: &SignupSource,
+    pool: &PgPool,
+    email_clients: &EmailClientPool,
+    base_url: &ApplicationBaseUrl,
+    settings: &SubscriptionSettings,
+    pii_cipher: &PiiCipher,
+    now: DateTime<Utc>,
+) -> Result<Uuid, SubscribeError> {
+    let new_subscriber: NewSubscriber = form.try_into()?;
+    settings.email_strictness.check(&new_subscriber.email)?;
+    let domain = new_subscriber.email.domain();
+    if !settings.accepts_domain(domain) {
+        return Err(SubscribeError::ValidationError(format!(
+            "Email addresses at '{domain}' can't subscribe to this newsletter."
+        )));
+    }
+    // Suppressed addresses get the same response as everyone else, so the list can't be probed.
+    // That includes an id, which just doesn't belong to anyone.
+    if is_suppressed(pool, new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check whether the address is suppressed.")?
+    {
+        tracing::info!("Dropping a subscription for a suppressed address.");
+        return Ok(Uuid::new_v4());
+    }
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool.")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
+    // Subscribing again is not an error: it returns the same subscriber and, while they are still
+    // pending, sends them a fresh confirmation email
+    let subscriber_id =
+        match find_subscriber(&mut transaction, new_subscriber.email.as_ref(), pii_cipher)
+            .await
+            .context("Failed to look up an existing subscriber.")?
+        {
+            Some(existing) if existing.status == "confirmed" => return Ok(existing.id),
+            Some(existing) => existing.id,
+            None => insert_subscriber(&mut transaction, &new_subscriber, source, pii_cipher, now)
+                .await
+                .context("Failed to insert new subscriber in the database.")?,
+        };
     let subscription_token = generate_subscription_token();
     store_token(&mut transaction, subscriber_id, &subscription_token)
         .await
@@ -85,15 +324,16 @@ pub async fn subscribe(
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
     send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
+        email_clients,
+        &new_subscriber.email,
+        new_subscriber.name.as_ref(),
+        base_url,
         &subscription_token,
     )
     .await
     .context("Failed to send a confirmation email.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(subscriber_id)
 }
 
 #[tracing::instrument(
@@ -119,48 +359,107 @@ pub async fn store_token(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(email_clients, email, name, base_url, subscription_token)
 )]
 pub async fn send_confirmation_email(
-    email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
-    base_url: &str,
+    email_clients: &EmailClientPool,
+    email: &SubscriberEmail,
+    name: &str,
+    base_url: &ApplicationBaseUrl,
+    subscription_token: &str,
+) -> Result<(), EmailClientError> {
+    let confirmation_email = confirmation_email(name, base_url, subscription_token);
+    email_clients
+        .send(
+            None,
+            email,
+            confirmation_email.subject,
+            &confirmation_email.html_body,
+            &confirmation_email.plain_body,
+        )
+        .await
+}
+
+pub struct ConfirmationEmail {
+    pub subject: &'static str,
+    pub html_body: String,
+    pub plain_body: String,
+}
+
+// Also rendered, unsent, by the admin preview
+pub fn confirmation_email(
+    name: &str,
+    base_url: &ApplicationBaseUrl,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link =
-        format!("{base_url}/subscriptions/confirm?subscription_token={subscription_token}");
-    let html_body = &format!(
-        "Welcome to our newsletter!<br />\
-            Click <a href=\"{confirmation_link}\">here</a> to confirm your subscription."
+) -> ConfirmationEmail {
+    let mut confirmation_link = base_url.join_path("subscriptions/confirm");
+    confirmation_link
+        .query_pairs_mut()
+        .append_pair("subscription_token", subscription_token);
+    let name = greeting_name(name);
+    let html_body = format!(
+        "Hello {}, please confirm your subscription.<br />\
+            Click <a href=\"{confirmation_link}\">here</a> to confirm your subscription.",
+        htmlescape::encode_minimal(name)
     );
-    let plain_body = &format!(
-        "Welcome to our newsletter!\nVisit {confirmation_link} to confirm your subscription."
+    let plain_body = format!(
+        "Hello {name}, please confirm your subscription.\n\
+            Visit {confirmation_link} to confirm your subscription."
     );
-    email_client
-        .send_email(&new_subscriber.email, "Welcome!", html_body, plain_body)
-        .await
+    ConfirmationEmail {
+        subject: "Welcome!",
+        html_body,
+        plain_body,
+    }
+}
+
+// "Hello there" reads better than "Hello ," for subscribers without a usable name
+pub fn greeting_name(name: &str) -> &str {
+    let name = name.trim();
+    if name.is_empty() { "there" } else { name }
 }
 
 #[tracing::instrument(
     name = "Saving new subscriber details in the database.",
-    skip(new_subscriber, transaction)
+    skip(new_subscriber, source, transaction, pii_cipher)
 )]
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
+    source: &SignupSource,
+    pii_cipher: &PiiCipher,
+    subscribed_at: DateTime<Utc>,
 ) -> Result<Uuid, sqlx::Error> {
-    email_exists(transaction, new_subscriber.email.as_ref()).await?;
-
     let subscriber_id = Uuid::new_v4();
+    let email = pii_cipher.store(new_subscriber.email.as_ref());
+    let name = pii_cipher.store(new_subscriber.name.as_ref());
     sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (
+            id,
+            email,
+            email_encrypted,
+            email_hash,
+            name,
+            name_encrypted,
+            subscribed_at,
+            status,
+            signup_referer,
+            signup_utm_source,
+            signup_country
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, 'pending_confirmation', $8, $9, $10)
         "#,
         subscriber_id,
-        new_subscriber.email.as_ref(),
-        new_subscriber.name.as_ref(),
-        Utc::now()
+        email.plain,
+        email.encrypted,
+        pii_cipher.email_hash(new_subscriber.email.as_ref()),
+        name.plain,
+        name.encrypted,
+        subscribed_at,
+        source.referer,
+        source.utm_source,
+        source.country
     )
     .execute(transaction)
     .await?;
@@ -168,29 +467,89 @@ pub async fn insert_subscriber(
     Ok(subscriber_id)
 }
 
-#[tracing::instrument(name = "Check if the email already exists in the database")]
-pub async fn email_exists(
+struct ExistingSubscriber {
+    id: Uuid,
+    status: String,
+}
+
+// Encrypted subscribers are found by their email hash
+#[tracing::instrument(name = "Look up an existing subscriber", skip(transaction, pii_cipher))]
+async fn find_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     email: &str,
-) -> Result<(), sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT EXISTS(SELECT 1 FROM subscriptions WHERE email = $1)"#,
+    pii_cipher: &PiiCipher,
+) -> Result<Option<ExistingSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingSubscriber,
+        r#"SELECT id, status FROM subscriptions WHERE email = $1 OR email_hash = $2"#,
         email,
+        pii_cipher.email_hash(email),
     )
-    .fetch_one(transaction)
-    .await?;
-
-    if result.exists.unwrap_or(false) {
-        return Err(sqlx::Error::RowNotFound);
-    } else {
-        Ok(())
-    }
+    .fetch_optional(transaction)
+    .await
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
         .take(25)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{clean_source, greeting_name, local_path, parse_ip};
+
+    #[test]
+    fn a_blank_name_is_greeted_as_there() {
+        assert_eq!(greeting_name("   "), "there");
+        assert_eq!(greeting_name(""), "there");
+    }
+
+    #[test]
+    fn a_name_is_used_as_is() {
+        assert_eq!(greeting_name(" Ursula "), "Ursula");
+    }
+
+    #[test]
+    fn a_signup_source_is_trimmed_and_capped() {
+        assert_eq!(clean_source("  podcast ".into()), Some("podcast".into()));
+        assert_eq!(clean_source("   ".into()), None);
+        assert_eq!(clean_source("a".repeat(600)).unwrap().len(), 512);
+    }
+
+    #[test]
+    fn paths_on_this_site_are_redirected_to() {
+        assert_eq!(local_path("/"), Some("/".into()));
+        assert_eq!(local_path("/?from=footer"), Some("/?from=footer".into()));
+        assert_eq!(
+            local_path("/archive/hello-world"),
+            Some("/archive/hello-world".into())
+        );
+    }
+
+    #[test]
+    fn anything_that_could_leave_the_site_is_refused() {
+        for target in [
+            "//evil.com",
+            "/\\evil.com",
+            "/\\/evil.com",
+            "/\t/evil.com",
+            "/.//evil.com",
+            "https://evil.com",
+            "javascript:alert(1)",
+            "evil.com",
+        ] {
+            assert_eq!(local_path(target), None, "{target:?} was allowed");
+        }
+    }
+
+    #[test]
+    fn client_ips_are_read_with_or_without_a_port() {
+        assert_eq!(parse_ip("203.0.113.7"), "203.0.113.7".parse().ok());
+        assert_eq!(parse_ip("203.0.113.7:5432"), "203.0.113.7".parse().ok());
+        assert_eq!(parse_ip("[2001:db8::1]:443"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_ip("unknown"), None);
+    }
+}
@@ -1,10 +1,24 @@
-use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use actix_web::{
+    HttpResponse, ResponseError,
+    http::{StatusCode, header::ContentType},
+    web,
+};
 use anyhow::Context;
-use sqlx::PgPool;
+use htmlescape::encode_minimal;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+use crate::{
+    configuration::{SiteSettings, SubscriptionSettings, WebhookSettings},
+    domain::PiiCipher,
+    utils::see_other,
+    webhooks::{WebhookEvent, enqueue_callback_event, enqueue_webhook_event},
+};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
+    // From the link in the confirmation email
     subscription_token: String,
 }
 
@@ -12,6 +26,9 @@ pub struct Parameters {
 pub enum ConfirmError {
     #[error("Invalid token")]
     InvalidToken,
+    // Not a failure for the subscriber, they are told they're already confirmed
+    #[error("The token has already been used")]
+    AlreadyUsed,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -20,38 +37,153 @@ impl ResponseError for ConfirmError {
     fn status_code(&self) -> StatusCode {
         match self {
             ConfirmError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ConfirmError::AlreadyUsed => StatusCode::OK,
             ConfirmError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ConfirmError::AlreadyUsed => already_confirmed_page(),
+            _ => HttpResponse::build(self.status_code())
+                .content_type(ContentType::plaintext())
+                .body(self.to_string()),
+        }
+    }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscription is confirmed, or the link was already used", content_type = "text/html"),
+        (status = 303, description = "The subscription is confirmed, on to the configured thank you page"),
+        (status = 400, description = "The token is missing"),
+        (status = 401, description = "The token doesn't belong to anyone"),
+        (status = 500, description = "Something went wrong on our side"),
+    )
+)]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(
+        parameters,
+        pool,
+        webhook_settings,
+        subscription_settings,
+        site,
+        pii_cipher
+    )
+)]
 pub async fn confirm(
     parameters: web::Query<Parameters>,
     pool: web::Data<PgPool>,
+    webhook_settings: web::Data<WebhookSettings>,
+    subscription_settings: web::Data<SubscriptionSettings>,
+    site: web::Data<SiteSettings>,
+    pii_cipher: web::Data<PiiCipher>,
 ) -> Result<HttpResponse, ConfirmError> {
     // web::Query<Parameter> tells actix-web to only call the handler if the extraction is a success
     let id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
         .await
         .context("Failed to get subscriber ID from token.")?;
     match id {
+        None if is_used_token(&pool, &parameters.subscription_token)
+            .await
+            .context("Failed to check whether the token was used.")? =>
+        {
+            Err(ConfirmError::AlreadyUsed)
+        }
         None => Err(ConfirmError::InvalidToken),
         Some(subscriber_id) => {
-            confirm_subscriber(&pool, subscriber_id)
+            let mut transaction = pool
+                .begin()
+                .await
+                .context("Failed to acquire a Postgres connection from the pool.")?;
+            // Another click may have got here first
+            if !mark_token_used(&mut transaction, &parameters.subscription_token)
+                .await
+                .context("Failed to mark the token as used.")?
+            {
+                return Err(ConfirmError::AlreadyUsed);
+            }
+            let newly_confirmed = confirm_subscriber(&mut transaction, subscriber_id, &pii_cipher)
                 .await
                 .context("Failed to update user status from 'pending' to 'confirmed'.")?;
-            Ok(HttpResponse::Ok().finish())
+            // The link from an earlier confirmation email shouldn't announce the subscriber twice
+            if let Some((email, name)) = newly_confirmed {
+                let event = WebhookEvent::SubscriberConfirmed {
+                    subscriber_id,
+                    email,
+                    name,
+                };
+                enqueue_webhook_event(&mut transaction, &event)
+                    .await
+                    .context("Failed to enqueue the subscriber.confirmed webhook event.")?;
+                if webhook_settings.confirmation_callback.is_some() {
+                    enqueue_callback_event(&mut transaction, &event)
+                        .await
+                        .context("Failed to enqueue the confirmation callback.")?;
+                }
+            }
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+            match &subscription_settings.confirmation_redirect_url {
+                Some(url) => Ok(see_other(url.as_str())),
+                None => Ok(landing_page(&site)),
+            }
         }
     }
 }
 
+fn landing_page(site: &SiteSettings) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Subscription confirmed</title>
+            </head>
+            <body>
+                <h1>Thanks for confirming!</h1>
+                <p>You're now subscribed to {}. The next issue will land in your inbox.</p>
+                <p><a href="/">Home</a></p>
+            </body>
+        </html>"#,
+            encode_minimal(&site.title),
+        ))
+}
+
+fn already_confirmed_page() -> HttpResponse {
+    HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Already confirmed</title>
+            </head>
+            <body>
+                <h1>You're already confirmed</h1>
+                <p>This link has been used, there's nothing more to do.</p>
+                <p><a href="/">Home</a></p>
+            </body>
+        </html>"#,
+    )
+}
+
+// Only tokens that haven't confirmed anyone yet
 #[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
 pub async fn get_subscriber_id_from_token(
     pool: &PgPool,
     subscription_token: &str,
 ) -> Result<Option<Uuid>, sqlx::Error> {
     let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
+        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1 AND used = FALSE"#,
         subscription_token,
     )
     .fetch_optional(pool)
@@ -59,13 +191,59 @@ pub async fn get_subscriber_id_from_token(
     Ok(result.map(|record| record.subscriber_id))
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+#[tracing::instrument(
+    name = "Check whether a token was used",
+    skip(subscription_token, pool)
+)]
+async fn is_used_token(pool: &PgPool, subscription_token: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT 1 AS "exists" FROM subscription_tokens WHERE subscription_token = $1 AND used = TRUE"#,
+        subscription_token,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result.is_some())
+}
+
+// False if the token was used in the meantime
+#[tracing::instrument(name = "Mark a token as used", skip(subscription_token, transaction))]
+async fn mark_token_used(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscription_tokens SET used = TRUE WHERE subscription_token = $1 AND used = FALSE"#,
+        subscription_token,
+    )
+    .execute(transaction)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// Returns the subscriber's email and name if they were still pending, None if already confirmed
+#[tracing::instrument(
+    name = "Mark subscriber as confirmed",
+    skip(subscriber_id, transaction, pii_cipher)
+)]
+pub async fn confirm_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    pii_cipher: &PiiCipher,
+) -> Result<Option<(String, String)>, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status <> 'confirmed'
+        RETURNING email, email_encrypted, name, name_encrypted
+        "#,
         subscriber_id,
     )
-    .execute(pool)
+    .fetch_optional(transaction)
     .await?;
-    Ok(())
+    let Some(record) = result else {
+        return Ok(None);
+    };
+    let email = pii_cipher.reveal(record.email, record.email_encrypted)?;
+    let name = pii_cipher.reveal(record.name, record.name_encrypted)?;
+    Ok(Some((email, name)))
 }
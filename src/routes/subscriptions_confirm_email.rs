@@ -0,0 +1,266 @@
+use actix_web::{
+    HttpResponse, ResponseError,
+    http::{StatusCode, header::ContentType},
+    web,
+};
+use anyhow::Context;
+use chrono::Duration;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+
+use crate::{
+    audit::record_audit_event,
+    clock::Clock,
+    configuration::SubscriptionSettings,
+    domain::{PiiCipher, SubscriberEmail},
+    email_client::EmailClientPool,
+};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EmailChangeParameters {
+    email_change_token: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfirmEmailChangeError {
+    #[error("Invalid token")]
+    InvalidToken,
+    #[error("The new address is already subscribed")]
+    AddressTaken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for ConfirmEmailChangeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmEmailChangeError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ConfirmEmailChangeError::AddressTaken => StatusCode::CONFLICT,
+            ConfirmEmailChangeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Whoever followed the link is a subscriber, not an API client
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ConfirmEmailChangeError::AddressTaken => page(
+                self.status_code(),
+                "Address already subscribed",
+                "Someone has subscribed with your new address since you asked for the change, \
+                so your subscription stays at your current address.",
+            ),
+            _ => HttpResponse::build(self.status_code())
+                .content_type(ContentType::plaintext())
+                .body(self.to_string()),
+        }
+    }
+}
+
+// Swaps in the pending address. A token is deleted as it is used so a link only ever works once,
+// an expired one takes its pending address with it.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm-email",
+    tag = "subscriptions",
+    params(EmailChangeParameters),
+    responses(
+        (status = 200, description = "The subscription has moved to the new address", content_type = "text/html"),
+        (status = 400, description = "The token is missing"),
+        (status = 401, description = "The token is unknown, used or expired"),
+        (status = 409, description = "The new address has been subscribed in the meantime", content_type = "text/html"),
+        (status = 500, description = "Something went wrong on our side"),
+    )
+)]
+#[tracing::instrument(
+    name = "Confirm a subscriber's new address",
+    skip(parameters, pool, email_clients, settings, clock, pii_cipher)
+)]
+pub async fn confirm_email_change(
+    parameters: web::Query<EmailChangeParameters>,
+    pool: web::Data<PgPool>,
+    email_clients: web::Data<EmailClientPool>,
+    settings: web::Data<SubscriptionSettings>,
+    clock: web::Data<dyn Clock>,
+    pii_cipher: web::Data<PiiCipher>,
+) -> Result<HttpResponse, ConfirmEmailChangeError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let token = sqlx::query!(
+        r#"
+        DELETE FROM email_change_tokens
+        WHERE email_change_token = $1
+        RETURNING subscriber_id, requested_by, created_at
+        "#,
+        parameters.email_change_token
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to use up the email change token.")?;
+    let Some(token) = token else {
+        return Err(ConfirmEmailChangeError::InvalidToken);
+    };
+    let ttl = Duration::hours(settings.email_change_token_ttl_hours.into());
+    if token.created_at + ttl < clock.now() {
+        sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET pending_email = NULL, pending_email_encrypted = NULL
+            WHERE id = $1
+            "#,
+            token.subscriber_id
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to drop an expired pending address.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to discard an expired email change token.")?;
+        return Err(ConfirmEmailChangeError::InvalidToken);
+    }
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT email, email_encrypted, pending_email, pending_email_encrypted
+        FROM subscriptions
+        WHERE id = $1 AND (pending_email IS NOT NULL OR pending_email_encrypted IS NOT NULL)
+        FOR UPDATE
+        "#,
+        token.subscriber_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to look up the subscriber's pending address.")?
+    .ok_or(ConfirmEmailChangeError::InvalidToken)?;
+    let previous_email = pii_cipher
+        .reveal(subscriber.email, subscriber.email_encrypted)
+        .context("Failed to read the subscriber's current address.")?;
+    let pending_email = pii_cipher
+        .reveal(subscriber.pending_email, subscriber.pending_email_encrypted)
+        .context("Failed to read the subscriber's new address.")?;
+    // Someone may have subscribed with the address since the change was asked for
+    let taken = sqlx::query!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM subscriptions WHERE email = $1 OR email_hash = $2) AS "taken!"
+        "#,
+        pending_email,
+        pii_cipher.email_hash(&pending_email)
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to check whether the new address is in use.")?
+    .taken;
+    if taken {
+        return Err(ConfirmEmailChangeError::AddressTaken);
+    }
+    let new_email = pii_cipher.store(&pending_email);
+    // Still possible if the address is subscribed between the check and here
+    let swapped = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            email = $2,
+            email_encrypted = $3,
+            email_hash = $4,
+            pending_email = NULL,
+            pending_email_encrypted = NULL
+        WHERE id = $1
+        "#,
+        token.subscriber_id,
+        new_email.plain,
+        new_email.encrypted,
+        pii_cipher.email_hash(&pending_email)
+    )
+    .execute(&mut transaction)
+    .await;
+    match swapped {
+        Err(e) if is_unique_violation(&e) => return Err(ConfirmEmailChangeError::AddressTaken),
+        swapped => swapped.context("Failed to swap in the new address.")?,
+    };
+    record_audit_event(
+        &mut transaction,
+        token.requested_by,
+        "subscriber.email_changed",
+        &token.subscriber_id.to_string(),
+        None,
+    )
+    .await
+    .context("Failed to audit the email change.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the email change.")?;
+    // The change has gone through by now, a notice that doesn't go out is only worth a log line
+    if let Err(e) = notify_previous_address(&email_clients, &previous_email, &pending_email).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to notify the previous address of an email change"
+        );
+    }
+    Ok(page(
+        StatusCode::OK,
+        "Address updated",
+        &format!(
+            "Your subscription has moved to {}. Future issues will be sent there.",
+            encode_minimal(&pending_email)
+        ),
+    ))
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "23505")
+}
+
+// `message` is HTML, anything from the subscriber must already be escaped
+fn page(status: StatusCode, title: &str, message: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>{title}</title>
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <p>{message}</p>
+                <p><a href="/">Home</a></p>
+            </body>
+        </html>"#
+        ))
+}
+
+#[tracing::instrument(skip(email_clients))]
+async fn notify_previous_address(
+    email_clients: &EmailClientPool,
+    previous_email: &str,
+    new_email: &str,
+) -> Result<(), anyhow::Error> {
+    let recipient =
+        SubscriberEmail::parse(previous_email.to_owned()).map_err(anyhow::Error::msg)?;
+    let html_body = &format!(
+        "Your newsletter subscription has moved to {}.<br />\
+            If you didn't ask for this, please reply to this email.",
+        htmlescape::encode_minimal(new_email)
+    );
+    let plain_body = &format!(
+        "Your newsletter subscription has moved to {new_email}.\n\
+            If you didn't ask for this, please reply to this email."
+    );
+    email_clients
+        .send(
+            None,
+            &recipient,
+            "Your subscription has moved",
+            html_body,
+            plain_body,
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use actix_web::{
+    HttpRequest, HttpResponse, ResponseError,
+    http::{StatusCode, header},
+    web,
+};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{PiiCipher, SubscriberEmail},
+    email_client::EmailClientPool,
+    idempotency::{
+        IdempotencyKey, IdempotencyKeyError, IdempotencyKeyMaxLength, IdempotencyWait, NextAction,
+        save_response, try_processing,
+    },
+    routes::{generate_subscription_token, send_confirmation_email, store_token},
+    startup::ApplicationBaseUrl,
+};
+
+const IDEMPOTENCY_KEY_HEADER: &str = "X-Idempotency-Key";
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ResendConfirmationFormData {
+    #[schema(example = "ursula_le_guin@gmail.com")]
+    email: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResendConfirmationError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for ResendConfirmationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ResendConfirmationError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ResendConfirmationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// Sends a pending subscriber a fresh confirmation link. Unknown and already confirmed addresses
+// get the same response, so the list can't be probed.
+//
+// With an `X-Idempotency-Key` a double submitted request gets the first one's response instead
+// of a second email. The key is claimed on behalf of the subscriber.
+#[utoipa::path(
+    post,
+    path = "/subscriptions/resend-confirmation",
+    tag = "subscriptions",
+    request_body(content = ResendConfirmationFormData, content_type = "application/x-www-form-urlencoded"),
+    params(
+        ("X-Idempotency-Key" = Option<String>, Header, description = "Repeats with the same key are only sent once"),
+    ),
+    responses(
+        (status = 200, description = "A new confirmation email is on its way if the address is pending"),
+        (status = 400, description = "The email or idempotency key is invalid"),
+        (status = 503, description = "A request with the same key is still being handled"),
+        (status = 500, description = "Something went wrong on our side"),
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip_all,
+    fields(subscriber_email = %form.email)
+)]
+pub async fn resend_confirmation(
+    request: HttpRequest,
+    form: web::Form<ResendConfirmationFormData>,
+    pool: web::Data<PgPool>,
+    email_clients: web::Data<EmailClientPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    idempotency_key_max_length: web::Data<IdempotencyKeyMaxLength>,
+    idempotency_wait: web::Data<IdempotencyWait>,
+    pii_cipher: web::Data<PiiCipher>,
+) -> Result<HttpResponse, ResendConfirmationError> {
+    let email = SubscriberEmail::parse(form.into_inner().email)
+        .map_err(ResendConfirmationError::ValidationError)?;
+    let idempotency_key = match request.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => {
+            let key = value.to_str().map_err(|_| {
+                ResendConfirmationError::ValidationError(
+                    IdempotencyKeyError::NotPrintable.to_string(),
+                )
+            })?;
+            let key = IdempotencyKey::parse(key.to_owned(), **idempotency_key_max_length)
+                .map_err(|e| ResendConfirmationError::ValidationError(e.to_string()))?;
+            Some(key)
+        }
+        None => None,
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // Locked so a duplicate waits here until the first request has claimed its key
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, name, name_encrypted
+        FROM subscriptions
+        WHERE (email = $1 OR email_hash = $2) AND status = 'pending_confirmation'
+        FOR UPDATE
+        "#,
+        email.as_ref(),
+        pii_cipher.email_hash(email.as_ref())
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to look up the pending subscriber.")?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    if let Some(idempotency_key) = &idempotency_key {
+        match try_processing(
+            &mut transaction,
+            idempotency_key,
+            subscriber.id,
+            **idempotency_wait,
+        )
+        .await?
+        {
+            NextAction::StartProcessing => {}
+            NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+            NextAction::StillProcessing => {
+                return Ok(HttpResponse::ServiceUnavailable()
+                    .insert_header((header::RETRY_AFTER, idempotency_wait.0.as_secs().max(1)))
+                    .body("The confirmation email is still being sent, try again shortly."));
+            }
+        }
+    }
+    let name = pii_cipher
+        .reveal(subscriber.name, subscriber.name_encrypted)
+        .context("Failed to read the subscriber's name.")?;
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, subscriber.id, &subscription_token)
+        .await
+        .context("Failed to store the new confirmation token.")?;
+    // Sent before committing, so a duplicate holding the same key is still waiting on the claim
+    send_confirmation_email(
+        &email_clients,
+        &email,
+        &name,
+        &base_url,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to send a confirmation email.")?;
+    let mut response = HttpResponse::Ok().finish();
+    if let Some(idempotency_key) = &idempotency_key {
+        response =
+            save_response(&mut transaction, idempotency_key, subscriber.id, response).await?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to resend a confirmation email.")?;
+    Ok(response)
+}
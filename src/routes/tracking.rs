@@ -0,0 +1,439 @@
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::header::{CacheControl, CacheDirective, LOCATION},
+    web,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    clock::Clock,
+    configuration::UtmSettings,
+    startup::ApplicationBaseUrl,
+    utils::{e404, e500},
+};
+
+// The smallest transparent 1x1 GIF
+const TRACKING_PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+// Always answers with the pixel, a broken image in the subscriber's inbox helps nobody. Unknown
+// deliveries are ignored and failures to record are only logged.
+#[tracing::instrument(name = "Track an issue open", skip(request, pool, clock))]
+pub async fn track_open(
+    delivery_id: web::Path<Uuid>,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+) -> HttpResponse {
+    let ip_address = request
+        .connection_info()
+        .realip_remote_addr()
+        .map(str::to_owned);
+    let user_agent = request
+        .headers()
+        .get("User-Agent")
+        .and_then(|value| value.to_str().ok());
+    if let Err(e) = record_open(
+        &pool,
+        *delivery_id,
+        ip_address.as_deref(),
+        user_agent,
+        clock.now(),
+    )
+    .await
+    {
+        tracing::error!(error.message = %e, "Failed to record an issue open.");
+    }
+
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(TRACKING_PIXEL)
+}
+
+// Mail clients often fetch images more than once while showing the same message, so a repeat from
+// the same address and client isn't counted again
+#[tracing::instrument(skip(pool))]
+async fn record_open(
+    pool: &PgPool,
+    delivery_id: Uuid,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_opens (
+            id,
+            delivery_id,
+            newsletter_issue_id,
+            subscriber_id,
+            opened_at,
+            ip_address,
+            user_agent
+        )
+        SELECT $1, d.delivery_id, d.newsletter_issue_id, s.id, $5, $3, $4
+        FROM newsletter_deliveries d
+        JOIN subscriptions s ON s.id = d.subscriber_id
+        WHERE
+            d.delivery_id = $2 AND
+            NOT EXISTS (
+                SELECT 1
+                FROM newsletter_opens o
+                WHERE
+                    o.delivery_id = $2 AND
+                    o.ip_address IS NOT DISTINCT FROM $3 AND
+                    o.user_agent IS NOT DISTINCT FROM $4
+            )
+        "#,
+        Uuid::new_v4(),
+        delivery_id,
+        ip_address,
+        user_agent,
+        now
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET last_opened_at = $2
+        FROM newsletter_deliveries d
+        WHERE d.delivery_id = $1 AND s.id = d.subscriber_id
+        "#,
+        delivery_id,
+        now
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub fn link_hash(original_url: &str) -> String {
+    hex::encode(Sha256::digest(original_url.as_bytes()))
+}
+
+// Points every http(s) `<a href>` in `html` at the click tracker for this delivery. Returns the
+// rewritten HTML and the original URLs, which need storing before the email goes out.
+pub fn rewrite_links(
+    html: &str,
+    base_url: &ApplicationBaseUrl,
+    delivery_id: Uuid,
+) -> (String, Vec<String>) {
+    let mut links = Vec::new();
+    let rewritten = map_links(html, |original_url| {
+        if !original_url.starts_with("http://") && !original_url.starts_with("https://") {
+            return None;
+        }
+        let tracked_url = base_url.join_path(&format!(
+            "track/click/{delivery_id}/{}",
+            link_hash(original_url)
+        ));
+        links.push(original_url.to_owned());
+        Some(tracked_url.into())
+    });
+    (rewritten, links)
+}
+
+// Adds the campaign parameters to links leaving the site. Links to our own pages, links that
+// already carry a `utm_source` and anything that isn't http(s) are left as they are.
+pub fn add_utm_parameters(html: &str, utm: &UtmSettings, base_url: &ApplicationBaseUrl) -> String {
+    let own_host = base_url.url.host_str();
+    map_links(html, |original_url| {
+        let mut url = Url::parse(original_url).ok()?;
+        if !matches!(url.scheme(), "http" | "https")
+            || url.host_str() == own_host
+            || url.query_pairs().any(|(key, _)| key == "utm_source")
+        {
+            return None;
+        }
+        url.query_pairs_mut()
+            .append_pair("utm_source", &utm.utm_source)
+            .append_pair("utm_medium", &utm.utm_medium)
+            .append_pair("utm_campaign", &utm.utm_campaign);
+        Some(url.into())
+    })
+}
+
+// Every http(s) `<a href>` in `html`, unescaped and in the order they appear
+pub fn extract_links(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    map_links(html, |url| {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            links.push(url.to_owned());
+        }
+        None
+    });
+    links
+}
+
+// Calls `replace` with the (unescaped) href of every `<a>` tag, swapping in whatever it returns
+fn map_links(html: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut rewritten = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = find_anchor_tag(rest) {
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let tag = &rest[start..end];
+        let replacement = find_href(tag).and_then(|(value_start, value_end)| {
+            let href = &tag[value_start..value_end];
+            let original_url = htmlescape::decode_html(href).unwrap_or_else(|_| href.into());
+            replace(&original_url).map(|url| (value_start, value_end, url))
+        });
+        match replacement {
+            Some((value_start, value_end, url)) => {
+                rewritten.push_str(&rest[..start + value_start]);
+                rewritten.push_str(&htmlescape::encode_minimal(&url));
+                rewritten.push_str(&tag[value_end..]);
+            }
+            None => rewritten.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+    rewritten
+}
+
+fn find_anchor_tag(html: &str) -> Option<usize> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lowercase[offset..].find("<a").map(|start| offset + start) {
+        // `<abbr>` and friends also start with `<a`
+        if lowercase[start + 2..].starts_with(|c: char| c.is_ascii_whitespace()) {
+            return Some(start);
+        }
+        offset = start + 2;
+    }
+    None
+}
+
+// The byte range of the href attribute's value within the tag, without its quotes
+fn find_href(tag: &str) -> Option<(usize, usize)> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lowercase[offset..].find("href").map(|start| offset + start) {
+        offset = start + 4;
+        let preceded_by_space = lowercase[..start].ends_with(|c: char| c.is_ascii_whitespace());
+        let after = lowercase[offset..].trim_start();
+        if !preceded_by_space || !after.starts_with('=') {
+            continue;
+        }
+        let value = after[1..].trim_start();
+        let value_start = tag.len() - value.len();
+        return match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..]
+                .find(quote)
+                .map(|len| (value_start + 1, value_start + 1 + len)),
+            Some(_) => {
+                let len = value
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(value.len());
+                Some((value_start, value_start + len))
+            }
+            None => None,
+        };
+    }
+    None
+}
+
+// Pairs every link with the issue it was sent in, the tracker only counts clicks on those
+#[tracing::instrument(skip(pool, links))]
+pub async fn store_links(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    links: &[String],
+) -> Result<(), sqlx::Error> {
+    let hashes: Vec<String> = links.iter().map(|url| link_hash(url)).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_links (link_hash, original_url)
+        SELECT * FROM UNNEST($1::text[], $2::text[])
+        ON CONFLICT DO NOTHING
+        "#,
+        &hashes,
+        links
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_links (newsletter_issue_id, link_hash)
+        SELECT $1, link_hash FROM UNNEST($2::text[]) AS h(link_hash)
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        &hashes
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Only links that were stored while rewriting an issue can be followed, so the tracker can't be
+// used as an open redirect. A click is only counted when the link was sent in the delivery's
+// issue, so another issue's link can't skew its report. Failing to record a click still sends
+// the subscriber on their way.
+#[tracing::instrument(name = "Track a link click", skip(pool))]
+pub async fn track_click(
+    path: web::Path<(Uuid, String)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (delivery_id, link_hash) = path.into_inner();
+    let original_url = get_original_url(&pool, &link_hash)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("No such link"))?;
+    if let Err(e) = record_click(&pool, delivery_id, &link_hash, &original_url).await {
+        tracing::error!(error.message = %e, "Failed to record a link click.");
+    }
+
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, original_url))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_original_url(pool: &PgPool, link_hash: &str) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT original_url FROM newsletter_links WHERE link_hash = $1"#,
+        link_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the link.")?;
+    Ok(row.map(|r| r.original_url))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn record_click(
+    pool: &PgPool,
+    delivery_id: Uuid,
+    link_hash: &str,
+    original_url: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_clicks (
+            id,
+            newsletter_issue_id,
+            subscriber_id,
+            original_url,
+            clicked_at
+        )
+        SELECT $1, d.newsletter_issue_id, s.id, $3, now()
+        FROM newsletter_deliveries d
+        JOIN newsletter_issue_links l
+            ON l.newsletter_issue_id = d.newsletter_issue_id AND l.link_hash = $4
+        JOIN subscriptions s ON s.id = d.subscriber_id
+        WHERE d.delivery_id = $2
+        "#,
+        Uuid::new_v4(),
+        delivery_id,
+        original_url,
+        link_hash
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{add_utm_parameters, extract_links, link_hash, rewrite_links};
+    use crate::configuration::UtmSettings;
+    use crate::startup::ApplicationBaseUrl;
+
+    fn base_url(url: &str) -> ApplicationBaseUrl {
+        ApplicationBaseUrl::parse(url).unwrap()
+    }
+
+    fn utm() -> UtmSettings {
+        UtmSettings {
+            utm_source: "newsletter".into(),
+            utm_medium: "email".into(),
+            utm_campaign: "spring sale".into(),
+        }
+    }
+
+    #[test]
+    fn http_links_are_pointed_at_the_click_tracker() {
+        let delivery_id = Uuid::new_v4();
+        let html = r#"<p>Read <a class="x" href="https://example.com/a?b=1&amp;c=2">this</a> and <A HREF='http://example.com'>that</A></p>"#;
+
+        let (rewritten, links) = rewrite_links(html, &base_url("http://localhost"), delivery_id);
+
+        assert_eq!(
+            links,
+            ["https://example.com/a?b=1&c=2", "http://example.com"]
+        );
+        assert_eq!(
+            rewritten,
+            format!(
+                r#"<p>Read <a class="x" href="http://localhost/track/click/{delivery_id}/{}">this</a> and <A HREF='http://localhost/track/click/{delivery_id}/{}'>that</A></p>"#,
+                link_hash(&links[0]),
+                link_hash(&links[1])
+            )
+        );
+    }
+
+    #[test]
+    fn other_links_and_tags_are_left_alone() {
+        let html = r##"<abbr href="https://example.com">a</abbr><a href="mailto:me@example.com">mail</a><a href="#top">top</a><a name="x">anchor</a><a data-href="https://example.com">data</a>"##;
+
+        let (rewritten, links) = rewrite_links(html, &base_url("http://localhost"), Uuid::new_v4());
+
+        assert!(links.is_empty());
+        assert_eq!(rewritten, html);
+    }
+
+    #[test]
+    fn only_http_links_are_extracted() {
+        let html = r#"<a href="https://example.com/a?b=1&amp;c=2">a</a><a href="mailto:me@example.com">mail</a><a href='http://example.com'>b</a>"#;
+
+        assert_eq!(
+            extract_links(html),
+            ["https://example.com/a?b=1&c=2", "http://example.com"]
+        );
+    }
+
+    #[test]
+    fn link_hashes_are_sha256_hex() {
+        assert_eq!(
+            link_hash("https://example.com"),
+            "100680ad546ce6a577f42f52df33b4cfdca756859e664b8d7de329b150d09ce9"
+        );
+    }
+
+    #[test]
+    fn utm_parameters_are_added_to_external_links() {
+        let html = r#"<a href="https://example.com/post?id=1&amp;x=2">post</a>"#;
+
+        let tagged = add_utm_parameters(html, &utm(), &base_url("http://localhost:8000"));
+
+        assert_eq!(
+            tagged,
+            r#"<a href="https://example.com/post?id=1&amp;x=2&amp;utm_source=newsletter&amp;utm_medium=email&amp;utm_campaign=spring+sale">post</a>"#
+        );
+    }
+
+    #[test]
+    fn utm_parameters_skip_our_own_mailto_and_tagged_links() {
+        let html = r#"<a href="http://localhost:8000/subscriptions/unsubscribe">u</a><a href="mailto:me@example.com">m</a><a href="https://example.com/?utm_source=blog">b</a>"#;
+
+        let tagged = add_utm_parameters(html, &utm(), &base_url("http://localhost:8000"));
+
+        assert_eq!(tagged, html);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Days, Utc};
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions, aio::ConnectionManager};
+
+use crate::configuration::WorkerSettings;
+
+// Keeps issue deliveries under the mail provider's overall limits, on top of the per domain
+// throttle. The per second limit only holds within a process, the daily count is kept in Redis so
+// every worker shares it. It is scoped to the database like the delivery pause.
+pub struct SendingRate {
+    connection: ConnectionManager,
+    key_prefix: String,
+    per_second: Option<f64>,
+    per_day: Option<u32>,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    // Goes negative while sends are waiting for their turn
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl SendingRate {
+    pub async fn new(
+        redis_uri: &str,
+        database_name: &str,
+        settings: &WorkerSettings,
+    ) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri).context("Invalid Redis URI.")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis.")?;
+        let per_second = settings.max_emails_per_second;
+        Ok(Self {
+            connection,
+            key_prefix: format!("emails_sent:{database_name}"),
+            per_second,
+            per_day: settings.max_emails_per_day,
+            bucket: Mutex::new(Bucket {
+                tokens: per_second.map_or(0.0, burst),
+                refilled_at: Instant::now(),
+            }),
+        })
+    }
+
+    pub fn per_second(&self) -> Option<f64> {
+        self.per_second
+    }
+
+    pub fn per_day(&self) -> Option<u32> {
+        self.per_day
+    }
+
+    // Waits for a token, a full bucket lets up to a second's worth of sends through at once
+    pub async fn acquire(&self) {
+        let Some(per_second) = self.per_second else {
+            return;
+        };
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let refilled_at = Instant::now();
+            let elapsed = refilled_at.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * per_second).min(burst(per_second));
+            bucket.refilled_at = refilled_at;
+            bucket.tokens -= 1.0;
+            (bucket.tokens < 0.0).then(|| Duration::from_secs_f64(-bucket.tokens / per_second))
+        };
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    #[tracing::instrument(name = "Count the emails sent today", skip(self))]
+    pub async fn sent_today(&self, now: DateTime<Utc>) -> Result<u64, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let sent: Option<u64> = connection
+            .get(self.key(now))
+            .await
+            .context("Failed to read today's send count from Redis.")?;
+        Ok(sent.unwrap_or(0))
+    }
+
+    // A quick look before claiming a task, `claim_send` has the final say
+    pub async fn daily_limit_reached(&self, now: DateTime<Utc>) -> Result<bool, anyhow::Error> {
+        match self.per_day {
+            Some(per_day) => Ok(self.sent_today(now).await? >= per_day as u64),
+            None => Ok(false),
+        }
+    }
+
+    // Counts an email about to be sent, `false` if it would go over the daily limit. The count
+    // going up is the check, so workers sending side by side can't both take the last one.
+    #[tracing::instrument(name = "Claim a send from the daily limit", skip(self))]
+    pub async fn claim_send(&self, now: DateTime<Utc>) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let key = self.key(now);
+        // Each day has its own key, it only needs to outlive the day. Creating it with its expiry
+        // in the same transaction means it can never be left without one.
+        let (sent,): (u64,) = redis::pipe()
+            .atomic()
+            .set_options(
+                &key,
+                0,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(seconds_until_midnight(now))),
+            )
+            .ignore()
+            .incr(&key, 1)
+            .query_async(&mut connection)
+            .await
+            .context("Failed to count a sent email in Redis.")?;
+        if self.per_day.is_some_and(|per_day| sent > per_day as u64) {
+            self.release_send(now).await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // Gives back a claimed send that didn't go out
+    #[tracing::instrument(name = "Release a send to the daily limit", skip(self))]
+    pub async fn release_send(&self, now: DateTime<Utc>) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .decr::<_, _, ()>(self.key(now), 1)
+            .await
+            .context("Failed to give back a sent email in Redis.")?;
+        Ok(())
+    }
+
+    fn key(&self, now: DateTime<Utc>) -> String {
+        format!("{}:{}", self.key_prefix, now.format("%Y-%m-%d"))
+    }
+}
+
+fn burst(per_second: f64) -> f64 {
+    per_second.max(1.0)
+}
+
+fn seconds_until_midnight(now: DateTime<Utc>) -> u64 {
+    let midnight = now.date_naive().checked_add_days(Days::new(1)).unwrap();
+    (midnight.and_hms_opt(0, 0, 0).unwrap().and_utc() - now)
+        .num_seconds()
+        .max(1) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::seconds_until_midnight;
+
+    #[test]
+    fn the_daily_count_expires_at_midnight_utc() {
+        let now = Utc.with_ymd_and_hms(2025, 7, 10, 23, 59, 0).unwrap();
+
+        assert_eq!(seconds_until_midnight(now), 60);
+    }
+}
@@ -1,13 +1,24 @@
 use std::future::{Ready, ready};
 
-use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
-use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError, SessionStatus};
+use actix_web::{FromRequest, HttpRequest, dev::Payload, dev::ResponseHead};
+use actix_web_flash_messages::{
+    FlashMessage,
+    storage::{CookieMessageStore, FlashMessageStore, LoadError, StoreError},
+};
+use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
+// Every key lives here as a constant so handlers never touch the session with raw strings
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const PENDING_2FA_KEY: &'static str = "pending_2fa";
+    const LOGIN_REDIRECT_KEY: &'static str = "login_redirect";
+    const FLASH_COUNT_KEY: &'static str = "flash_count";
+    const AUTHENTICATED_AT_KEY: &'static str = "authenticated_at";
+    const FLASH_MESSAGES_KEY: &'static str = "flash_messages";
 
     pub fn renew(&self) {
         self.0.renew()
@@ -21,6 +32,78 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    // When the user last proved who they are with their password
+    pub fn set_authenticated_at(&self, at: DateTime<Utc>) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::AUTHENTICATED_AT_KEY, at)
+    }
+
+    pub fn get_authenticated_at(&self) -> Result<Option<DateTime<Utc>>, SessionGetError> {
+        self.0.get(Self::AUTHENTICATED_AT_KEY)
+    }
+
+    // A session from before the timestamp was recorded never counts as recent
+    pub fn authenticated_within(
+        &self,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<bool, SessionGetError> {
+        Ok(self
+            .get_authenticated_at()?
+            .is_some_and(|at| now - at < window))
+    }
+
+    // A missing flag means the second factor isn't pending
+    pub fn get_pending_2fa(&self) -> Result<bool, SessionGetError> {
+        Ok(self.0.get(Self::PENDING_2FA_KEY)?.unwrap_or(false))
+    }
+
+    pub fn set_pending_2fa(&self, pending: bool) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_2FA_KEY, pending)
+    }
+
+    pub fn get_login_redirect(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::LOGIN_REDIRECT_KEY)
+    }
+
+    pub fn set_login_redirect(&self, location: String) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LOGIN_REDIRECT_KEY, location)
+    }
+
+    pub fn get_flash_count(&self) -> Result<u32, SessionGetError> {
+        Ok(self.0.get(Self::FLASH_COUNT_KEY)?.unwrap_or(0))
+    }
+
+    pub fn set_flash_count(&self, count: u32) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::FLASH_COUNT_KEY, count)
+    }
+
+    pub fn get_flash_messages(&self) -> Result<Vec<FlashMessage>, SessionGetError> {
+        Ok(self.0.get(Self::FLASH_MESSAGES_KEY)?.unwrap_or_default())
+    }
+
+    // Replaces whatever was waiting. A session without any is left untouched, so pages that send
+    // none don't start a session for every visitor.
+    pub fn set_flash_messages(&self, messages: &[FlashMessage]) -> Result<(), SessionInsertError> {
+        if !messages.is_empty() {
+            return self.0.insert(Self::FLASH_MESSAGES_KEY, messages);
+        }
+        let is_waiting = self.0.entries().contains_key(Self::FLASH_MESSAGES_KEY);
+        if is_waiting {
+            self.0.remove(Self::FLASH_MESSAGES_KEY);
+        }
+        Ok(())
+    }
+
+    // Nothing more can be stored in a session once it's been logged out of
+    pub fn is_logged_out(&self) -> bool {
+        self.0.status() == SessionStatus::Purged
+    }
+
+    // Drops every value but keeps the session itself alive, unlike log_out
+    pub fn clear_all(&self) {
+        self.0.clear()
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
@@ -36,3 +119,230 @@ impl FromRequest for TypedSession {
         ready(Ok(TypedSession(req.get_session())))
     }
 }
+
+const FLASH_COOKIE_NAME: &str = "_flash";
+
+// Keeps flash messages in the session rather than in a cookie of their own, so they aren't cut
+// short at a cookie's size and their contents don't travel with the request. Messages sent while
+// logging out go in a cookie after all, the session is gone by the time they're stored.
+pub struct SessionMessageStore {
+    logged_out: CookieMessageStore,
+}
+
+impl SessionMessageStore {
+    pub fn new(logged_out: CookieMessageStore) -> Self {
+        Self { logged_out }
+    }
+
+    pub fn cookie_store(signing_key: actix_web::cookie::Key) -> CookieMessageStore {
+        CookieMessageStore::builder(signing_key)
+            .cookie_name(FLASH_COOKIE_NAME.into())
+            .build()
+    }
+}
+
+impl FlashMessageStore for SessionMessageStore {
+    fn load(&self, request: &HttpRequest) -> Result<Vec<FlashMessage>, LoadError> {
+        let mut messages = TypedSession(request.get_session())
+            .get_flash_messages()
+            .map_err(|e| LoadError::GenericError(e.into()))?;
+        messages.extend(self.logged_out.load(request)?);
+        Ok(messages)
+    }
+
+    fn store(
+        &self,
+        messages: &[FlashMessage],
+        request: HttpRequest,
+        response: &mut ResponseHead,
+    ) -> Result<(), StoreError> {
+        let session = TypedSession(request.get_session());
+        if session.is_logged_out() {
+            return self.logged_out.store(messages, request, response);
+        }
+        // Clears the cookie left by logging out once its messages have been shown
+        if request.cookie(FLASH_COOKIE_NAME).is_some() {
+            self.logged_out.store(&[], request.clone(), response)?;
+        }
+        session
+            .set_flash_messages(messages)
+            .map_err(|e| StoreError::GenericError(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_session::{SessionExt, SessionStatus};
+    use actix_web::test::TestRequest;
+    use actix_web_flash_messages::{FlashMessage, Level};
+    use chrono::{Duration, Utc};
+    use claim::{assert_none, assert_ok, assert_some_eq};
+    use uuid::Uuid;
+
+    use super::TypedSession;
+
+    fn session() -> TypedSession {
+        TypedSession(TestRequest::default().to_http_request().get_session())
+    }
+
+    #[test]
+    fn user_id_round_trips() {
+        let session = session();
+        let user_id = Uuid::new_v4();
+        assert_ok!(session.insert_user_id(user_id));
+        assert_some_eq!(session.get_user_id().unwrap(), user_id);
+    }
+
+    #[test]
+    fn missing_user_id_is_none() {
+        assert_none!(session().get_user_id().unwrap());
+    }
+
+    #[test]
+    fn only_a_recent_authentication_is_within_the_window() {
+        let session = session();
+        let now = Utc::now();
+        assert!(
+            !session
+                .authenticated_within(Duration::minutes(15), now)
+                .unwrap()
+        );
+
+        session
+            .set_authenticated_at(now - Duration::minutes(10))
+            .unwrap();
+
+        assert!(
+            session
+                .authenticated_within(Duration::minutes(15), now)
+                .unwrap()
+        );
+        assert!(
+            !session
+                .authenticated_within(Duration::minutes(5), now)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn pending_2fa_round_trips() {
+        let session = session();
+        assert_ok!(session.set_pending_2fa(true));
+        assert!(session.get_pending_2fa().unwrap());
+        assert_ok!(session.set_pending_2fa(false));
+        assert!(!session.get_pending_2fa().unwrap());
+    }
+
+    #[test]
+    fn missing_pending_2fa_is_false() {
+        assert!(!session().get_pending_2fa().unwrap());
+    }
+
+    #[test]
+    fn login_redirect_round_trips() {
+        let session = session();
+        assert_ok!(session.set_login_redirect("/admin/newsletter".into()));
+        assert_some_eq!(
+            session.get_login_redirect().unwrap(),
+            "/admin/newsletter".to_string()
+        );
+    }
+
+    #[test]
+    fn missing_login_redirect_is_none() {
+        assert_none!(session().get_login_redirect().unwrap());
+    }
+
+    #[test]
+    fn flash_count_round_trips() {
+        let session = session();
+        assert_ok!(session.set_flash_count(3));
+        assert_eq!(session.get_flash_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn missing_flash_count_is_zero() {
+        assert_eq!(session().get_flash_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn a_value_of_the_wrong_type_is_an_error() {
+        let session = session();
+        session
+            .0
+            .insert(TypedSession::FLASH_COUNT_KEY, "many")
+            .unwrap();
+        assert!(session.get_flash_count().is_err());
+    }
+
+    #[test]
+    fn clear_all_removes_every_value() {
+        let session = session();
+        session.insert_user_id(Uuid::new_v4()).unwrap();
+        session.set_pending_2fa(true).unwrap();
+        session
+            .set_login_redirect("/admin/dashboard".into())
+            .unwrap();
+        session.set_flash_count(1).unwrap();
+
+        session.clear_all();
+
+        assert_none!(session.get_user_id().unwrap());
+        assert!(!session.get_pending_2fa().unwrap());
+        assert_none!(session.get_login_redirect().unwrap());
+        assert_eq!(session.get_flash_count().unwrap(), 0);
+        assert_eq!(session.0.status(), SessionStatus::Changed);
+    }
+
+    #[test]
+    fn renewing_keeps_the_stored_values() {
+        let session = session();
+        let user_id = Uuid::new_v4();
+        session.insert_user_id(user_id).unwrap();
+        session
+            .set_login_redirect("/admin/password".into())
+            .unwrap();
+
+        session.renew();
+
+        assert_eq!(session.0.status(), SessionStatus::Renewed);
+        assert_some_eq!(session.get_user_id().unwrap(), user_id);
+        assert_some_eq!(
+            session.get_login_redirect().unwrap(),
+            "/admin/password".to_string()
+        );
+    }
+
+    #[test]
+    fn logging_out_purges_the_session() {
+        let session = session();
+        session.insert_user_id(Uuid::new_v4()).unwrap();
+        let inner = session.0.clone();
+
+        session.log_out();
+
+        assert_eq!(inner.status(), SessionStatus::Purged);
+        assert!(inner.entries().is_empty());
+    }
+
+    #[test]
+    fn flash_messages_round_trip_and_are_cleared() {
+        let session = session();
+        let message = FlashMessage::new("Saved.".into(), Level::Info);
+        session.set_flash_messages(&[message]).unwrap();
+        assert_eq!(session.get_flash_messages().unwrap()[0].content(), "Saved.");
+
+        session.set_flash_messages(&[]).unwrap();
+
+        assert!(session.get_flash_messages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn no_flash_messages_leave_a_fresh_session_unchanged() {
+        let session = session();
+
+        session.set_flash_messages(&[]).unwrap();
+
+        assert_eq!(session.0.status(), SessionStatus::Unchanged);
+    }
+}
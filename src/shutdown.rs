@@ -0,0 +1,48 @@
+use std::fmt::{Debug, Display};
+
+use tokio::task::JoinError;
+
+// Resolves on Ctrl+C, or on the SIGTERM a container runtime sends before killing the process
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+// Error reporting, informs which component failed first, why it failed, and what the error was
+pub fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.name = %e,
+                    "{} failed",
+                    task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.name = %e,
+                    "{}' task failed to complete",
+                    task_name
+            )
+        }
+    }
+}
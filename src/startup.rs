@@ -1,26 +1,78 @@
-use std::net::TcpListener;
+use std::{net::TcpListener, num::NonZeroUsize, str::FromStr, sync::Arc, thread, time::Duration};
 
 use actix_session::{SessionMiddleware, storage::RedisSessionStore};
-use actix_web::{App, HttpServer, cookie::Key, dev::Server, web, web::Data};
+use actix_web::{
+    App, HttpServer,
+    cookie::Key,
+    dev::{Server, ServerHandle},
+    http::StatusCode,
+    middleware::{Condition, ErrorHandlers, NormalizePath},
+    web,
+    web::Data,
+};
 use actix_web_flash_messages::{FlashMessagesFramework, storage::CookieMessageStore};
 use actix_web_lab::middleware::from_fn;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
-use sqlx::{PgPool, postgres::PgPoolOptions};
+use sqlx::{
+    PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+use tokio::task::JoinHandle;
 use tracing_actix_web::TracingLogger;
+use url::Url;
 
 use crate::{
-    authentication::reject_anonymous_users,
-    configuration::{DatabaseSettings, Settings},
-    email_client::EmailClient,
+    authentication::{LoginThrottle, PasswordHistoryDepth, reject_anonymous_users},
+    clock::Clock,
+    configuration::{
+        CacheSettings, DatabaseSettings, DeliverySettings, DisplaySettings, FlashStore,
+        LinkCheckSettings, PublicStatsSettings, SessionSettings, Settings, SiteSettings,
+        SubscriptionSettings, WebhookSettings, WorkerSettings,
+    },
+    delivery_pause::DeliveryPause,
+    domain::PiiCipher,
+    email_client::EmailClientPool,
+    geo_lookup::GeoLookup,
+    idempotency::{IdempotencyKeyMaxLength, IdempotencyWait},
+    issue_delivery_worker::run_worker_until_stopped,
+    link_checker::LinkChecker,
+    mail_domain_checker::MailDomainChecker,
+    middleware::{AllowedHosts, AllowedOrigins, cache_public_pages, reject_unknown_hosts},
+    public_stats::run_stats_refresher_until_stopped,
+    readiness::Readiness,
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, health_check, home,
-        log_out, login, login_form, publish_newsletter, send_newsletter_form, subscribe,
+        add_api_token, add_sender, add_suppression, admin_dashboard, api_error, api_tokens,
+        archive_feed, archive_index, archive_issue, archive_newsletter_issue,
+        background_tasks_health_check, can_receive_email, change_password, change_password_form,
+        confirm, confirm_email_change, confirmation_email_preview, create_template, create_webhook,
+        delete_api_token, delete_template, delete_webhook, estimate_audience, export_subscriber,
+        export_subscribers, get_click_report, get_delivery_report, get_open_rate,
+        get_queue_metrics, get_sending_rate, get_subscriber, get_template, health_check, home,
+        inactive_subscribers_page, inbox_preview, json_error, list_drafts, list_templates,
+        liveness_check, log_out, login, login_form, merge_subscribers, method_not_allowed,
+        mount_api_version, not_found, openapi_json, pause_domain, pause_worker,
+        paused_domains_page, preview_inlined_html, publish_composed_issue, publish_newsletter,
+        readiness_check, regenerate_api_token, remove_api_token, remove_suppression,
+        render_preview, request_email_change, resend_confirmation, resend_failed_deliveries,
+        resend_to_subscriber, resume_domain, resume_worker, save_draft, search_subscribers,
+        send_newsletter_form, send_preview_to_me, senders_page, sitemap, submit_feedback,
+        subscribe, subscribers_page, suppressions_page, swagger_ui_page, track_click, track_open,
+        unarchive_newsletter_issue, unsubscribe_inactive_subscribers, unsubscribed_subscribers,
+        unverify_sender, unversioned_api, update_notification_settings, update_subscriber_note,
+        update_template, v1_public_routes, v1_routes, validate_issue, verify_sender, version,
+        webhooks_page, worker_health_check,
     },
+    sending_rate::SendingRate,
+    session_state::SessionMessageStore,
+    supervisor::Supervisor,
+    webhooks::run_dispatcher_until_stopped,
 };
 
 pub struct Application {
     port: u16,
     server: Server,
+    supervisor: Arc<Supervisor>,
 }
 
 #[derive(Clone, Debug)]
@@ -30,8 +82,37 @@ impl Application {
     pub async fn build(
         configuration: Settings,
         connection_pool: PgPool,
+        clock: Arc<dyn Clock>,
+        geo_lookup: Arc<dyn GeoLookup>,
     ) -> Result<Self, anyhow::Error> {
-        let email_client = configuration.email_client.client();
+        // Matches the number of workers actix starts by default
+        let n_workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let email_clients = EmailClientPool::new(n_workers, &configuration.email_client);
+        let base_url = ApplicationBaseUrl::parse(&configuration.application.base_url)
+            .context("Invalid application.base_url")?;
+        let delivery_pause = DeliveryPause::new(
+            configuration.redis_uri.expose_secret(),
+            &configuration.database.database_name,
+        )
+        .await?;
+        let sending_rate = SendingRate::new(
+            configuration.redis_uri.expose_secret(),
+            &configuration.database.database_name,
+            &configuration.worker,
+        )
+        .await?;
+        let login_throttle = LoginThrottle::new(
+            configuration.redis_uri.expose_secret(),
+            &configuration.database.database_name,
+            &configuration.login_throttle,
+        )
+        .await?;
+        let readiness = Data::new(Readiness::new(configuration.redis_uri.expose_secret()).await?);
+        // Background tasks are restarted when they fail, the API is not
+        let supervisor = Supervisor::new(configuration.supervisor.clone());
+        let pii_cipher = configuration
+            .pii_cipher()
+            .context("Invalid pii_encryption")?;
 
         let requested_port = if configuration.application.port == 0 {
             0
@@ -48,16 +129,44 @@ impl Application {
         let server = run(
             listener,
             connection_pool,
-            email_client,
-            configuration.application.base_url,
+            email_clients,
+            base_url,
             configuration.application.hmac_secret,
+            configuration.application.swagger_ui,
+            configuration.application.version_admin_only,
+            configuration.application.allowed_hosts,
+            configuration.application.flash_store,
             configuration.redis_uri,
+            login_throttle,
+            configuration.site,
+            configuration.webhooks,
+            configuration.caching,
+            configuration.delivery,
+            configuration.worker,
+            configuration.subscriptions,
+            configuration.session,
+            configuration.link_check,
+            configuration.display,
+            configuration.public_stats,
+            PasswordHistoryDepth(configuration.password_history_depth),
+            IdempotencyKeyMaxLength(configuration.idempotency_key_max_length),
+            IdempotencyWait(Duration::from_secs(configuration.idempotency_wait_seconds)),
+            delivery_pause,
+            sending_rate,
+            readiness.clone(),
+            supervisor.clone(),
+            pii_cipher,
+            clock,
+            geo_lookup,
         )
         .await?;
+        // Until here the readiness probe fails even if Postgres and Redis already answer
+        readiness.mark_started();
 
         Ok(Self {
             port: designated_port,
             server,
+            supervisor,
         })
     }
 
@@ -65,79 +174,574 @@ impl Application {
         self.port
     }
 
+    // Shared with the health check, so restarts of tasks started outside the API still show up
+    pub fn supervisor(&self) -> Arc<Supervisor> {
+        self.supervisor.clone()
+    }
+
+    // For stopping the server from outside, e.g. once a background task has been given up on
+    pub fn handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         self.server.await
     }
 }
 
+// The tasks the API process runs next to the server, each restarted by the app's supervisor
+pub struct BackgroundTasks {
+    // `None` with `worker.embedded` off, the zero2prod-worker binary delivers instead
+    pub worker: Option<JoinHandle<Result<(), anyhow::Error>>>,
+    pub webhook_dispatcher: JoinHandle<Result<(), anyhow::Error>>,
+    pub stats_refresher: JoinHandle<Result<(), anyhow::Error>>,
+}
+
+pub fn spawn_background_tasks(
+    configuration: &Settings,
+    clock: Arc<dyn Clock>,
+    supervisor: Arc<Supervisor>,
+) -> BackgroundTasks {
+    let worker = configuration.worker.embedded.then(|| {
+        tokio::spawn(supervisor.clone().supervise("Background worker", {
+            let configuration = configuration.clone();
+            let clock = clock.clone();
+            move || {
+                run_worker_until_stopped(
+                    configuration.worker.clone(),
+                    configuration.clone(),
+                    clock.clone(),
+                )
+            }
+        }))
+    });
+    let webhook_dispatcher = tokio::spawn(supervisor.clone().supervise("Webhook dispatcher", {
+        let configuration = configuration.clone();
+        move || run_dispatcher_until_stopped(configuration.clone())
+    }));
+    let stats_refresher = tokio::spawn(supervisor.supervise("Stats refresher", {
+        let configuration = configuration.clone();
+        move || run_stats_refresher_until_stopped(configuration.clone(), clock.clone())
+    }));
+    BackgroundTasks {
+        worker,
+        webhook_dispatcher,
+        stats_refresher,
+    }
+}
+
 // Application state can only access a single unique specific type, thus make a new one
-pub struct ApplicationBaseUrl(pub String);
+#[derive(Clone, Debug)]
+pub struct ApplicationBaseUrl {
+    pub url: Url,
+}
+
+impl ApplicationBaseUrl {
+    pub fn parse(base_url: &str) -> Result<Self, anyhow::Error> {
+        let mut url = Url::parse(base_url).context("The base URL is not a valid URL.")?;
+        if url.cannot_be_a_base() {
+            anyhow::bail!("{base_url} cannot be used as a base URL.");
+        }
+        // Without a trailing slash the last path segment would be replaced when joining
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Ok(Self { url })
+    }
+
+    // `path` is always relative to the base URL, a leading slash doesn't escape a path prefix
+    pub fn join_path(&self, path: &str) -> Url {
+        self.url
+            .join(&format!("./{}", path.trim_start_matches('/')))
+            .expect("A relative path always joins onto a valid base URL")
+    }
+}
 
 pub async fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     if let Ok(database_url) = std::env::var("DATABASE_URL") {
+        let options = PgConnectOptions::from_str(&database_url).expect("Invalid DATABASE_URL");
         PgPoolOptions::new()
-            .acquire_timeout(std::time::Duration::from_secs(2))
-            .connect(&database_url)
+            .acquire_timeout(Duration::from_secs(2))
+            .connect_with(configuration.with_timeouts(options))
             .await
             .expect("Failed to connect to Postgres")
     } else {
         PgPoolOptions::new()
-            .acquire_timeout(std::time::Duration::from_secs(2))
+            .acquire_timeout(Duration::from_secs(2))
             .connect_lazy_with(configuration.with_db())
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    email_client: EmailClient,
-    base_url: String,
+    email_clients: EmailClientPool,
+    base_url: ApplicationBaseUrl,
     hmac_secret: Secret<String>,
+    swagger_ui: bool,
+    version_admin_only: bool,
+    allowed_hosts: Vec<String>,
+    flash_store: FlashStore,
     redis_uri: Secret<String>,
+    login_throttle: LoginThrottle,
+    site: SiteSettings,
+    webhooks: WebhookSettings,
+    caching: CacheSettings,
+    delivery: DeliverySettings,
+    worker: WorkerSettings,
+    subscriptions: SubscriptionSettings,
+    session: SessionSettings,
+    link_check: LinkCheckSettings,
+    display: DisplaySettings,
+    public_stats: PublicStatsSettings,
+    password_history_depth: PasswordHistoryDepth,
+    idempotency_key_max_length: IdempotencyKeyMaxLength,
+    idempotency_wait: IdempotencyWait,
+    delivery_pause: DeliveryPause,
+    sending_rate: SendingRate,
+    readiness: Data<Readiness>,
+    supervisor: Arc<Supervisor>,
+    pii_cipher: PiiCipher,
+    clock: Arc<dyn Clock>,
+    geo_lookup: Arc<dyn GeoLookup>,
 ) -> Result<Server, anyhow::Error> {
     let db_pool = Data::new(db_pool);
-    let email_client = Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let email_clients = Data::new(email_clients);
+    let allowed_hosts = Data::new(AllowedHosts::new(allowed_hosts, &base_url));
+    let base_url = Data::new(base_url);
+    let site = Data::new(site);
+    let webhooks = Data::new(webhooks);
+    let caching = Data::new(caching);
+    let delivery = Data::new(delivery);
+    let worker = Data::new(worker);
+    let subscriptions = Data::new(subscriptions);
+    let session = Data::new(session);
+    let link_checker = Data::new(LinkChecker::new(&link_check));
+    let mail_domains = Data::new(MailDomainChecker::default());
+    let display = Data::new(display);
+    let allowed_origins = Data::new(AllowedOrigins::new(public_stats.allowed_origins.clone()));
+    let public_stats = Data::new(public_stats);
+    let delivery_pause = Data::new(delivery_pause);
+    let sending_rate = Data::new(sending_rate);
+    let pii_cipher = Data::new(pii_cipher);
+    let supervisor = Data::from(supervisor);
+    let clock: Data<dyn Clock> = Data::from(clock);
+    let geo_lookup: Data<dyn GeoLookup> = Data::from(geo_lookup);
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
-    // Storage backend - where flash messages are stored in cookies, how they are secured, and what
-    // format they use.
-    let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     // Orchestrates the storage backend defined, provided api for sending and receiving messages,
     // and handles the lifecycle of the flash messages.
-    let message_framework = FlashMessagesFramework::builder(message_store).build();
+    let message_framework = match flash_store {
+        // Storage backend - where flash messages are stored in cookies, how they are secured, and
+        // what format they use.
+        FlashStore::Cookie => {
+            FlashMessagesFramework::builder(CookieMessageStore::builder(secret_key.clone()).build())
+                .build()
+        }
+        FlashStore::Session => FlashMessagesFramework::builder(SessionMessageStore::new(
+            SessionMessageStore::cookie_store(secret_key.clone()),
+        ))
+        .build(),
+    };
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let login_throttle = Data::new(login_throttle);
     let server = HttpServer::new(move || {
         App::new()
+            // `/login/` and `/login` are the same page, as are `/admin//dashboard` and `/admin/dashboard`
+            .wrap(NormalizePath::trim())
+            .wrap(from_fn(reject_unknown_hosts))
             .wrap(TracingLogger::default())
-            .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
-            .route("/health_check", web::get().to(health_check))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
-            .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
+            // The JSON API for other programs: API tokens only, no sessions or flash messages
             .service(
-                // web::scope() needs a .service() for mounting
-                web::scope("/admin") // Can only wrap a scope not a service
-                    .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/newsletter", web::get().to(send_newsletter_form))
-                    .route("/newsletter", web::post().to(publish_newsletter))
-                    .route("/logout", web::post().to(log_out)),
+                web::scope("/api")
+                    .wrap(ErrorHandlers::new().default_handler(api_error))
+                    .service(web::resource("/openapi.json").route(web::get().to(openapi_json)))
+                    .configure(|cfg| {
+                        if swagger_ui {
+                            cfg.service(
+                                web::resource("/docs").route(web::get().to(swagger_ui_page)),
+                            );
+                        }
+                    })
+                    .configure(|cfg| mount_api_version(cfg, "v1", v1_public_routes, v1_routes))
+                    .default_service(web::to(unversioned_api)),
             )
+            // The pages for people
+            .service(
+                web::scope("")
+                    .wrap(
+                        ErrorHandlers::new()
+                            .handler(StatusCode::METHOD_NOT_ALLOWED, method_not_allowed),
+                    )
+                    .wrap(message_framework.clone())
+                    .wrap(
+                        SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                            .cookie_name(session.cookie_name.clone())
+                            .cookie_path(session.cookie_path.clone())
+                            .cookie_domain(session.cookie_domain.clone())
+                            .cookie_secure(session.cookie_secure)
+                            .build(),
+                    )
+                    .service(web::resource("/health_check").route(web::get().to(health_check)))
+                    .service(
+                        web::resource("/health_check/live").route(web::get().to(liveness_check)),
+                    )
+                    .service(
+                        web::resource("/health_check/ready").route(web::get().to(readiness_check)),
+                    )
+                    .service(
+                        web::resource("/health_check/worker")
+                            .route(web::get().to(worker_health_check)),
+                    )
+                    .service(
+                        web::resource("/health_check/tasks")
+                            .route(web::get().to(background_tasks_health_check)),
+                    )
+                    .service(
+                        web::resource("/version")
+                            .wrap(Condition::new(
+                                version_admin_only,
+                                from_fn(reject_anonymous_users),
+                            ))
+                            .route(web::get().to(version)),
+                    )
+                    .service(web::resource("/subscriptions").route(web::post().to(subscribe)))
+                    .service(web::resource("/subscriptions/confirm").route(web::get().to(confirm)))
+                    .service(
+                        web::resource("/subscriptions/confirm-email")
+                            .route(web::get().to(confirm_email_change)),
+                    )
+                    .service(
+                        web::resource("/subscriptions/resend-confirmation")
+                            .route(web::post().to(resend_confirmation)),
+                    )
+                    .service(web::resource("/feedback").route(web::get().to(submit_feedback)))
+                    .service(
+                        web::resource("/archive")
+                            .wrap(from_fn(cache_public_pages))
+                            .route(web::get().to(archive_index)),
+                    )
+                    .service(web::resource("/archive/feed.xml").route(web::get().to(archive_feed)))
+                    .service(
+                        web::resource("/archive/{slug}")
+                            .wrap(from_fn(cache_public_pages))
+                            .route(web::get().to(archive_issue)),
+                    )
+                    .service(web::resource("/sitemap.xml").route(web::get().to(sitemap)))
+                    .service(
+                        web::resource("/track/open/{delivery_id}.gif")
+                            .route(web::get().to(track_open)),
+                    )
+                    .service(
+                        web::resource("/track/click/{delivery_id}/{link_hash}")
+                            .route(web::get().to(track_click)),
+                    )
+                    .service(
+                        web::resource("/")
+                            .wrap(from_fn(cache_public_pages))
+                            .route(web::get().to(home)),
+                    )
+                    .service(
+                        web::resource("/login")
+                            .route(web::get().to(login_form))
+                            .route(web::post().to(login)),
+                    )
+                    .service(
+                        // web::scope() needs a .service() for mounting
+                        web::scope("/admin") // Can only wrap a scope not a service
+                            .wrap(from_fn(reject_anonymous_users))
+                            .service(
+                                web::resource("/dashboard").route(web::get().to(admin_dashboard)),
+                            )
+                            .service(
+                                web::resource("/emails/confirmation/preview")
+                                    .route(web::get().to(confirmation_email_preview)),
+                            )
+                            .service(
+                                web::resource("/api_token")
+                                    .route(web::post().to(regenerate_api_token))
+                                    .route(web::delete().to(delete_api_token)),
+                            )
+                            .service(
+                                web::resource("/api-tokens")
+                                    .route(web::get().to(api_tokens))
+                                    .route(web::post().to(add_api_token)),
+                            )
+                            .service(
+                                web::resource("/api-tokens/{token_id}")
+                                    .route(web::delete().to(remove_api_token)),
+                            )
+                            .service(
+                                web::resource("/password")
+                                    .route(web::get().to(change_password_form))
+                                    .route(web::post().to(change_password)),
+                            )
+                            .service(
+                                web::resource("/newsletter")
+                                    .route(web::get().to(send_newsletter_form))
+                                    .route(web::post().to(publish_newsletter)),
+                            )
+                            .service(
+                                web::resource("/newsletter/compose")
+                                    .route(web::get().to(list_drafts)),
+                            )
+                            .service(
+                                web::resource("/newsletter/compose/save-draft")
+                                    .route(web::post().to(save_draft)),
+                            )
+                            .service(
+                                web::resource("/newsletter/compose/validate")
+                                    .route(web::post().to(validate_issue)),
+                            )
+                            .service(
+                                web::resource("/newsletter/compose/render-preview")
+                                    .route(web::post().to(render_preview)),
+                            )
+                            .service(
+                                web::resource("/newsletter/compose/estimate-audience")
+                                    .route(web::post().to(estimate_audience)),
+                            )
+                            .service(
+                                web::resource("/newsletter/compose/publish")
+                                    .route(web::post().to(publish_composed_issue)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/html-inline")
+                                    .route(web::get().to(preview_inlined_html)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/open-rate")
+                                    .route(web::get().to(get_open_rate)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/click-report")
+                                    .route(web::get().to(get_click_report)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/delivery-report")
+                                    .route(web::get().to(get_delivery_report)),
+                            )
+                            .service(
+                                web::resource("/newsletter/inbox-preview")
+                                    .route(web::get().to(inbox_preview)),
+                            )
+                            .service(
+                                web::resource("/newsletter/sending-rate")
+                                    .route(web::get().to(get_sending_rate)),
+                            )
+                            .service(
+                                web::resource("/newsletter/queue-metrics")
+                                    .route(web::get().to(get_queue_metrics)),
+                            )
+                            .service(
+                                web::resource("/newsletter/templates")
+                                    .route(web::get().to(list_templates))
+                                    .route(web::post().to(create_template)),
+                            )
+                            .service(
+                                web::resource("/newsletter/templates/{id}")
+                                    .route(web::get().to(get_template))
+                                    .route(web::put().to(update_template))
+                                    .route(web::delete().to(delete_template)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/preview-to-me")
+                                    .route(web::post().to(send_preview_to_me)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/resend-failed")
+                                    .route(web::post().to(resend_failed_deliveries)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/resend")
+                                    .route(web::post().to(resend_to_subscriber)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/archive")
+                                    .route(web::post().to(archive_newsletter_issue)),
+                            )
+                            .service(
+                                web::resource("/newsletter/{issue_id}/unarchive")
+                                    .route(web::post().to(unarchive_newsletter_issue)),
+                            )
+                            .service(
+                                web::resource("/notifications")
+                                    .route(web::post().to(update_notification_settings)),
+                            )
+                            .service(
+                                web::resource("/webhooks")
+                                    .route(web::get().to(webhooks_page))
+                                    .route(web::post().to(create_webhook)),
+                            )
+                            .service(
+                                web::resource("/webhooks/{webhook_id}/delete")
+                                    .route(web::post().to(delete_webhook)),
+                            )
+                            .service(
+                                web::resource("/worker/pause").route(web::post().to(pause_worker)),
+                            )
+                            .service(
+                                web::resource("/worker/resume")
+                                    .route(web::post().to(resume_worker)),
+                            )
+                            .service(
+                                web::resource("/paused-domains")
+                                    .route(web::get().to(paused_domains_page))
+                                    .route(web::post().to(pause_domain)),
+                            )
+                            .service(
+                                web::resource("/paused-domains/{domain}/resume")
+                                    .route(web::post().to(resume_domain)),
+                            )
+                            .service(
+                                web::resource("/subscribers")
+                                    .route(web::get().to(subscribers_page)),
+                            )
+                            .service(
+                                web::resource("/subscribers/inactive")
+                                    .route(web::get().to(inactive_subscribers_page))
+                                    .route(web::post().to(unsubscribe_inactive_subscribers)),
+                            )
+                            .service(
+                                web::resource("/subscribers/unsubscribed")
+                                    .route(web::get().to(unsubscribed_subscribers)),
+                            )
+                            .service(
+                                web::resource("/subscribers/search")
+                                    .route(web::get().to(search_subscribers)),
+                            )
+                            .service(
+                                web::resource("/subscribers/export.jsonl")
+                                    .route(web::get().to(export_subscribers)),
+                            )
+                            .service(
+                                web::resource("/subscribers/merge")
+                                    .route(web::post().to(merge_subscribers)),
+                            )
+                            .service(
+                                web::resource("/subscribers/{subscriber_id}")
+                                    .route(web::get().to(get_subscriber)),
+                            )
+                            .service(
+                                web::resource("/subscribers/{subscriber_id}/can-receive-email")
+                                    .route(web::get().to(can_receive_email)),
+                            )
+                            .service(
+                                web::resource("/subscribers/{subscriber_id}/note")
+                                    .route(web::patch().to(update_subscriber_note)),
+                            )
+                            .service(
+                                web::resource("/subscribers/{subscriber_id}/email")
+                                    .route(web::post().to(request_email_change)),
+                            )
+                            .service(
+                                web::resource("/subscribers/{subscriber_id}/export")
+                                    .route(web::get().to(export_subscriber)),
+                            )
+                            .service(
+                                web::resource("/suppressions")
+                                    .route(web::get().to(suppressions_page))
+                                    .route(web::post().to(add_suppression)),
+                            )
+                            .service(
+                                web::resource("/suppressions/remove")
+                                    .route(web::post().to(remove_suppression)),
+                            )
+                            .service(
+                                web::resource("/senders")
+                                    .route(web::get().to(senders_page))
+                                    .route(web::post().to(add_sender)),
+                            )
+                            .service(
+                                web::resource("/senders/{sender_id}/verify")
+                                    .route(web::post().to(verify_sender)),
+                            )
+                            .service(
+                                web::resource("/senders/{sender_id}/unverify")
+                                    .route(web::post().to(unverify_sender)),
+                            )
+                            .service(web::resource("/logout").route(web::post().to(log_out))),
+                    ),
+            )
+            .default_service(web::to(not_found))
+            .app_data(web::JsonConfig::default().error_handler(json_error))
             .app_data(db_pool.clone())
-            .app_data(email_client.clone())
+            .app_data(email_clients.clone())
             .app_data(base_url.clone())
-            .app_data(Data::new(hmac_secret.clone()))
+            .app_data(allowed_hosts.clone())
+            .app_data(login_throttle.clone())
+            .app_data(site.clone())
+            .app_data(webhooks.clone())
+            .app_data(caching.clone())
+            .app_data(delivery.clone())
+            .app_data(worker.clone())
+            .app_data(subscriptions.clone())
+            .app_data(session.clone())
+            .app_data(link_checker.clone())
+            .app_data(mail_domains.clone())
+            .app_data(display.clone())
+            .app_data(public_stats.clone())
+            .app_data(allowed_origins.clone())
+            .app_data(Data::new(password_history_depth))
+            .app_data(Data::new(idempotency_key_max_length))
+            .app_data(Data::new(idempotency_wait))
+            .app_data(delivery_pause.clone())
+            .app_data(sending_rate.clone())
+            .app_data(readiness.clone())
+            .app_data(supervisor.clone())
+            .app_data(pii_cipher.clone())
+            .app_data(clock.clone())
+            .app_data(geo_lookup.clone())
+            .app_data(Data::new(HmacSecret(hmac_secret.clone())))
     })
     .listen(listener)?
     .run();
 
     Ok(server)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ApplicationBaseUrl;
+
+    #[test]
+    fn paths_are_joined_with_a_single_slash() {
+        for base_url in ["http://localhost:8000", "http://localhost:8000/"] {
+            let base_url = ApplicationBaseUrl::parse(base_url).unwrap();
+            assert_eq!(
+                base_url.join_path("archive").as_str(),
+                "http://localhost:8000/archive"
+            );
+        }
+    }
+
+    #[test]
+    fn absolute_paths_stay_under_the_base_path() {
+        let base_url = ApplicationBaseUrl::parse("https://example.com/newsletter").unwrap();
+
+        assert_eq!(
+            base_url.join_path("/subscriptions/confirm").as_str(),
+            "https://example.com/newsletter/subscriptions/confirm"
+        );
+        assert_eq!(
+            base_url.join_path("archive/").as_str(),
+            "https://example.com/newsletter/archive/"
+        );
+    }
+
+    #[test]
+    fn query_strings_are_kept() {
+        let base_url = ApplicationBaseUrl::parse("http://localhost:8000").unwrap();
+
+        assert_eq!(
+            base_url.join_path("feedback?issue=1&rating=up").as_str(),
+            "http://localhost:8000/feedback?issue=1&rating=up"
+        );
+    }
+
+    #[test]
+    fn invalid_base_urls_are_rejected() {
+        assert!(ApplicationBaseUrl::parse("localhost:8000").is_err());
+        assert!(ApplicationBaseUrl::parse("not a url").is_err());
+    }
+}
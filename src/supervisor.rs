@@ -0,0 +1,209 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::configuration::SupervisorSettings;
+
+// Keeps background tasks running. A task that fails or panics is restarted after a growing delay,
+// and one that keeps failing is given up on so the process can shut down rather than limp along.
+pub struct Supervisor {
+    settings: SupervisorSettings,
+    restarts: Mutex<HashMap<&'static str, u32>>,
+}
+
+impl Supervisor {
+    pub fn new(settings: SupervisorSettings) -> Arc<Self> {
+        Arc::new(Self {
+            settings,
+            restarts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // How many times the task has been restarted since the process started
+    pub fn restarts(&self, task_name: &str) -> u32 {
+        self.restarts
+            .lock()
+            .unwrap()
+            .get(task_name)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Every task supervised so far, including those never restarted, for the health check
+    pub fn restart_counts(&self) -> BTreeMap<&'static str, u32> {
+        self.restarts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(task_name, restarts)| (*task_name, *restarts))
+            .collect()
+    }
+
+    // Returns once the task exits cleanly, or with an error once it has failed more times in a row
+    // than `max_restarts` allows
+    pub async fn supervise<F, Fut>(
+        self: Arc<Self>,
+        task_name: &'static str,
+        mut start: F,
+    ) -> Result<(), anyhow::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.restarts.lock().unwrap().entry(task_name).or_insert(0);
+        let mut failures_in_a_row = 0;
+        loop {
+            let started_at = Instant::now();
+            let error = match tokio::spawn(start()).await {
+                Ok(Ok(())) => {
+                    tracing::info!("{} has exited", task_name);
+                    return Ok(());
+                }
+                Ok(Err(e)) => e,
+                Err(e) => anyhow::Error::new(e),
+            };
+            // A task that stayed up for a while before failing starts its backoff over
+            if started_at.elapsed() >= self.max_backoff() {
+                failures_in_a_row = 0;
+            }
+            if failures_in_a_row >= self.settings.max_restarts {
+                tracing::error!(
+                    error.cause_chain = ?error,
+                    error.message = %error,
+                    "{} failed {} times in a row, giving up",
+                    task_name,
+                    failures_in_a_row + 1
+                );
+                return Err(error.context(format!("{task_name} kept failing")));
+            }
+            let backoff = self.backoff(failures_in_a_row);
+            failures_in_a_row += 1;
+            let restarts = self.record_restart(task_name);
+            tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                restarts,
+                "{} failed, restarting in {:?}",
+                task_name,
+                backoff
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    fn record_restart(&self, task_name: &'static str) -> u32 {
+        let mut restarts = self.restarts.lock().unwrap();
+        let count = restarts.entry(task_name).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    // Doubles with every failure in a row, up to the configured maximum
+    fn backoff(&self, failures_in_a_row: u32) -> Duration {
+        let backoff = self
+            .settings
+            .initial_backoff_milliseconds
+            .saturating_mul(2u64.saturating_pow(failures_in_a_row));
+        Duration::from_millis(backoff).min(self.max_backoff())
+    }
+
+    fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.settings.max_backoff_milliseconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use claim::{assert_err, assert_ok};
+
+    use super::Supervisor;
+    use crate::configuration::SupervisorSettings;
+
+    fn settings(max_restarts: u32) -> SupervisorSettings {
+        SupervisorSettings {
+            max_restarts,
+            initial_backoff_milliseconds: 1,
+            max_backoff_milliseconds: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_task_that_panics_is_restarted_until_it_runs() {
+        let supervisor = Supervisor::new(settings(3));
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let outcome = supervisor
+            .clone()
+            .supervise("Flaky worker", {
+                let attempts = attempts.clone();
+                move || {
+                    let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if attempt < 2 {
+                            panic!("Malformed row");
+                        }
+                        Ok(())
+                    }
+                }
+            })
+            .await;
+
+        assert_ok!(outcome);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(supervisor.restarts("Flaky worker"), 2);
+    }
+
+    #[tokio::test]
+    async fn a_task_that_keeps_failing_is_given_up_on() {
+        let supervisor = Supervisor::new(settings(2));
+
+        let outcome = supervisor
+            .clone()
+            .supervise("Broken worker", || async {
+                Err(anyhow::anyhow!("The database is gone"))
+            })
+            .await;
+
+        assert_err!(outcome);
+        assert_eq!(supervisor.restarts("Broken worker"), 2);
+    }
+
+    #[tokio::test]
+    async fn tasks_that_never_failed_are_listed_with_no_restarts() {
+        let supervisor = Supervisor::new(settings(2));
+
+        supervisor
+            .clone()
+            .supervise("Steady worker", || async { Ok(()) })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            supervisor.restart_counts().into_iter().collect::<Vec<_>>(),
+            vec![("Steady worker", 0)]
+        );
+    }
+
+    #[test]
+    fn the_backoff_doubles_up_to_the_maximum() {
+        let supervisor = Supervisor::new(SupervisorSettings {
+            max_restarts: 10,
+            initial_backoff_milliseconds: 100,
+            max_backoff_milliseconds: 1000,
+        });
+
+        let backoffs: Vec<u128> = (0..6)
+            .map(|failures| supervisor.backoff(failures).as_millis())
+            .collect();
+
+        assert_eq!(backoffs, vec![100, 200, 400, 800, 1000, 1000]);
+    }
+}
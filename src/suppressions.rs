@@ -0,0 +1,17 @@
+use sqlx::{Executor, Postgres};
+
+// Addresses are compared case-insensitively, `Jane@Example.com` is the same inbox as
+// `jane@example.com`
+#[tracing::instrument(skip(executor))]
+pub async fn is_suppressed<'c, E>(executor: E, email: &str) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let row = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM suppressed_emails WHERE email = lower($1)) AS "suppressed!""#,
+        email
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(row.suppressed)
+}
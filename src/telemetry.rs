@@ -1,20 +1,192 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{Arc, RwLock},
+};
+
+use serde_json::Value;
 use tokio::task::JoinHandle;
 use tracing::{Subscriber, subscriber::set_global_default};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
 use tracing_subscriber::{EnvFilter, Registry, fmt::MakeWriter, layer::SubscriberExt};
 
+use crate::build_info::BUILD_INFO;
+
+// Masked until the configuration says otherwise, so the logs written while it loads are covered
+pub const DEFAULT_REDACTED_FIELDS: [&str; 4] =
+    ["password", "subscription_token", "email", "authorization"];
+const REDACTED: &str = "[REDACTED]";
+
+// The fields whose values are masked in every log line. `email` also covers `subscriber_email`
+// and `http.email`, and query string parameters of the same names, e.g. in `http.target`. Shared
+// with the subscriber so it can be changed once the configuration is loaded.
+#[derive(Clone)]
+pub struct Redaction(Arc<RwLock<Vec<String>>>);
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self::new(DEFAULT_REDACTED_FIELDS.map(String::from).to_vec())
+    }
+}
+
+impl Redaction {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self(Arc::new(RwLock::new(normalise(fields))))
+    }
+
+    pub fn set(&self, fields: Vec<String>) {
+        *self.0.write().unwrap() = normalise(fields);
+    }
+
+    fn is_redacted(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.0.read().unwrap().iter().any(|field| {
+            key == *field
+                || key
+                    .strip_suffix(field.as_str())
+                    .is_some_and(|prefix| prefix.ends_with(['_', '.']))
+        })
+    }
+
+    // Lines that aren't JSON objects are passed through as they are
+    fn redact_line(&self, line: &[u8]) -> Vec<u8> {
+        let Ok(mut record) = serde_json::from_slice::<Value>(line) else {
+            return line.to_vec();
+        };
+        self.redact_value(&mut record);
+        let mut line = serde_json::to_vec(&record).unwrap();
+        line.push(b'\n');
+        line
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields.iter_mut() {
+                    if self.is_redacted(key) {
+                        *value = Value::String(REDACTED.into());
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|v| self.redact_value(v)),
+            Value::String(s) => {
+                if let Some(redacted) = self.redact_query(s) {
+                    *s = redacted;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Masks `name=value` pairs after a `?` whose name is redacted
+    fn redact_query(&self, s: &str) -> Option<String> {
+        let (path, query) = s.split_once('?')?;
+        let (query, fragment) = match query.split_once('#') {
+            Some((query, fragment)) => (query, Some(fragment)),
+            None => (query, None),
+        };
+        let mut changed = false;
+        let pairs: Vec<String> = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.is_redacted(name) => {
+                    changed = true;
+                    format!("{name}={REDACTED}")
+                }
+                _ => pair.to_owned(),
+            })
+            .collect();
+        if !changed {
+            return None;
+        }
+        let mut redacted = format!("{path}?{}", pairs.join("&"));
+        if let Some(fragment) = fragment {
+            redacted.push('#');
+            redacted.push_str(fragment);
+        }
+        Some(redacted)
+    }
+}
+
+fn normalise(fields: Vec<String>) -> Vec<String> {
+    fields
+        .into_iter()
+        .map(|field| field.to_lowercase())
+        .collect()
+}
+
+// Hands the formatter a writer that redacts each record before it reaches the sink
+struct RedactingMakeWriter<Sink> {
+    sink: Sink,
+    redaction: Redaction,
+}
+
+impl<'a, Sink> MakeWriter<'a> for RedactingMakeWriter<Sink>
+where
+    Sink: MakeWriter<'a>,
+{
+    type Writer = RedactingWriter<Sink::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.sink.make_writer(),
+            redaction: self.redaction.clone(),
+            buffer: Vec::new(),
+        }
+    }
+}
+
+// Each writer carries a single record, written out redacted once the formatter drops it
+struct RedactingWriter<W: Write> {
+    inner: W,
+    redaction: Redaction,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for RedactingWriter<W> {
+    fn drop(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let line = self.redaction.redact_line(&self.buffer);
+        // Nowhere to report a failed log write to
+        let _ = self.inner.write_all(&line);
+        let _ = self.inner.flush();
+    }
+}
+
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    redaction: Redaction,
 ) -> impl Subscriber + Send + Sync
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    // Every line says which build wrote it
+    let default_fields = HashMap::from([("version".into(), BUILD_INFO.log_version().into())]);
+    let formatting_layer = BunyanFormattingLayer::with_default_fields(
+        name,
+        RedactingMakeWriter { sink, redaction },
+        default_fields,
+    );
     Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
@@ -34,3 +206,108 @@ where
     let current_span = tracing::Span::current();
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::{Redaction, get_subscriber};
+    use crate::build_info::BUILD_INFO;
+
+    // Keeps every line written to it
+    #[derive(Clone, Default)]
+    struct CapturingSink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturingSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturingSink {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn redacted_fields_are_masked_and_the_rest_pass_through() {
+        let sink = CapturingSink::default();
+        let redaction = Redaction::default();
+        let subscriber = get_subscriber("test".into(), "info".into(), sink.clone(), redaction);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(
+                subscriber_email = "ged@earthsea.org",
+                subscriber_name = "Ged",
+                url = "/subscriptions/confirm?subscription_token=abc123&source=email",
+                "Confirming a subscriber"
+            );
+        });
+
+        let output = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        let record: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(record["subscriber_email"], "[REDACTED]");
+        assert_eq!(record["subscriber_name"], "Ged");
+        assert_eq!(
+            record["url"],
+            "/subscriptions/confirm?subscription_token=[REDACTED]&source=email"
+        );
+        assert!(!output.contains("earthsea") && !output.contains("abc123"));
+    }
+
+    #[test]
+    fn startup_lines_carry_the_build_details() {
+        let sink = CapturingSink::default();
+        let subscriber = get_subscriber(
+            "test".into(),
+            "info".into(),
+            sink.clone(),
+            Redaction::default(),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            BUILD_INFO
+                .startup_span()
+                .in_scope(|| tracing::info!("Starting"));
+        });
+
+        let output = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        let record: serde_json::Value = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .find(|record: &serde_json::Value| {
+                record["msg"].as_str().unwrap().ends_with("EVENT] Starting")
+            })
+            .unwrap();
+        assert_eq!(record["version"], BUILD_INFO.log_version());
+        assert_eq!(record["git_sha"], BUILD_INFO.git_sha);
+        assert_eq!(record["crate_version"], env!("CARGO_PKG_VERSION"));
+        assert!(record["built_at"].is_string());
+    }
+
+    #[test]
+    fn the_fields_can_be_changed_after_the_subscriber_is_built() {
+        let redaction = Redaction::default();
+        assert!(redaction.is_redacted("password"));
+
+        redaction.set(vec!["api_key".into()]);
+
+        assert!(!redaction.is_redacted("password"));
+        assert!(redaction.is_redacted("API_KEY"));
+        assert!(redaction.is_redacted("http.api_key"));
+        assert!(!redaction.is_redacted("api_keys"));
+    }
+}
@@ -4,6 +4,10 @@ pub fn e400<T: std::fmt::Debug + std::fmt::Display + 'static>(e: T) -> actix_web
     actix_web::error::ErrorBadRequest(e)
 }
 
+pub fn e404<T: std::fmt::Debug + std::fmt::Display + 'static>(e: T) -> actix_web::Error {
+    actix_web::error::ErrorNotFound(e)
+}
+
 pub fn e500<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
@@ -16,3 +20,55 @@ pub fn see_other(location: &str) -> HttpResponse {
         .insert_header((LOCATION, location))
         .finish()
 }
+
+// How long ago something happened in the largest whole unit, e.g. "2h ago"
+pub fn time_ago(elapsed: chrono::Duration) -> String {
+    let seconds = elapsed.num_seconds();
+    match seconds {
+        ..60 => "just now".into(),
+        60..3_600 => format!("{}m ago", seconds / 60),
+        3_600..86_400 => format!("{}h ago", seconds / 3_600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+// Counts with thousands separators, e.g. "4,210"
+pub fn group_thousands(n: i64) -> String {
+    let digits = n.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if n < 0 {
+        grouped.insert(0, '-');
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::{group_thousands, time_ago};
+
+    #[test]
+    fn durations_are_shown_in_their_largest_whole_unit() {
+        assert_eq!(time_ago(Duration::seconds(59)), "just now");
+        assert_eq!(time_ago(Duration::seconds(-5)), "just now");
+        assert_eq!(time_ago(Duration::minutes(5)), "5m ago");
+        assert_eq!(time_ago(Duration::minutes(150)), "2h ago");
+        assert_eq!(time_ago(Duration::hours(49)), "2d ago");
+    }
+
+    #[test]
+    fn counts_are_grouped_in_thousands() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(4_210), "4,210");
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+        assert_eq!(group_thousands(-1_000), "-1,000");
+    }
+}
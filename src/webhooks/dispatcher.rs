@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{Span, field::display};
+use uuid::Uuid;
+
+use crate::{
+    configuration::{Settings, WebhookSettings},
+    issue_delivery_worker::ExecutionOutcome,
+    startup::get_connection_pool,
+};
+
+// `sha256=<hex digest>` of the raw request body, keyed with the webhook's own secret
+pub fn signature(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        delivery_id=tracing::field::Empty,
+        event=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_dispatch_webhook(
+    pool: &PgPool,
+    http_client: &Client,
+    settings: &WebhookSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    // Claimed and committed on its own, so no row stays locked while the POST is in flight. The
+    // claim counts as an attempt and pushes the delivery back until well after the request would
+    // have timed out, so if this process dies mid-send another one picks it up later.
+    let lease_seconds = (settings.timeout_milliseconds / 1000 + 60) as f64;
+    let delivery = sqlx::query!(
+        r#"
+    WITH claimed AS (
+        UPDATE webhook_deliveries
+        SET
+            attempts = attempts + 1,
+            next_attempt_at = now() + make_interval(secs => $1)
+        WHERE delivery_id = (
+            SELECT delivery_id
+            FROM webhook_deliveries
+            WHERE next_attempt_at <= now()
+            ORDER BY next_attempt_at
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+        )
+        RETURNING delivery_id, webhook_id, event, payload, attempts
+    )
+    SELECT
+        c.delivery_id AS "delivery_id!",
+        c.event AS "event!",
+        c.payload AS "payload!",
+        c.attempts AS "attempts!",
+        w.url AS "url?",
+        w.secret AS "secret?"
+    FROM claimed c
+    LEFT JOIN webhooks w ON w.webhook_id = c.webhook_id
+    "#,
+        lease_seconds
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(delivery) = delivery else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("delivery_id", display(delivery.delivery_id))
+        .record("event", display(&delivery.event));
+
+    // Without a webhook the delivery is meant for the configured confirmation callback
+    let target = match (delivery.url, delivery.secret) {
+        (Some(url), Some(secret)) => Some((url, secret)),
+        _ => settings
+            .confirmation_callback
+            .as_ref()
+            .map(|c| (c.url.clone(), c.secret.expose_secret().clone())),
+    };
+    let Some((url, secret)) = target else {
+        tracing::warn!("Dropping a callback delivery, no confirmation callback is configured.");
+        delete_delivery(pool, delivery.delivery_id).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
+
+    let outcome = http_client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .header("X-Signature", signature(&secret, &delivery.payload))
+        .body(delivery.payload)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+
+    let attempts = delivery.attempts;
+    match outcome {
+        Ok(_) => {
+            delete_delivery(pool, delivery.delivery_id).await?;
+        }
+        Err(e) if attempts as u32 >= settings.max_attempts => {
+            tracing::error!(
+                error.message = %e,
+                "Giving up on a webhook delivery after {} attempts.",
+                attempts
+            );
+            delete_delivery(pool, delivery.delivery_id).await?;
+        }
+        Err(e) => {
+            tracing::warn!(
+                error.message = %e,
+                "Failed to deliver a webhook. Retrying later.",
+            );
+            // Exponential backoff: 2s, 4s, 8s, ...
+            let backoff_seconds = 2_f64.powi(attempts);
+            sqlx::query!(
+                r#"
+    UPDATE webhook_deliveries
+    SET next_attempt_at = now() + make_interval(secs => $2)
+    WHERE delivery_id = $1
+    "#,
+                delivery.delivery_id,
+                backoff_seconds
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+async fn delete_delivery(pool: &PgPool, delivery_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM webhook_deliveries WHERE delivery_id = $1"#,
+        delivery_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn run_dispatcher_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    let http_client = configuration.webhooks.client();
+    dispatcher_loop(&connection_pool, &http_client, &configuration.webhooks).await
+}
+
+async fn dispatcher_loop(
+    pool: &PgPool,
+    http_client: &Client,
+    settings: &WebhookSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_dispatch_webhook(pool, http_client, settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::Delivered | ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
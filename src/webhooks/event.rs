@@ -0,0 +1,160 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+// Bump when a payload changes shape so receivers can tell old and new deliveries apart
+pub const PAYLOAD_VERSION: u32 = 1;
+
+pub const EVENT_NAMES: [&str; 4] = [
+    "issue.published",
+    "issue.completed",
+    "subscriber.confirmed",
+    "subscriber.unsubscribed",
+];
+
+#[derive(serde::Serialize, Debug)]
+#[serde(tag = "event", content = "data")]
+pub enum WebhookEvent {
+    #[serde(rename = "issue.published")]
+    IssuePublished {
+        newsletter_issue_id: Uuid,
+        title: String,
+        recipients: u64,
+    },
+    #[serde(rename = "issue.completed")]
+    IssueCompleted {
+        newsletter_issue_id: Uuid,
+        title: String,
+        delivered: i32,
+        failed: i32,
+    },
+    #[serde(rename = "subscriber.confirmed")]
+    SubscriberConfirmed {
+        subscriber_id: Uuid,
+        email: String,
+        name: String,
+    },
+    #[serde(rename = "subscriber.unsubscribed")]
+    SubscriberUnsubscribed { subscriber_id: Uuid, email: String },
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::IssuePublished { .. } => EVENT_NAMES[0],
+            WebhookEvent::IssueCompleted { .. } => EVENT_NAMES[1],
+            WebhookEvent::SubscriberConfirmed { .. } => EVENT_NAMES[2],
+            WebhookEvent::SubscriberUnsubscribed { .. } => EVENT_NAMES[3],
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Payload<'a> {
+    version: u32,
+    occurred_at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+// Queues a delivery for every webhook subscribed to the event. Pass the caller's transaction so the
+// event is only sent if whatever triggered it is committed.
+#[tracing::instrument(name = "Enqueue webhook event", skip(executor))]
+pub async fn enqueue_webhook_event<'c, E>(
+    executor: E,
+    event: &WebhookEvent,
+) -> Result<(), anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let payload = serialise(event)?;
+    sqlx::query!(
+        r#"
+    INSERT INTO webhook_deliveries (
+        delivery_id,
+        webhook_id,
+        event,
+        payload,
+        next_attempt_at
+    )
+    SELECT gen_random_uuid(), webhook_id, $1, $2, now()
+    FROM webhooks
+    WHERE $1 = ANY(events)
+    "#,
+        event.name(),
+        payload
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Queues a delivery to the confirmation callback from the configuration, which isn't stored as a
+// webhook. Only call it when a callback is configured.
+#[tracing::instrument(name = "Enqueue confirmation callback", skip(executor))]
+pub async fn enqueue_callback_event<'c, E>(
+    executor: E,
+    event: &WebhookEvent,
+) -> Result<(), anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let payload = serialise(event)?;
+    sqlx::query!(
+        r#"
+    INSERT INTO webhook_deliveries (
+        delivery_id,
+        webhook_id,
+        event,
+        payload,
+        next_attempt_at
+    )
+    VALUES ($1, NULL, $2, $3, now())
+    "#,
+        Uuid::new_v4(),
+        event.name(),
+        payload
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Serialised once up front, the stored text is exactly what gets signed and sent
+fn serialise(event: &WebhookEvent) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&Payload {
+        version: PAYLOAD_VERSION,
+        occurred_at: Utc::now(),
+        event,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{Payload, WebhookEvent};
+
+    #[test]
+    fn payloads_carry_the_version_event_name_and_data() {
+        let event = WebhookEvent::IssueCompleted {
+            newsletter_issue_id: Uuid::nil(),
+            title: "Issue X".into(),
+            delivered: 4231,
+            failed: 2,
+        };
+        let payload = serde_json::to_value(Payload {
+            version: 1,
+            occurred_at: chrono::Utc::now(),
+            event: &event,
+        })
+        .unwrap();
+
+        assert_eq!(payload["version"], 1);
+        assert_eq!(payload["event"], "issue.completed");
+        assert_eq!(payload["data"]["title"], "Issue X");
+        assert_eq!(payload["data"]["delivered"], 4231);
+        assert_eq!(payload["data"]["failed"], 2);
+        assert!(payload["occurred_at"].is_string());
+    }
+}
@@ -0,0 +1,7 @@
+mod dispatcher;
+mod event;
+
+pub use dispatcher::{run_dispatcher_until_stopped, signature, try_dispatch_webhook};
+pub use event::{
+    EVENT_NAMES, PAYLOAD_VERSION, WebhookEvent, enqueue_callback_event, enqueue_webhook_event,
+};
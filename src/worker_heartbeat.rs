@@ -0,0 +1,132 @@
+use std::sync::Mutex;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::issue_delivery_worker::ExecutionOutcome;
+
+// Rows of workers that stopped beating this long ago are gone for good, e.g. from an old deploy
+const FORGET_AFTER: chrono::Duration = chrono::Duration::days(1);
+
+// Kept in the database rather than in memory so the health check still works once the worker runs
+// in a process of its own
+pub struct WorkerHeartbeat {
+    pub worker_id: String,
+    pub last_beat: DateTime<Utc>,
+    pub last_task_at: Option<DateTime<Utc>>,
+    pub queue_depth: i64,
+}
+
+impl WorkerHeartbeat {
+    pub fn age(&self, now: DateTime<Utc>) -> chrono::Duration {
+        now - self.last_beat
+    }
+}
+
+// Tells workers on different hosts, and restarts on the same one, apart
+pub fn worker_id() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".into());
+    format!("{host}:{}", std::process::id())
+}
+
+// Shared by a process's worker loops. Every pass beats, even a failed one, since a worker that
+// keeps erroring is still running, but the row is only written once per interval.
+pub struct Heartbeat {
+    worker_id: String,
+    interval: chrono::Duration,
+    state: Mutex<HeartbeatState>,
+}
+
+#[derive(Default)]
+struct HeartbeatState {
+    written_at: Option<DateTime<Utc>>,
+    // The newest delivery not written yet
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl Heartbeat {
+    pub fn new(worker_id: String, interval: std::time::Duration) -> Self {
+        Self {
+            worker_id,
+            interval: chrono::Duration::from_std(interval).unwrap_or(chrono::Duration::MAX),
+            state: Mutex::new(HeartbeatState::default()),
+        }
+    }
+
+    // Only a delivered email moves `last_task_at` forward, skipped and deferred tasks don't
+    pub async fn beat(
+        &self,
+        pool: &PgPool,
+        outcome: Option<&ExecutionOutcome>,
+        now: DateTime<Utc>,
+    ) -> Result<(), anyhow::Error> {
+        let last_task_at = {
+            let mut state = self.state.lock().unwrap();
+            if matches!(outcome, Some(ExecutionOutcome::Delivered)) {
+                state.delivered_at = Some(now);
+            }
+            if state
+                .written_at
+                .is_some_and(|written_at| now - written_at < self.interval)
+            {
+                return Ok(());
+            }
+            state.written_at = Some(now);
+            state.delivered_at.take()
+        };
+        record_heartbeat(pool, &self.worker_id, last_task_at, now).await
+    }
+}
+
+// Also forgets workers that stopped beating a day ago, every restart beats under a new id
+#[tracing::instrument(skip(pool))]
+pub async fn record_heartbeat(
+    pool: &PgPool,
+    worker_id: &str,
+    last_task_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO worker_heartbeats (worker_id, last_beat, last_task_at, queue_depth)
+        VALUES ($1, $2, $3, (SELECT COUNT(*) FROM issue_delivery_queue))
+        ON CONFLICT (worker_id) DO UPDATE
+        SET last_beat = EXCLUDED.last_beat,
+            last_task_at = COALESCE(EXCLUDED.last_task_at, worker_heartbeats.last_task_at),
+            queue_depth = EXCLUDED.queue_depth
+        "#,
+        worker_id,
+        now,
+        last_task_at,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to record the worker's heartbeat.")?;
+    sqlx::query!(
+        r#"DELETE FROM worker_heartbeats WHERE last_beat < $1"#,
+        now - FORGET_AFTER
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to forget workers that stopped beating.")?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn latest_heartbeat(pool: &PgPool) -> Result<Option<WorkerHeartbeat>, anyhow::Error> {
+    sqlx::query_as!(
+        WorkerHeartbeat,
+        r#"
+        SELECT worker_id, last_beat, last_task_at, queue_depth
+        FROM worker_heartbeats
+        ORDER BY last_beat DESC
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the latest worker heartbeat.")
+}
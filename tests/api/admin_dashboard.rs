@@ -35,3 +35,86 @@ async fn logout_clears_session_state() {
     let response = app.get_admin_dashboard().await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn a_trailing_slash_reaches_the_same_dashboard() {
+    let app = spawn_app().await;
+    let dashboard = format!("{}/admin/dashboard/", &app.address);
+
+    let response = app.api_client.get(&dashboard).send().await.unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    app.test_user.login(&app).await;
+    let response = app.api_client.get(&dashboard).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn a_rejected_notification_address_is_escaped_on_the_dashboard() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_notification_settings(&serde_json::json!({
+        "email": "<script>alert(1)</script>",
+    }))
+    .await;
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!html_page.contains("<script>"));
+}
+
+#[tokio::test]
+async fn the_dashboard_shows_recent_admin_activity_newest_first() {
+    let app = spawn_app().await;
+    app.clock.set(chrono::Utc::now());
+    app.test_user.login(&app).await;
+    app.publish_issue("March <issue>").await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    // A little over, the entries were made after the clock was set
+    app.clock.advance(chrono::Duration::minutes(121));
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    let username = &app.test_user.username;
+    let changed = html_page
+        .find(&format!("{username} changed their password 2h ago"))
+        .expect("The password change is missing");
+    let published = html_page
+        .find(&format!(
+            "{username} published <a href=\"/admin/newsletter/"
+        ))
+        .expect("The published issue is missing");
+    assert!(changed < published);
+    assert!(html_page.contains("/delivery-report\">'March &lt;issue&gt;'</a> 2h ago"));
+}
+
+#[tokio::test]
+async fn activity_about_a_deleted_issue_is_shown_without_a_link() {
+    let app = spawn_app().await;
+    app.clock.set(chrono::Utc::now());
+    app.test_user.login(&app).await;
+    app.publish_issue("March issue").await;
+    sqlx::query!("UPDATE newsletter_issues SET deleted_at = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains(&format!(
+        "{} published 'March issue' just now",
+        app.test_user.username
+    )));
+    assert!(!html_page.contains("/delivery-report"));
+}
@@ -0,0 +1,63 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+
+async fn get_with_host(address: &str, path: &str, host: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!("{address}{path}"))
+        .header("Host", host)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn a_request_for_an_unknown_host_is_rejected_with_a_400() {
+    let app = spawn_app().await;
+
+    let response = get_with_host(&app.address, "/login", "attacker.example").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn a_request_for_the_base_url_host_proceeds_with_or_without_a_port() {
+    let app = spawn_app().await;
+
+    let with_port = get_with_host(&app.address, "/login", &format!("127.0.0.1:{}", app.port)).await;
+    let without_port = get_with_host(&app.address, "/login", "127.0.0.1").await;
+
+    assert_eq!(with_port.status().as_u16(), 200);
+    assert_eq!(without_port.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn configured_hosts_replace_the_base_url_host() {
+    let app = spawn_app_with(|c| {
+        c.application.allowed_hosts = vec!["newsletter.example.com".into(), "localhost:8000".into()]
+    })
+    .await;
+
+    let configured = get_with_host(&app.address, "/login", "newsletter.example.com:443").await;
+    let with_its_port = get_with_host(&app.address, "/login", "localhost:8000").await;
+    let with_another_port = get_with_host(&app.address, "/login", "localhost:9000").await;
+    let base_url_host = get_with_host(&app.address, "/login", "127.0.0.1").await;
+
+    assert_eq!(configured.status().as_u16(), 200);
+    assert_eq!(with_its_port.status().as_u16(), 200);
+    assert_eq!(with_another_port.status().as_u16(), 400);
+    assert_eq!(base_url_host.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn health_checks_answer_whatever_host_they_are_asked_for() {
+    let app = spawn_app().await;
+
+    for path in [
+        "/health_check",
+        "/health_check/live",
+        "/health_check/ready/",
+    ] {
+        let response = get_with_host(&app.address, path, "10.0.0.12:8000").await;
+
+        assert_ne!(response.status().as_u16(), 400, "{path} was rejected");
+    }
+}
@@ -0,0 +1,199 @@
+use chrono::Duration;
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+async fn generate_token(app: &TestApp) -> String {
+    let response = app.post_api_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["token"].as_str().unwrap().to_owned()
+}
+
+async fn generate_token_without_revoking(app: &TestApp) -> String {
+    let response = app
+        .post_api_tokens(&serde_json::json!({ "expires_in_days": 30 }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["token"].as_str().unwrap().to_owned()
+}
+
+// A client without a cookie store, so only the bearer token can authenticate it
+async fn get_templates_with_token(app: &TestApp, token: &str) -> reqwest::Response {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}/admin/newsletter/templates", &app.address))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_generate_an_api_token() {
+    let app = spawn_app().await;
+
+    let response = app.post_api_token().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_generated_token_authenticates_admin_requests() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token(&app).await;
+
+    let response = get_templates_with_token(&app, &token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_token_is_stored_hashed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token(&app).await;
+
+    let stored = sqlx::query!(
+        "SELECT token_hash FROM api_tokens WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .token_hash;
+    assert!(stored.starts_with("$argon2id$"));
+    assert!(!stored.contains(token.split_once('.').unwrap().1));
+}
+
+#[tokio::test]
+async fn an_invalid_token_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token(&app).await;
+
+    let response = get_templates_with_token(&app, &format!("{token}x")).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()["WWW-Authenticate"],
+        r#"Bearer realm="admin""#
+    );
+
+    let response = get_templates_with_token(&app, "not-a-token").await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn regenerating_the_token_revokes_the_previous_one() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let old_token = generate_token(&app).await;
+
+    let new_token = generate_token(&app).await;
+
+    assert_ne!(old_token, new_token);
+    let response = get_templates_with_token(&app, &old_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = get_templates_with_token(&app, &new_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_revoked_token_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token(&app).await;
+
+    let response = app.delete_api_token().await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = get_templates_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_api_tokens() {
+    let app = spawn_app().await;
+
+    let response = app.get_api_tokens().await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.post_api_tokens(&serde_json::json!({})).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .delete_api_token_by_id(&Uuid::new_v4().to_string())
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn api_tokens_can_be_listed_used_and_revoked_one_at_a_time() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app.post_api_tokens(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let created: serde_json::Value = response.json().await.unwrap();
+    let token_id = created["id"].as_str().unwrap().to_owned();
+    let token = created["token"].as_str().unwrap().to_owned();
+    let other_token = generate_token_without_revoking(&app).await;
+
+    let response = get_templates_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens: serde_json::Value = app.get_api_tokens().await.json().await.unwrap();
+    let tokens = tokens.as_array().unwrap();
+    assert_eq!(tokens.len(), 2);
+    let listed = &tokens[0];
+    assert_eq!(listed["id"], token_id.as_str());
+    assert_eq!(
+        listed["masked_token"],
+        format!("****{}", &token[token.len() - 4..])
+    );
+    assert!(listed["expires_at"].is_null());
+    assert!(!listed["last_used_at"].is_null());
+
+    let response = app.delete_api_token_by_id(&token_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = get_templates_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // The user's other tokens are unaffected
+    let response = get_templates_with_token(&app, &other_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.delete_api_token_by_id(&token_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn an_expired_api_token_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token_without_revoking(&app).await;
+
+    // Part 1 - A day before it expires the token still works
+    app.clock.advance(Duration::days(29));
+    let response = get_templates_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Part 2 - Past its 30 days it is rejected
+    app.clock.advance(Duration::days(2));
+    let response = get_templates_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_expiry_outside_the_allowed_range_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for days in [0, 3651, u32::MAX] {
+        let response = app
+            .post_api_tokens(&serde_json::json!({ "expires_in_days": days }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400, "{days} days was accepted");
+    }
+}
@@ -0,0 +1,200 @@
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, spawn_app};
+
+async fn generate_token(app: &TestApp) -> String {
+    let response = app.post_api_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["token"].as_str().unwrap().to_owned()
+}
+
+// A client without a cookie store, so only what the request carries can authenticate it
+fn cookieless_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn newsletters_are_listed_for_a_bearer_token() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    let token = generate_token(&app).await;
+
+    let response = cookieless_client()
+        .get(format!("{}/api/v1/newsletters", &app.address))
+        .bearer_auth(&token)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let issues: serde_json::Value = response.json().await.unwrap();
+    let issues = issues.as_array().unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["title"], "Newsletter title");
+    assert_eq!(issues[0]["archived"], false);
+}
+
+async fn publish_tagged_issue(app: &TestApp, title: &str, tags: &str) {
+    app.post_newsletter(&serde_json::json!({
+        "title": title,
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "tags": tags,
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn newsletters_can_be_filtered_by_tag() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_tagged_issue(&app, "Rust issue", "rust").await;
+    publish_tagged_issue(&app, "Backend issue", "backend").await;
+    let token = generate_token(&app).await;
+
+    let response = cookieless_client()
+        .get(format!("{}/api/v1/newsletters?tag=rust", &app.address))
+        .bearer_auth(&token)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let issues: serde_json::Value = response.json().await.unwrap();
+    let issues = issues.as_array().unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["title"], "Rust issue");
+    assert_eq!(issues[0]["tags"], serde_json::json!(["rust"]));
+}
+
+#[tokio::test]
+async fn tags_are_listed_with_how_many_issues_carry_them() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_tagged_issue(&app, "Rust issue", "Rust").await;
+    publish_tagged_issue(&app, "Backend issue", "backend, rust").await;
+    let token = generate_token(&app).await;
+
+    let response = cookieless_client()
+        .get(format!("{}/api/v1/newsletters/tags", &app.address))
+        .bearer_auth(&token)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let tags: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        tags,
+        serde_json::json!([
+            { "tag": "rust", "count": 2 },
+            { "tag": "backend", "count": 1 },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn a_session_cookie_alone_is_not_accepted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // The logged in client sends its session cookie, but no token
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/newsletters", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()["WWW-Authenticate"],
+        r#"Bearer realm="admin""#
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "Invalid API token.");
+}
+
+#[tokio::test]
+async fn clients_that_do_not_accept_json_get_a_406() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token(&app).await;
+
+    let response = cookieless_client()
+        .get(format!("{}/api/v1/newsletters", &app.address))
+        .bearer_auth(&token)
+        .header("Accept", "text/html")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 406);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "This API only responds with application/json."
+    );
+}
+
+#[tokio::test]
+async fn api_requests_without_a_version_point_to_the_latest_one() {
+    let app = spawn_app().await;
+
+    let response = cookieless_client()
+        .get(format!("{}/api/newsletters", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "The API is versioned, try /api/v1/newsletters"
+    );
+}
+
+#[tokio::test]
+async fn unknown_api_versions_and_endpoints_get_json_404s() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = generate_token(&app).await;
+
+    let response = cookieless_client()
+        .get(format!("{}/api/v2/newsletters", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "API version v2 does not exist, the latest is v1."
+    );
+
+    let response = cookieless_client()
+        .get(format!("{}/api/v1/subscribers", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "This endpoint does not exist.");
+}
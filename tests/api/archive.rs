@@ -0,0 +1,298 @@
+use crate::helpers::{TestApp, spawn_app};
+
+const ATOM: &str = "http://www.w3.org/2005/Atom";
+
+async fn get_feed(app: &TestApp) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/archive/feed.xml", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// (id, title, content, link to the issue's page) of every entry, in feed order
+fn parse_entries(feed: &str) -> Vec<(String, String, String, String)> {
+    let document = roxmltree::Document::parse(feed).expect("The feed is not valid XML.");
+    let root = document.root_element();
+    assert!(root.has_tag_name((ATOM, "feed")));
+    root.children()
+        .filter(|n| n.has_tag_name((ATOM, "entry")))
+        .map(|entry| {
+            let text = |name: &str| {
+                entry
+                    .children()
+                    .find(|n| n.has_tag_name((ATOM, name)))
+                    .and_then(|n| n.text())
+                    .unwrap_or_default()
+                    .to_owned()
+            };
+            let link = entry
+                .children()
+                .find(|n| n.has_tag_name((ATOM, "link")) && n.attribute("rel") == Some("alternate"))
+                .and_then(|n| n.attribute("href"))
+                .unwrap_or_default()
+                .to_owned();
+            (text("id"), text("title"), text("content"), link)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn the_feed_lists_published_issues_newest_first() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("First issue").await;
+    app.publish_issue("Second issue").await;
+
+    let response = get_feed(&app).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/atom+xml; charset=utf-8"
+    );
+    assert!(response.headers().get("Last-Modified").is_some());
+    let entries = parse_entries(&response.text().await.unwrap());
+    let titles: Vec<_> = entries
+        .iter()
+        .map(|(_, title, _, _)| title.as_str())
+        .collect();
+    assert_eq!(titles, ["Second issue", "First issue"]);
+    assert_eq!(entries[0].2, "<p>Second issue as HTML</p>");
+    let issue = sqlx::query!(
+        "SELECT newsletter_issue_id, slug FROM newsletter_issues WHERE title = 'Second issue'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        entries[0].0,
+        format!("urn:uuid:{}", issue.newsletter_issue_id)
+    );
+    assert_eq!(
+        entries[0].3,
+        format!(
+            "{}/archive/{}",
+            app.configuration.application.base_url.trim_end_matches('/'),
+            issue.slug
+        )
+    );
+}
+
+#[tokio::test]
+async fn an_unchanged_feed_is_not_sent_again() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("First issue").await;
+    app.publish_issue("Second issue").await;
+    let last_modified = get_feed(&app).await.headers()["Last-Modified"].clone();
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive/feed.xml", &app.address))
+        .header("If-Modified-Since", last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive/feed.xml", &app.address))
+        .header("If-Modified-Since", "Thu, 01 Jan 2015 00:00:00 GMT")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn soft_deleted_issues_disappear_from_the_feed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("First issue").await;
+    app.publish_issue("Second issue").await;
+
+    sqlx::query!("UPDATE newsletter_issues SET deleted_at = now() WHERE title = 'First issue'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = get_feed(&app).await;
+
+    let entries = parse_entries(&response.text().await.unwrap());
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].1, "Second issue");
+}
+
+#[tokio::test]
+async fn issues_sharing_a_title_get_numbered_slugs() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("Hello, World!").await;
+    app.publish_issue("Hello, World!").await;
+
+    let slugs: Vec<String> =
+        sqlx::query!("SELECT slug FROM newsletter_issues ORDER BY published_at::timestamptz")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.slug)
+            .collect();
+    assert_eq!(slugs, ["hello-world", "hello-world-2"]);
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive/hello-world-2", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("<p>Hello, World! as HTML</p>")
+    );
+}
+
+#[tokio::test]
+async fn issues_published_together_with_the_same_title_both_get_a_slug() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let newsletter = || {
+        serde_json::json!({
+            "title": "Hello, World!",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        })
+    };
+    let (first, second) = (newsletter(), newsletter());
+
+    let (first, second) = tokio::join!(app.post_newsletter(&first), app.post_newsletter(&second));
+
+    assert_eq!(first.status().as_u16(), 303);
+    assert_eq!(second.status().as_u16(), 303);
+    let mut slugs: Vec<String> = sqlx::query!("SELECT slug FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.slug)
+        .collect();
+    slugs.sort();
+    assert_eq!(slugs, ["hello-world", "hello-world-2"]);
+}
+
+#[tokio::test]
+async fn the_id_of_an_issue_redirects_to_its_slug() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("Hello, World!").await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive/{issue_id}", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 301);
+    assert_eq!(response.headers()["Location"], "/archive/hello-world");
+}
+
+#[tokio::test]
+async fn an_unknown_slug_is_not_found() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive/no-such-issue", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_sitemap_lists_the_archive_and_every_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("Hello, World!").await;
+    app.publish_issue("Hello, World!").await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/sitemap.xml", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    let document = roxmltree::Document::parse(&body).expect("The sitemap is not valid XML.");
+    let urls: Vec<_> = document
+        .descendants()
+        .filter(|n| n.has_tag_name("url"))
+        .map(|url| {
+            let child = |name: &str| {
+                url.children()
+                    .find(|n| n.has_tag_name(name))
+                    .and_then(|n| n.text())
+                    .map(str::to_owned)
+            };
+            (child("loc").unwrap(), child("lastmod"))
+        })
+        .collect();
+    let locs: Vec<_> = urls.iter().map(|(loc, _)| loc.as_str()).collect();
+    assert_eq!(
+        locs,
+        [
+            app.base_url.join_path("archive").to_string(),
+            app.base_url.join_path("archive/hello-world-2").to_string(),
+            app.base_url.join_path("archive/hello-world").to_string(),
+        ]
+    );
+    assert!(urls.iter().all(|(_, lastmod)| lastmod.is_some()));
+}
+
+#[tokio::test]
+async fn the_archive_can_be_filtered_by_tag() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for (title, tags) in [("Rust issue", "Rust"), ("Backend issue", "backend, rust")] {
+        app.post_newsletter(&serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "tags": tags,
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    }
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive?tag=backend", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Backend issue"));
+    assert!(!body.contains("Rust issue"));
+    // The tag cloud still lists every tag, most used first
+    assert!(body.contains(
+        r#"<a href="/archive?tag=rust">rust (2)</a>
+<a href="/archive?tag=backend">backend (1)</a>"#
+    ));
+}
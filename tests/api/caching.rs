@@ -0,0 +1,58 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn public_pages_are_revalidated_with_an_etag() {
+    let app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let archive = format!("{}/archive", &app.address);
+
+    let response = client.get(&archive).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Cache-Control"], "public, max-age=60");
+    let etag = response.headers()["ETag"].clone();
+    assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+    let response = client
+        .get(&archive)
+        .header("If-None-Match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+    assert_eq!(response.headers()["ETag"], etag);
+    assert!(response.text().await.unwrap().is_empty());
+
+    app.test_user.login(&app).await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "A new issue",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    let response = client
+        .get(&archive)
+        .header("If-None-Match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(response.headers()["ETag"], etag);
+}
+
+#[tokio::test]
+async fn admin_pages_are_not_cached() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.headers().get("ETag").is_none());
+    assert!(response.headers().get("Cache-Control").is_none());
+}
@@ -1,6 +1,8 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use chrono::Duration;
 use uuid::Uuid;
 
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+
 #[tokio::test]
 async fn you_must_be_logged_in_to_see_the_change_password_form() {
     let app = spawn_app().await;
@@ -126,3 +128,172 @@ async fn change_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn all_validation_errors_are_reported_together() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "short",
+            "new_password_check": "shorter",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(
+        html_page.contains("<p><i>Password must be between 12 and 128 characters, got 5</i></p>")
+    );
+    assert!(html_page.contains(
+        "<p><i>You entered two different new passwords - \
+        the field values must match.</i></p>"
+    ));
+    assert!(!html_page.contains("The current password is incorrect."));
+}
+
+#[tokio::test]
+async fn a_recent_password_cant_be_reused() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    app.get_change_password_html().await;
+
+    // Back to the password it replaced
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &new_password,
+            "new_password": &app.test_user.password,
+            "new_password_check": &app.test_user.password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(
+        html_page
+            .contains("<p><i>The new password must differ from your last 5 passwords.</i></p>")
+    );
+    assert!(!html_page.contains("Your password has been changed."));
+    // The password is still the one it was changed to
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn only_the_configured_number_of_passwords_is_remembered() {
+    let app = spawn_app_with(|c| c.password_history_depth = 2).await;
+    app.test_user.login(&app).await;
+    let passwords: Vec<String> = (0..3).map(|_| Uuid::new_v4().to_string()).collect();
+    let mut current_password = app.test_user.password.clone();
+    for password in &passwords {
+        app.post_change_password(&serde_json::json!({
+            "current_password": &current_password,
+            "new_password": password,
+            "new_password_check": password,
+        }))
+        .await;
+        current_password = password.clone();
+    }
+
+    // The first password has dropped out of the history by now
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &current_password,
+            "new_password": &passwords[0],
+            "new_password_check": &passwords[0],
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("Your password has been changed."));
+    let n_remembered = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM password_history")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_remembered, 2);
+}
+
+#[tokio::test]
+async fn a_fresh_session_can_change_the_password_without_the_current_one() {
+    let app = spawn_app_with(|c| c.session.reauthentication_window_minutes = 15).await;
+    app.test_user.login(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.clock.advance(Duration::minutes(10));
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": "",
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_stale_session_must_enter_the_current_password() {
+    let app = spawn_app_with(|c| c.session.reauthentication_window_minutes = 15).await;
+    app.test_user.login(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.clock.advance(Duration::minutes(16));
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": "",
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Enter your current password to change it.</i></p>"));
+    // Still signed in with the old password
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_current_password_is_always_required_by_default() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.post_change_password(&serde_json::json!({
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .await;
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Enter your current password to change it.</i></p>"));
+}
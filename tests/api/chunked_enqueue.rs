@@ -0,0 +1,108 @@
+use std::num::NonZeroUsize;
+
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::delivery_queue::resume_interrupted_enqueues;
+
+use crate::helpers::{TestApp, create_confirmed_subscriber_with_email, spawn_app_with};
+
+async fn spawn_app_with_five_subscribers() -> TestApp {
+    let app =
+        spawn_app_with(|c| c.delivery.enqueue_chunk_size = NonZeroUsize::new(2).unwrap()).await;
+    for email in [
+        "ged@earthsea.org",
+        "tenar@earthsea.org",
+        "arha@earthsea.org",
+        "genly@gethen.net",
+        "shevek@anarres.io",
+    ] {
+        create_confirmed_subscriber_with_email(&app, email).await;
+    }
+    app.test_user.login(&app).await;
+    app
+}
+
+#[tokio::test]
+async fn an_audience_bigger_than_a_chunk_is_enqueued_in_full() {
+    let app = spawn_app_with_five_subscribers().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(5)
+        .mount(&app.email_server)
+        .await;
+
+    app.publish_issue("Newsletter title").await;
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 5 subscribers.</i></p>"));
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!(
+        "SELECT status, estimated_audience, n_delivered, enqueue_cursor FROM newsletter_issues"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.status, "sent");
+    assert_eq!(issue.estimated_audience, 5);
+    assert_eq!(issue.n_delivered, 5);
+    assert_eq!(issue.enqueue_cursor, None);
+}
+
+#[tokio::test]
+async fn an_interrupted_enqueue_is_resumed_without_sending_twice() {
+    let app = spawn_app_with_five_subscribers().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(5)
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter title").await;
+    // Rewind to just after the first chunk, as if publishing had been cut short there
+    let second_id = sqlx::query!("SELECT id FROM subscriptions ORDER BY id OFFSET 1 LIMIT 1")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_id > $1",
+        second_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE newsletter_issues \
+         SET status = 'enqueuing', enqueue_cursor = $1, estimated_audience = 2",
+        second_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Part 1 - Nothing goes out while the issue is still being enqueued
+    app.dispatch_all_pending_emails().await;
+    let issue = sqlx::query!("SELECT status, n_delivered FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "enqueuing");
+    assert_eq!(issue.n_delivered, 0);
+
+    // Part 2 - The worker picks it up where it stopped
+    resume_interrupted_enqueues(&app.db_pool, &app.pii_cipher, NonZeroUsize::new(2).unwrap())
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+    let issue =
+        sqlx::query!("SELECT status, estimated_audience, n_delivered FROM newsletter_issues")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(issue.status, "sent");
+    assert_eq!(issue.estimated_audience, 5);
+    assert_eq!(issue.n_delivered, 5);
+}
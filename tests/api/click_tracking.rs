@@ -0,0 +1,196 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::routes::link_hash;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+// Publishes an issue with two links to one subscriber and returns the issue id and delivered HTML
+async fn deliver_an_issue_with_links(app: &TestApp) -> (Uuid, String) {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Read <a href="https://example.com/first">this</a> and <a href="https://example.com/second?a=1&amp;b=2">that</a></p>"#,
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    (issue_id, body["HtmlBody"].as_str().unwrap().to_owned())
+}
+
+fn click_links(app: &TestApp, html: &str) -> Vec<reqwest::Url> {
+    linkify::LinkFinder::new()
+        .links(html)
+        .filter(|l| l.as_str().contains("/track/click/"))
+        .map(|l| {
+            let mut link = reqwest::Url::parse(l.as_str()).unwrap();
+            link.set_port(Some(app.port)).unwrap();
+            link
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn links_in_the_delivered_issue_are_rewritten() {
+    let app = spawn_app().await;
+
+    let (_, html) = deliver_an_issue_with_links(&app).await;
+
+    assert!(!html.contains("https://example.com/first"));
+    assert!(!html.contains("https://example.com/second"));
+    assert_eq!(click_links(&app, &html).len(), 2);
+}
+
+#[tokio::test]
+async fn following_a_rewritten_link_redirects_to_the_original_url() {
+    let app = spawn_app().await;
+    let (issue_id, html) = deliver_an_issue_with_links(&app).await;
+    let links = click_links(&app, &html);
+
+    let response = app.api_client.get(links[1].clone()).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://example.com/second?a=1&b=2"
+    );
+    let click = sqlx::query!("SELECT newsletter_issue_id, original_url FROM newsletter_clicks")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(click.newsletter_issue_id, issue_id);
+    assert_eq!(click.original_url, "https://example.com/second?a=1&b=2");
+}
+
+#[tokio::test]
+async fn unknown_links_are_not_redirected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/track/click/{}/{}",
+            &app.address,
+            Uuid::new_v4(),
+            "0".repeat(64)
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_link_from_another_issue_is_not_counted() {
+    let app = spawn_app().await;
+    deliver_an_issue_with_links(&app).await;
+    let delivery_id = sqlx::query!("SELECT delivery_id FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .delivery_id;
+    // Stored while rewriting some other issue
+    let other_url = "https://example.com/elsewhere";
+    sqlx::query!(
+        "INSERT INTO newsletter_links (link_hash, original_url) VALUES ($1, $2)",
+        link_hash(other_url),
+        other_url
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/track/click/{delivery_id}/{}",
+            &app.address,
+            link_hash(other_url)
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 302);
+    let n_clicks = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_clicks"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_clicks, 0);
+}
+
+#[tokio::test]
+async fn the_click_report_counts_clicks_per_link() {
+    let app = spawn_app().await;
+    let (issue_id, html) = deliver_an_issue_with_links(&app).await;
+    let links = click_links(&app, &html);
+    for link in [&links[0], &links[0], &links[1]] {
+        app.api_client.get(link.clone()).send().await.unwrap();
+    }
+
+    let report: serde_json::Value = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter/{issue_id}/click-report",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "links": [
+                { "url": "https://example.com/first", "clicks": 2, "unique_clicks": 1 },
+                { "url": "https://example.com/second?a=1&b=2", "clicks": 1, "unique_clicks": 1 },
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_click_report() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter/{}/click-report",
+            &app.address,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
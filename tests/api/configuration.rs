@@ -0,0 +1,180 @@
+use std::{fs, net::TcpListener, path::PathBuf, sync::Arc};
+
+use uuid::Uuid;
+use zero_to_prod::{
+    clock::SystemClock,
+    configuration::{get_configuration, load_configuration},
+    geo_lookup::NoGeoLookup,
+    startup::{Application, get_connection_pool},
+};
+
+// A copy of the repository's configuration files in a directory of its own
+fn copy_configuration() -> PathBuf {
+    let configuration_directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    fs::create_dir(&configuration_directory).unwrap();
+    for file in ["base.yaml", "local.yaml", "production.yaml"] {
+        fs::copy(
+            format!("configuration/{file}"),
+            configuration_directory.join(file),
+        )
+        .unwrap();
+    }
+    configuration_directory
+}
+
+fn variables(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn the_application_binds_to_the_port_from_a_custom_configuration_directory() {
+    // Grab a port that is free right now, then release it for the application to claim
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let configuration_directory = copy_configuration();
+    let base = fs::read_to_string("configuration/base.yaml").unwrap();
+    fs::write(
+        configuration_directory.join("base.yaml"),
+        base.replace("port: 8000", &format!("port: {port}")),
+    )
+    .unwrap();
+
+    let configuration = get_configuration(&configuration_directory).unwrap();
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    let application = Application::build(
+        configuration,
+        connection_pool,
+        Arc::new(SystemClock),
+        Arc::new(NoGeoLookup),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(application.port(), port);
+    fs::remove_dir_all(configuration_directory).unwrap();
+}
+
+#[test]
+fn each_configuration_layer_overrides_the_ones_before_it() {
+    let configuration_directory = copy_configuration();
+    let production = configuration_directory.join("production.yaml");
+    let environment = fs::read_to_string(&production).unwrap();
+    fs::write(&production, environment + "password_history_depth: 4\n").unwrap();
+    // Production leaves the public URL to the deployment
+    let production_variables = [
+        ("APP_ENVIRONMENT", "production"),
+        ("APP_APPLICATION__BASE_URL", "https://example.com"),
+    ];
+
+    // base.yaml, then the environment's file
+    let configuration = load_configuration(&configuration_directory, variables(&[])).unwrap();
+    assert_eq!(configuration.password_history_depth, 5);
+    let configuration =
+        load_configuration(&configuration_directory, variables(&production_variables)).unwrap();
+    assert_eq!(configuration.password_history_depth, 4);
+
+    // Then the local overrides
+    fs::write(
+        configuration_directory.join("overrides.yaml"),
+        "password_history_depth: 3\n",
+    )
+    .unwrap();
+    let configuration =
+        load_configuration(&configuration_directory, variables(&production_variables)).unwrap();
+    assert_eq!(configuration.password_history_depth, 3);
+
+    // Then environment variables
+    let configuration = load_configuration(
+        &configuration_directory,
+        variables(&[
+            ("APP_ENVIRONMENT", "production"),
+            ("APP_APPLICATION__BASE_URL", "https://example.com"),
+            ("APP_PASSWORD_HISTORY_DEPTH", "2"),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(configuration.password_history_depth, 2);
+    fs::remove_dir_all(configuration_directory).unwrap();
+}
+
+#[test]
+fn a_named_local_overrides_file_replaces_the_default_one_and_must_exist() {
+    let configuration_directory = copy_configuration();
+    fs::write(
+        configuration_directory.join("overrides.yaml"),
+        "application:\n  port: 8001\n",
+    )
+    .unwrap();
+    let second_instance = variables(&[("APP_CONFIG_LOCAL", "second-instance.yaml")]);
+
+    assert!(load_configuration(&configuration_directory, second_instance.clone()).is_err());
+
+    fs::write(
+        configuration_directory.join("second-instance.yaml"),
+        "application:\n  port: 8002\n",
+    )
+    .unwrap();
+    let configuration = load_configuration(&configuration_directory, second_instance).unwrap();
+    assert_eq!(configuration.application.port, 8002);
+    fs::remove_dir_all(configuration_directory).unwrap();
+}
+
+#[test]
+fn an_unknown_environment_is_rejected_with_the_supported_ones() {
+    let configuration_directory = copy_configuration();
+
+    let error = load_configuration(
+        &configuration_directory,
+        variables(&[("APP_ENVIRONMENT", "staging")]),
+    )
+    .err()
+    .unwrap();
+
+    assert_eq!(
+        error.to_string(),
+        "staging is not a supported environment. Use one of: local, production."
+    );
+    fs::remove_dir_all(configuration_directory).unwrap();
+}
+
+#[test]
+fn worker_settings_left_under_delivery_are_rejected() {
+    let configuration_directory = copy_configuration();
+    fs::write(
+        configuration_directory.join("overrides.yaml"),
+        "delivery:\n  max_emails_per_day: 100\n",
+    )
+    .unwrap();
+
+    let error = load_configuration(&configuration_directory, variables(&[]))
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "delivery.max_emails_per_day, set in {}, is now worker.max_emails_per_day",
+            configuration_directory.join("overrides.yaml").display()
+        )
+    );
+
+    // Environment variables too
+    fs::remove_file(configuration_directory.join("overrides.yaml")).unwrap();
+    let error = load_configuration(
+        &configuration_directory,
+        variables(&[("APP_DELIVERY__SENDING_WINDOW__START", "08:00")]),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "delivery.sending_window, set in environment variables, is now worker.sending_window"
+    );
+    fs::remove_dir_all(configuration_directory).unwrap();
+}
@@ -0,0 +1,81 @@
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+fn newsletter_with(text_content: &str, html_content: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": text_content,
+        "html_content": html_content,
+        "idempotency_key": Uuid::new_v4().to_string()
+    })
+}
+
+fn long_html() -> String {
+    "<p>A paragraph that goes on for quite a while about the week's news.</p>".repeat(10)
+}
+
+async fn n_issues(app: &TestApp) -> usize {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .len()
+}
+
+#[tokio::test]
+async fn an_empty_text_part_is_flagged_and_can_be_published_once_confirmed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let mut newsletter = newsletter_with("", &long_html());
+
+    // Part 1 - Nothing is queued until the author confirms
+    let response = app.post_newsletter(&newsletter).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(
+        "<p><i>The plain text content is empty but the HTML content isn&#x27;t.</i></p>"
+    ));
+    assert!(html_page.contains(r#"<input type="hidden" name="confirm" value="1">"#));
+    assert_eq!(n_issues(&app).await, 0);
+
+    // Part 2 - Confirming publishes it as submitted
+    newsletter["confirm"] = "1".into();
+    let response = app.post_newsletter(&newsletter).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 1 subscribers.</i></p>"));
+    assert_eq!(n_issues(&app).await, 1);
+}
+
+#[tokio::test]
+async fn a_part_much_shorter_than_the_other_is_flagged() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletter(&newsletter_with("See the HTML version.", &long_html()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("The plain text content is much shorter than the HTML content."));
+    assert_eq!(n_issues(&app).await, 0);
+}
+
+#[tokio::test]
+async fn short_issues_with_both_parts_are_not_flagged() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletter(&newsletter_with(
+            "Short and sweet.",
+            "<p>Short and sweet, with <b>bold</b>.</p>",
+        ))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    assert_eq!(n_issues(&app).await, 1);
+}
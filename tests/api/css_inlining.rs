@@ -0,0 +1,139 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app, spawn_app_with,
+};
+
+const STYLED_HTML: &str = "<style>\
+    .highlight { color: red; } \
+    @media (max-width: 600px) { .highlight { color: blue; } }\
+    </style>\
+    <p class=\"highlight\">Hello</p>";
+
+// Publishes an issue with a class based rule and returns the HTML the subscriber was sent
+async fn deliver_styled_issue(app: &TestApp, inline_css: bool) -> String {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let mut newsletter = serde_json::json!({
+        "title": "Styled issue",
+        "text_content": "Hello",
+        "html_content": STYLED_HTML,
+        "idempotency_key": Uuid::new_v4().to_string()
+    });
+    if inline_css {
+        newsletter["inline_css"] = "on".into();
+    }
+    app.post_newsletter(&newsletter).await;
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    body["HtmlBody"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn style_rules_are_inlined_when_requested() {
+    let app = spawn_app().await;
+
+    let html = deliver_styled_issue(&app, true).await;
+
+    assert!(html.contains(r#"<p class="highlight" style="color: red;">Hello</p>"#));
+    // Media queries can't be inlined so they are kept for the clients that understand them
+    assert!(html.contains("@media (max-width: 600px)"));
+
+    // The archive shows the issue as it was written
+    let archive_page = app
+        .api_client
+        .get(format!("{}/archive/styled-issue", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(archive_page.contains(STYLED_HTML));
+}
+
+#[tokio::test]
+async fn style_rules_are_left_alone_when_not_requested() {
+    let app = spawn_app().await;
+
+    let html = deliver_styled_issue(&app, false).await;
+
+    assert!(html.starts_with(STYLED_HTML));
+}
+
+#[tokio::test]
+async fn the_publish_form_defaults_to_the_configured_inlining() {
+    let app = spawn_app_with(|c| c.delivery.inline_css = true).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_newsletter_html().await;
+
+    assert!(html_page.contains(r#"<input type="checkbox" name="inline_css" checked>"#));
+}
+
+#[tokio::test]
+async fn the_inlined_preview_moves_style_rules_onto_the_elements() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // Published without inlining, the preview still shows the inlined version
+    app.post_newsletter(&serde_json::json!({
+        "title": "Styled issue",
+        "text_content": "Hello",
+        "html_content": STYLED_HTML,
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    let response = app.get_inlined_html(issue_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<p class="highlight" style="color: red;">Hello</p>"#));
+    assert!(!html.contains(".highlight { color: red; }"));
+}
+
+#[tokio::test]
+async fn the_inlined_preview_of_a_missing_issue_is_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_inlined_html(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_preview_the_inlined_html() {
+    let app = spawn_app().await;
+
+    let response = app.get_inlined_html(Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
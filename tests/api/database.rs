@@ -0,0 +1,29 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use zero_to_prod::{
+    configuration::{DEFAULT_CONFIGURATION_DIRECTORY, get_configuration},
+    startup::get_connection_pool,
+};
+
+#[tokio::test]
+async fn slow_queries_are_cut_off_at_the_statement_timeout() {
+    let mut configuration = get_configuration(Path::new(DEFAULT_CONFIGURATION_DIRECTORY))
+        .expect("Failed to read configuration.");
+    configuration.database.statement_timeout_milliseconds = 200;
+    let pool = get_connection_pool(&configuration.database).await;
+
+    let start = Instant::now();
+    let outcome = sqlx::query("SELECT pg_sleep(5)").execute(&pool).await;
+
+    let error = outcome.expect_err("The slow query was not cancelled.");
+    let code = error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .map(|code| code.into_owned());
+    // query_canceled
+    assert_eq!(code.as_deref(), Some("57014"));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,88 @@
+use chrono::{Duration, Utc};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::configuration::DomainRateLimit;
+
+use crate::helpers::{TestApp, create_confirmed_subscriber_with_email, spawn_app_with};
+
+// Recipients of the issue emails sent since the first `skip` requests, in order
+async fn newsletter_recipients(app: &TestApp, skip: usize) -> Vec<String> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .skip(skip)
+        .map(|request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["To"].as_str().unwrap().to_owned()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn a_throttled_domain_waits_while_other_domains_keep_sending() {
+    let app = spawn_app_with(|c| {
+        c.worker.domain_rate_limits = vec![DomainRateLimit {
+            domain: "gmail.com".into(),
+            per_second: 1.0,
+        }]
+    })
+    .await;
+    for email in [
+        "ged@gmail.com",
+        "tenar@gmail.com",
+        "arha@gmail.com",
+        "genly@gethen.net",
+        "shevek@anarres.io",
+    ] {
+        create_confirmed_subscriber_with_email(&app, email).await;
+    }
+    let n_confirmations = app.email_server.received_requests().await.unwrap().len();
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(5)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    // Stopped so the test decides when a second has passed
+    app.clock.set(Utc::now());
+
+    // Part 1 - One gmail.com delivery goes out, the other domains aren't held up behind the rest
+    app.dispatch_all_pending_emails().await;
+    let sent = newsletter_recipients(&app, n_confirmations).await;
+    let to_gmail = sent.iter().filter(|to| to.ends_with("@gmail.com")).count();
+    assert_eq!(to_gmail, 1);
+    assert!(sent.contains(&"genly@gethen.net".to_owned()));
+    assert!(sent.contains(&"shevek@anarres.io".to_owned()));
+
+    // Part 2 - Nothing more for gmail.com until a second has passed
+    app.clock.advance(Duration::milliseconds(500));
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(newsletter_recipients(&app, n_confirmations).await.len(), 3);
+
+    // Part 3 - Then one more each second
+    app.clock.advance(Duration::milliseconds(500));
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(newsletter_recipients(&app, n_confirmations).await.len(), 4);
+    app.clock.advance(Duration::seconds(1));
+    app.dispatch_all_pending_emails().await;
+    let sent = newsletter_recipients(&app, n_confirmations).await;
+    assert_eq!(sent.len(), 5);
+    assert!(sent[3..].iter().all(|to| to.ends_with("@gmail.com")));
+    let issue = sqlx::query!("SELECT status FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "sent");
+}
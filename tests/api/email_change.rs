@@ -0,0 +1,236 @@
+use chrono::Duration;
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, create_confirmed_subscriber_with_email, spawn_app};
+
+async fn post_email_change(app: &TestApp, subscriber_id: Uuid, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscribers/{subscriber_id}/email",
+            &app.address
+        ))
+        .json(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// Every email sent to `to` since the app started, as (subject, plain text body)
+async fn emails_to(app: &TestApp, to: &str) -> Vec<(String, String)> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+        .filter(|body| body["To"] == to)
+        .map(|body| {
+            (
+                body["Subject"].as_str().unwrap().to_owned(),
+                body["TextBody"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+// The confirmation link in the last email sent
+async fn last_confirmation_link(app: &TestApp) -> reqwest::Url {
+    let requests = app.email_server.received_requests().await.unwrap();
+    app.get_confirmation_links(requests.last().unwrap()).html
+}
+
+async fn publish_and_deliver(app: &TestApp, title: &str) {
+    app.post_newsletter(&serde_json::json!({
+        "title": title,
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn setup() -> (TestApp, Uuid) {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    (app, subscriber_id)
+}
+
+#[tokio::test]
+async fn a_confirmed_email_change_moves_the_subscription_to_the_new_address() {
+    let (app, subscriber_id) = setup().await;
+
+    // Part 1 - The new address is asked to confirm
+    let response = post_email_change(&app, subscriber_id, "sparrowhawk@earthsea.org").await;
+    assert_eq!(response.status().as_u16(), 202);
+    let sent = emails_to(&app, "sparrowhawk@earthsea.org").await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "Confirm your new address");
+    let confirmation_link = last_confirmation_link(&app).await;
+
+    // Part 2 - Confirming swaps the address and tells the old one
+    let response = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Your subscription has moved to sparrowhawk@earthsea.org."));
+    let subscriber = sqlx::query!(
+        "SELECT email, pending_email, status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        subscriber.email.as_deref(),
+        Some("sparrowhawk@earthsea.org")
+    );
+    assert_eq!(subscriber.pending_email, None);
+    assert_eq!(subscriber.status, "confirmed");
+    let notices = emails_to(&app, "ged@earthsea.org").await;
+    let notice = notices.last().unwrap();
+    assert_eq!(notice.0, "Your subscription has moved");
+    assert!(notice.1.contains("sparrowhawk@earthsea.org"));
+    let audit = sqlx::query!(
+        "SELECT action, subject, details FROM audit_log \
+         WHERE action LIKE 'subscriber.email_%' ORDER BY created_at"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(audit.len(), 2);
+    assert_eq!(audit[0].action, "subscriber.email_change_requested");
+    assert_eq!(audit[1].action, "subscriber.email_changed");
+    assert_eq!(audit[1].subject, subscriber_id.to_string());
+    // Neither address is kept in the audit log
+    assert!(audit.iter().all(|a| a.details.is_none()));
+
+    // Part 3 - Issues go to the new address from now on
+    publish_and_deliver(&app, "After the move").await;
+    let issues = emails_to(&app, "sparrowhawk@earthsea.org").await;
+    assert_eq!(issues.last().unwrap().0, "After the move");
+    assert!(
+        emails_to(&app, "ged@earthsea.org")
+            .await
+            .iter()
+            .all(|(subject, _)| subject != "After the move")
+    );
+}
+
+#[tokio::test]
+async fn an_unconfirmed_email_change_leaves_the_old_address_active() {
+    let (app, subscriber_id) = setup().await;
+
+    post_email_change(&app, subscriber_id, "sparrowhawk@earthsea.org").await;
+    publish_and_deliver(&app, "Before the move").await;
+
+    let issues = emails_to(&app, "ged@earthsea.org").await;
+    assert_eq!(issues.last().unwrap().0, "Before the move");
+    let subscriber: serde_json::Value = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{subscriber_id}",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber["email"], "ged@earthsea.org");
+    assert_eq!(subscriber["pending_email"], "sparrowhawk@earthsea.org");
+}
+
+#[tokio::test]
+async fn an_email_change_link_only_works_once() {
+    let (app, subscriber_id) = setup().await;
+    post_email_change(&app, subscriber_id, "sparrowhawk@earthsea.org").await;
+    let confirmation_link = last_confirmation_link(&app).await;
+
+    let first = reqwest::get(confirmation_link.clone()).await.unwrap();
+    let second = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_expired_email_change_link_is_rejected() {
+    let (app, subscriber_id) = setup().await;
+    post_email_change(&app, subscriber_id, "sparrowhawk@earthsea.org").await;
+    let confirmation_link = last_confirmation_link(&app).await;
+
+    app.clock.advance(Duration::hours(49));
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    let subscriber = sqlx::query!(
+        "SELECT email, pending_email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(subscriber.email.as_deref(), Some("ged@earthsea.org"));
+    assert_eq!(subscriber.pending_email, None);
+}
+
+#[tokio::test]
+async fn an_email_change_to_an_address_already_subscribed_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "tenar@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let response = post_email_change(&app, subscriber_id, "tenar@earthsea.org").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    // Nothing beyond the welcome email
+    assert_eq!(emails_to(&app, "tenar@earthsea.org").await.len(), 1);
+}
+
+#[tokio::test]
+async fn an_address_subscribed_before_the_change_is_confirmed_is_not_taken_over() {
+    let (app, subscriber_id) = setup().await;
+    post_email_change(&app, subscriber_id, "sparrowhawk@earthsea.org").await;
+    let confirmation_link = last_confirmation_link(&app).await;
+    app.post_subscriptions("name=tenar&email=sparrowhawk%40earthsea.org".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 409);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("your subscription stays at your current address"));
+    let subscriber = sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(subscriber.email.as_deref(), Some("ged@earthsea.org"));
+}
+
+#[tokio::test]
+async fn an_invalid_new_address_is_rejected() {
+    let (app, subscriber_id) = setup().await;
+
+    let response = post_email_change(&app, subscriber_id, "not-an-email").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,46 @@
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+async fn get_confirmation_preview(app: &TestApp) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/emails/confirmation/preview",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_preview_the_confirmation_email() {
+    let app = spawn_app().await;
+
+    let response = get_confirmation_preview(&app).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_confirmation_email_preview_shows_the_subject_and_a_sample_link() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // Nothing may be sent
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = get_confirmation_preview(&app).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Subject: <strong>Welcome!</strong>"));
+    let sample_link = format!(
+        "{}?subscription_token=sample-confirmation-token",
+        app.base_url.join_path("subscriptions/confirm")
+    );
+    assert!(html_page.contains(&format!(r#"<a href="{sample_link}">here</a>"#)));
+}
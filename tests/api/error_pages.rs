@@ -0,0 +1,102 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn unknown_pages_get_an_html_404_in_a_browser() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/nonexistent", &app.address))
+        .header("Accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("<p>This page does not exist.</p>")
+    );
+}
+
+#[tokio::test]
+async fn unknown_pages_get_a_json_404_when_json_is_asked_for() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/nonexistent", &app.address))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "This page does not exist.");
+}
+
+#[tokio::test]
+async fn a_method_a_route_does_not_support_gets_a_405_listing_the_ones_it_does() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .delete(format!("{}/subscriptions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 405);
+    assert_eq!(response.headers()["Allow"], "POST");
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("<p>This method is not allowed here.</p>")
+    );
+}
+
+#[tokio::test]
+async fn routes_with_several_methods_list_them_all() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .put(format!("{}/login", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 405);
+    assert_eq!(response.headers()["Allow"], "GET, POST");
+}
+
+#[tokio::test]
+async fn malformed_json_bodies_get_a_json_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletter/templates", &app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"name": "Weekly""#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"].is_string());
+}
@@ -0,0 +1,105 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, create_confirmed_subscriber, spawn_app};
+
+async fn publish_and_deliver_an_issue(app: &TestApp) -> Vec<reqwest::Url> {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    linkify::LinkFinder::new()
+        .links(body["TextBody"].as_str().unwrap())
+        .filter(|l| l.as_str().contains("/feedback"))
+        .map(|l| {
+            let mut link = reqwest::Url::parse(l.as_str()).unwrap();
+            link.set_port(Some(app.port)).unwrap();
+            link
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn a_valid_feedback_link_records_the_rating() {
+    let app = spawn_app().await;
+    let links = publish_and_deliver_an_issue(&app).await;
+    assert_eq!(links.len(), 2);
+    let thumbs_up = links
+        .iter()
+        .find(|l| l.as_str().ends_with("rating=up"))
+        .unwrap();
+
+    let response = reqwest::get(thumbs_up.clone()).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("Thanks for your feedback!")
+    );
+    let saved = sqlx::query!("SELECT rating FROM issue_feedback")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.rating, "up");
+}
+
+#[tokio::test]
+async fn a_tampered_feedback_link_is_rejected() {
+    let app = spawn_app().await;
+    let links = publish_and_deliver_an_issue(&app).await;
+    let mut link = links[0].clone();
+    let tampered: Vec<(String, String)> = link
+        .query_pairs()
+        .map(|(k, v)| {
+            let v = if k == "token" {
+                // Flip the last character of the signature
+                let mut v = v.into_owned();
+                let last = if v.ends_with('0') { '1' } else { '0' };
+                v.pop();
+                v.push(last);
+                v
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), v)
+        })
+        .collect();
+    link.query_pairs_mut().clear().extend_pairs(tampered);
+
+    let response = reqwest::get(link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    let saved = sqlx::query!("SELECT rating FROM issue_feedback")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
@@ -0,0 +1,55 @@
+use zero_to_prod::configuration::FlashStore;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app_with};
+
+async fn spawn_session_flash_app() -> TestApp {
+    spawn_app_with(|c| c.application.flash_store = FlashStore::Session).await
+}
+
+async fn get_paused_domains_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/paused-domains", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn a_huge_flash_message_is_shown_in_full_from_the_session() {
+    let app = spawn_session_flash_app().await;
+    app.test_user.login(&app).await;
+    // Far past what a cookie can hold
+    let domain = "not_a_domain_".repeat(500);
+
+    let response = app.post_paused_domain(&domain).await;
+    assert_is_redirect_to(&response, "/admin/paused-domains");
+    for cookie in response.headers().get_all("Set-Cookie") {
+        let cookie = cookie.to_str().unwrap();
+        assert!(!cookie.starts_with("_flash="));
+        assert!(!cookie.contains("not_a_domain_"));
+    }
+
+    let html_page = get_paused_domains_html(&app).await;
+    assert!(html_page.contains(&domain));
+    assert!(html_page.contains("is not a valid domain."));
+    // Shown once only
+    let html_page = get_paused_domains_html(&app).await;
+    assert!(!html_page.contains("is not a valid domain."));
+}
+
+#[tokio::test]
+async fn logging_out_still_says_so_with_the_session_store() {
+    let app = spawn_session_flash_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(r#"<p><i>You have successfully logged out.</i></p>"#));
+    let html_page = app.get_login_html().await;
+    assert!(!html_page.contains("You have successfully logged out."));
+}
@@ -0,0 +1,84 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::configuration::FrequencyCap;
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn a_subscriber_receives_no_more_issues_than_the_cap_allows() {
+    let mut app = spawn_app().await;
+    app.worker_settings.frequency_cap = Some(FrequencyCap {
+        max_issues: 1,
+        window_seconds: 3600,
+    });
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    for i in 0..3 {
+        app.post_newsletter(&serde_json::json!({
+            "title": format!("Issue #{i}"),
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    }
+    app.dispatch_all_pending_emails().await;
+
+    // The other issues wait for the window to pass instead of being dropped
+    let deferred = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "n!"
+        FROM issue_delivery_queue
+        WHERE execute_after > now() + interval '50 minutes'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deferred.n, 2);
+}
+
+#[tokio::test]
+async fn the_cap_window_follows_the_apps_clock() {
+    let mut app = spawn_app().await;
+    app.worker_settings.frequency_cap = Some(FrequencyCap {
+        max_issues: 1,
+        window_seconds: 3600,
+    });
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    for i in 0..2 {
+        app.post_newsletter(&serde_json::json!({
+            "title": format!("Issue #{i}"),
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    }
+    app.dispatch_all_pending_emails().await;
+
+    app.clock.advance(chrono::Duration::minutes(61));
+    app.dispatch_all_pending_emails().await;
+
+    let queued = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.n, 0);
+}
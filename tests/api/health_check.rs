@@ -1,4 +1,13 @@
-use crate::helpers::spawn_app;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+
+fn is_git_sha(s: &str) -> bool {
+    s.len() >= 7 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
 
 #[tokio::test]
 async fn health_check_works() {
@@ -14,3 +23,120 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn the_liveness_check_works() {
+    let app = spawn_app().await;
+
+    let response = app.get_liveness_check().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(Some(0), response.content_length());
+}
+
+#[tokio::test]
+async fn the_app_is_ready_once_started_with_its_dependencies_reachable() {
+    let app = spawn_app().await;
+
+    let response = app.get_readiness_check().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["started"], true);
+    assert_eq!(body["database"], true);
+    assert_eq!(body["redis"], true);
+    assert!(is_git_sha(body["git_sha"].as_str().unwrap()));
+}
+
+#[tokio::test]
+async fn the_app_is_alive_but_not_ready_without_its_database() {
+    let app = spawn_app().await;
+
+    // The app shares this pool, so closing it cuts the app off from Postgres
+    app.db_pool.close().await;
+
+    let response = app.get_readiness_check().await;
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["database"], false);
+    assert_eq!(body["redis"], true);
+    let response = app.get_liveness_check().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn background_task_restarts_are_reported() {
+    let app = spawn_app_with(|c| c.supervisor.initial_backoff_milliseconds = 1).await;
+    let attempts = Arc::new(AtomicU32::new(0));
+    app.supervisor
+        .clone()
+        .supervise("Flaky task", {
+            let attempts = attempts.clone();
+            move || {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if attempt == 0 {
+                        anyhow::bail!("The database went away");
+                    }
+                    Ok(())
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+    let response = app.get_background_tasks_health_check().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["tasks"],
+        serde_json::json!([{ "name": "Flaky task", "restarts": 1 }])
+    );
+}
+
+#[tokio::test]
+async fn the_version_names_the_commit_it_was_built_from() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/version", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let git_sha = body["git_sha"].as_str().unwrap();
+    assert!(
+        is_git_sha(git_sha),
+        "{git_sha} doesn't look like a commit SHA"
+    );
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["built_at"].is_string());
+}
+
+#[tokio::test]
+async fn the_version_can_be_kept_behind_the_admin_login() {
+    let app = spawn_app_with(|c| c.application.version_admin_only = true).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/version", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/login");
+
+    app.test_user.login(&app).await;
+    let response = app
+        .api_client
+        .get(format!("{}/version", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -1,19 +1,40 @@
+use std::{path::Path, sync::Arc};
+
 use argon2::{
     password_hash::SaltString,
     {Algorithm, Argon2, Params, PasswordHasher, Version},
 };
+use fake::{
+    Fake,
+    faker::{internet::en::SafeEmail, name::en::Name},
+};
 use once_cell::sync::Lazy;
 use secrecy::ExposeSecret;
 use sqlx::{Connection, Executor, PgConnection, PgPool, postgres::PgConnectOptions};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
 
 use zero_to_prod::{
-    configuration::{DatabaseSettings, get_configuration},
+    clock::{Clock, TestClock},
+    configuration::{
+        DEFAULT_CONFIGURATION_DIRECTORY, DatabaseSettings, DeliverySettings, NotificationSettings,
+        Settings, WebhookSettings, WorkerSettings, get_configuration,
+    },
+    delivery_pause::DeliveryPause,
+    delivery_throttle::DeliveryThrottle,
+    domain::PiiCipher,
     email_client::EmailClient,
+    geo_lookup::NoGeoLookup,
     issue_delivery_worker::{ExecutionOutcome, try_execute_task},
-    startup::Application,
-    telemetry::{get_subscriber, init_subscriber},
+    sending_rate::SendingRate,
+    startup::{Application, ApplicationBaseUrl, HmacSecret},
+    supervisor::Supervisor,
+    telemetry::{Redaction, get_subscriber, init_subscriber},
+    webhooks::try_dispatch_webhook,
+    worker_heartbeat::Heartbeat,
 };
 
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -22,11 +43,21 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 
     if std::env::var("TEST_LOG").is_ok() {
         // Logs go to terminal
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::stdout,
+            Redaction::default(),
+        );
         init_subscriber(subscriber);
     } else {
         // Logs go to the void (sink)/ deleted;
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::sink,
+            Redaction::default(),
+        );
         init_subscriber(subscriber);
     }
 });
@@ -44,6 +75,23 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub base_url: ApplicationBaseUrl,
+    pub hmac_secret: HmacSecret,
+    pub notification_settings: NotificationSettings,
+    pub webhook_settings: WebhookSettings,
+    pub delivery_settings: DeliverySettings,
+    pub worker_settings: WorkerSettings,
+    // Kept between dispatches like the worker does
+    pub delivery_throttle: DeliveryThrottle,
+    pub delivery_pause: DeliveryPause,
+    pub sending_rate: SendingRate,
+    pub pii_cipher: PiiCipher,
+    // Shared with the app, moving it moves the time every handler and the worker sees
+    pub clock: Arc<TestClock>,
+    // For starting the app's background tasks the way the binaries do
+    pub configuration: Settings,
+    // The app's own, what it supervises shows up in its health check
+    pub supervisor: Arc<Supervisor>,
 }
 
 pub struct TestUser {
@@ -94,10 +142,73 @@ impl TestUser {
 }
 
 impl TestApp {
+    // Beats like the worker loop does, so the worker looks alive to the health check afterwards
     pub async fn dispatch_all_pending_emails(&self) {
+        let heartbeat = Heartbeat::new("test-worker".into(), std::time::Duration::ZERO);
+        loop {
+            let outcome = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.hmac_secret,
+                &self.notification_settings,
+                &self.delivery_settings,
+                &self.worker_settings,
+                &self.delivery_throttle,
+                &self.delivery_pause,
+                &self.sending_rate,
+                &self.pii_cipher,
+                self.clock.as_ref(),
+            )
+            .await
+            .unwrap();
+            heartbeat
+                .beat(&self.db_pool, Some(&outcome), self.clock.now())
+                .await
+                .unwrap();
+            if let ExecutionOutcome::EmptyQueue = outcome {
+                break;
+            }
+        }
+    }
+
+    pub async fn get_liveness_check(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check/live", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_readiness_check(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_background_tasks_health_check(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check/tasks", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_worker_health_check(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check/worker", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn dispatch_all_pending_webhooks(&self) {
+        let http_client = self.webhook_settings.client();
         loop {
             if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
+                try_dispatch_webhook(&self.db_pool, &http_client, &self.webhook_settings)
                     .await
                     .unwrap()
             {
@@ -150,6 +261,163 @@ impl TestApp {
         self.get_admin_dashboard().await.text().await.unwrap()
     }
 
+    pub async fn post_notification_settings<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/notifications", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_pause_worker(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/worker/pause", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resume_worker(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/worker/resume", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_paused_domain(&self, domain: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/paused-domains", &self.address))
+            .form(&serde_json::json!({ "domain": domain }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webhook<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/webhooks", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_webhooks_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/webhooks", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_suppression<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/suppressions", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_remove_suppression(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions/remove", &self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    // `query` is appended as it is, e.g. `search=example.com&per_page=3`
+    pub async fn post_merge_subscribers(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/subscribers/merge", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_stats(&self, origin: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .get(format!("{}/api/v1/stats", &self.address));
+        if let Some(origin) = origin {
+            request = request.header("Origin", origin);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_html(&self, query: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{query}", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_sender<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/senders", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_sender(&self, sender_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/senders/{sender_id}/verify",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_senders_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/senders", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -162,18 +430,92 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
-    pub async fn get_login_html(&self) -> String {
-        // Fetches HTML content as string from the /login endpoint
+    pub async fn post_api_token(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/api_token", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_api_token(&self) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/admin/api_token", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_tokens(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_tokens<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_api_token_by_id(&self, token_id: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/admin/api-tokens/{token_id}", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        // Fetches HTML content as string from the /login endpoint
+        self.get_login()
+            .await
             .text()
             .await // Wait for the response body to be read as text
             .unwrap()
     }
 
+    pub async fn get_home_html(&self) -> String {
+        self.api_client
+            .get(&self.address)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    // Submits the home page form the way a browser would
+    pub async fn post_subscriptions_from_browser<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .header("Accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(format!("{}/subscriptions", &self.address))
@@ -184,6 +526,24 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_confirmation(
+        &self,
+        email: &str,
+        idempotency_key: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .post(format!(
+                "{}/subscriptions/resend-confirmation",
+                &self.address
+            ))
+            .form(&serde_json::json!({ "email": email }));
+        if let Some(idempotency_key) = idempotency_key {
+            request = request.header("X-Idempotency-Key", idempotency_key);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
     pub async fn post_newsletter<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -196,6 +556,153 @@ impl TestApp {
             .expect("Failed to exectute request.")
     }
 
+    pub async fn post_newsletter_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/newsletter", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Publishes through the form as a logged in admin, the HTML content repeats the title. Returns
+    // the newest issue with that title.
+    pub async fn publish_issue(&self, title: &str) -> Uuid {
+        let response = self
+            .post_newsletter(&serde_json::json!({
+                "title": title,
+                "text_content": "Newsletter body as plain text",
+                "html_content": format!("<p>{title} as HTML</p>"),
+                "idempotency_key": Uuid::new_v4().to_string()
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/newsletter");
+        sqlx::query!(
+            r#"
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            WHERE title = $1
+            ORDER BY published_at::timestamptz DESC
+            LIMIT 1
+            "#,
+            title
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+    }
+
+    // Finds a subscriber whether their address is stored in plain text or encrypted
+    pub async fn subscriber_id(&self, email: &str) -> Uuid {
+        sqlx::query!(
+            "SELECT id FROM subscriptions WHERE email = $1 OR email_hash = $2",
+            email,
+            self.pii_cipher.email_hash(email)
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .unwrap()
+        .id
+    }
+
+    // `endpoint` is one of the compose API's, e.g. `save-draft`
+    pub async fn post_compose(
+        &self,
+        endpoint: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/compose/{endpoint}",
+                &self.address
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_newsletter_template<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletter/templates", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sending_rate(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletter/sending-rate", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_inlined_html(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletter/{}/html-inline",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_failed(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{}/resend-failed",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_to_subscriber(
+        &self,
+        issue_id: Uuid,
+        email: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{}/resend",
+                &self.address, issue_id
+            ))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_archive_issue(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{}/archive",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unarchive_issue(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{}/unarchive",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_newsletter(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/newsletter", &self.address))
@@ -204,6 +711,17 @@ impl TestApp {
             .expect("Failed to exectute request.")
     }
 
+    pub async fn post_preview_to_me(&self, issue_id: uuid::Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{issue_id}/preview-to-me",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_newsletter_html(&self) -> String {
         self.get_newsletter().await.text().await.unwrap()
     }
@@ -232,25 +750,41 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+// For tests that need settings the shared configuration files don't have
+pub async fn spawn_app_with(customise: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
     // Modify configuration to ensure test isolation, no collisions
     let configuration = {
-        let mut config = get_configuration().expect("Failed to read configuration.");
+        let mut config = get_configuration(Path::new(DEFAULT_CONFIGURATION_DIRECTORY))
+            .expect("Failed to read configuration.");
         // Use a different database for each test case
         config.database.database_name = Uuid::new_v4().to_string();
         // Request a random OS-assigned port
         config.application.port = 0;
         config.email_client.base_url = email_server.uri();
+        // Whatever proxy the machine running the tests has, the mock server is local
+        config.email_client.use_env_proxy = false;
+        customise(&mut config);
         config
     };
 
     let db_pool = configure_database(&configuration.database).await;
-    let application = Application::build(configuration.clone(), db_pool.clone())
-        .await
-        .expect("Failed to build application.");
+    let clock = Arc::new(TestClock::default());
+    let application = Application::build(
+        configuration.clone(),
+        db_pool.clone(),
+        clock.clone(),
+        Arc::new(NoGeoLookup),
+    )
+    .await
+    .expect("Failed to build application.");
     let application_port = application.port();
+    let supervisor = application.supervisor();
     tokio::spawn(application.run_until_stopped());
 
     let client = reqwest::Client::builder()
@@ -261,6 +795,7 @@ pub async fn spawn_app() -> TestApp {
         .unwrap();
 
     let test_app = TestApp {
+        configuration: configuration.clone(),
         address: format!("http://127.0.0.1:{}", application_port),
         port: application_port,
         db_pool,
@@ -268,6 +803,29 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        pii_cipher: configuration.pii_cipher().unwrap(),
+        base_url: ApplicationBaseUrl::parse(&configuration.application.base_url).unwrap(),
+        hmac_secret: HmacSecret(configuration.application.hmac_secret),
+        notification_settings: configuration.notifications,
+        webhook_settings: configuration.webhooks,
+        delivery_throttle: DeliveryThrottle::new(&configuration.worker.domain_rate_limits),
+        delivery_pause: DeliveryPause::new(
+            configuration.redis_uri.expose_secret(),
+            &configuration.database.database_name,
+        )
+        .await
+        .unwrap(),
+        sending_rate: SendingRate::new(
+            configuration.redis_uri.expose_secret(),
+            &configuration.database.database_name,
+            &configuration.worker,
+        )
+        .await
+        .unwrap(),
+        delivery_settings: configuration.delivery,
+        worker_settings: configuration.worker,
+        clock,
+        supervisor,
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
@@ -303,6 +861,93 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+// Makes every insert and update on `table` fail, like a crash partway through a request. Whatever
+// the request wrote before reaching `table` should be rolled back with it.
+pub async fn break_writes_to(pool: &PgPool, table: &str) {
+    pool.execute(
+        r#"
+        CREATE OR REPLACE FUNCTION fail_write() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'Injected failure writing to %', TG_TABLE_NAME;
+        END;
+        $$ LANGUAGE plpgsql
+        "#,
+    )
+    .await
+    .expect("Failed to create the failing trigger function.");
+    pool.execute(
+        format!(
+            "CREATE TRIGGER fail_writes BEFORE INSERT OR UPDATE ON {table} \
+             FOR EACH ROW EXECUTE FUNCTION fail_write()"
+        )
+        .as_str(),
+    )
+    .await
+    .expect("Failed to break writes to the table.");
+}
+
+pub async fn fix_writes_to(pool: &PgPool, table: &str) {
+    pool.execute(format!("DROP TRIGGER fail_writes ON {table}").as_str())
+        .await
+        .expect("Failed to fix writes to the table.");
+}
+
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let email: String = SafeEmail().fake();
+    create_unconfirmed_subscriber_with_email(app, &email).await
+}
+
+pub async fn create_unconfirmed_subscriber_with_email(
+    app: &TestApp,
+    email: &str,
+) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": name,
+        "email": email,
+    }))
+    .unwrap();
+
+    // Mimicing user client interacting with the /subscriptions endpoint
+    let _mock_guard = Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let email: String = SafeEmail().fake();
+    create_confirmed_subscriber_with_email(app, &email).await;
+}
+
+pub async fn create_confirmed_subscriber_with_email(app: &TestApp, email: &str) {
+    let confirmation_link = create_unconfirmed_subscriber_with_email(app, email).await;
+    // Mimicing user clicking on the link
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
 // Check to see if redirection is correct
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
@@ -0,0 +1,94 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn the_home_page_has_a_subscribe_form_and_links_to_the_archive() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_home_html().await;
+
+    assert!(html_page.contains("<h1>Zero To Production Newsletter</h1>"));
+    assert!(html_page.contains(r#"<form action="/subscriptions" method="post">"#));
+    assert!(html_page.contains(r#"name="name""#));
+    assert!(html_page.contains(r#"name="email""#));
+    assert!(html_page.contains(r#"href="/archive""#));
+}
+
+#[tokio::test]
+async fn subscribing_from_the_home_page_shows_a_success_message() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Part 1 - Submit the form
+    let response = app
+        .post_subscriptions_from_browser(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/");
+
+    // Part 2 - Follow the redirect
+    let html_page = app.get_home_html().await;
+    assert!(
+        html_page.contains(
+            "<p><i>Thanks for subscribing! Check your inbox to confirm your email.</i></p>"
+        )
+    );
+
+    let saved =
+        sqlx::query!("SELECT status FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+
+    // Part 3 - Reload the page, the message is only shown once
+    let html_page = app.get_home_html().await;
+    assert!(!html_page.contains("Thanks for subscribing!"));
+}
+
+#[tokio::test]
+async fn invalid_home_page_submissions_show_an_error_message() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions_from_browser(&serde_json::json!({
+            "name": "le guin",
+            "email": "definitely-not-an-email",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/");
+
+    let html_page = app.get_home_html().await;
+    assert!(html_page.contains("is not a valid subscriber email."));
+}
+
+#[tokio::test]
+async fn the_flash_message_is_not_cached() {
+    let app = spawn_app().await;
+
+    app.post_subscriptions_from_browser(&serde_json::json!({
+        "name": "le guin",
+        "email": "definitely-not-an-email",
+    }))
+    .await;
+    let response = app
+        .api_client
+        .get(&app.address)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+    assert!(response.headers().get("ETag").is_none());
+}
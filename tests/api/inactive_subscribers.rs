@@ -0,0 +1,309 @@
+use chrono::{Duration, Months, Utc};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber_with_email, spawn_app,
+};
+
+async fn publish_and_deliver_issue(app: &TestApp) {
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn get_inactive_html(app: &TestApp, months: u32) -> String {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/inactive?months={months}",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn delivering_an_issue_records_when_subscribers_were_last_emailed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    publish_and_deliver_issue(&app).await;
+
+    let subscriber =
+        sqlx::query!("SELECT last_emailed_at FROM subscriptions WHERE email = 'ged@earthsea.org'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    let last_emailed_at = subscriber.last_emailed_at.expect("Not marked as emailed.");
+    assert!(Utc::now() - last_emailed_at < Duration::minutes(1));
+}
+
+#[tokio::test]
+async fn subscribers_who_have_not_opened_since_the_cutoff_are_reported() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "reader@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "lurker@earthsea.org").await;
+    app.test_user.login(&app).await;
+    publish_and_deliver_issue(&app).await;
+    // Both have been around for a while
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '2 years'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let delivery = sqlx::query!(
+        "SELECT delivery_id FROM newsletter_deliveries WHERE subscriber_id = $1",
+        app.subscriber_id("reader@earthsea.org").await
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    app.api_client
+        .get(format!(
+            "{}/track/open/{}.gif",
+            &app.address, delivery.delivery_id
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // Part 1 - Six months on, the open is inside the last year
+    app.clock
+        .set(Utc::now().checked_add_months(Months::new(6)).unwrap());
+    let html_page = get_inactive_html(&app, 12).await;
+    assert!(html_page.contains("<td>lurker@earthsea.org</td>"));
+    assert!(!html_page.contains("<td>reader@earthsea.org</td>"));
+
+    // Part 2 - Thirteen months on, it isn't any more
+    app.clock
+        .set(Utc::now().checked_add_months(Months::new(13)).unwrap());
+    let html_page = get_inactive_html(&app, 12).await;
+    assert!(html_page.contains("<td>lurker@earthsea.org</td>"));
+    assert!(html_page.contains("<td>reader@earthsea.org</td>"));
+}
+
+#[tokio::test]
+async fn opens_are_recorded_at_the_apps_time() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "reader@earthsea.org").await;
+    app.test_user.login(&app).await;
+    publish_and_deliver_issue(&app).await;
+    let delivery = sqlx::query!("SELECT delivery_id FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let opened_at = Utc::now().checked_add_months(Months::new(3)).unwrap();
+    app.clock.set(opened_at);
+
+    app.api_client
+        .get(format!(
+            "{}/track/open/{}.gif",
+            &app.address, delivery.delivery_id
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    let subscriber = sqlx::query!("SELECT last_opened_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let last_opened_at = subscriber
+        .last_opened_at
+        .expect("The open wasn't recorded.");
+    assert!((last_opened_at - opened_at).num_seconds().abs() < 1);
+}
+
+#[tokio::test]
+async fn the_inactive_report_is_paginated() {
+    let app = spawn_app().await;
+    for i in 0..3 {
+        create_confirmed_subscriber_with_email(&app, &format!("lurker{i}@earthsea.org")).await;
+    }
+    app.test_user.login(&app).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '2 years'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let listed = |html_page: &str| {
+        (0..3)
+            .filter(|i| html_page.contains(&format!("<td>lurker{i}@earthsea.org</td>")))
+            .count()
+    };
+
+    let first_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/inactive?months=12&per_page=2",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let second_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/inactive?months=12&page=2&per_page=2",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert_eq!(listed(&first_page), 2);
+    assert!(first_page.contains("<p>3 confirmed subscribers haven't opened an issue"));
+    assert!(first_page.contains("months=12&page=2&per_page=2"));
+    assert_eq!(listed(&second_page), 1);
+    assert!(!second_page.contains("page=3"));
+}
+
+#[tokio::test]
+async fn a_full_last_page_has_no_next_link() {
+    let app = spawn_app().await;
+    for i in 0..2 {
+        create_confirmed_subscriber_with_email(&app, &format!("lurker{i}@earthsea.org")).await;
+    }
+    app.test_user.login(&app).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '2 years'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/inactive?months=12&per_page=2",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("<td>lurker1@earthsea.org</td>"));
+    assert!(!html_page.contains("page=2"));
+}
+
+#[tokio::test]
+async fn inactive_subscribers_can_be_unsubscribed_in_bulk() {
+    let app = spawn_app().await;
+    for i in 0..3 {
+        create_confirmed_subscriber_with_email(&app, &format!("lurker{i}@earthsea.org")).await;
+    }
+    create_confirmed_subscriber_with_email(&app, "newcomer@earthsea.org").await;
+    app.test_user.login(&app).await;
+    app.post_webhook(&serde_json::json!({
+        "url": "https://example.com/hook",
+        "secret": "webhook-signing-secret",
+        "subscriber_unsubscribed": "on",
+    }))
+    .await;
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = now() - interval '2 years' WHERE email LIKE 'lurker%'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/inactive", &app.address))
+        .form(&serde_json::json!({ "months": 12 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/admin/subscribers/inactive?months=12");
+    let html_page = get_inactive_html(&app, 12).await;
+    assert!(html_page.contains("<p><i>3 inactive subscribers have been unsubscribed.</i></p>"));
+    assert!(html_page.contains("<p>0 confirmed subscribers haven't opened an issue"));
+    let newcomer_id = app.subscriber_id("newcomer@earthsea.org").await;
+    let statuses = sqlx::query!("SELECT id, status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    for subscriber in statuses {
+        let expected = if subscriber.id == newcomer_id {
+            "confirmed"
+        } else {
+            "unsubscribed"
+        };
+        assert_eq!(subscriber.status, expected);
+    }
+    let events = sqlx::query!("SELECT event FROM webhook_deliveries")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 3);
+    assert!(events.iter().all(|e| e.event == "subscriber.unsubscribed"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_unsubscribe_inactive_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "lurker@earthsea.org").await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/inactive", &app.address))
+        .form(&serde_json::json!({ "months": 0 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+}
+
+#[tokio::test]
+async fn subscribers_who_joined_after_the_cutoff_are_not_reported() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "newcomer@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let html_page = get_inactive_html(&app, 12).await;
+
+    assert!(!html_page.contains("<td>newcomer@earthsea.org</td>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_inactive_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers/inactive", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,62 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+async fn get_inbox_preview(app: &TestApp, query: &[(&str, &str)]) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/newsletter/inbox-preview", &app.address))
+        .query(query)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn each_client_shows_the_line_cut_to_its_own_length() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subject = "A very long subject line that keeps on going";
+    let preheader = "and a preheader that goes on even longer than that";
+
+    let response = get_inbox_preview(
+        &app,
+        &[
+            ("subject", subject),
+            ("from_name", "Ursula"),
+            ("from_email", "ursula@example.com"),
+            ("preheader", preheader),
+        ],
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let previews: Vec<serde_json::Value> = response.json().await.unwrap();
+    let line = format!("Ursula – {subject} – {preheader}");
+    let cut = |limit: usize| format!("{}…", line.chars().take(limit - 1).collect::<String>());
+    assert_eq!(
+        previews,
+        vec![
+            serde_json::json!({ "client": "Gmail", "preview": cut(77), "truncated": true }),
+            serde_json::json!({ "client": "Outlook", "preview": cut(50), "truncated": true }),
+            serde_json::json!({ "client": "Apple Mail", "preview": line, "truncated": false }),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_preview_needs_a_subject_and_a_sender_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = get_inbox_preview(&app, &[("from_email", "ursula@example.com")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = get_inbox_preview(&app, &[("subject", "Issue 12")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_preview_the_inbox() {
+    let app = spawn_app().await;
+
+    let response = get_inbox_preview(&app, &[("subject", "Issue 12")]).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
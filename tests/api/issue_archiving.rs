@@ -0,0 +1,113 @@
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+async fn get_newsletter_list_html(app: &TestApp, query: &str) -> String {
+    app.api_client
+        .get(format!("{}/admin/newsletter{query}", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn archived_issues_are_left_out_of_the_issue_list_unless_asked_for() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.publish_issue("Kept issue").await;
+    let issue_id = app.publish_issue("Old issue").await;
+
+    let response = app.post_archive_issue(issue_id).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let html_page = get_newsletter_list_html(&app, "").await;
+    assert!(html_page.contains("<p><i>\"Old issue\" has been archived.</i></p>"));
+    assert!(html_page.contains("Kept issue"));
+    assert!(!html_page.contains("<td>Old issue</td>"));
+
+    let html_page = get_newsletter_list_html(&app, "?include_archived=true").await;
+    assert!(html_page.contains("<td>Kept issue</td>"));
+    assert!(html_page.contains("<td>Old issue</td>"));
+    assert!(html_page.contains(&format!("/admin/newsletter/{issue_id}/unarchive")));
+}
+
+#[tokio::test]
+async fn unarchiving_an_issue_lists_it_again() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.publish_issue("Old issue").await;
+    app.post_archive_issue(issue_id).await;
+
+    let response = app.post_unarchive_issue(issue_id).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let html_page = get_newsletter_list_html(&app, "").await;
+    assert!(html_page.contains("<td>Old issue</td>"));
+    let issue = sqlx::query!(
+        "SELECT archived_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(issue.archived_at.is_none());
+}
+
+#[tokio::test]
+async fn archived_issues_are_hidden_from_the_public_archive() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.publish_issue("Old issue").await;
+    let archive = app
+        .api_client
+        .get(format!("{}/archive", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(archive.contains("Old issue"));
+
+    app.post_archive_issue(issue_id).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/archive", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!response.text().await.unwrap().contains("Old issue"));
+    let response = app
+        .api_client
+        .get(format!("{}/archive/old-issue", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn archiving_an_unknown_issue_is_reported() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_archive_issue(Uuid::new_v4()).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let html_page = get_newsletter_list_html(&app, "").await;
+    assert!(html_page.contains("<p><i>No such newsletter issue.</i></p>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_archive_an_issue() {
+    let app = spawn_app().await;
+
+    let response = app.post_archive_issue(Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, spawn_app, spawn_app_with};
+
+// The linked pages are served on this machine
+async fn spawn_app_with_quick_link_checks() -> TestApp {
+    let app = spawn_app_with(|c| {
+        c.link_check.timeout_milliseconds = 300;
+        c.link_check.allow_private_targets = true;
+    })
+    .await;
+    app.test_user.login(&app).await;
+    app
+}
+
+// Serves a healthy page, a missing one and one too slow to answer in time
+async fn link_targets() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(path("/ok"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    Mock::given(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(path("/slow"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn newsletter_linking_to(links: &[String]) -> serde_json::Value {
+    let html_content: String = links
+        .iter()
+        .map(|link| format!(r#"<p><a href="{link}">Read more</a></p>"#))
+        .collect();
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": html_content,
+        "idempotency_key": Uuid::new_v4().to_string()
+    })
+}
+
+async fn n_issues(app: &TestApp) -> usize {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .len()
+}
+
+#[tokio::test]
+async fn broken_links_are_reported_before_anything_is_queued() {
+    let app = spawn_app_with_quick_link_checks().await;
+    let targets = link_targets().await;
+    let links = ["ok", "missing", "slow"].map(|p| format!("{}/{p}", targets.uri()));
+
+    let response = app.post_newsletter(&newsletter_linking_to(&links)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p><i>1 link returned an error</i></p>"));
+    assert!(html_page.contains(&format!("<li>{} returned 404 Not Found</li>", links[1])));
+    assert!(html_page.contains(&format!("<li>{} timed out</li>", links[2])));
+    assert!(!html_page.contains(&format!("<li>{}", links[0])));
+    assert!(html_page.contains(r#"action="/admin/newsletter?skip_link_check=1""#));
+    assert_eq!(n_issues(&app).await, 0);
+}
+
+#[tokio::test]
+async fn an_issue_with_broken_links_can_be_published_anyway() {
+    let app = spawn_app_with_quick_link_checks().await;
+    let targets = link_targets().await;
+    let newsletter = newsletter_linking_to(&[format!("{}/missing", targets.uri())]);
+
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/newsletter?skip_link_check=1",
+            &app.address
+        ))
+        .form(&newsletter)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(n_issues(&app).await, 1);
+}
+
+#[tokio::test]
+async fn slow_links_alone_do_not_hold_up_publishing() {
+    let app = spawn_app_with_quick_link_checks().await;
+    let targets = link_targets().await;
+    let links = ["ok", "slow"].map(|p| format!("{}/{p}", targets.uri()));
+
+    let response = app.post_newsletter(&newsletter_linking_to(&links)).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(n_issues(&app).await, 1);
+}
+
+#[tokio::test]
+async fn links_that_refuse_head_requests_are_checked_with_get() {
+    let app = spawn_app_with_quick_link_checks().await;
+    let targets = MockServer::start().await;
+    Mock::given(path("/page"))
+        .and(method("HEAD"))
+        .respond_with(ResponseTemplate::new(405))
+        .mount(&targets)
+        .await;
+    Mock::given(path("/page"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&targets)
+        .await;
+
+    let response = app
+        .post_newsletter(&newsletter_linking_to(&[format!("{}/page", targets.uri())]))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn links_to_private_addresses_are_never_requested() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let targets = MockServer::start().await;
+    Mock::given(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(0)
+        .mount(&targets)
+        .await;
+    let links = [
+        format!("{}/missing", targets.uri()),
+        format!("http://localhost:{}/missing", targets.address().port()),
+    ];
+
+    let response = app.post_newsletter(&newsletter_linking_to(&links)).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn checking_an_issues_links_stops_at_the_deadline() {
+    let app = spawn_app_with(|c| {
+        c.link_check.timeout_milliseconds = 10_000;
+        c.link_check.deadline_milliseconds = 500;
+        c.link_check.max_concurrency = 1;
+        c.link_check.allow_private_targets = true;
+    })
+    .await;
+    app.test_user.login(&app).await;
+    let targets = link_targets().await;
+    let links = ["slow", "missing"].map(|p| format!("{}/{p}", targets.uri()));
+
+    let started = std::time::Instant::now();
+    let response = app.post_newsletter(&newsletter_linking_to(&links)).await;
+
+    assert!(started.elapsed() < Duration::from_secs(3));
+    // Neither link got an answer in time, so nothing is known to be broken
+    assert_eq!(response.status().as_u16(), 303);
+}
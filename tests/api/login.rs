@@ -1,4 +1,11 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use std::path::Path;
+
+use redis::AsyncCommands;
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+use zero_to_prod::configuration::{DEFAULT_CONFIGURATION_DIRECTORY, get_configuration};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -35,3 +42,197 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn logging_in_through_a_trailing_slash_works() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/login/", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_session_cookie_uses_the_configured_name_and_path() {
+    let app = spawn_app_with(|c| {
+        c.session.cookie_name = "newsletter_session".into();
+        c.session.cookie_path = "/admin".into();
+    })
+    .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let session_cookie = response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .map(|h| h.to_str().unwrap())
+        .find(|c| c.starts_with("newsletter_session="))
+        .expect("No session cookie was set.");
+    assert!(session_cookie.contains("Path=/admin"));
+}
+
+#[tokio::test]
+async fn an_anonymous_user_gets_the_login_form() {
+    let app = spawn_app().await;
+
+    let response = app.get_login().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains(r#"<form action="/login""#)
+    );
+}
+
+#[tokio::test]
+async fn a_logged_in_user_is_redirected_from_the_login_form_to_the_dashboard() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_login().await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn failed_logins_across_usernames_from_one_ip_are_throttled() {
+    let app = spawn_app().await;
+    let max_attempts = get_configuration(Path::new(DEFAULT_CONFIGURATION_DIRECTORY))
+        .unwrap()
+        .login_throttle
+        .max_attempts;
+
+    // Spray a different username on every attempt
+    for _ in 0..max_attempts {
+        let response = app
+            .post_login(&serde_json::json!({
+                "username": Uuid::new_v4().to_string(),
+                "password": Uuid::new_v4().to_string(),
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": Uuid::new_v4().to_string(),
+            "password": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().get("Retry-After").is_some());
+
+    // Valid credentials are turned away too while the IP is throttled
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+async fn fail_login_from(app: &TestApp, forwarded_for: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .form(&serde_json::json!({
+            "username": Uuid::new_v4().to_string(),
+            "password": Uuid::new_v4().to_string(),
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn rotating_x_forwarded_for_does_not_reset_the_count() {
+    let app = spawn_app_with(|c| c.login_throttle.max_attempts = 3).await;
+
+    for i in 0..3 {
+        let response = fail_login_from(&app, &format!("10.0.0.{i}")).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    let response = fail_login_from(&app, "10.0.0.99").await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn a_trusted_proxy_passes_on_the_client_ip() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.max_attempts = 1;
+        c.login_throttle.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+
+    let response = fail_login_from(&app, "203.0.113.7").await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = fail_login_from(&app, "203.0.113.7").await;
+    assert_eq!(response.status().as_u16(), 429);
+    let response = fail_login_from(&app, "198.51.100.4").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn retry_after_is_the_time_left_in_the_window() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.max_attempts = 1;
+        c.login_throttle.window_seconds = 30;
+    })
+    .await;
+    fail_login_from(&app, "10.0.0.1").await;
+
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let response = fail_login_from(&app, "10.0.0.1").await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=28).contains(&retry_after), "Retry-After {retry_after}");
+}
+
+#[tokio::test]
+async fn a_throttled_ip_without_an_expiry_is_given_one() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.max_attempts = 1;
+        c.login_throttle.window_seconds = 30;
+    })
+    .await;
+    let key = format!(
+        "login_attempts:{}:127.0.0.1",
+        app.configuration.database.database_name
+    );
+    let client = redis::Client::open(app.configuration.redis_uri.expose_secret().as_str()).unwrap();
+    let mut connection = client.get_multiplexed_async_connection().await.unwrap();
+    connection.set::<_, _, ()>(&key, 5).await.unwrap();
+
+    let response = fail_login_from(&app, "10.0.0.1").await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    let ttl: i64 = connection.ttl(&key).await.unwrap();
+    assert!((1..=30).contains(&ttl), "TTL {ttl}");
+}
@@ -1,8 +1,57 @@
 mod admin_dashboard;
+mod allowed_hosts;
+mod api_token;
+mod api_v1;
+mod archive;
+mod caching;
 mod change_password;
+mod chunked_enqueue;
+mod click_tracking;
+mod configuration;
+mod content_check;
+mod css_inlining;
+mod database;
+mod domain_throttling;
+mod email_change;
+mod email_previews;
+mod error_pages;
+mod feedback;
+mod flash_messages;
+mod frequency_cap;
 mod health_check;
 mod helpers;
+mod home;
+mod inactive_subscribers;
+mod inbox_preview;
+mod issue_archiving;
+mod link_check;
 mod login;
 mod newsletter;
+mod newsletter_compose;
+mod newsletter_preview;
+mod newsletter_templates;
+mod open_tracking;
+mod openapi;
+mod paused_domains;
+mod pii_encryption;
+mod public_stats;
+mod query_plans;
+mod queue_metrics;
+mod resend_confirmation;
+mod senders;
+mod sending_rate;
+mod sending_window;
+mod separate_worker;
+mod subscriber_deliverability;
+mod subscriber_export;
+mod subscriber_merge;
+mod subscriber_notes;
+mod subscriber_search;
 mod subscriptions;
 mod subscriptions_confirm;
+mod suppressions;
+mod unsubscribed_subscribers;
+mod utm_injection;
+mod webhooks;
+mod worker_health;
+mod worker_pause;
@@ -1,16 +1,17 @@
 use std::time::Duration;
 
-use fake::{
-    Fake,
-    faker::{internet::en::SafeEmail, name::en::Name},
-};
 use uuid::Uuid;
 use wiremock::{
     Mock, ResponseTemplate,
     matchers::{any, method, path},
 };
+use zero_to_prod::issue_delivery_worker::delivery_idempotency_key;
 
-use crate::helpers::{ConfirmationLinks, TestApp, assert_is_redirect_to, spawn_app};
+use crate::helpers::{
+    assert_is_redirect_to, break_writes_to, create_confirmed_subscriber,
+    create_confirmed_subscriber_with_email, create_unconfirmed_subscriber,
+    create_unconfirmed_subscriber_with_email, fix_writes_to, spawn_app, spawn_app_with,
+};
 
 #[tokio::test]
 async fn non_existing_user_is_rejected() {
@@ -43,6 +44,42 @@ async fn invalid_password_is_rejected() {
     assert_is_redirect_to(&response, "/login");
 }
 
+// Publishing goes through the admin session or nothing. The old JSON endpoint with Basic auth,
+// which sent inline without idempotency, must not come back.
+#[tokio::test]
+async fn there_is_no_basic_auth_publish_endpoint() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/newsletters", &app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_issues, 0);
+}
+
 #[tokio::test]
 async fn newsletters_are_not_delievered_to_unconfirmed_subscribers() {
     let app = spawn_app().await;
@@ -73,7 +110,7 @@ async fn newsletters_are_not_delievered_to_unconfirmed_subscribers() {
     let response = app.post_newsletter(&newsletter_request_body).await;
     assert_eq!(response.status().as_u16(), 303);
     let html_page = app.get_newsletter_html().await;
-    assert!(html_page.contains(r#"<p><i>The newsletter issue has been published!</i></p>"#));
+    assert!(html_page.contains(r#"<p><i>Newsletter queued for 0 subscribers.</i></p>"#));
     app.dispatch_all_pending_emails().await;
 }
 
@@ -108,7 +145,7 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     let response = app.post_newsletter(&newsletter_request_body).await;
     assert_eq!(response.status().as_u16(), 303);
     let html_page = app.get_newsletter_html().await;
-    assert!(html_page.contains(r#"<p><i>The newsletter issue has been published!</i></p>"#));
+    assert!(html_page.contains(r#"<p><i>Newsletter queued for 1 subscribers.</i></p>"#));
     app.dispatch_all_pending_emails().await;
 }
 
@@ -149,6 +186,37 @@ async fn newsletter_returns_400_for_invalid_data() {
     }
 }
 
+#[tokio::test]
+async fn newsletter_returns_400_for_an_invalid_idempotency_key() {
+    let app = spawn_app_with(|c| c.idempotency_key_max_length = 8).await;
+    app.test_user.login(&app).await;
+
+    let test_cases = [
+        ("", "The idempotency key cannot be empty"),
+        (
+            "123456789",
+            "The idempotency key must be at most 8 characters long",
+        ),
+        (
+            "1234\u{7}",
+            "The idempotency key can only contain printable characters",
+        ),
+    ];
+    for (idempotency_key, error_message) in test_cases {
+        let response = app
+            .post_newsletter(&serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+                "idempotency_key": idempotency_key
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(response.text().await.unwrap(), error_message);
+    }
+}
+
 #[tokio::test]
 async fn newsletter_creation_is_idempotent() {
     let app = spawn_app().await;
@@ -172,13 +240,55 @@ async fn newsletter_creation_is_idempotent() {
     let response = app.post_newsletter(&newsletter_request_body).await;
     assert_is_redirect_to(&response, "/admin/newsletter");
     let html_page = app.get_newsletter_html().await;
-    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+    assert!(html_page.contains("<p><i>Newsletter queued for 1 subscribers.</i></p>"));
 
     // Submit newsletter form again
     let response = app.post_newsletter(&newsletter_request_body).await;
     assert_is_redirect_to(&response, "/admin/newsletter");
     let html_page = app.get_newsletter_html().await;
-    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+    assert!(html_page.contains("<p><i>The newsletter issue has already been queued.</i></p>"));
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn only_the_redirect_is_saved_for_a_repeated_submission() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let saved = sqlx::query!(
+        "SELECT response_location, response_headers IS NULL AS \"no_headers!\", \
+        response_body IS NULL AS \"no_body!\" FROM idempotency"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        saved.response_location.as_deref(),
+        Some("/admin/newsletter")
+    );
+    assert!(saved.no_headers);
+    assert!(saved.no_body);
+
+    // The replay still redirects to the same place
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
     app.dispatch_all_pending_emails().await;
 }
 
@@ -215,46 +325,558 @@ async fn concurrent_form_submission_is_handled_gracefully() {
     app.dispatch_all_pending_emails().await;
 }
 
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(serde_json::json!({
-        "name": name,
-        "email": email,
-    }))
+#[tokio::test]
+async fn a_resubmission_gives_up_on_a_stuck_request_with_a_503() {
+    let app = spawn_app_with(|c| c.idempotency_wait_seconds = 1).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    // A first request that claimed the key and never finished
+    let mut stuck = app.db_pool.begin().await.unwrap();
+    sqlx::query!(
+        "INSERT INTO idempotency (user_id, idempotency_key, created_at) VALUES ($1, $2, now())",
+        app.test_user.user_id,
+        idempotency_key
+    )
+    .execute(&mut stuck)
+    .await
     .unwrap();
 
-    // Mimicing user client interacting with the /subscriptions endpoint
-    let _mock_guard = Mock::given(path("v3/mail/send"))
+    let started = std::time::Instant::now();
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": idempotency_key
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    assert_eq!(response.headers()["Retry-After"], "1");
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    stuck.rollback().await.unwrap();
+    let n_issues = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn the_publisher_is_notified_once_an_issue_is_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let admin_email = "admin@example.com";
+    let response = app
+        .post_notification_settings(&serde_json::json!({
+            "email": admin_email,
+            "notify_on_completion": "on",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("<p><i>Your notification settings have been updated.</i></p>"));
+
+    // Two subscriber copies plus the summary for the publisher
+    Mock::given(path("v3/mail/send"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let summary: serde_json::Value =
+        serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(summary["To"], admin_email);
+    let text = summary["TextBody"].as_str().unwrap();
+    assert!(text.contains("Newsletter title"));
+    assert!(text.contains("Delivered: 2"));
+    assert!(text.contains("Failed: 0"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_resend_failed_deliveries() {
+    let app = spawn_app().await;
+
+    let response = app.post_resend_failed(Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn failed_deliveries_can_be_resent() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    // The email provider is down for the first attempt
+    let outage = Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
         .expect(1)
         .mount_as_scoped(&app.email_server)
         .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    drop(outage);
+
+    let delivery = sqlx::query!("SELECT newsletter_issue_id, status FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.status, "failed");
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_resend_failed(delivery.newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["requeued"], 1);
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>1 failed deliveries have been requeued.</i></p>"));
+
+    app.dispatch_all_pending_emails().await;
+    let delivery = sqlx::query!("SELECT status FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.status, "delivered");
+}
+
+#[tokio::test]
+async fn an_issue_can_be_resent_to_a_single_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "reader@example.com").await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    // (recipient, idempotency key) of every copy of the issue, confirmation emails left out
+    let issue_copies = || async {
+        let mut copies = Vec::new();
+        for request in app.email_server.received_requests().await.unwrap() {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            if body["Subject"] != "Newsletter title" {
+                continue;
+            }
+            assert!(
+                body["TextBody"]
+                    .as_str()
+                    .unwrap()
+                    .contains("Newsletter body as plain text")
+            );
+            let key = request.headers.get(&"Idempotency-Key".into()).unwrap()[0].to_string();
+            copies.push((body["To"].as_str().unwrap().to_owned(), key));
+        }
+        copies
+    };
+    let first_copies = issue_copies().await;
+    assert_eq!(first_copies.len(), 2);
+
+    let response = app
+        .post_resend_to_subscriber(issue_id, "reader@example.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["resend_id"].is_string());
+    app.dispatch_all_pending_emails().await;
+
+    let copies = issue_copies().await;
+    assert_eq!(copies.len(), 3);
+    let (to, key) = &copies[2];
+    assert_eq!(to, "reader@example.com");
+    // Not a retry of the first copy, which the provider would drop
+    let (_, original_key) = first_copies
+        .iter()
+        .find(|(to, _)| to == "reader@example.com")
+        .unwrap();
+    assert_ne!(key, original_key);
+    // The subscriber still only counts once
+    let issue = sqlx::query!(
+        "SELECT n_delivered, n_failed FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!((issue.n_delivered, issue.n_failed), (2, 0));
+}
+
+#[tokio::test]
+async fn an_issue_is_not_resent_to_an_unconfirmed_address() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber_with_email(&app, "pending@example.com").await;
+    app.test_user.login(&app).await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    let response = app
+        .post_resend_to_subscriber(issue_id, "pending@example.com")
+        .await;
 
-    app.post_subscriptions(body)
+    assert_eq!(response.status().as_u16(), 400);
+    // The address isn't echoed back
+    assert!(
+        !response
+            .text()
+            .await
+            .unwrap()
+            .contains("pending@example.com")
+    );
+    let queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(queued, 0);
+}
+
+#[tokio::test]
+async fn the_delivery_report_keeps_the_audience_at_send_time() {
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 3 subscribers.</i></p>"));
+    app.dispatch_all_pending_emails().await;
+
+    // One of the readers leaves after the issue went out
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' \
+        WHERE id = (SELECT id FROM subscriptions LIMIT 1)"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let report: serde_json::Value = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter/{issue_id}/delivery-report",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
         .await
-        .error_for_status()
         .unwrap();
+    assert_eq!(report["audience_at_send"], 3);
+    assert_eq!(report["delivered"], 3);
+    assert_eq!(report["status"], "sent");
+}
 
-    let email_request = &app
-        .email_server
-        .received_requests()
+#[tokio::test]
+async fn a_dry_run_counts_the_recipients_a_publish_then_sends_to() {
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "tenar@earthsea.org").await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE email = 'tenar@earthsea.org'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({ "email": "ged@earthsea.org", "reason": "Bounced" }))
+        .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+
+    // Part 1 - Dry run
+    let mut dry_run_body = newsletter_request_body.clone();
+    dry_run_body["action"] = "dry_run".into();
+    let response = app.post_newsletter(&dry_run_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(
+        html_page.contains(
+            "Would send to 3 confirmed subscribers; 1 suppressed, 1 unsubscribed excluded."
+        )
+    );
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
         .await
         .unwrap()
-        .pop()
+        .n;
+    assert_eq!(n_issues, 0);
+
+    // Part 2 - The same submission for real, its idempotency key wasn't used up
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 3 subscribers.</i></p>"));
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn the_delivery_report_of_an_unknown_issue_is_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter/{}/delivery-report",
+            &app.address,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
         .unwrap();
 
-    app.get_confirmation_links(email_request)
+    assert_eq!(response.status().as_u16(), 404);
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-    // Mimicing user clicking on the link
-    reqwest::get(confirmation_link.html)
+#[tokio::test]
+async fn each_delivery_carries_an_idempotency_key_for_its_issue_and_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "tenar@earthsea.org").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for title in ["First issue", "Second issue"] {
+        app.post_newsletter(&serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    }
+    app.dispatch_all_pending_emails().await;
+
+    let mut keys = Vec::new();
+    for request in app.email_server.received_requests().await.unwrap() {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let title = body["Subject"].as_str().unwrap();
+        if !title.ends_with(" issue") {
+            continue;
+        }
+        let issue_id = sqlx::query!(
+            "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = $1",
+            title
+        )
+        .fetch_one(&app.db_pool)
         .await
         .unwrap()
-        .error_for_status()
+        .newsletter_issue_id;
+        let key = request.headers.get(&"Idempotency-Key".into()).unwrap()[0].to_string();
+        assert_eq!(
+            key,
+            delivery_idempotency_key(
+                issue_id,
+                app.subscriber_id(body["To"].as_str().unwrap()).await
+            )
+        );
+        keys.push(key);
+    }
+    keys.sort();
+    keys.dedup();
+    // Two issues to two subscribers, each pair with a key of its own
+    assert_eq!(keys.len(), 4);
+}
+
+#[tokio::test]
+async fn a_publish_that_fails_partway_leaves_nothing_behind() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    });
+
+    // Saving the response is the last write, after the key and the issue
+    break_writes_to(&app.db_pool, "idempotency").await;
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 500);
+
+    let leftovers = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM idempotency) AS "keys!",
+            (SELECT COUNT(*) FROM newsletter_issues) AS "issues!",
+            (SELECT COUNT(*) FROM issue_delivery_queue) AS "tasks!"
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        (leftovers.keys, leftovers.issues, leftovers.tasks),
+        (0, 0, 0)
+    );
+
+    // The key was never used up, so the same submission goes through once the fault is gone
+    fix_writes_to(&app.db_pool, "idempotency").await;
+    let response = app.post_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 1 subscribers.</i></p>"));
+}
+
+#[tokio::test]
+async fn an_api_client_can_publish_with_json() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token: serde_json::Value = app.post_api_token().await.json().await.unwrap();
+    app.post_logout().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/newsletter", &app.address))
+        .bearer_auth(token["token"].as_str().unwrap())
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "tags": "rust, backend",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .send()
+        .await
         .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let issue_id: Uuid = body["issue_id"].as_str().unwrap().parse().unwrap();
+    let issue = sqlx::query!(
+        "SELECT title, tags FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.title, "Newsletter title");
+    assert_eq!(issue.tags, vec!["rust", "backend"]);
+}
+
+#[tokio::test]
+async fn a_json_publish_with_a_content_warning_is_rejected_until_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    });
+
+    let response = app.post_newsletter_json(&body).await;
+    assert_eq!(response.status().as_u16(), 422);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["warnings"].as_array().unwrap().len(), 1);
+
+    body["confirm"] = "yes".into();
+    let response = app.post_newsletter_json(&body).await;
+    assert_eq!(response.status().as_u16(), 201);
 }
@@ -0,0 +1,278 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber_with_email, spawn_app};
+
+fn issue() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Spring issue",
+        "text_content": "The spring issue as plain text",
+        "html_content": "<p>The spring issue as HTML</p>",
+        "tags": ["Rust", "backend"],
+    })
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_compose() {
+    let app = spawn_app().await;
+
+    let response = app.post_compose("save-draft", &issue()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_api_token_can_compose() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let body: serde_json::Value = app.post_api_token().await.json().await.unwrap();
+    app.post_logout().await;
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/admin/newsletter/compose/estimate-audience",
+            &app.address
+        ))
+        .bearer_auth(body["token"].as_str().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn an_issue_can_be_drafted_previewed_and_published() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Part 1 - Save a draft, then save it again
+    let response = app.post_compose("save-draft", &issue()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let saved: serde_json::Value = response.json().await.unwrap();
+    let draft_id = saved["draft_id"].as_str().unwrap().to_owned();
+    let mut draft = issue();
+    draft["draft_id"] = draft_id.clone().into();
+    draft["title"] = "Spring issue, revised".into();
+    let saved: serde_json::Value = app
+        .post_compose("save-draft", &draft)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(saved["draft_id"], draft_id.as_str());
+    let drafts: serde_json::Value = app
+        .api_client
+        .get(format!("{}/admin/newsletter/compose", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(drafts.as_array().unwrap().len(), 1);
+    assert_eq!(drafts[0]["title"], "Spring issue, revised");
+    assert_eq!(drafts[0]["tags"], serde_json::json!(["rust", "backend"]));
+
+    // Part 2 - Validate it
+    let report: serde_json::Value = app
+        .post_compose("validate", &draft)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["valid"], true);
+    assert_eq!(report["errors"], serde_json::json!([]));
+    assert_eq!(report["warnings"], serde_json::json!([]));
+
+    // Part 3 - Preview it
+    let preview: serde_json::Value = app
+        .post_compose("render-preview", &draft)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(preview["subject"], "Spring issue, revised");
+    assert!(
+        preview["html"]
+            .as_str()
+            .unwrap()
+            .contains("The spring issue as HTML")
+    );
+
+    // Part 4 - See who it would go to
+    let estimate: serde_json::Value = app
+        .post_compose("estimate-audience", &serde_json::json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(estimate["recipients"], 1);
+
+    // Part 5 - Publish it
+    let mut publish = draft.clone();
+    publish["idempotency_key"] = Uuid::new_v4().to_string().into();
+    let response = app.post_compose("publish", &publish).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let published: serde_json::Value = response.json().await.unwrap();
+    let issue_id: Uuid = published["issue_id"].as_str().unwrap().parse().unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!(
+        "SELECT title, status, tags FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.title, "Spring issue, revised");
+    assert_eq!(issue.status, "sent");
+    assert_eq!(issue.tags, vec!["rust", "backend"]);
+    let n_drafts = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_drafts"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_drafts, 0);
+}
+
+#[tokio::test]
+async fn validation_reports_errors_and_warnings() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let report: serde_json::Value = app
+        .post_compose(
+            "validate",
+            &serde_json::json!({
+                "title": " ",
+                "text_content": "",
+                "html_content": "<p>Only HTML</p>",
+                "template_id": Uuid::new_v4(),
+            }),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(report["valid"], false);
+    assert_eq!(
+        report["errors"],
+        serde_json::json!([
+            "The issue needs a title.",
+            "The selected template does not exist."
+        ])
+    );
+    assert_eq!(
+        report["warnings"],
+        serde_json::json!(["The plain text content is empty but the HTML content isn't."])
+    );
+}
+
+#[tokio::test]
+async fn publishing_with_warnings_needs_confirming() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut publish = serde_json::json!({
+        "title": "Spring issue",
+        "text_content": "",
+        "html_content": "<p>Only HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+
+    let response = app.post_compose("publish", &publish).await;
+    assert_eq!(response.status().as_u16(), 422);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["valid"], true);
+    assert_eq!(report["warnings"].as_array().unwrap().len(), 1);
+
+    publish["confirm"] = true.into();
+    let response = app.post_compose("publish", &publish).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn publishing_is_idempotent() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut publish = issue();
+    publish["idempotency_key"] = Uuid::new_v4().to_string().into();
+
+    let first: serde_json::Value = app
+        .post_compose("publish", &publish)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let second: serde_json::Value = app
+        .post_compose("publish", &publish)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(first["issue_id"], second["issue_id"]);
+    let n_issues = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn a_replayed_publish_gets_the_saved_response_without_a_second_review() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // The linked page breaks after the first check
+    let target = MockServer::start().await;
+    Mock::given(path("/page"))
+        .respond_with(ResponseTemplate::new(200))
+        .up_to_n_times(1)
+        .mount(&target)
+        .await;
+    Mock::given(path("/page"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(0)
+        .mount(&target)
+        .await;
+    let mut publish = issue();
+    publish["html_content"] =
+        format!(r#"<p><a href="{}/page">Read more</a></p>"#, target.uri()).into();
+    publish["idempotency_key"] = Uuid::new_v4().to_string().into();
+
+    let first = app.post_compose("publish", &publish).await;
+    assert_eq!(first.status().as_u16(), 201);
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second = app.post_compose("publish", &publish).await;
+
+    assert_eq!(second.status().as_u16(), 201);
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(first["issue_id"], second["issue_id"]);
+}
+
+#[tokio::test]
+async fn a_rejected_publish_leaves_the_key_free() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let mut publish = issue();
+    publish["title"] = " ".into();
+    publish["idempotency_key"] = idempotency_key.clone().into();
+
+    let response = app.post_compose("publish", &publish).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    publish["title"] = "Spring issue".into();
+    let response = app.post_compose("publish", &publish).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
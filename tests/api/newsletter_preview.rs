@@ -0,0 +1,79 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn a_preview_is_sent_to_the_admins_own_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_notification_settings(&serde_json::json!({ "email": "admin@example.com" }))
+        .await;
+    let issue_id = app.publish_issue("March issue").await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_preview_to_me(issue_id).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("A preview of \"March issue\" has been sent to admin@example.com."));
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "admin@example.com");
+    assert_eq!(body["Subject"], "[Preview] March issue");
+    // Not a delivery, so nothing is tracked
+    assert!(!body["HtmlBody"].as_str().unwrap().contains("track/open"));
+}
+
+#[tokio::test]
+async fn an_admin_without_an_email_address_is_told_to_add_one() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = app.publish_issue("March issue").await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_preview_to_me(issue_id).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("Add a valid email address to your notification settings"));
+}
+
+#[tokio::test]
+async fn previewing_an_unknown_issue_sends_nothing() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_notification_settings(&serde_json::json!({ "email": "admin@example.com" }))
+        .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_preview_to_me(Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("No such newsletter issue."));
+}
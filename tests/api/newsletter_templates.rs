@@ -0,0 +1,155 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+fn template_body() -> serde_json::Value {
+    serde_json::json!({
+        "name": "Weekly layout",
+        "html_body": "<header>Weekly</header>{{content}}<footer>Unsubscribe</footer>",
+        "text_body": "WEEKLY\n{{content}}\n-- unsubscribe",
+    })
+}
+
+async fn create_template(app: &TestApp) -> Uuid {
+    let response = app.post_newsletter_template(&template_body()).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let template: serde_json::Value = response.json().await.unwrap();
+    template["id"].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_templates() {
+    let app = spawn_app().await;
+
+    let response = app.post_newsletter_template(&template_body()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn templates_without_a_content_placeholder_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletter_template(&serde_json::json!({
+            "name": "Broken layout",
+            "html_body": "<header>Weekly</header>",
+            "text_body": "WEEKLY\n{{content}}",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn templates_can_be_read_updated_and_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let template_id = create_template(&app).await;
+    let url = format!("{}/admin/newsletter/templates/{template_id}", &app.address);
+
+    let templates: serde_json::Value = app
+        .api_client
+        .get(format!("{}/admin/newsletter/templates", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(templates.as_array().unwrap().len(), 1);
+    assert_eq!(
+        templates[0]["created_by"],
+        app.test_user.user_id.to_string()
+    );
+
+    let mut updated = template_body();
+    updated["name"] = "Monthly layout".into();
+    let response = app
+        .api_client
+        .put(&url)
+        .json(&updated)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let template: serde_json::Value = app
+        .api_client
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(template["name"], "Monthly layout");
+
+    let response = app.api_client.delete(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.api_client.get(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_newsletter_published_with_a_template_is_wrapped_in_its_layout() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let template_id = create_template(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "template_id": template_id,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with(
+        "<header>Weekly</header><p>Newsletter body as HTML</p><footer>Unsubscribe</footer>"
+    ));
+    let text = body["TextBody"].as_str().unwrap();
+    assert!(text.starts_with("WEEKLY\nNewsletter body as plain text\n-- unsubscribe"));
+}
+
+#[tokio::test]
+async fn publishing_with_an_unknown_template_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "template_id": Uuid::new_v4(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,124 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, create_confirmed_subscriber, spawn_app};
+
+// Publishes an issue to one subscriber and returns the issue id and the pixel's URL
+async fn deliver_an_issue(app: &TestApp) -> (Uuid, reqwest::Url) {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    let pixel = linkify::LinkFinder::new()
+        .links(body["HtmlBody"].as_str().unwrap())
+        .find(|l| l.as_str().contains("/track/open/"))
+        .expect("The email has no tracking pixel.");
+    let mut pixel = reqwest::Url::parse(pixel.as_str()).unwrap();
+    pixel.set_port(Some(app.port)).unwrap();
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    (issue_id, pixel)
+}
+
+async fn fetch_pixel(pixel: &reqwest::Url, user_agent: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(pixel.clone())
+        .header("User-Agent", user_agent)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn fetching_the_pixel_records_an_open() {
+    let app = spawn_app().await;
+    let (issue_id, pixel) = deliver_an_issue(&app).await;
+
+    let response = fetch_pixel(&pixel, "Mail/1.0").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+    assert!(response.bytes().await.unwrap().starts_with(b"GIF89a"));
+    let open = sqlx::query!("SELECT newsletter_issue_id, user_agent FROM newsletter_opens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(open.newsletter_issue_id, issue_id);
+    assert_eq!(open.user_agent.as_deref(), Some("Mail/1.0"));
+}
+
+#[tokio::test]
+async fn the_open_rate_counts_repeat_opens_once_per_subscriber() {
+    let app = spawn_app().await;
+    let (issue_id, pixel) = deliver_an_issue(&app).await;
+
+    fetch_pixel(&pixel, "Mail/1.0").await;
+    // The same client rendering the message again isn't a new open
+    fetch_pixel(&pixel, "Mail/1.0").await;
+    fetch_pixel(&pixel, "Phone/2.0").await;
+
+    let stats: serde_json::Value = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter/{issue_id}/open-rate",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["opens"], 2);
+    assert_eq!(stats["unique_opens"], 1);
+    assert_eq!(stats["rate"], 1.0);
+}
+
+#[tokio::test]
+async fn an_unknown_delivery_still_gets_a_pixel_but_records_nothing() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/track/open/{}.gif",
+        &app.address,
+        Uuid::new_v4()
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let n_opens = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM newsletter_opens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_opens, 0);
+}
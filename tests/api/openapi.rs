@@ -0,0 +1,76 @@
+use openapiv3::{OpenAPI, ReferenceOr};
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+async fn get_openapi_spec(app: &crate::helpers::TestApp) -> OpenAPI {
+    let response = reqwest::get(format!("{}/api/openapi.json", &app.address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn the_spec_describes_subscribing_and_its_errors() {
+    let app = spawn_app().await;
+
+    let spec = get_openapi_spec(&app).await;
+
+    let ReferenceOr::Item(subscriptions) = &spec.paths.paths["/subscriptions"] else {
+        panic!("/subscriptions is a reference");
+    };
+    let subscribe = subscriptions.post.as_ref().unwrap();
+    let ReferenceOr::Item(bad_request) =
+        &subscribe.responses.responses[&openapiv3::StatusCode::Code(400)]
+    else {
+        panic!("The 400 response is a reference");
+    };
+    let schema = bad_request.content["application/json"]
+        .schema
+        .as_ref()
+        .unwrap();
+    assert!(matches!(
+        schema,
+        ReferenceOr::Reference { reference } if reference == "#/components/schemas/ErrorBody"
+    ));
+    let components = spec.components.unwrap();
+    assert!(components.schemas.contains_key("ErrorBody"));
+    assert!(components.schemas.contains_key("SubscriptionsFormData"));
+    assert!(spec.paths.paths.contains_key("/subscriptions/confirm"));
+    assert!(spec.paths.paths.contains_key("/health_check"));
+    assert!(spec.paths.paths.contains_key("/health_check/live"));
+    assert!(spec.paths.paths.contains_key("/health_check/ready"));
+    assert!(spec.paths.paths.contains_key("/version"));
+    assert!(spec.paths.paths.contains_key("/api/v1/newsletters"));
+    assert!(spec.paths.paths.contains_key("/api/v1/newsletters/tags"));
+}
+
+#[tokio::test]
+async fn the_spec_leaves_out_the_admin_pages() {
+    let app = spawn_app().await;
+
+    let spec = get_openapi_spec(&app).await;
+
+    assert!(
+        spec.paths
+            .paths
+            .keys()
+            .all(|path| !path.starts_with("/admin"))
+    );
+}
+
+#[tokio::test]
+async fn the_swagger_ui_is_only_served_when_turned_on() {
+    let off = spawn_app().await;
+    let on = spawn_app_with(|c| c.application.swagger_ui = true).await;
+
+    let response = reqwest::get(format!("{}/api/docs", &off.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+    let response = reqwest::get(format!("{}/api/docs", &on.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("/api/openapi.json"));
+}
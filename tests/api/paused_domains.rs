@@ -0,0 +1,137 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber_with_email, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_pause_a_domain() {
+    let app = spawn_app().await;
+
+    let response = app.post_paused_domain("example.com").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn deliveries_to_a_paused_domain_are_deferred_while_others_proceed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@paused.example.com").await;
+    create_confirmed_subscriber_with_email(&app, "le_guin@example.org").await;
+    app.test_user.login(&app).await;
+    let response = app.post_paused_domain("Paused.Example.com").await;
+    assert_is_redirect_to(&response, "/admin/paused-domains");
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .named("Deliveries while the domain is paused")
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["To"], "le_guin@example.org");
+    let deferred = sqlx::query!(
+        r#"
+        SELECT subscriber_id, execute_after > now() AS "deferred!"
+        FROM issue_delivery_queue
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        deferred.subscriber_id,
+        Some(app.subscriber_id("ursula@paused.example.com").await)
+    );
+    assert!(deferred.deferred);
+    let n_failed = sqlx::query!(r#"SELECT n_failed FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n_failed;
+    assert_eq!(n_failed, 0);
+}
+
+#[tokio::test]
+async fn deferred_deliveries_go_out_once_the_domain_is_resumed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@paused.example.com").await;
+    app.test_user.login(&app).await;
+    app.post_paused_domain("paused.example.com").await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/paused-domains/paused.example.com/resume",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/paused-domains");
+    // Skip the retry delay
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let status = sqlx::query!(r#"SELECT status FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "sent");
+}
+
+#[tokio::test]
+async fn a_rejected_domain_is_escaped_in_the_flash_message() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_paused_domain("<script>alert(1)</script>").await;
+    assert_is_redirect_to(&response, "/admin/paused-domains");
+
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/paused-domains", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!html_page.contains("<script>alert(1)</script>"));
+}
@@ -0,0 +1,436 @@
+use secrecy::Secret;
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::{
+    configuration::PiiEncryptionSettings,
+    domain::{EncryptedString, PiiCipher},
+    pii_migration::encrypt_stored_pii,
+};
+
+use crate::helpers::{
+    TestApp, create_confirmed_subscriber_with_email, create_unconfirmed_subscriber_with_email,
+    spawn_app, spawn_app_with,
+};
+
+fn key(byte: u8) -> Secret<String> {
+    Secret::new(base64::encode([byte; 32]))
+}
+
+fn pii_encryption(current: u8, previous: &[u8]) -> PiiEncryptionSettings {
+    PiiEncryptionSettings {
+        key: key(current),
+        previous_keys: previous.iter().map(|byte| key(*byte)).collect(),
+        hash_key: key(0),
+    }
+}
+
+fn cipher(current: u8, previous: &[u8]) -> PiiCipher {
+    let settings = pii_encryption(current, previous);
+    PiiCipher::new(&settings.key, &settings.previous_keys, &settings.hash_key).unwrap()
+}
+
+async fn spawn_encrypting_app() -> TestApp {
+    spawn_app_with(|c| c.pii_encryption = Some(pii_encryption(1, &[]))).await
+}
+
+#[tokio::test]
+async fn a_new_subscriber_is_stored_encrypted() {
+    let app = spawn_encrypting_app().await;
+
+    create_unconfirmed_subscriber_with_email(&app, "ursula@example.com").await;
+
+    let saved = sqlx::query!(
+        "SELECT email, email_encrypted, email_hash, name, name_encrypted FROM subscriptions"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.email, None);
+    assert_eq!(saved.name, None);
+    let email_encrypted = EncryptedString::from(saved.email_encrypted.unwrap());
+    assert!(!email_encrypted.as_ref().contains("ursula"));
+    assert_eq!(
+        app.pii_cipher.decrypt(&email_encrypted).unwrap(),
+        "ursula@example.com"
+    );
+    assert_eq!(
+        saved.email_hash,
+        app.pii_cipher.email_hash("ursula@example.com")
+    );
+    assert!(saved.name_encrypted.is_some());
+}
+
+#[tokio::test]
+async fn signing_up_again_finds_the_encrypted_subscriber() {
+    let app = spawn_encrypting_app().await;
+
+    create_unconfirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    create_unconfirmed_subscriber_with_email(&app, "ursula@example.com").await;
+
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 1);
+}
+
+#[tokio::test]
+async fn an_encrypted_subscriber_is_sent_newsletters_at_their_address() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.publish_issue("Newsletter title").await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(body["To"], "ursula@example.com");
+    let subscriber = sqlx::query!("SELECT last_emailed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(subscriber.last_emailed_at.is_some());
+}
+
+#[tokio::test]
+async fn deliveries_keep_no_copy_of_an_encrypted_address() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    let subscriber_id = app.subscriber_id("ursula@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Part 1 - The queue only knows who the delivery is for
+    app.publish_issue("Newsletter title").await;
+    let queued = sqlx::query!("SELECT subscriber_id, subscriber_email FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.subscriber_id, Some(subscriber_id));
+    assert_eq!(queued.subscriber_email, None);
+
+    // Part 2 - And so does the record of the delivery
+    app.dispatch_all_pending_emails().await;
+    let delivery =
+        sqlx::query!("SELECT subscriber_id, subscriber_email FROM newsletter_deliveries")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(delivery.subscriber_id, Some(subscriber_id));
+    assert_eq!(delivery.subscriber_email, None);
+}
+
+#[tokio::test]
+async fn an_address_waiting_to_be_confirmed_is_stored_encrypted() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    let subscriber_id = app.subscriber_id("ursula@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.api_client
+        .post(format!(
+            "{}/admin/subscribers/{subscriber_id}/email",
+            &app.address
+        ))
+        .json(&serde_json::json!({ "email": "le_guin@example.com" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let saved = sqlx::query!("SELECT pending_email, pending_email_encrypted FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.pending_email, None);
+    let pending_email_encrypted = EncryptedString::from(saved.pending_email_encrypted.unwrap());
+    assert_eq!(
+        app.pii_cipher.decrypt(&pending_email_encrypted).unwrap(),
+        "le_guin@example.com"
+    );
+    let subscriber: serde_json::Value = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{subscriber_id}",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber["pending_email"], "le_guin@example.com");
+}
+
+#[tokio::test]
+async fn a_suppressed_encrypted_subscriber_is_not_sent_newsletters() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "email": "Ursula@Example.com",
+        "reason": "Asked to be left alone",
+    }))
+    .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.publish_issue("Newsletter title").await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 0 subscribers.</i></p>"));
+}
+
+#[tokio::test]
+async fn encrypted_subscribers_are_only_found_by_their_full_address() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    app.test_user.login(&app).await;
+
+    let html_page = app
+        .get_subscribers_html("search=Ursula%40example.com")
+        .await;
+    assert!(html_page.contains("<td>ursula@example.com</td>"));
+
+    let html_page = app.get_subscribers_html("search=example.com").await;
+    assert!(!html_page.contains("ursula@example.com"));
+}
+
+#[tokio::test]
+async fn encrypt_pii_encrypts_plain_text_subscribers_and_rotates_keys() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "email": "ged@earthsea.org",
+        "reason": "Bounced",
+    }))
+    .await;
+
+    // Part 1 - Plain text is encrypted with the first key
+    let report = encrypt_stored_pii(&app.db_pool, &cipher(1, &[]), 1)
+        .await
+        .unwrap();
+    assert_eq!(report.subscribers, 1);
+    assert_eq!(report.suppressions, 1);
+    let saved = sqlx::query!("SELECT email, email_encrypted, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, None);
+    assert_eq!(saved.name, None);
+    let first_key_prefix = cipher(1, &[]).current_key_prefix().unwrap();
+    assert!(
+        saved
+            .email_encrypted
+            .unwrap()
+            .starts_with(&first_key_prefix)
+    );
+
+    // Part 2 - Rotating re-encrypts it with the new key
+    let report = encrypt_stored_pii(&app.db_pool, &cipher(2, &[1]), 1)
+        .await
+        .unwrap();
+    assert_eq!(report.subscribers, 1);
+    let saved = sqlx::query!("SELECT email_encrypted, email_hash FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let email_encrypted = EncryptedString::from(saved.email_encrypted.unwrap());
+    assert_eq!(
+        cipher(2, &[]).decrypt(&email_encrypted).unwrap(),
+        "ursula@example.com"
+    );
+    assert_eq!(
+        saved.email_hash,
+        cipher(2, &[]).email_hash("ursula@example.com")
+    );
+
+    // Part 3 - Nothing is left to do
+    let report = encrypt_stored_pii(&app.db_pool, &cipher(2, &[]), 1)
+        .await
+        .unwrap();
+    assert_eq!(report.subscribers, 0);
+    assert_eq!(report.suppressions, 0);
+}
+
+#[tokio::test]
+async fn encrypt_pii_matches_deliveries_that_still_have_an_address() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    let subscriber_id = app.subscriber_id("ursula@example.com").await;
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, slug
+        )
+        VALUES ($1, 'Issue', 'Text', '<p>HTML</p>', now()::TEXT, $2)
+        "#,
+        issue_id,
+        issue_id.to_string()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    // As left behind for encrypted subscribers from before deliveries were keyed by subscriber
+    for email in ["ursula@example.com", "gone@example.com"] {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email, recipient_domain)
+            VALUES ($1, $2, 'example.com')
+            "#,
+            issue_id,
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            r#"
+            INSERT INTO newsletter_deliveries (
+                newsletter_issue_id, subscriber_email, status, attempted_at, delivery_id
+            )
+            VALUES ($1, $2, 'failed', now(), $3)
+            "#,
+            issue_id,
+            email,
+            Uuid::new_v4()
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    let report = encrypt_stored_pii(&app.db_pool, &cipher(1, &[]), 10)
+        .await
+        .unwrap();
+
+    // One queued delivery matched, one dropped and both recorded ones cleared
+    assert_eq!(report.deliveries, 4);
+    let queued = sqlx::query!("SELECT subscriber_id, subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].subscriber_id, Some(subscriber_id));
+    assert_eq!(queued[0].subscriber_email, None);
+    let deliveries = sqlx::query!(
+        "SELECT subscriber_id, subscriber_email FROM newsletter_deliveries ORDER BY subscriber_id"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deliveries.len(), 2);
+    assert_eq!(deliveries[0].subscriber_id, Some(subscriber_id));
+    assert_eq!(deliveries[1].subscriber_id, None);
+    assert!(deliveries.iter().all(|d| d.subscriber_email.is_none()));
+}
+
+// As left behind by a key that was dropped from the configuration
+async fn encrypt_under_a_lost_key(app: &TestApp, subscriber_id: Uuid, email: &str) {
+    let email_encrypted = cipher(9, &[]).encrypt(email).unwrap();
+    sqlx::query!(
+        "UPDATE subscriptions SET email = NULL, email_encrypted = $2 WHERE id = $1",
+        subscriber_id,
+        email_encrypted.as_ref()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn a_subscriber_whose_address_cant_be_decrypted_is_left_out_and_audited() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    create_confirmed_subscriber_with_email(&app, "ged@example.com").await;
+    let lost_id = app.subscriber_id("ged@example.com").await;
+    encrypt_under_a_lost_key(&app, lost_id, "ged@example.com").await;
+    app.test_user.login(&app).await;
+
+    let issue_id = app.publish_issue("Newsletter title").await;
+
+    let queued = sqlx::query!("SELECT subscriber_id FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_ne!(queued[0].subscriber_id, Some(lost_id));
+    let audit = sqlx::query!(
+        "SELECT subject, details FROM audit_log WHERE action = 'issue.recipient_skipped'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(audit.subject, issue_id.to_string());
+    assert_eq!(audit.details, Some(lost_id.to_string()));
+}
+
+#[tokio::test]
+async fn a_queued_delivery_whose_address_cant_be_decrypted_is_recorded_as_failed() {
+    let app = spawn_encrypting_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    create_confirmed_subscriber_with_email(&app, "ged@example.com").await;
+    let lost_id = app.subscriber_id("ged@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter title").await;
+    encrypt_under_a_lost_key(&app, lost_id, "ged@example.com").await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let queued = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued, 0);
+    let delivery = sqlx::query!(
+        "SELECT status FROM newsletter_deliveries WHERE subscriber_id = $1",
+        lost_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(delivery.status, "failed");
+}
+
+#[tokio::test]
+async fn encrypt_pii_needs_a_key() {
+    let app = spawn_app().await;
+
+    let outcome = encrypt_stored_pii(&app.db_pool, &PiiCipher::default(), 100).await;
+
+    assert!(outcome.is_err());
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use zero_to_prod::{
+    clock::Clock,
+    public_stats::{RECOUNT_LOCK, refresh_public_stats},
+};
+
+use crate::helpers::{
+    TestApp, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app, spawn_app_with,
+};
+
+async fn get_stats(app: &TestApp) -> serde_json::Value {
+    let response = app.get_stats(None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+// As the stats refresher does
+async fn refresh(app: &TestApp) {
+    refresh_public_stats(&app.db_pool, app.clock.now())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn stats_count_confirmed_subscribers_without_an_api_token() {
+    let app = spawn_app().await;
+    app.clock.set(chrono::Utc::now());
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    refresh(&app).await;
+
+    let stats = get_stats(&app).await;
+
+    assert_eq!(stats["confirmed_subscribers"], 1);
+    assert_eq!(stats["issues_published"], 0);
+}
+
+#[tokio::test]
+async fn stats_are_counted_on_the_first_request() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let stats = get_stats(&app).await;
+
+    assert_eq!(stats["confirmed_subscribers"], 1);
+}
+
+#[tokio::test]
+async fn stale_stats_are_recounted_on_demand() {
+    let app = spawn_app().await;
+    app.clock.set(chrono::Utc::now());
+    create_confirmed_subscriber(&app).await;
+    refresh(&app).await;
+    assert_eq!(get_stats(&app).await["confirmed_subscribers"], 1);
+
+    create_confirmed_subscriber(&app).await;
+    app.clock.advance(chrono::Duration::hours(1));
+
+    let stats = get_stats(&app).await;
+    assert_eq!(stats["confirmed_subscribers"], 2);
+    let computed_at: DateTime<Utc> = stats["computed_at"].as_str().unwrap().parse().unwrap();
+    assert!((computed_at - app.clock.now()).num_seconds().abs() < 1);
+}
+
+#[tokio::test]
+async fn stale_stats_are_served_while_another_request_recounts() {
+    let app = spawn_app().await;
+    app.clock.set(chrono::Utc::now());
+    refresh(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.clock.advance(chrono::Duration::hours(1));
+    // Stands in for a request that is part way through recounting
+    let mut recounting = app.db_pool.begin().await.unwrap();
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", RECOUNT_LOCK)
+        .execute(&mut recounting)
+        .await
+        .unwrap();
+
+    assert_eq!(get_stats(&app).await["confirmed_subscribers"], 0);
+
+    recounting.rollback().await.unwrap();
+    assert_eq!(get_stats(&app).await["confirmed_subscribers"], 1);
+}
+
+#[tokio::test]
+async fn the_refresh_job_updates_the_cached_stats() {
+    let app = spawn_app().await;
+    // Whole seconds, Postgres keeps timestamps to the microsecond
+    let first_computed_at: DateTime<Utc> = "2025-07-10T09:00:00Z".parse().unwrap();
+    app.clock.set(first_computed_at);
+    refresh(&app).await;
+    let stats = get_stats(&app).await;
+    assert_eq!(stats["confirmed_subscribers"], 0);
+    let computed_at: DateTime<Utc> = stats["computed_at"].as_str().unwrap().parse().unwrap();
+    assert_eq!(computed_at, first_computed_at);
+
+    // Part 1 - Served from the cache
+    create_confirmed_subscriber(&app).await;
+    app.clock.advance(chrono::Duration::seconds(10));
+    assert_eq!(get_stats(&app).await["confirmed_subscribers"], 0);
+
+    // Part 2 - The job recounts
+    refresh(&app).await;
+    let stats = get_stats(&app).await;
+    assert_eq!(stats["confirmed_subscribers"], 1);
+    let computed_at: DateTime<Utc> = stats["computed_at"].as_str().unwrap().parse().unwrap();
+    assert_eq!(computed_at, app.clock.now());
+}
+
+#[tokio::test]
+async fn the_subscriber_count_can_be_rounded() {
+    let app = spawn_app_with(|c| c.public_stats.round_to = 10).await;
+    app.clock.set(chrono::Utc::now());
+    for _ in 0..6 {
+        create_confirmed_subscriber(&app).await;
+    }
+    refresh(&app).await;
+
+    let stats = get_stats(&app).await;
+
+    assert_eq!(stats["confirmed_subscribers"], 10);
+}
+
+#[tokio::test]
+async fn allowed_origins_may_read_the_stats_from_a_browser() {
+    let app = spawn_app_with(|c| {
+        c.public_stats.allowed_origins = vec!["https://marketing.example.com".into()]
+    })
+    .await;
+    refresh(&app).await;
+
+    // Part 1 - Preflight
+    let response = app
+        .api_client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/api/v1/stats", &app.address),
+        )
+        .header("Origin", "https://marketing.example.com")
+        .header("Access-Control-Request-Method", "GET")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(
+        response.headers()["Access-Control-Allow-Origin"],
+        "https://marketing.example.com"
+    );
+
+    // Part 2 - The request itself
+    let response = app.get_stats(Some("https://marketing.example.com")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Access-Control-Allow-Origin"],
+        "https://marketing.example.com"
+    );
+    assert_eq!(response.headers()["Vary"], "Origin");
+}
+
+#[tokio::test]
+async fn other_origins_get_no_cors_headers() {
+    let app = spawn_app_with(|c| {
+        c.public_stats.allowed_origins = vec!["https://marketing.example.com".into()]
+    })
+    .await;
+    refresh(&app).await;
+
+    let response = app.get_stats(Some("https://elsewhere.example.com")).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        !response
+            .headers()
+            .contains_key("Access-Control-Allow-Origin")
+    );
+}
+
+#[tokio::test]
+async fn the_rest_of_the_api_still_needs_a_token() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/newsletters", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,120 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::helpers::spawn_app;
+
+// Enough rows that reading the whole table costs more than going through an index
+async fn seed(pool: &PgPool) -> Uuid {
+    sqlx::query(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT
+            gen_random_uuid(),
+            'reader' || n || '@example.com',
+            'Reader ' || n,
+            now() - make_interval(days => n),
+            CASE WHEN n % 10 = 0 THEN 'pending_confirmation' ELSE 'confirmed' END
+        FROM generate_series(1, 20000) AS n
+        "#,
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    let issue_ids: Vec<Uuid> = (0..20).map(|_| Uuid::new_v4()).collect();
+    sqlx::query(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, slug
+        )
+        SELECT id, 'Issue', 'Text', '<p>HTML</p>', now()::TEXT, id::TEXT
+        FROM UNNEST($1::uuid[]) AS id
+        "#,
+    )
+    .bind(&issue_ids)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO newsletter_deliveries (
+            newsletter_issue_id, subscriber_id, status, attempted_at, delivery_id
+        )
+        SELECT
+            i.id,
+            s.id,
+            CASE WHEN n % 50 = 0 THEN 'failed' ELSE 'delivered' END,
+            now(),
+            gen_random_uuid()
+        FROM UNNEST($1::uuid[]) AS i(id), generate_series(1, 2000) AS n
+        JOIN subscriptions s ON s.email = 'reader' || n || '@example.com'
+        "#,
+    )
+    .bind(&issue_ids)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query("ANALYZE").execute(pool).await.unwrap();
+    issue_ids[0]
+}
+
+// The plan Postgres actually ran `query` with, one node per line
+async fn explain_analyze(pool: &PgPool, query: &str) -> String {
+    let rows: Vec<String> = sqlx::query_scalar(&format!("EXPLAIN ANALYZE {query}"))
+        .fetch_all(pool)
+        .await
+        .unwrap();
+    rows.join("\n")
+}
+
+fn assert_uses_index(plan: &str, table: &str, index: &str) {
+    assert!(
+        !plan.contains(&format!("Seq Scan on {table}")),
+        "Expected no sequential scan on {table}:\n{plan}"
+    );
+    assert!(
+        plan.contains(&format!("Index Scan using {index}"))
+            || plan.contains(&format!("Index Only Scan using {index}"))
+            || plan.contains(&format!("Bitmap Index Scan on {index}")),
+        "Expected a scan of {index}:\n{plan}"
+    );
+}
+
+#[tokio::test]
+async fn an_issues_failed_deliveries_are_found_through_an_index() {
+    let app = spawn_app().await;
+    let issue_id = seed(&app.db_pool).await;
+
+    let plan = explain_analyze(
+        &app.db_pool,
+        &format!(
+            "SELECT subscriber_id FROM newsletter_deliveries \
+            WHERE newsletter_issue_id = '{issue_id}' AND status = 'failed'"
+        ),
+    )
+    .await;
+
+    assert_uses_index(
+        &plan,
+        "newsletter_deliveries",
+        "newsletter_deliveries_issue_status_idx",
+    );
+}
+
+#[tokio::test]
+async fn long_standing_confirmed_subscribers_are_found_through_an_index() {
+    let app = spawn_app().await;
+    seed(&app.db_pool).await;
+
+    let plan = explain_analyze(
+        &app.db_pool,
+        "SELECT id FROM subscriptions \
+        WHERE status = 'confirmed' AND subscribed_at < now() - interval '19900 days'",
+    )
+    .await;
+
+    assert_uses_index(
+        &plan,
+        "subscriptions",
+        "subscriptions_status_subscribed_at_idx",
+    );
+}
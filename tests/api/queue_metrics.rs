@@ -0,0 +1,262 @@
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
+
+async fn get_queue_metrics(app: &TestApp) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/newsletter/queue-metrics", &app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn insert_issue(app: &TestApp, published_at: DateTime<Utc>) -> Uuid {
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, slug
+        )
+        VALUES ($1, 'Issue', 'Text', '<p>HTML</p>', $2, $3)
+        "#,
+        issue_id,
+        published_at.to_rfc3339(),
+        issue_id.to_string()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    issue_id
+}
+
+async fn insert_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Reader', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+async fn queue_task(
+    app: &TestApp,
+    issue_id: Uuid,
+    email: &str,
+    execute_after: DateTime<Utc>,
+) -> Uuid {
+    let subscriber_id = insert_subscriber(app, email).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id, subscriber_id, recipient_domain, execute_after
+        )
+        VALUES ($1, $2, 'example.com', $3)
+        "#,
+        issue_id,
+        subscriber_id,
+        execute_after
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+async fn record_delivery(
+    app: &TestApp,
+    issue_id: Uuid,
+    email: &str,
+    status: &str,
+    attempted_at: DateTime<Utc>,
+) {
+    let subscriber_id = insert_subscriber(app, email).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries (
+            newsletter_issue_id, subscriber_id, status, attempted_at, delivery_id
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        issue_id,
+        subscriber_id,
+        status,
+        attempted_at,
+        Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_queue_metrics() {
+    let app = spawn_app().await;
+
+    let response = get_queue_metrics(&app).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_empty_queue_reports_zeroes() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let metrics: serde_json::Value = get_queue_metrics(&app).await.json().await.unwrap();
+
+    assert_eq!(metrics["pending_tasks"], 0);
+    assert_eq!(metrics["in_progress_tasks"], 0);
+    assert_eq!(metrics["completed_today"], 0);
+    assert_eq!(metrics["failed_today"], 0);
+    assert_eq!(metrics["average_delivery_time_ms"], serde_json::Value::Null);
+    assert_eq!(metrics["oldest_pending_age_seconds"], 0);
+    assert_eq!(metrics["warning"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn queue_metrics_count_due_tasks_and_todays_deliveries() {
+    let app = spawn_app().await;
+    let now: DateTime<Utc> = "2025-07-10T12:00:00Z".parse().unwrap();
+    app.clock.set(now);
+    app.test_user.login(&app).await;
+    let issue_id = insert_issue(&app, now - Duration::hours(1)).await;
+    queue_task(
+        &app,
+        issue_id,
+        "ursula@example.com",
+        now - Duration::minutes(10),
+    )
+    .await;
+    queue_task(
+        &app,
+        issue_id,
+        "ged@example.com",
+        now - Duration::minutes(1),
+    )
+    .await;
+    // Deferred, not due yet
+    queue_task(
+        &app,
+        issue_id,
+        "tenar@example.com",
+        now + Duration::minutes(5),
+    )
+    .await;
+    record_delivery(
+        &app,
+        issue_id,
+        "a@example.com",
+        "delivered",
+        now - Duration::minutes(40),
+    )
+    .await;
+    record_delivery(
+        &app,
+        issue_id,
+        "b@example.com",
+        "delivered",
+        now - Duration::minutes(20),
+    )
+    .await;
+    record_delivery(
+        &app,
+        issue_id,
+        "c@example.com",
+        "failed",
+        now - Duration::minutes(30),
+    )
+    .await;
+    // Yesterday's
+    record_delivery(
+        &app,
+        issue_id,
+        "d@example.com",
+        "delivered",
+        now - Duration::days(1),
+    )
+    .await;
+
+    let metrics: serde_json::Value = get_queue_metrics(&app).await.json().await.unwrap();
+
+    assert_eq!(metrics["pending_tasks"], 2);
+    assert_eq!(metrics["in_progress_tasks"], 0);
+    assert_eq!(metrics["completed_today"], 2);
+    assert_eq!(metrics["failed_today"], 1);
+    // 20 and 40 minutes after publishing
+    assert_eq!(metrics["average_delivery_time_ms"], 30 * 60 * 1000);
+    let oldest = metrics["oldest_pending_age_seconds"].as_i64().unwrap();
+    assert!((oldest - 600).abs() <= 1, "{oldest}");
+    assert!(
+        metrics["warning"]
+            .as_str()
+            .unwrap()
+            .contains("waiting for 600 seconds")
+    );
+}
+
+#[tokio::test]
+async fn the_warning_threshold_is_configurable() {
+    let app = spawn_app_with(|c| c.delivery.queue_age_warning_seconds = 900).await;
+    let now: DateTime<Utc> = "2025-07-10T12:00:00Z".parse().unwrap();
+    app.clock.set(now);
+    app.test_user.login(&app).await;
+    let issue_id = insert_issue(&app, now).await;
+    queue_task(
+        &app,
+        issue_id,
+        "ursula@example.com",
+        now - Duration::minutes(10),
+    )
+    .await;
+
+    let metrics: serde_json::Value = get_queue_metrics(&app).await.json().await.unwrap();
+
+    let oldest = metrics["oldest_pending_age_seconds"].as_i64().unwrap();
+    assert!((oldest - 600).abs() <= 1, "{oldest}");
+    assert_eq!(metrics["warning"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn a_task_being_sent_is_in_progress() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let now = Utc::now();
+    let issue_id = insert_issue(&app, now).await;
+    let ursula_id = queue_task(
+        &app,
+        issue_id,
+        "ursula@example.com",
+        now - Duration::minutes(1),
+    )
+    .await;
+    queue_task(
+        &app,
+        issue_id,
+        "ged@example.com",
+        now - Duration::minutes(1),
+    )
+    .await;
+    // Held the way a worker holds the task it's sending
+    let mut worker = app.db_pool.begin().await.unwrap();
+    sqlx::query!(
+        "SELECT subscriber_id FROM issue_delivery_queue WHERE subscriber_id = $1 FOR UPDATE",
+        ursula_id
+    )
+    .fetch_one(&mut worker)
+    .await
+    .unwrap();
+
+    let metrics: serde_json::Value = get_queue_metrics(&app).await.json().await.unwrap();
+    worker.rollback().await.unwrap();
+
+    assert_eq!(metrics["pending_tasks"], 1);
+    assert_eq!(metrics["in_progress_tasks"], 1);
+}
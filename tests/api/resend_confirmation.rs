@@ -0,0 +1,93 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    create_confirmed_subscriber_with_email, create_unconfirmed_subscriber_with_email, spawn_app,
+};
+
+async fn count_tokens(app: &crate::helpers::TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn a_pending_subscriber_gets_a_new_confirmation_email() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_resend_confirmation("ursula@example.com", None)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_confirmation_links(requests.last().unwrap());
+    assert_eq!(
+        reqwest::get(links.html).await.unwrap().status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn a_double_submitted_resend_sends_a_single_email() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let key = uuid::Uuid::new_v4().to_string();
+    let (first, second) = tokio::join!(
+        app.post_resend_confirmation("ursula@example.com", Some(&key)),
+        app.post_resend_confirmation("ursula@example.com", Some(&key)),
+    );
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    // The signup's token plus a single new one
+    assert_eq!(count_tokens(&app).await, 2);
+    // Mock verifies on Drop that we have sent exactly one confirmation email
+}
+
+#[tokio::test]
+async fn unknown_and_confirmed_addresses_get_the_same_response_without_an_email() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@example.com").await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["ged@example.com", "nobody@example.com"] {
+        let response = app.post_resend_confirmation(email, None).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn an_invalid_idempotency_key_is_rejected() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber_with_email(&app, "ursula@example.com").await;
+
+    let response = app
+        .post_resend_confirmation("ursula@example.com", Some(&"a".repeat(1000)))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,256 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app, spawn_app_with,
+};
+
+async fn add_sender(app: &TestApp, email: &str, display_name: &str) -> Uuid {
+    let response = app
+        .post_sender(&serde_json::json!({
+            "email": email,
+            "display_name": display_name,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/senders");
+    sqlx::query!(
+        "SELECT sender_id FROM senders WHERE email = $1",
+        email.to_lowercase()
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .sender_id
+}
+
+fn newsletter_from(sender_id: Uuid) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Product announcement",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "sender_id": sender_id.to_string(),
+    })
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_senders() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_sender(&serde_json::json!({
+            "email": "product@example.com",
+            "display_name": "Product team",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn senders_can_be_added_and_verified() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let sender_id = add_sender(&app, "Product@Example.com", "Product team").await;
+    let html_page = app.get_senders_html().await;
+    assert!(html_page.contains("<td>product@example.com</td>"));
+    assert!(html_page.contains("<td>Unverified</td>"));
+    // Unverified senders aren't offered when publishing
+    assert!(
+        !app.get_newsletter_html()
+            .await
+            .contains("product@example.com")
+    );
+
+    let response = app.post_verify_sender(sender_id).await;
+    assert_is_redirect_to(&response, "/admin/senders");
+    let html_page = app.get_senders_html().await;
+    assert!(html_page.contains("<p><i>product@example.com has been verified.</i></p>"));
+    assert!(html_page.contains("<td>Verified</td>"));
+    assert!(app.get_newsletter_html().await.contains(&format!(
+        r#"<option value="{sender_id}">Product team &lt;product@example.com&gt;</option>"#
+    )));
+
+    let audited = sqlx::query!("SELECT action, subject FROM audit_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(audited.action, "sender.verified");
+    assert_eq!(audited.subject, "product@example.com");
+}
+
+#[tokio::test]
+async fn an_issue_is_sent_from_its_selected_sender() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let sender_id = add_sender(&app, "product@example.com", "Product team").await;
+    app.post_verify_sender(sender_id).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_newsletter(&newsletter_from(sender_id)).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(body["From"], r#""Product team" <product@example.com>"#);
+}
+
+#[tokio::test]
+async fn an_issue_without_a_sender_uses_the_configured_one() {
+    let app = spawn_app_with(|c| c.email_client.sender_email = "news@example.com".into()).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Weekly digest",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "sender_id": "",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(body["From"], "news@example.com");
+}
+
+#[tokio::test]
+async fn an_unverified_sender_cannot_be_selected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let sender_id = add_sender(&app, "product@example.com", "Product team").await;
+
+    let response = app.post_newsletter(&newsletter_from(sender_id)).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[tokio::test]
+async fn only_configured_senders_can_be_selected_when_a_list_is_configured() {
+    let app =
+        spawn_app_with(|c| c.delivery.verified_senders = vec!["product@example.com".into()]).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let listed = add_sender(&app, "product@example.com", "Product team").await;
+    app.post_verify_sender(listed).await;
+    let unlisted = add_sender(&app, "prodcut@example.com", "Product team").await;
+    app.post_verify_sender(unlisted).await;
+
+    let response = app.post_newsletter(&newsletter_from(listed)).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let response = app.post_newsletter(&newsletter_from(unlisted)).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "prodcut@example.com is not one of the configured verified senders."
+    );
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 1);
+}
+
+#[tokio::test]
+async fn a_from_name_is_shown_next_to_the_configured_sender() {
+    let app = spawn_app_with(|c| c.email_client.sender_email = "news@example.com".into()).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Weekly digest",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "from_name": " Ursula Le Guin ",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(body["From"], r#""Ursula Le Guin" <news@example.com>"#);
+}
+
+#[tokio::test]
+async fn a_from_name_replaces_the_selected_senders_display_name() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let sender_id = add_sender(&app, "product@example.com", "Product team").await;
+    app.post_verify_sender(sender_id).await;
+
+    Mock::given(path("v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let mut newsletter = newsletter_from(sender_id);
+    newsletter["from_name"] = "Ursula Le Guin".into();
+    let response = app.post_newsletter(&newsletter).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(body["From"], r#""Ursula Le Guin" <product@example.com>"#);
+}
+
+#[tokio::test]
+async fn a_rejected_sender_address_is_escaped_in_the_flash_message() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_sender(&serde_json::json!({
+            "email": "<script>alert(1)</script>",
+            "display_name": "Product team",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/senders");
+
+    let html_page = app.get_senders_html().await;
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!html_page.contains("<script>alert(1)</script>"));
+}
@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use chrono::Duration;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::clock::Clock;
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app, spawn_app_with,
+};
+
+async fn queued_deliveries(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n
+}
+
+#[tokio::test]
+async fn deliveries_past_the_daily_limit_wait_for_the_next_day() {
+    let app = spawn_app_with(|c| c.worker.max_emails_per_day = Some(2)).await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    app.publish_issue("Newsletter title").await;
+
+    // Part 1 - The limit is reached
+    let limited = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    drop(limited);
+    assert_eq!(queued_deliveries(&app).await, 1);
+    let response = app.get_sending_rate().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "emails_sent_today": 2,
+            "daily_limit": 2,
+            "emails_per_second_limit": null,
+            "is_rate_limited": true,
+        })
+    );
+
+    // Part 2 - The count starts over at midnight UTC
+    app.clock.advance(Duration::days(1));
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(queued_deliveries(&app).await, 0);
+    let body: serde_json::Value = app.get_sending_rate().await.json().await.unwrap();
+    assert_eq!(body["emails_sent_today"], 1);
+    assert_eq!(body["is_rate_limited"], false);
+}
+
+#[tokio::test]
+async fn only_one_of_two_workers_gets_the_last_send_of_the_day() {
+    let app = spawn_app_with(|c| c.worker.max_emails_per_day = Some(1)).await;
+    let now = app.clock.now();
+
+    let (first, second) = tokio::join!(
+        app.sending_rate.claim_send(now),
+        app.sending_rate.claim_send(now)
+    );
+
+    assert_ne!(first.unwrap(), second.unwrap());
+    assert_eq!(app.sending_rate.sent_today(now).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn deliveries_are_spaced_out_past_the_per_second_limit() {
+    let app = spawn_app_with(|c| c.worker.max_emails_per_second = Some(10.0)).await;
+    for _ in 0..15 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter title").await;
+
+    let started_at = Instant::now();
+    app.dispatch_all_pending_emails().await;
+
+    // The first second's worth goes out at once, the other 5 wait a tenth of a second each
+    assert!(started_at.elapsed() >= std::time::Duration::from_millis(450));
+    assert_eq!(queued_deliveries(&app).await, 0);
+    let body: serde_json::Value = app.get_sending_rate().await.json().await.unwrap();
+    assert_eq!(body["emails_sent_today"], 15);
+    assert_eq!(body["emails_per_second_limit"], 10.0);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_sending_rate() {
+    let app = spawn_app().await;
+
+    let response = app.get_sending_rate().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,158 @@
+use chrono::{Duration, TimeZone, Utc};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::configuration::SendingWindow;
+
+use crate::helpers::{TestApp, create_confirmed_subscriber, spawn_app, spawn_app_with};
+
+async fn queued_deliveries(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n
+}
+
+#[tokio::test]
+async fn deliveries_outside_the_sending_window_wait_for_it_to_open() {
+    let mut app = spawn_app().await;
+    app.worker_settings.sending_window = Some(SendingWindow {
+        start: "08:00".parse().unwrap(),
+        end: "20:00".parse().unwrap(),
+        timezone: "UTC".parse().unwrap(),
+    });
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let sent_so_far = app.email_server.received_requests().await.unwrap().len();
+
+    // Part 1 - At 3am the delivery is deferred, not sent
+    app.clock
+        .set(Utc.with_ymd_and_hms(2030, 1, 1, 3, 0, 0).unwrap());
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        sent_so_far
+    );
+    assert_eq!(queued_deliveries(&app).await, 1);
+
+    // Part 2 - Just before the window opens it is still waiting
+    app.clock
+        .set(Utc.with_ymd_and_hms(2030, 1, 1, 7, 59, 0).unwrap());
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(queued_deliveries(&app).await, 1);
+
+    // Part 3 - Once the window opens it goes out
+    app.clock
+        .set(Utc.with_ymd_and_hms(2030, 1, 1, 8, 0, 0).unwrap());
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(queued_deliveries(&app).await, 0);
+    // Mock verifies on Drop that we have sent the newsletter email once
+}
+
+#[tokio::test]
+async fn deliveries_inside_the_sending_window_go_out_straight_away() {
+    let mut app = spawn_app().await;
+    app.worker_settings.sending_window = Some(SendingWindow {
+        start: "08:00".parse().unwrap(),
+        end: "20:00".parse().unwrap(),
+        timezone: "UTC".parse().unwrap(),
+    });
+    app.clock
+        .set(Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap());
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    assert_eq!(queued_deliveries(&app).await, 0);
+}
+
+#[tokio::test]
+async fn notifications_go_out_while_the_sending_window_is_closed() {
+    let mut app = spawn_app().await;
+    app.worker_settings.sending_window = Some(SendingWindow {
+        start: "08:00".parse().unwrap(),
+        end: "20:00".parse().unwrap(),
+        timezone: "UTC".parse().unwrap(),
+    });
+    app.clock
+        .set(Utc.with_ymd_and_hms(2030, 1, 1, 3, 0, 0).unwrap());
+    sqlx::query!(
+        r#"
+        INSERT INTO notification_queue
+            (notification_id, recipient, subject, html_content, text_content, enqueued_at)
+        VALUES ($1, 'admin@example.com', 'Issue delivered', '<p>Done</p>', 'Done', now())
+        "#,
+        uuid::Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"], "admin@example.com");
+}
+
+#[tokio::test]
+async fn the_dashboard_shows_when_deliveries_are_paused() {
+    let app = spawn_app_with(|c| {
+        c.worker.sending_window = Some(SendingWindow {
+            start: "08:00".parse().unwrap(),
+            end: "20:00".parse().unwrap(),
+            timezone: "Europe/London".parse().unwrap(),
+        })
+    })
+    .await;
+    app.test_user.login(&app).await;
+
+    // Part 1 - At 3am the pause is shown
+    app.clock
+        .set(Utc.with_ymd_and_hms(2030, 1, 1, 3, 0, 0).unwrap());
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(
+        "Newsletter deliveries are paused outside the sending window. \
+        They resume at 08:00 (Europe/London)."
+    ));
+
+    // Part 2 - Once the window opens it goes away
+    app.clock.advance(Duration::hours(5));
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(!html_page.contains("Newsletter deliveries are paused"));
+}
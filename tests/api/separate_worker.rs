@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::{
+    issue_delivery_worker::run_worker_until_stopped, startup::spawn_background_tasks,
+};
+
+use crate::helpers::{
+    TestApp, create_confirmed_subscriber, create_confirmed_subscriber_with_email, spawn_app,
+    spawn_app_with,
+};
+
+#[tokio::test]
+async fn the_api_delivers_with_its_embedded_worker_by_default() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let sent_before = app.email_server.received_requests().await.unwrap().len();
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let tasks = spawn_background_tasks(
+        &app.configuration,
+        app.clock.clone(),
+        app.supervisor.clone(),
+    );
+    let delivered = wait_for_deliveries(&app, sent_before + 1).await;
+    tasks.webhook_dispatcher.abort();
+    tasks.stats_refresher.abort();
+    let worker = tasks.worker.expect("The embedded worker wasn't started");
+    worker.abort();
+
+    assert!(delivered, "The embedded worker didn't deliver the issue");
+}
+
+#[tokio::test]
+async fn a_separate_worker_delivers_what_the_api_queued() {
+    let app = spawn_app_with(|c| c.worker.embedded = false).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // The subscriber's confirmation email
+    let sent_before = app.email_server.received_requests().await.unwrap().len();
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    // Part 1 - The API only queues
+    let tasks = spawn_background_tasks(
+        &app.configuration,
+        app.clock.clone(),
+        app.supervisor.clone(),
+    );
+    tasks.webhook_dispatcher.abort();
+    tasks.stats_refresher.abort();
+    assert!(tasks.worker.is_none());
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let sent = app.email_server.received_requests().await.unwrap().len();
+    assert_eq!(sent, sent_before);
+
+    // Part 2 - The worker, as the worker binary runs it
+    let worker = tokio::spawn(run_worker_until_stopped(
+        app.configuration.worker.clone(),
+        app.configuration.clone(),
+        app.clock.clone(),
+    ));
+    let delivered = wait_for_deliveries(&app, sent_before + 1).await;
+    worker.abort();
+    assert!(delivered, "The worker didn't deliver the issue");
+}
+
+#[tokio::test]
+async fn a_concurrent_worker_delivers_each_email_once() {
+    let app = spawn_app_with(|c| {
+        c.worker.embedded = false;
+        c.worker.concurrency = 3;
+    })
+    .await;
+    for i in 0..5 {
+        create_confirmed_subscriber_with_email(&app, &format!("reader{i}@example.com")).await;
+    }
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let sent_before = app.email_server.received_requests().await.unwrap().len();
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let worker = tokio::spawn(run_worker_until_stopped(
+        app.configuration.worker.clone(),
+        app.configuration.clone(),
+        app.clock.clone(),
+    ));
+    let delivered = wait_for_deliveries(&app, sent_before + 5).await;
+    // Give a loop that claimed a task twice the chance to send it
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    worker.abort();
+
+    assert!(delivered, "The worker didn't deliver the issue");
+    let sent = app.email_server.received_requests().await.unwrap().len();
+    assert_eq!(sent, sent_before + 5);
+}
+
+// Done once the emails are out and the worker has also taken them off the queue
+async fn wait_for_deliveries(app: &TestApp, n_sent: usize) -> bool {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let sent = app.email_server.received_requests().await.unwrap().len();
+            let queued = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+                .fetch_one(&app.db_pool)
+                .await
+                .unwrap();
+            if sent >= n_sent && queued.n == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .is_ok()
+}
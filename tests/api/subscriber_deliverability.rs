@@ -0,0 +1,94 @@
+use uuid::Uuid;
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber_with_email,
+    create_unconfirmed_subscriber_with_email, spawn_app,
+};
+
+async fn get_can_receive_email(app: &TestApp, subscriber_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/{subscriber_id}/can-receive-email",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// `localhost` resolves without a network, nothing under `.invalid` ever does
+#[tokio::test]
+async fn a_confirmed_subscriber_at_a_resolving_domain_can_receive_email() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@localhost").await;
+    app.test_user.login(&app).await;
+    let id = app.subscriber_id("ursula@localhost").await;
+
+    let response = get_can_receive_email(&app, id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["can_receive"], true);
+    assert_eq!(body["reasons"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn a_suppressed_subscriber_cannot_receive_email() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@localhost").await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "email": "Ursula@localhost",
+        "reason": "Asked to never be emailed again",
+    }))
+    .await;
+    let id = app.subscriber_id("ursula@localhost").await;
+
+    let response = get_can_receive_email(&app, id).await;
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["can_receive"], false);
+    assert_eq!(
+        body["reasons"],
+        serde_json::json!(["The address is on the suppression list."])
+    );
+}
+
+#[tokio::test]
+async fn every_reason_a_subscriber_cannot_receive_email_is_listed() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber_with_email(&app, "ursula@nowhere.invalid").await;
+    app.test_user.login(&app).await;
+    let id = app.subscriber_id("ursula@nowhere.invalid").await;
+
+    let response = get_can_receive_email(&app, id).await;
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["can_receive"], false);
+    assert_eq!(
+        body["reasons"],
+        serde_json::json!([
+            "The domain nowhere.invalid doesn't resolve, so mail to it can't be delivered.",
+            "The subscriber is pending_confirmation, only confirmed subscribers are emailed."
+        ])
+    );
+}
+
+#[tokio::test]
+async fn checking_an_unknown_subscriber_is_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = get_can_receive_email(&app, Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_check_a_subscriber() {
+    let app = spawn_app().await;
+
+    let response = get_can_receive_email(&app, Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
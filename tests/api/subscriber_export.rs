@@ -0,0 +1,235 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber_with_email,
+    create_unconfirmed_subscriber_with_email, spawn_app,
+};
+
+async fn get_export(app: &TestApp, subscriber_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/{subscriber_id}/export",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// Links in the email `email` received, pointed at the test server
+async fn links_sent_to(app: &TestApp, email: &str) -> Vec<reqwest::Url> {
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .find(|b| b["To"] == email && b["Subject"] == "Newsletter title")
+        .unwrap();
+    linkify::LinkFinder::new()
+        .links(body["HtmlBody"].as_str().unwrap())
+        .filter(|l| l.as_str().contains("/track/"))
+        .map(|l| {
+            let mut link = reqwest::Url::parse(l.as_str()).unwrap();
+            link.set_port(Some(app.port)).unwrap();
+            link
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_a_subscriber() {
+    let app = spawn_app().await;
+
+    let response = get_export(&app, Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn exporting_an_unknown_subscriber_is_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = get_export(&app, Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_export_holds_everything_about_the_subscriber_and_nothing_else() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "reader@example.com").await;
+    create_confirmed_subscriber_with_email(&app, "other@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p><a href="https://example.com/post">Read</a></p>"#,
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    // The reader opens the issue and follows its link, the other subscriber only opens it
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    for link in links_sent_to(&app, "reader@example.com").await {
+        client.get(link).send().await.unwrap();
+    }
+    for link in links_sent_to(&app, "other@example.com").await {
+        if link.path().starts_with("/track/open/") {
+            client.get(link).send().await.unwrap();
+        }
+    }
+    app.post_suppression(&serde_json::json!({
+        "email": "other@example.com",
+        "reason": "Legal request",
+    }))
+    .await;
+    let reader_id = app.subscriber_id("reader@example.com").await;
+
+    let response = get_export(&app, reader_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response.headers()["Content-Disposition"]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment")
+    );
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscription"]["id"], reader_id.to_string());
+    assert_eq!(export["subscription"]["email"], "reader@example.com");
+    assert_eq!(export["subscription"]["status"], "confirmed");
+    assert!(export["suppression"].is_null());
+    assert_eq!(export["deliveries"].as_array().unwrap().len(), 1);
+    assert_eq!(export["deliveries"][0]["issue_title"], "Newsletter title");
+    assert_eq!(export["deliveries"][0]["status"], "delivered");
+    assert_eq!(export["opens"].as_array().unwrap().len(), 1);
+    assert_eq!(export["clicks"].as_array().unwrap().len(), 1);
+    assert_eq!(export["clicks"][0]["url"], "https://example.com/post");
+    assert_eq!(export["feedback"], serde_json::json!([]));
+    assert!(!export.to_string().contains("other@example.com"));
+
+    let audit =
+        sqlx::query!("SELECT action, subject FROM audit_log WHERE action = 'subscriber.exported'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(audit.subject, reader_id.to_string());
+}
+
+#[tokio::test]
+async fn the_export_includes_the_suppression_status() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "other@example.com").await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "email": "other@example.com",
+        "reason": "Legal request",
+    }))
+    .await;
+
+    let export: serde_json::Value = get_export(&app, app.subscriber_id("other@example.com").await)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(export["suppression"]["reason"], "Legal request");
+}
+
+async fn get_jsonl_export(app: &TestApp, query: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/export.jsonl{query}",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn parse_jsonl(body: &str) -> Vec<serde_json::Value> {
+    body.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_all_subscribers() {
+    let app = spawn_app().await;
+
+    let response = get_jsonl_export(&app, "").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn every_subscriber_is_exported_as_one_json_line() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "confirmed@example.com").await;
+    create_unconfirmed_subscriber_with_email(&app, "pending@example.com").await;
+    app.test_user.login(&app).await;
+
+    let response = get_jsonl_export(&app, "").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "application/x-ndjson");
+    let subscribers = parse_jsonl(&response.text().await.unwrap());
+    assert_eq!(subscribers.len(), 2);
+    let confirmed = &subscribers[0];
+    assert_eq!(
+        confirmed["id"],
+        app.subscriber_id("confirmed@example.com").await.to_string()
+    );
+    assert_eq!(confirmed["email"], "confirmed@example.com");
+    assert_eq!(confirmed["status"], "confirmed");
+    assert!(confirmed["name"].is_string());
+    assert!(confirmed["subscribed_at"].is_string());
+    assert_eq!(subscribers[1]["email"], "pending@example.com");
+    assert_eq!(subscribers[1]["status"], "pending_confirmation");
+}
+
+#[tokio::test]
+async fn the_jsonl_export_can_be_filtered_by_status() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "confirmed@example.com").await;
+    create_unconfirmed_subscriber_with_email(&app, "pending@example.com").await;
+    app.test_user.login(&app).await;
+
+    let response = get_jsonl_export(&app, "?status=confirmed").await;
+
+    let subscribers = parse_jsonl(&response.text().await.unwrap());
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "confirmed@example.com");
+}
+
+#[tokio::test]
+async fn exports_and_signups_are_stamped_with_the_apps_time() {
+    let app = spawn_app().await;
+    let now = chrono::Utc::now() + chrono::Duration::days(30);
+    app.clock.set(now);
+    create_unconfirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let export: serde_json::Value = get_export(&app, subscriber_id).await.json().await.unwrap();
+
+    for at in [
+        &export["exported_at"],
+        &export["subscription"]["subscribed_at"],
+    ] {
+        let at: chrono::DateTime<chrono::Utc> = at.as_str().unwrap().parse().unwrap();
+        assert!((at - now).num_seconds().abs() < 1, "{at} isn't {now}");
+    }
+}
@@ -0,0 +1,150 @@
+use uuid::Uuid;
+
+use crate::helpers::{
+    create_confirmed_subscriber_with_email, create_unconfirmed_subscriber_with_email, spawn_app,
+};
+
+#[tokio::test]
+async fn merging_keeps_the_confirmed_record_and_carries_over_the_duplicates_details() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "sparrowhawk@earthsea.org").await;
+    let pending_id = app.subscriber_id("ged@earthsea.org").await;
+    let confirmed_id = app.subscriber_id("sparrowhawk@earthsea.org").await;
+    // The pending record is the older one and carries extra details
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET subscribed_at = subscribed_at - interval '1 year',
+            last_opened_at = now(),
+            admin_note = 'Met at the conference'
+        WHERE id = $1
+        "#,
+        pending_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_merge_subscribers(&serde_json::json!({
+            "subscriber_ids": [pending_id, confirmed_id]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["subscriber_id"], confirmed_id.to_string());
+    assert_eq!(body["merged_id"], pending_id.to_string());
+    let survivor = sqlx::query!(
+        r#"
+        SELECT email, status, admin_note, last_opened_at,
+            subscribed_at < now() - interval '300 days' AS "older!"
+        FROM subscriptions
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(survivor.email.as_deref(), Some("sparrowhawk@earthsea.org"));
+    assert_eq!(survivor.status, "confirmed");
+    assert_eq!(
+        survivor.admin_note.as_deref(),
+        Some("Met at the conference")
+    );
+    assert!(survivor.last_opened_at.is_some());
+    assert!(survivor.older);
+    let tokens = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM subscription_tokens WHERE subscriber_id = $1",
+        pending_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tokens.count, 0);
+    let audit = sqlx::query!("SELECT action, subject, details FROM audit_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(audit.action, "subscriber.merged");
+    assert_eq!(audit.subject, confirmed_id.to_string());
+    // The duplicate's address isn't kept
+    assert_eq!(audit.details, Some(format!("Merged {pending_id}")));
+}
+
+#[tokio::test]
+async fn merging_keeps_an_unsubscribed_duplicates_opt_out() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "sparrowhawk@earthsea.org").await;
+    let unsubscribed_id = app.subscriber_id("ged@earthsea.org").await;
+    let confirmed_id = app.subscriber_id("sparrowhawk@earthsea.org").await;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1",
+        unsubscribed_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_merge_subscribers(&serde_json::json!({
+            "subscriber_ids": [unsubscribed_id, confirmed_id]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let survivor = sqlx::query!("SELECT id, status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(survivor.id, confirmed_id);
+    assert_eq!(survivor.status, "unsubscribed");
+    assert!(survivor.unsubscribed_at.is_some());
+}
+
+#[tokio::test]
+async fn merging_a_subscriber_with_itself_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_merge_subscribers(&serde_json::json!({ "subscriber_ids": [id, id] }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn merging_with_an_unknown_subscriber_returns_404_and_changes_nothing() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_merge_subscribers(&serde_json::json!({
+            "subscriber_ids": [id, Uuid::new_v4()]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    app.subscriber_id("ged@earthsea.org").await;
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_merge_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_merge_subscribers(&serde_json::json!({
+            "subscriber_ids": [Uuid::new_v4(), Uuid::new_v4()]
+        }))
+        .await;
+
+    crate::helpers::assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,137 @@
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, create_confirmed_subscriber_with_email, spawn_app};
+
+async fn get_subscriber(app: &TestApp, subscriber_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/{subscriber_id}",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn patch_note(app: &TestApp, subscriber_id: Uuid, note: &str) -> reqwest::Response {
+    app.api_client
+        .patch(format!(
+            "{}/admin/subscribers/{subscriber_id}/note",
+            &app.address
+        ))
+        .json(&serde_json::json!({ "note": note }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn notes_can_be_set_and_updated_and_are_audited() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    // Part 1 - Set
+    let response = patch_note(&app, subscriber_id, "VIP customer").await;
+    assert_eq!(response.status().as_u16(), 204);
+    let subscriber: serde_json::Value = get_subscriber(&app, subscriber_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber["email"], "ged@earthsea.org");
+    assert_eq!(subscriber["admin_note"], "VIP customer");
+
+    // Part 2 - Update
+    let response = patch_note(&app, subscriber_id, "Do not contact").await;
+    assert_eq!(response.status().as_u16(), 204);
+    let subscriber: serde_json::Value = get_subscriber(&app, subscriber_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber["admin_note"], "Do not contact");
+
+    let audit = sqlx::query!("SELECT action, subject, details FROM audit_log ORDER BY created_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let changes: Vec<_> = audit
+        .iter()
+        .map(|a| (a.action.as_str(), a.details.as_deref()))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            ("subscriber.note_changed", Some("VIP customer")),
+            ("subscriber.note_changed", Some("Do not contact")),
+        ]
+    );
+    assert!(audit.iter().all(|a| a.subject == subscriber_id.to_string()));
+}
+
+#[tokio::test]
+async fn notes_are_limited_to_500_characters() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let response = patch_note(&app, subscriber_id, &"a".repeat(501)).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let subscriber: serde_json::Value = get_subscriber(&app, subscriber_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(subscriber["admin_note"].is_null());
+}
+
+#[tokio::test]
+async fn notes_on_unknown_subscribers_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = patch_note(&app, Uuid::new_v4(), "VIP customer").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn notes_are_left_out_of_the_subscriber_export() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+    patch_note(&app, subscriber_id, "Do not contact").await;
+
+    let export = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{subscriber_id}/export",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(export.contains("ged@earthsea.org"));
+    assert!(!export.contains("Do not contact"));
+    assert!(!export.contains("admin_note"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_a_note() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    let subscriber_id = app.subscriber_id("ged@earthsea.org").await;
+
+    let response = patch_note(&app, subscriber_id, "VIP customer").await;
+
+    crate::helpers::assert_is_redirect_to(&response, "/login");
+}
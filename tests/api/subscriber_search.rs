@@ -0,0 +1,164 @@
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber_with_email,
+    create_unconfirmed_subscriber_with_email, spawn_app, spawn_app_with,
+};
+
+const EMAILS: [&str; 5] = [
+    "ursula@earthsea.org",
+    "ged@earthsea.org",
+    "genly@gethen.net",
+    "shevek@anarres.io",
+    "tenar@atuan.com",
+];
+
+async fn seed_subscribers(app: &TestApp) {
+    for email in EMAILS {
+        create_unconfirmed_subscriber_with_email(app, email).await;
+    }
+}
+
+fn listed(html_page: &str) -> Vec<&'static str> {
+    EMAILS
+        .into_iter()
+        .filter(|email| html_page.contains(&format!("<td>{email}</td>")))
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_list_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn searching_by_domain_only_lists_matching_subscribers() {
+    let app = spawn_app().await;
+    seed_subscribers(&app).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_subscribers_html("search=EARTHSEA.org").await;
+
+    let mut found = listed(&html_page);
+    found.sort();
+    assert_eq!(found, vec!["ged@earthsea.org", "ursula@earthsea.org"]);
+}
+
+#[tokio::test]
+async fn the_search_alias_matches_the_same_subscribers() {
+    let app = spawn_app().await;
+    seed_subscribers(&app).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/search?q=gethen",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert_eq!(listed(&html_page), vec!["genly@gethen.net"]);
+}
+
+#[tokio::test]
+async fn wildcards_in_a_search_are_matched_literally() {
+    let app = spawn_app().await;
+    seed_subscribers(&app).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_subscribers_html("search=%25").await;
+
+    assert!(listed(&html_page).is_empty());
+}
+
+#[tokio::test]
+async fn an_empty_search_lists_every_subscriber_up_to_the_page_limit() {
+    let app = spawn_app().await;
+    seed_subscribers(&app).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_subscribers_html("search=").await;
+    assert_eq!(listed(&html_page).len(), 5);
+
+    let html_page = app.get_subscribers_html("search=&per_page=3").await;
+    assert_eq!(listed(&html_page).len(), 3);
+    assert!(html_page.contains("page=2&per_page=3"));
+
+    let html_page = app.get_subscribers_html("search=&page=2&per_page=3").await;
+    assert_eq!(listed(&html_page).len(), 2);
+}
+
+#[tokio::test]
+async fn out_of_range_pages_list_nobody_rather_than_failing() {
+    let app = spawn_app().await;
+    seed_subscribers(&app).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers?page={max}&per_page={max}",
+            &app.address,
+            max = i64::MAX
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(listed(&response.text().await.unwrap()).is_empty());
+}
+
+#[tokio::test]
+async fn subscription_times_are_shown_in_the_display_timezone() {
+    let app = spawn_app_with(|c| c.display.timezone = "Asia/Tokyo".parse().unwrap()).await;
+    app.test_user.login(&app).await;
+    create_unconfirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    // 15:45 UTC is 00:45 the next day in Tokyo, which is always UTC+9
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = '2025-07-10T15:45:00Z' WHERE email = $1",
+        "ged@earthsea.org"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let html_page = app.get_subscribers_html("").await;
+
+    assert!(html_page.contains("<td>2025-07-11 00:45 JST</td>"));
+}
+
+#[tokio::test]
+async fn the_subscribers_page_counts_confirmed_subscribers_by_signup_source() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_unconfirmed_subscriber_with_email(&app, "tenar@atuan.com").await;
+    sqlx::query!(
+        "UPDATE subscriptions SET signup_utm_source = 'podcast', \
+        signup_referer = 'https://blog.example.com/posts/1'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_subscribers_html("").await;
+
+    // The unconfirmed subscriber isn't counted
+    assert!(html_page.contains("<tr><td>utm_source</td><td>podcast</td><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><td>referer</td><td>blog.example.com</td><td>2</td></tr>"));
+}
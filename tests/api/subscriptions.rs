@@ -1,9 +1,13 @@
 // If tests/api/subscriptions.rs grows too unwieldy -> tests/api/subscriptions/helper.rs
-use crate::helpers::spawn_app;
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, break_writes_to, create_confirmed_subscriber_with_email,
+    spawn_app, spawn_app_with,
+};
 use wiremock::{
     matchers::{method, path},
     {Mock, ResponseTemplate},
 };
+use zero_to_prod::domain::EmailStrictness;
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -39,11 +43,74 @@ async fn subscribe_persists_the_new_subscriber() {
         .await
         .expect("Failed to fetch saved subscription.");
 
-    assert_eq!(test_data.email, "ursula_le_guin@gmail.com");
-    assert_eq!(test_data.name, "le guin");
+    assert_eq!(test_data.email.as_deref(), Some("ursula_le_guin@gmail.com"));
+    assert_eq!(test_data.name.as_deref(), Some("le guin"));
     assert_eq!(test_data.status, "pending_confirmation");
 }
 
+#[tokio::test]
+async fn subscribe_records_where_the_signup_came_from() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&utm_source=podcast";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Referer", "https://blog.example.com/posts/why-subscribe")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let saved =
+        sqlx::query!("SELECT signup_referer, signup_utm_source, signup_country FROM subscriptions")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch saved subscription.");
+    assert_eq!(
+        saved.signup_referer.as_deref(),
+        Some("https://blog.example.com/posts/why-subscribe")
+    );
+    assert_eq!(saved.signup_utm_source.as_deref(), Some("podcast"));
+    // No geo lookup is plugged in
+    assert_eq!(saved.signup_country, None);
+}
+
+#[tokio::test]
+async fn the_utm_source_can_come_from_the_query_string() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.api_client
+        .post(format!(
+            "{}/subscriptions?utm_source=newsletter-swap",
+            &app.address
+        ))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let saved = sqlx::query!("SELECT signup_referer, signup_utm_source FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.signup_referer, None);
+    assert_eq!(saved.signup_utm_source.as_deref(), Some("newsletter-swap"));
+}
+
 #[tokio::test]
 async fn subscribe_returns_a_400_when_data_is_missing() {
     let app = spawn_app().await;
@@ -120,13 +187,33 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
     assert_eq!(confirmation_links.html, confirmation_links.plain_text);
 }
 
+#[tokio::test]
+async fn the_confirmation_email_addresses_the_subscriber_by_name() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let greeting = "Hello le guin, please confirm your subscription.";
+    assert!(body["HtmlBody"].as_str().unwrap().starts_with(greeting));
+    assert!(body["TextBody"].as_str().unwrap().starts_with(greeting));
+}
+
 #[tokio::test]
 async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     let app = spawn_app().await;
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
     // Sabotage the database
-    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;",)
+    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email CASCADE;",)
         .execute(&app.db_pool)
         .await
         .unwrap();
@@ -134,3 +221,296 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     let response = app.post_subscriptions(body.into()).await;
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn a_subscriber_is_not_stored_without_their_confirmation_token() {
+    let app = spawn_app().await;
+    break_writes_to(&app.db_pool, "subscription_tokens").await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+    let subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.count, 0);
+}
+
+#[tokio::test]
+async fn browser_form_posts_are_redirected_back_to_the_form() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&redirect_to=%2F".into(),
+        )
+        .await;
+
+    assert_is_redirect_to(&response, "/");
+    let html_page = app.get_home_html().await;
+    assert!(html_page.contains("Thanks for subscribing! Check your inbox to confirm your email."));
+}
+
+#[tokio::test]
+async fn html_clients_are_redirected_to_the_referring_page() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Accept", "text/html")
+        .header("Referer", format!("{}/?from=footer", &app.address))
+        .form(&serde_json::json!({ "name": "le guin", "email": "not-an-email" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/?from=footer");
+    let html_page = app.get_home_html().await;
+    assert!(html_page.contains("is not a valid subscriber email."));
+}
+
+#[tokio::test]
+async fn redirects_never_leave_the_site() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=not-an-email&redirect_to=%2F%2Fevil.com".into())
+        .await;
+
+    assert_is_redirect_to(&response, "/");
+}
+
+#[tokio::test]
+async fn non_html_clients_keep_getting_status_codes() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let post = |body: &'static str| {
+        app.api_client
+            .post(format!("{}/subscriptions", &app.address))
+            .header("Accept", "application/json, text/html;q=0.5")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+    };
+
+    let response = post("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post("name=le%20guin&email=not-an-email").await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn post_subscriptions_json(app: &TestApp, body: &serde_json::Value) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn json_subscriptions_return_the_subscriber_id() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_subscriptions_json(
+        &app,
+        &serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let subscriber_id: uuid::Uuid = body["subscriber_id"].as_str().unwrap().parse().unwrap();
+    let saved = sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The returned id is not a subscriber.");
+    assert_eq!(saved.email.as_deref(), Some("ursula_le_guin@gmail.com"));
+}
+
+#[tokio::test]
+async fn subscribing_again_returns_the_same_id() {
+    let app = spawn_app().await;
+    // A pending subscriber gets a fresh confirmation email each time
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" });
+
+    let first: serde_json::Value = post_subscriptions_json(&app, &body)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let second: serde_json::Value = post_subscriptions_json(&app, &body)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(first["subscriber_id"], second["subscriber_id"]);
+    let subscriptions = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.n, 1);
+}
+
+#[tokio::test]
+async fn subscribing_a_confirmed_address_again_returns_its_id() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ursula_le_guin@gmail.com").await;
+    let existing_id = app.subscriber_id("ursula_le_guin@gmail.com").await;
+
+    let response = post_subscriptions_json(
+        &app,
+        &serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let returned_id: uuid::Uuid = body["subscriber_id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(returned_id, existing_id);
+}
+
+#[tokio::test]
+async fn form_clients_asking_for_json_get_the_subscriber_id() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Accept", "application/json")
+        .form(&serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["subscriber_id"].is_string());
+}
+
+#[tokio::test]
+async fn invalid_json_subscriptions_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = post_subscriptions_json(
+        &app,
+        &serde_json::json!({ "name": "le guin", "email": "not-an-email" }),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("not-an-email"));
+}
+
+#[tokio::test]
+async fn subscribe_rejects_a_denied_domain() {
+    let app =
+        spawn_app_with(|c| c.subscriptions.denied_domains = vec!["mailinator.com".into()]).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=throwaway%40mailinator.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.text().await.unwrap().contains("mailinator.com"));
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn strict_email_validation_rejects_an_address_without_a_real_domain() {
+    let app = spawn_app_with(|c| c.subscriptions.email_strictness = EmailStrictness::Strict).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Accepted in the default lenient mode
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40localhost".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn an_allowlist_only_accepts_its_own_domains() {
+    let app =
+        spawn_app_with(|c| c.subscriptions.allowed_domains = vec!["corp.example".into()]).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let allowed = app
+        .post_subscriptions("name=le%20guin&email=ursula%40corp.example".into())
+        .await;
+    let rejected = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(allowed.status().as_u16(), 200);
+    assert_eq!(rejected.status().as_u16(), 400);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email.as_deref(), Some("ursula@corp.example"));
+}
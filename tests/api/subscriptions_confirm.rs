@@ -1,4 +1,6 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{
+    assert_is_redirect_to, create_unconfirmed_subscriber_with_email, spawn_app, spawn_app_with,
+};
 use wiremock::{
     matchers::{method, path},
     {Mock, ResponseTemplate},
@@ -62,7 +64,105 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
         .await
         .unwrap();
 
-    assert_eq!(saved_data.email, "ursula_le_guin@gmail.com");
-    assert_eq!(saved_data.name, "le guin");
+    assert_eq!(
+        saved_data.email.as_deref(),
+        Some("ursula_le_guin@gmail.com")
+    );
+    assert_eq!(saved_data.name.as_deref(), Some("le guin"));
     assert_eq!(saved_data.status, "confirmed");
 }
+
+#[tokio::test]
+async fn confirming_shows_the_built_in_landing_page_by_default() {
+    let app = spawn_app().await;
+    let confirmation_link =
+        create_unconfirmed_subscriber_with_email(&app, "ursula_le_guin@gmail.com").await;
+
+    let response = app
+        .api_client
+        .get(confirmation_link.html)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("<h1>Thanks for confirming!</h1>")
+    );
+}
+
+#[tokio::test]
+async fn confirming_redirects_to_the_configured_landing_page() {
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_redirect_url =
+            Some(reqwest::Url::parse("https://example.com/thank-you").unwrap())
+    })
+    .await;
+    let confirmation_link =
+        create_unconfirmed_subscriber_with_email(&app, "ursula_le_guin@gmail.com").await;
+
+    let response = app
+        .api_client
+        .get(confirmation_link.html)
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "https://example.com/thank-you");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn a_confirmation_link_only_works_once() {
+    let app = spawn_app().await;
+    let confirmation_link =
+        create_unconfirmed_subscriber_with_email(&app, "ursula_le_guin@gmail.com").await;
+    reqwest::get(confirmation_link.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    // Since unsubscribed, the link must not bring them back
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("<h1>You're already confirmed</h1>")
+    );
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn an_unknown_token_is_still_rejected() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=not-a-real-token",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
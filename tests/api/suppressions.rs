@@ -0,0 +1,198 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber_with_email, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_suppression(&serde_json::json!({
+            "email": "harasser@example.com",
+            "reason": "Harassment",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn suppressions_can_be_added_and_removed_and_are_audited() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Part 1 - Add
+    let response = app
+        .post_suppression(&serde_json::json!({
+            "email": "Harasser@Example.com",
+            "reason": "Harassment",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("<p><i>harasser@example.com will no longer be emailed.</i></p>"));
+    assert!(html_page.contains("<td>harasser@example.com</td>"));
+    assert!(html_page.contains("<td>Harassment</td>"));
+
+    // Part 2 - Remove
+    let response = app.post_remove_suppression("harasser@example.com").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("<p><i>harasser@example.com can be emailed again.</i></p>"));
+    assert!(!html_page.contains("<td>harasser@example.com</td>"));
+
+    let audit = sqlx::query!("SELECT user_id, action, subject FROM audit_log ORDER BY created_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let actions: Vec<_> = audit.iter().map(|a| a.action.as_str()).collect();
+    assert_eq!(actions, ["suppression.added", "suppression.removed"]);
+    assert!(audit.iter().all(|a| a.subject == "harasser@example.com"));
+    assert!(
+        audit
+            .iter()
+            .all(|a| a.user_id == Some(app.test_user.user_id))
+    );
+}
+
+#[tokio::test]
+async fn suppressions_need_a_valid_email_and_a_reason() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_suppression(&serde_json::json!({ "email": "not-an-email", "reason": "Spam" }))
+        .await;
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("is not a valid subscriber email."));
+
+    app.post_suppression(&serde_json::json!({ "email": "someone@example.com", "reason": " " }))
+        .await;
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("<p><i>A reason is required to suppress an address.</i></p>"));
+
+    let suppressed = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM suppressed_emails"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppressed.n, 0);
+}
+
+#[tokio::test]
+async fn a_submitted_address_is_escaped_when_it_is_echoed_back() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_remove_suppression("<script>alert(1)</script>")
+        .await;
+    let html_page = app.get_suppressions_html().await;
+
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt; is not suppressed."));
+    assert!(!html_page.contains("<script>"));
+}
+
+#[tokio::test]
+async fn subscribing_a_suppressed_address_is_silently_dropped() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "email": "harasser@example.com",
+        "reason": "Legal request",
+    }))
+    .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=someone&email=harasser%40example.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriptions = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.n, 0);
+}
+
+#[tokio::test]
+async fn issues_are_not_delivered_to_suppressed_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "harasser@example.com").await;
+    create_confirmed_subscriber_with_email(&app, "reader@example.com").await;
+    app.test_user.login(&app).await;
+    app.post_suppression(&serde_json::json!({
+        "email": "harasser@example.com",
+        "reason": "Harassment",
+    }))
+    .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    // Suppressed subscribers aren't queued at all
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Newsletter queued for 1 subscribers.</i></p>"));
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let newsletter: serde_json::Value =
+        serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(newsletter["To"], "reader@example.com");
+    let issue = sqlx::query!("SELECT status, n_delivered, n_failed FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "sent");
+    assert_eq!((issue.n_delivered, issue.n_failed), (1, 0));
+}
+
+#[tokio::test]
+async fn a_suppression_added_after_publishing_still_stops_the_delivery() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "bounced@example.com").await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.post_suppression(&serde_json::json!({
+        "email": "bounced@example.com",
+        "reason": "Hard bounce",
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!("SELECT status, n_delivered FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "sent");
+    assert_eq!(issue.n_delivered, 0);
+}
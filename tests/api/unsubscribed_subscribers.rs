@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, create_confirmed_subscriber_with_email, spawn_app,
+};
+
+async fn get_unsubscribed(app: &TestApp, query: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/unsubscribed{query}",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn unsubscribe(app: &TestApp, email: &str) {
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE email = $1",
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_list_unsubscribed_subscribers() {
+    let app = spawn_app().await;
+
+    let response = get_unsubscribed(&app, "").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_are_listed_with_when_they_left() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "tenar@earthsea.org").await;
+    app.test_user.login(&app).await;
+    let before = Utc::now();
+
+    unsubscribe(&app, "tenar@earthsea.org").await;
+
+    let response = get_unsubscribed(&app, "").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["page"], 1);
+    assert_eq!(body["per_page"], 50);
+    let subscribers = body["subscribers"].as_array().unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "tenar@earthsea.org");
+    let stored = sqlx::query!(
+        "SELECT id, unsubscribed_at FROM subscriptions WHERE email = 'tenar@earthsea.org'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(subscribers[0]["id"], stored.id.to_string());
+    let unsubscribed_at: DateTime<Utc> =
+        serde_json::from_value(subscribers[0]["unsubscribed_at"].clone()).unwrap();
+    assert_eq!(Some(unsubscribed_at), stored.unsubscribed_at);
+    assert!(before <= unsubscribed_at && unsubscribed_at <= Utc::now());
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_can_be_filtered_by_date_and_paged() {
+    let app = spawn_app().await;
+    for email in [
+        "ged@earthsea.org",
+        "tenar@earthsea.org",
+        "arren@earthsea.org",
+    ] {
+        create_confirmed_subscriber_with_email(&app, email).await;
+        unsubscribe(&app, email).await;
+    }
+    app.test_user.login(&app).await;
+    // Left long ago, before anything was recorded
+    sqlx::query!(
+        "UPDATE subscriptions SET unsubscribed_at = '2020-01-01T00:00:00Z' \
+         WHERE email = 'arren@earthsea.org'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let body: serde_json::Value = get_unsubscribed(&app, "?from=2024-01-01&per_page=1")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["subscribers"].as_array().unwrap().len(), 1);
+
+    let body: serde_json::Value = get_unsubscribed(&app, "?from=2024-01-01&per_page=1&page=3")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["subscribers"].as_array().unwrap().len(), 0);
+
+    let body: serde_json::Value = get_unsubscribed(&app, "").await.json().await.unwrap();
+    assert_eq!(body["total"], 3);
+    assert_eq!(body["subscribers"][2]["email"], "arren@earthsea.org");
+}
+
+#[tokio::test]
+async fn out_of_range_pages_are_empty_rather_than_failing() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    unsubscribe(&app, "ged@earthsea.org").await;
+    app.test_user.login(&app).await;
+
+    let response = get_unsubscribed(&app, &format!("?page={}", i64::MAX)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["subscribers"].as_array().unwrap().len(), 0);
+    assert_eq!(body["total"], 1);
+}
+
+#[tokio::test]
+async fn resubscribing_clears_the_unsubscribe_date() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    unsubscribe(&app, "ged@earthsea.org").await;
+
+    sqlx::query!("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let stored = sqlx::query!("SELECT unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored.unsubscribed_at, None);
+}
@@ -0,0 +1,83 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::configuration::UtmSettings;
+
+use crate::helpers::{TestApp, create_confirmed_subscriber, spawn_app, spawn_app_with};
+
+// Publishes an issue with one external link and returns where its tracked link redirects to
+async fn deliver_a_link(app: &TestApp) -> String {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Read <a href="https://example.com/post?id=1">this</a></p>"#,
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    let link = linkify::LinkFinder::new()
+        .links(body["HtmlBody"].as_str().unwrap())
+        .find(|l| l.as_str().contains("/track/click/"))
+        .expect("The email has no tracked link.");
+    let mut link = reqwest::Url::parse(link.as_str()).unwrap();
+    link.set_port(Some(app.port)).unwrap();
+    let response = app.api_client.get(link).send().await.unwrap();
+    response.headers()["Location"].to_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn external_links_get_utm_parameters_when_enabled() {
+    let app = spawn_app_with(|c| {
+        c.delivery.utm_injection = Some(UtmSettings {
+            utm_source: "newsletter".into(),
+            utm_medium: "email".into(),
+            utm_campaign: "weekly".into(),
+        })
+    })
+    .await;
+
+    let location = deliver_a_link(&app).await;
+
+    assert_eq!(
+        location,
+        "https://example.com/post?id=1&utm_source=newsletter&utm_medium=email&utm_campaign=weekly"
+    );
+    let issue = sqlx::query!("SELECT utm_injection FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issue.utm_injection);
+}
+
+#[tokio::test]
+async fn links_are_left_alone_when_utm_injection_is_disabled() {
+    let app = spawn_app().await;
+
+    let location = deliver_a_link(&app).await;
+
+    assert_eq!(location, "https://example.com/post?id=1");
+    let issue = sqlx::query!("SELECT utm_injection FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!issue.utm_injection);
+}
@@ -0,0 +1,272 @@
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::{configuration::CallbackSettings, webhooks::signature};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_confirmed_subscriber_with_email,
+    spawn_app, spawn_app_with,
+};
+
+const SECRET: &str = "webhook-signing-secret";
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_webhooks() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_webhook(&serde_json::json!({
+            "url": "https://example.com/hook",
+            "secret": SECRET,
+            "issue_published": "on",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn invalid_webhooks_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"url": "not a url", "secret": SECRET, "issue_published": "on"}),
+            "The webhook URL must be a valid http(s) URL.",
+        ),
+        (
+            serde_json::json!({"url": "ftp://example.com", "secret": SECRET, "issue_published": "on"}),
+            "The webhook URL must be a valid http(s) URL.",
+        ),
+        (
+            serde_json::json!({"url": "https://example.com", "secret": "", "issue_published": "on"}),
+            "A signing secret is required.",
+        ),
+        (
+            serde_json::json!({"url": "https://example.com", "secret": SECRET}),
+            "Select at least one event.",
+        ),
+    ];
+
+    for (body, error_message) in test_cases {
+        let response = app.post_webhook(&body).await;
+        assert_is_redirect_to(&response, "/admin/webhooks");
+        assert!(app.get_webhooks_html().await.contains(error_message));
+    }
+    let n_webhooks = sqlx::query!("SELECT COUNT(*) AS \"n!\" FROM webhooks")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_webhooks, 0);
+}
+
+#[tokio::test]
+async fn an_opt_out_carried_over_by_a_merge_is_announced() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_webhook(&serde_json::json!({
+        "url": "https://example.com/hook",
+        "secret": SECRET,
+        "subscriber_unsubscribed": "on",
+    }))
+    .await;
+    create_confirmed_subscriber_with_email(&app, "ged@earthsea.org").await;
+    create_confirmed_subscriber_with_email(&app, "sparrowhawk@earthsea.org").await;
+    let unsubscribed_id = app.subscriber_id("ged@earthsea.org").await;
+    let confirmed_id = app.subscriber_id("sparrowhawk@earthsea.org").await;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1",
+        unsubscribed_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.post_merge_subscribers(&serde_json::json!({
+        "subscriber_ids": [unsubscribed_id, confirmed_id]
+    }))
+    .await;
+
+    let delivery = sqlx::query!("SELECT event, payload FROM webhook_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.event, "subscriber.unsubscribed");
+    let payload: serde_json::Value = serde_json::from_str(&delivery.payload).unwrap();
+    assert_eq!(payload["data"]["subscriber_id"], confirmed_id.to_string());
+    assert_eq!(payload["data"]["email"], "sparrowhawk@earthsea.org");
+}
+
+#[tokio::test]
+async fn subscribed_events_are_delivered_with_a_valid_signature() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&receiver)
+        .await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_webhook(&serde_json::json!({
+            "url": format!("{}/hook", receiver.uri()),
+            "secret": SECRET,
+            "issue_published": "on",
+            "issue_completed": "on",
+            "subscriber_confirmed": "on",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/webhooks");
+    assert!(
+        app.get_webhooks_html()
+            .await
+            .contains("The webhook has been added.")
+    );
+
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    app.dispatch_all_pending_webhooks().await;
+
+    let requests = receiver.received_requests().await.unwrap();
+    let mut events = Vec::new();
+    for request in &requests {
+        let body = std::str::from_utf8(&request.body).unwrap();
+        assert_eq!(
+            request.headers.get(&"X-Signature".into()).unwrap().as_str(),
+            signature(SECRET, body)
+        );
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["version"], 1);
+        events.push(payload);
+    }
+    events.sort_by_key(|e| e["occurred_at"].as_str().unwrap().to_owned());
+    let names: Vec<_> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["subscriber.confirmed", "issue.published", "issue.completed"]
+    );
+    assert_eq!(events[1]["data"]["recipients"], 1);
+    assert_eq!(events[2]["data"]["delivered"], 1);
+    assert_eq!(events[2]["data"]["failed"], 0);
+}
+
+#[tokio::test]
+async fn a_failed_webhook_delivery_is_retried_later() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&receiver)
+        .await;
+    app.test_user.login(&app).await;
+    app.post_webhook(&serde_json::json!({
+        "url": format!("{}/hook", receiver.uri()),
+        "secret": SECRET,
+        "subscriber_confirmed": "on",
+    }))
+    .await;
+
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    let delivery = sqlx::query!(
+        "SELECT attempts, next_attempt_at > now() AS \"backed_off!\" FROM webhook_deliveries"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(delivery.attempts, 1);
+    assert!(delivery.backed_off);
+}
+
+#[tokio::test]
+async fn no_delivery_is_locked_while_its_webhook_is_being_sent() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(2)))
+        .mount(&receiver)
+        .await;
+    app.test_user.login(&app).await;
+    app.post_webhook(&serde_json::json!({
+        "url": format!("{}/hook", receiver.uri()),
+        "secret": SECRET,
+        "subscriber_confirmed": "on",
+    }))
+    .await;
+    create_confirmed_subscriber(&app).await;
+
+    let dispatch = app.dispatch_all_pending_webhooks();
+    let look = async {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let mut transaction = app.db_pool.begin().await.unwrap();
+        sqlx::query!("SELECT attempts FROM webhook_deliveries FOR UPDATE NOWAIT")
+            .fetch_one(&mut transaction)
+            .await
+    };
+    let (_, in_flight) = tokio::join!(dispatch, look);
+
+    // The claim was committed before the request went out
+    assert_eq!(in_flight.unwrap().attempts, 1);
+    let remaining = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM webhook_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.n, 0);
+}
+
+#[tokio::test]
+async fn the_configured_callback_is_told_about_confirmed_subscribers() {
+    let receiver = MockServer::start().await;
+    let callback_url = format!("{}/crm", receiver.uri());
+    let app = spawn_app_with(|config| {
+        config.webhooks.confirmation_callback = Some(CallbackSettings {
+            url: callback_url,
+            secret: SECRET.to_string().into(),
+        });
+    })
+    .await;
+    Mock::given(path("/crm"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&receiver)
+        .await;
+
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+    // Nothing is sent until the dispatcher runs, confirming doesn't wait on the callback
+    assert!(receiver.received_requests().await.unwrap().is_empty());
+    app.dispatch_all_pending_webhooks().await;
+
+    let request = receiver.received_requests().await.unwrap().pop().unwrap();
+    let body = std::str::from_utf8(&request.body).unwrap();
+    assert_eq!(
+        request.headers.get(&"X-Signature".into()).unwrap().as_str(),
+        signature(SECRET, body)
+    );
+    let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(payload["event"], "subscriber.confirmed");
+    assert_eq!(payload["data"]["email"], "ursula@example.com");
+    assert!(payload["data"]["name"].is_string());
+    assert!(payload["occurred_at"].is_string());
+}
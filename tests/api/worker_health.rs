@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use chrono::{SubsecRound, Utc};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero_to_prod::{issue_delivery_worker::ExecutionOutcome, worker_heartbeat::Heartbeat};
+
+use crate::helpers::{TestApp, create_confirmed_subscriber, spawn_app};
+
+async fn heartbeat_row(app: &TestApp) -> (chrono::DateTime<Utc>, Option<chrono::DateTime<Utc>>) {
+    let row = sqlx::query!(
+        "SELECT last_beat, last_task_at FROM worker_heartbeats WHERE worker_id = 'test-worker'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    (row.last_beat, row.last_task_at)
+}
+
+#[tokio::test]
+async fn worker_is_unhealthy_until_it_has_beaten() {
+    let app = spawn_app().await;
+
+    let response = app.get_worker_health_check().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["healthy"], false);
+    assert!(body["age_seconds"].is_null());
+}
+
+#[tokio::test]
+async fn worker_is_healthy_after_dispatching_and_unhealthy_once_its_heartbeat_is_stale() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter title").await;
+
+    // Part 1 - Fresh heartbeat
+    app.dispatch_all_pending_emails().await;
+    let response = app.get_worker_health_check().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["healthy"], true);
+    assert_eq!(body["worker_id"], "test-worker");
+    assert_eq!(body["queue_depth"], 0);
+    assert!(!body["last_task_at"].is_null());
+
+    // Part 2 - Stale heartbeat
+    sqlx::query!("UPDATE worker_heartbeats SET last_beat = last_beat - interval '5 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.get_worker_health_check().await;
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["healthy"], false);
+    assert!(body["age_seconds"].as_i64().unwrap() >= 300);
+}
+
+#[tokio::test]
+async fn heartbeat_reports_deliveries_still_queued() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.publish_issue("Newsletter title").await;
+    app.post_pause_worker().await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let response = app.get_worker_health_check().await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["queue_depth"], 2);
+    assert!(body["last_task_at"].is_null());
+}
+
+#[tokio::test]
+async fn dashboard_shows_the_worker_status() {
+    let app = spawn_app().await;
+    app.clock.set(chrono::Utc::now());
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("The delivery worker has never reported in."));
+
+    app.dispatch_all_pending_emails().await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Delivery worker up: last heartbeat 0s ago from test-worker"));
+    assert!(html_page.contains("last delivery never, 0 emails queued."));
+
+    sqlx::query!("UPDATE worker_heartbeats SET last_beat = last_beat - interval '5 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Delivery worker <b>not responding</b>: last heartbeat 300s ago"));
+}
+
+#[tokio::test]
+async fn a_failed_delivery_is_not_the_last_task() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter title").await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let (_, last_task_at) = heartbeat_row(&app).await;
+    assert!(last_task_at.is_none());
+}
+
+#[tokio::test]
+async fn the_heartbeat_is_written_at_most_once_per_interval() {
+    let app = spawn_app().await;
+    let heartbeat = Heartbeat::new("test-worker".into(), Duration::from_secs(60));
+    // Postgres keeps microseconds
+    let start = Utc::now().trunc_subsecs(0);
+    let beat = |outcome, seconds| {
+        heartbeat.beat(
+            &app.db_pool,
+            Some(outcome),
+            start + chrono::Duration::seconds(seconds),
+        )
+    };
+
+    // Part 1 - The first beat is written
+    beat(&ExecutionOutcome::EmptyQueue, 0).await.unwrap();
+    assert_eq!(heartbeat_row(&app).await, (start, None));
+
+    // Part 2 - A delivery within the interval waits for the next write
+    beat(&ExecutionOutcome::Delivered, 10).await.unwrap();
+    assert_eq!(heartbeat_row(&app).await, (start, None));
+
+    // Part 3 - Once the interval is up the delivery is written with the beat
+    beat(&ExecutionOutcome::TaskCompleted, 60).await.unwrap();
+    assert_eq!(
+        heartbeat_row(&app).await,
+        (
+            start + chrono::Duration::seconds(60),
+            Some(start + chrono::Duration::seconds(10))
+        )
+    );
+}
+
+#[tokio::test]
+async fn workers_that_stopped_a_day_ago_are_forgotten() {
+    let app = spawn_app().await;
+    app.clock.set(Utc::now());
+    sqlx::query!(
+        r#"
+        INSERT INTO worker_heartbeats (worker_id, last_beat, queue_depth)
+        VALUES ('old-host:42', $1, 0)
+        "#,
+        Utc::now() - chrono::Duration::days(2)
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.dispatch_all_pending_emails().await;
+
+    let workers: Vec<String> = sqlx::query!("SELECT worker_id FROM worker_heartbeats")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.worker_id)
+        .collect();
+    assert_eq!(workers, vec!["test-worker".to_string()]);
+}
@@ -0,0 +1,79 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_pause_deliveries() {
+    let app = spawn_app().await;
+
+    let response = app.post_pause_worker().await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(!app.delivery_pause.is_paused().await.unwrap());
+}
+
+#[tokio::test]
+async fn pausing_holds_back_queued_deliveries_until_resumed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    // Part 1 - Pause
+    let response = app.post_pause_worker().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("<p><i>Newsletter deliveries have been paused.</i></p>"));
+    assert!(html_page.contains("Newsletter deliveries are paused."));
+    let sent_while_paused = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    drop(sent_while_paused);
+    let queued = sqlx::query!(r#"SELECT COUNT(*) AS "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.n, 2);
+
+    // Part 2 - Resume
+    let response = app.post_resume_worker().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("<p><i>Newsletter deliveries have been resumed.</i></p>"));
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    let issue = sqlx::query!("SELECT status, n_delivered FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.status, "sent");
+    assert_eq!(issue.n_delivered, 2);
+
+    let actions = sqlx::query!(
+        "SELECT action FROM audit_log WHERE action LIKE 'worker.%' ORDER BY created_at"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let actions: Vec<_> = actions.iter().map(|a| a.action.as_str()).collect();
+    assert_eq!(actions, ["worker.paused", "worker.resumed"]);
+}